@@ -0,0 +1,16 @@
+use rust_comp_core::pos::Span;
+
+pub mod translate;
+pub mod types;
+
+#[cfg(test)]
+mod test;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The construct can't be translated to C yet.
+    Unsupported(Span),
+    UndefinedName(Span),
+    /// The integer literal doesn't fit in its type.
+    LiteralOutOfRange(Span),
+}
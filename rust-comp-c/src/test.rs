@@ -0,0 +1,51 @@
+use crate::translate::translate;
+use crate::Error;
+use rust_comp_check::test_support;
+use rust_comp_core::profile::Profile;
+use std::fs;
+use std::process::{Command, Output};
+
+/// Translates `file_contents` as file 0 with overflow checks.
+pub fn translate_str(file_contents: &str) -> Result<String, Error> {
+    translate_str_with(file_contents, Profile::Debug)
+}
+
+pub fn translate_str_with(file_contents: &str, profile: Profile) -> Result<String, Error> {
+    let (diagnostic, top_levels) = test_support::desugar(file_contents);
+    translate(&diagnostic, profile, &top_levels)
+}
+
+/// Translates `file_contents`, compiles it as C99 using `cc`, runs it, and
+/// returns its output.
+pub fn run_c(file_contents: &str) -> String {
+    let output = run_c_output(file_contents, Profile::Debug);
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Like `run_c` but returns the output whether or not the program succeeds.
+pub fn run_c_output(file_contents: &str, profile: Profile) -> Output {
+    let source = translate_str_with(file_contents, profile).unwrap();
+    let directory = std::env::temp_dir().join(format!(
+        "rust-comp-c-{}-{:?}",
+        std::process::id(),
+        std::thread::current().id()
+    ));
+    fs::create_dir_all(&directory).unwrap();
+    let source_path = directory.join("program.c");
+    let executable_path = directory.join("program");
+    fs::write(&source_path, &source).unwrap();
+
+    let status = Command::new("cc")
+        .arg("-std=c99")
+        .arg("-pedantic-errors")
+        .arg(&source_path)
+        .arg("-o")
+        .arg(&executable_path)
+        .status()
+        .unwrap();
+    assert!(status.success(), "{}", source);
+    let output = Command::new(&executable_path).output().unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+    output
+}
@@ -0,0 +1,1349 @@
+use crate::types::{CType, Integer, Types};
+use crate::Error;
+use rust_comp_check::integers::{self, Integers};
+use rust_comp_core::diagnostic::Diagnostic;
+use rust_comp_core::integer;
+use rust_comp_core::pos::Span;
+use rust_comp_core::profile::Profile;
+use rust_comp_syntax::*;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+const PRELUDE: &str = "#include <inttypes.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+static void print_i64(int64_t value) {
+    printf(\"%\" PRId64 \"\\n\", value);
+}
+
+static void rust_panic(const char *message) {
+    fflush(stdout);
+    fprintf(stderr, \"panicked at %s\\n\", message);
+    exit(101);
+}
+";
+
+/// Translates the functions, structs, and enums in `top_levels` to a C99
+/// translation unit.  Enums become tagged unions and `main` is called by the
+/// C `main`.  Bindings without annotations and integer literals have the
+/// types `integers::infer_integers` gives them.  Overflow checks call
+/// `rust_panic` and are only emitted for debug builds.
+pub fn translate(
+    diagnostic: &Diagnostic,
+    profile: Profile,
+    top_levels: &[TopLevel],
+) -> Result<String, Error> {
+    let mut types = Types::new(diagnostic, top_levels)?;
+    let integers = integers::infer_integers(diagnostic, top_levels);
+    let mut functions = HashMap::new();
+    for top_level in top_levels {
+        if let TopLevelKind::Function(function) = &top_level.kind {
+            let mut parameters = Vec::new();
+            for parameter in &function.parameters {
+                let type_ = types.convert(&parameter.type_)?;
+                if !type_.has_value() {
+                    return Err(Error::Unsupported(parameter.span));
+                }
+                parameters.push(type_);
+            }
+            let return_type = types.convert(&function.return_type)?;
+            functions.insert(
+                function.name.id,
+                Signature {
+                    name: function_name(&types.name(&function.name)),
+                    parameters,
+                    return_type,
+                },
+            );
+        }
+    }
+
+    let mut prototypes = String::new();
+    let mut bodies = String::new();
+    let mut has_main = false;
+    for top_level in top_levels {
+        if let TopLevelKind::Function(function) = &top_level.kind {
+            has_main |= types.name(&function.name) == "main";
+            let signature = &functions[&function.name.id];
+            let mut translator = Translator {
+                diagnostic,
+                profile,
+                types: &mut types,
+                functions: &functions,
+                integers: &integers,
+                lines: Vec::new(),
+                indent: 0,
+                bindings: Vec::new(),
+                counter: 0,
+                loops: Vec::new(),
+                return_type: signature.return_type.clone(),
+            };
+            let (prototype, body) = translator.function(function, signature)?;
+            writeln!(prototypes, "{};", prototype).unwrap();
+            bodies.push_str(&body);
+        }
+    }
+
+    let mut output = PRELUDE.to_string();
+    output.push_str(&types.definitions());
+    output.push_str(&prototypes);
+    output.push_str(&bodies);
+    if has_main {
+        output.push_str("int main(void) {\n    rust_main();\n    return 0;\n}\n");
+    }
+    Ok(output)
+}
+
+/// Functions are prefixed so they can't collide with the C library.
+fn function_name(name: &str) -> String {
+    format!("rust_{}", name)
+}
+
+struct Signature {
+    name: String,
+    parameters: Vec<CType>,
+    return_type: CType,
+}
+
+struct Binding {
+    id: SymbolId,
+    name: String,
+    type_: CType,
+}
+
+/// Loops are translated to `for (;;)` with labels to jump to so `break`
+/// works inside of a `switch`.
+struct LoopContext {
+    label: usize,
+    /// The variable and type of the value passed to `break`.
+    result: Option<(String, CType)>,
+    breaks: bool,
+}
+
+/// A C expression without side effects and its type.  Side effects are
+/// emitted as statements before the expression is used.
+struct Translated {
+    text: String,
+    type_: CType,
+}
+
+impl Translated {
+    fn new(text: String, type_: CType) -> Self {
+        Translated { text, type_ }
+    }
+
+    fn unit() -> Self {
+        Translated::new(String::new(), CType::Unit)
+    }
+
+    fn never() -> Self {
+        Translated::new(String::new(), CType::Never)
+    }
+}
+
+struct Translator<'a, 'b> {
+    diagnostic: &'a Diagnostic,
+    profile: Profile,
+    types: &'b mut Types<'a>,
+    functions: &'b HashMap<SymbolId, Signature>,
+    integers: &'b Integers,
+    lines: Vec<String>,
+    indent: usize,
+    bindings: Vec<Binding>,
+    /// Numbers temporaries, locals, and labels so they are unique.
+    counter: usize,
+    loops: Vec<LoopContext>,
+    return_type: CType,
+}
+
+impl<'a, 'b> Translator<'a, 'b> {
+    fn function(
+        &mut self,
+        function: &Function,
+        signature: &Signature,
+    ) -> Result<(String, String), Error> {
+        let mut parameters = Vec::new();
+        for (parameter, type_) in function.parameters.iter().zip(&signature.parameters) {
+            let name = self.local(parameter.name.span);
+            parameters.push(format!("{} {}", self.types.c_name(type_), name));
+            self.bindings.push(Binding {
+                id: parameter.name.id,
+                name,
+                type_: type_.clone(),
+            });
+        }
+        if parameters.is_empty() {
+            parameters.push("void".to_string());
+        }
+        let prototype = format!(
+            "static {} {}({})",
+            self.types.c_name(&signature.return_type),
+            signature.name,
+            parameters.join(", ")
+        );
+
+        self.indent = 1;
+        let return_type = self.return_type.clone();
+        let body = self.block(&function.body, Some(&return_type))?;
+        if body.type_.has_value() && return_type.has_value() {
+            self.line(format!("return {};", body.text));
+        }
+
+        let mut output = format!("{} {{\n", prototype);
+        for line in &self.lines {
+            writeln!(output, "{}", line).unwrap();
+        }
+        output.push_str("}\n");
+        Ok((prototype, output))
+    }
+
+    fn line(&mut self, line: String) {
+        self.lines
+            .push(format!("{}{}", "    ".repeat(self.indent), line));
+    }
+
+    /// Declares `name` at `index`, before code that was already emitted.
+    fn declare_at(&mut self, index: usize, indent: usize, type_: &CType, name: &str) {
+        let declaration = format!(
+            "{}{} {};",
+            "    ".repeat(indent),
+            self.types.c_name(type_),
+            name
+        );
+        self.lines.insert(index, declaration);
+    }
+
+    fn next(&mut self) -> usize {
+        self.counter += 1;
+        self.counter
+    }
+
+    fn temporary(&mut self) -> String {
+        format!("_{}", self.next())
+    }
+
+    /// A unique name for the local named by `span`.  Locals are renamed so
+    /// shadowing doesn't redeclare them.
+    fn local(&mut self, span: Span) -> String {
+        let name = self.diagnostic.file_span(span);
+        let name =
+            if !name.is_empty() && name.chars().all(|c| c == '_' || c.is_ascii_alphanumeric()) {
+                name
+            } else {
+                "v"
+            };
+        format!("{}_{}", name, self.next())
+    }
+
+    /// Stores `value` in a temporary unless it is already a variable.
+    fn materialize(&mut self, value: Translated) -> Translated {
+        let is_variable = value
+            .text
+            .chars()
+            .all(|c| c == '_' || c.is_ascii_alphanumeric());
+        if !value.type_.has_value() || is_variable {
+            return value;
+        }
+        let temporary = self.temporary();
+        self.line(format!(
+            "{} {} = {};",
+            self.types.c_name(&value.type_),
+            temporary,
+            value.text
+        ));
+        Translated::new(temporary, value.type_)
+    }
+
+    /// The type inferred for the binding spelled at `span` or the integer
+    /// literal at `span`.
+    fn inferred(&self, span: Span) -> Option<Integer> {
+        self.integers.of(span).and_then(Integer::from_name)
+    }
+
+    fn lookup(&self, id: SymbolId) -> Option<&Binding> {
+        self.bindings.iter().rev().find(|binding| binding.id == id)
+    }
+
+    fn variant(&self, id: SymbolId, span: Span) -> Result<(SymbolId, usize), Error> {
+        self.types
+            .variants
+            .get(&id)
+            .copied()
+            .ok_or(Error::Unsupported(span))
+    }
+
+    fn block(&mut self, block: &Block, expected: Option<&CType>) -> Result<Translated, Error> {
+        let scope = self.bindings.len();
+        let mut diverges = false;
+        for statement in &block.statements {
+            diverges |= self.statement(statement)?;
+        }
+        let result = match &block.expression {
+            Some(expression) => self.expression(expression, expected)?,
+            None if diverges => Translated::never(),
+            None => Translated::unit(),
+        };
+        self.bindings.truncate(scope);
+        Ok(result)
+    }
+
+    /// Translates `statement` and returns whether it diverges.
+    fn statement(&mut self, statement: &Statement) -> Result<bool, Error> {
+        match &statement.kind {
+            StatementKind::Empty => Ok(false),
+            StatementKind::Expression(expression) => {
+                Ok(self.expression(expression, None)?.type_ == CType::Never)
+            }
+            StatementKind::Let(let_) => self.let_(statement.span, let_),
+        }
+    }
+
+    fn let_(&mut self, span: Span, let_: &Let) -> Result<bool, Error> {
+        let declared = match &let_.type_ {
+            Some(type_) => Some(self.types.convert(type_)?),
+            None => self.inferred(let_.pattern.span).map(CType::Integer),
+        };
+        let value = match &let_.value {
+            Some(value) => value,
+            None => {
+                let type_ = declared.ok_or(Error::Unsupported(span))?;
+                let id = match let_.pattern.kind {
+                    PatternKind::Named(id) => id,
+                    _ => return Err(Error::Unsupported(let_.pattern.span)),
+                };
+                let name = self.local(let_.pattern.span);
+                if type_.has_value() {
+                    self.line(format!("{} {};", self.types.c_name(&type_), name));
+                }
+                self.bindings.push(Binding { id, name, type_ });
+                return Ok(false);
+            }
+        };
+
+        let value = self.expression(value, declared.as_ref())?;
+        if value.type_ == CType::Never {
+            return Ok(true);
+        }
+        let value = self.materialize(Translated::new(value.text, declared.unwrap_or(value.type_)));
+        if let Some(else_) = &let_.else_ {
+            let conditions = self.test(&value.text, &value.type_, &let_.pattern)?;
+            self.line(format!("if (!({})) {{", conjunction(&conditions)));
+            self.indent += 1;
+            self.block(&else_.block, None)?;
+            self.indent -= 1;
+            self.line("}".to_string());
+        }
+        self.bind(&value.text, &value.type_, &let_.pattern)?;
+        Ok(false)
+    }
+
+    /// The conditions under which `value` matches `pattern`.
+    fn test(&self, value: &str, type_: &CType, pattern: &Pattern) -> Result<Vec<String>, Error> {
+        let mut conditions = Vec::new();
+        match &pattern.kind {
+            PatternKind::Hole => {}
+            PatternKind::Named(id) => {
+                if self.types.variants.contains_key(id) {
+                    let (_, tag) = self.variant(*id, pattern.span)?;
+                    conditions.push(format!("{}.tag == {}", value, tag));
+                }
+            }
+            PatternKind::Value(Value::Bool(true)) => conditions.push(value.to_string()),
+            PatternKind::Value(Value::Bool(false)) => conditions.push(format!("!{}", value)),
+            PatternKind::Value(Value::Integer(integer)) => {
+                conditions.push(format!("{} == {}", value, integer))
+            }
+            PatternKind::Tuple(patterns) => {
+                let fields = match type_ {
+                    CType::Tuple(index) => self.types.tuples[*index].clone(),
+                    _ => return Err(Error::Unsupported(pattern.span)),
+                };
+                if fields.len() != patterns.len() {
+                    return Err(Error::Unsupported(pattern.span));
+                }
+                for (i, (pattern, field)) in patterns.iter().zip(&fields).enumerate() {
+                    conditions.extend(self.test(&format!("{}._{}", value, i), field, pattern)?);
+                }
+            }
+            PatternKind::NamedTuple(symbol, patterns) => {
+                let (enum_, tag) = self.variant(symbol.id, pattern.span)?;
+                let variant = &self.types.enums[&enum_].variants[tag];
+                if variant.fields.len() != patterns.len() {
+                    return Err(Error::Unsupported(pattern.span));
+                }
+                conditions.push(format!("{}.tag == {}", value, tag));
+                for (i, (pattern, field)) in patterns.iter().zip(&variant.fields).enumerate() {
+                    let field_value = format!("{}.data.{}._{}", value, variant.name, i);
+                    conditions.extend(self.test(&field_value, field, pattern)?);
+                }
+            }
+        }
+        Ok(conditions)
+    }
+
+    /// Declares the variables bound by matching `value` against `pattern`.
+    fn bind(&mut self, value: &str, type_: &CType, pattern: &Pattern) -> Result<(), Error> {
+        match &pattern.kind {
+            PatternKind::Hole | PatternKind::Value(_) => {}
+            PatternKind::Named(id) => {
+                if !self.types.variants.contains_key(id) {
+                    let name = self.local(pattern.span);
+                    if type_.has_value() {
+                        self.line(format!(
+                            "{} {} = {};",
+                            self.types.c_name(type_),
+                            name,
+                            value
+                        ));
+                    }
+                    self.bindings.push(Binding {
+                        id: *id,
+                        name,
+                        type_: type_.clone(),
+                    });
+                }
+            }
+            PatternKind::Tuple(patterns) => {
+                let fields = match type_ {
+                    CType::Tuple(index) => self.types.tuples[*index].clone(),
+                    _ => return Err(Error::Unsupported(pattern.span)),
+                };
+                for (i, (pattern, field)) in patterns.iter().zip(&fields).enumerate() {
+                    self.bind(&format!("{}._{}", value, i), field, pattern)?;
+                }
+            }
+            PatternKind::NamedTuple(symbol, patterns) => {
+                let (enum_, tag) = self.variant(symbol.id, pattern.span)?;
+                let variant = &self.types.enums[&enum_].variants[tag];
+                let name = variant.name.clone();
+                let fields = variant.fields.clone();
+                for (i, (pattern, field)) in patterns.iter().zip(&fields).enumerate() {
+                    self.bind(&format!("{}.data.{}._{}", value, name, i), field, pattern)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn expression(
+        &mut self,
+        expression: &Expression,
+        expected: Option<&CType>,
+    ) -> Result<Translated, Error> {
+        let span = expression.span;
+        match &expression.kind {
+            ExpressionKind::Variable(symbol) => {
+                if let Some(binding) = self.lookup(symbol.id) {
+                    return Ok(Translated::new(binding.name.clone(), binding.type_.clone()));
+                }
+                match self.types.variants.get(&symbol.id) {
+                    Some(&(enum_, tag)) => Ok(Translated::new(
+                        format!("{}()", self.types.constructor_name(enum_, tag)),
+                        CType::Enum(enum_),
+                    )),
+                    None if self.functions.contains_key(&symbol.id) => {
+                        Err(Error::Unsupported(span))
+                    }
+                    None => Err(Error::UndefinedName(symbol.span)),
+                }
+            }
+            ExpressionKind::Block(block) => self.block(block, expected),
+            ExpressionKind::If(if_) => self.if_(if_, expected),
+            ExpressionKind::Loop(loop_) => self.loop_(loop_),
+            ExpressionKind::While(while_) => self.while_(while_),
+            ExpressionKind::Match(match_) => self.match_(match_, expected),
+            ExpressionKind::Binary(binary) => self.binary(span, binary, expected),
+            ExpressionKind::FunctionCall(call) => self.call(span, call),
+            ExpressionKind::MemberAccess(access) => self.member(access),
+            ExpressionKind::Tuple(elements) if elements.is_empty() => Ok(Translated::unit()),
+            ExpressionKind::Tuple(elements) => {
+                let expected = match expected {
+                    Some(CType::Tuple(index)) => self.types.tuples[*index].clone(),
+                    _ => Vec::new(),
+                };
+                let mut texts = Vec::new();
+                let mut types = Vec::new();
+                for (i, element) in elements.iter().enumerate() {
+                    let element = self.expression(element, expected.get(i))?;
+                    if !element.type_.has_value() {
+                        return Err(Error::Unsupported(span));
+                    }
+                    texts.push(element.text);
+                    types.push(element.type_);
+                }
+                let type_ = self.types.tuple(types);
+                Ok(Translated::new(
+                    format!("(({}){{{}}})", self.types.c_name(&type_), texts.join(", ")),
+                    type_,
+                ))
+            }
+            ExpressionKind::Value(Value::Bool(b)) => {
+                Ok(Translated::new(b.to_string(), CType::Bool))
+            }
+            ExpressionKind::Value(Value::Integer(value)) => {
+                let integer = match (self.inferred(span), expected) {
+                    (Some(integer), _) => integer,
+                    (None, Some(CType::Integer(integer))) => *integer,
+                    (None, _) => Integer::from_name(integer::DEFAULT).unwrap(),
+                };
+                if *value > integer.max_value() {
+                    return Err(Error::LiteralOutOfRange(span));
+                }
+                let text = if *value <= i32::MAX as u128 {
+                    value.to_string()
+                } else {
+                    format!("(({}){}ULL)", integer.c_name(), value)
+                };
+                Ok(Translated::new(text, CType::Integer(integer)))
+            }
+            ExpressionKind::Ref(inner)
+            | ExpressionKind::RefMut(inner)
+            | ExpressionKind::RawConst(inner)
+            | ExpressionKind::RawMut(inner) => {
+                let place = match inner.kind {
+                    ExpressionKind::Variable(_)
+                    | ExpressionKind::Deref(_)
+                    | ExpressionKind::MemberAccess(_) => self.place(inner)?,
+                    _ => {
+                        let value = self.expression(inner, None)?;
+                        self.materialize(value)
+                    }
+                };
+                if !place.type_.has_value() {
+                    return Err(Error::Unsupported(span));
+                }
+                Ok(Translated::new(
+                    format!("(&{})", place.text),
+                    CType::Pointer(Box::new(place.type_)),
+                ))
+            }
+            ExpressionKind::Deref(inner) => {
+                let pointer = self.expression(inner, None)?;
+                match pointer.type_ {
+                    CType::Pointer(type_) => {
+                        Ok(Translated::new(format!("(*{})", pointer.text), *type_))
+                    }
+                    _ => Err(Error::Unsupported(span)),
+                }
+            }
+            ExpressionKind::Cast(cast) => {
+                let target = self.types.convert(&cast.type_)?;
+                let value = self.expression(&cast.expression, None)?;
+                let text = match (&value.type_, &target) {
+                    (CType::Integer(_), CType::Integer(_))
+                    | (CType::Bool, CType::Integer(_))
+                    | (CType::Pointer(_), CType::Pointer(_))
+                    | (CType::Pointer(_), CType::Integer(Integer::Usize))
+                    | (CType::Pointer(_), CType::Integer(Integer::Isize))
+                    | (CType::Integer(_), CType::Pointer(_)) => value.text,
+                    (CType::Enum(enum_), CType::Integer(_)) if self.types.is_fieldless(*enum_) => {
+                        format!("{}.tag", value.text)
+                    }
+                    _ => return Err(Error::Unsupported(span)),
+                };
+                Ok(Translated::new(
+                    format!("(({}){})", self.types.c_name(&target), text),
+                    target,
+                ))
+            }
+            ExpressionKind::Return(value) => {
+                let return_type = self.return_type.clone();
+                let value = match value {
+                    Some(value) => self.expression(value, Some(&return_type))?,
+                    None => Translated::unit(),
+                };
+                if value.type_ == CType::Never {
+                    return Ok(value);
+                }
+                if return_type.has_value() {
+                    self.line(format!("return {};", value.text));
+                } else {
+                    self.line("return;".to_string());
+                }
+                Ok(Translated::never())
+            }
+            ExpressionKind::Break(value) => {
+                if self.loops.is_empty() {
+                    return Err(Error::Unsupported(span));
+                }
+                if let Some(value) = value {
+                    let expected = self.loops.last().unwrap().result.clone();
+                    let value =
+                        self.expression(value, expected.as_ref().map(|(_, type_)| type_))?;
+                    if value.type_ == CType::Never {
+                        return Ok(value);
+                    }
+                    if value.type_.has_value() {
+                        let name = match expected {
+                            Some((name, _)) => name,
+                            None => {
+                                let name = self.temporary();
+                                self.loops.last_mut().unwrap().result =
+                                    Some((name.clone(), value.type_.clone()));
+                                name
+                            }
+                        };
+                        self.line(format!("{} = {};", name, value.text));
+                    }
+                }
+                let loop_ = self.loops.last_mut().unwrap();
+                loop_.breaks = true;
+                let label = loop_.label;
+                self.line(format!("goto break_{};", label));
+                Ok(Translated::never())
+            }
+            ExpressionKind::Continue => {
+                let label = match self.loops.last() {
+                    Some(loop_) => loop_.label,
+                    None => return Err(Error::Unsupported(span)),
+                };
+                self.line(format!("goto continue_{};", label));
+                Ok(Translated::never())
+            }
+            ExpressionKind::For(_) | ExpressionKind::MemberCall(_) | ExpressionKind::Try(_) => {
+                Err(Error::Unsupported(span))
+            }
+        }
+    }
+
+    /// Translates an expression that can be assigned to or borrowed.
+    fn place(&mut self, expression: &Expression) -> Result<Translated, Error> {
+        match &expression.kind {
+            ExpressionKind::Variable(symbol) => match self.lookup(symbol.id) {
+                Some(binding) => Ok(Translated::new(binding.name.clone(), binding.type_.clone())),
+                None => Err(Error::UndefinedName(symbol.span)),
+            },
+            ExpressionKind::Deref(_) | ExpressionKind::MemberAccess(_) => {
+                self.expression(expression, None)
+            }
+            _ => Err(Error::Unsupported(expression.span)),
+        }
+    }
+
+    /// Fields are accessed through pointers automatically.
+    fn member(&mut self, access: &MemberAccess) -> Result<Translated, Error> {
+        let object = self.expression(&access.object, None)?;
+        let (struct_, text) = match &object.type_ {
+            CType::Struct(struct_) => (*struct_, format!("{}.", object.text)),
+            CType::Pointer(inner) => match **inner {
+                CType::Struct(struct_) => (struct_, format!("{}->", object.text)),
+                _ => return Err(Error::Unsupported(access.object.span)),
+            },
+            _ => return Err(Error::Unsupported(access.object.span)),
+        };
+        let name = self.diagnostic.file_span(access.member.span);
+        match self.types.structs[&struct_]
+            .fields
+            .iter()
+            .find(|(field, _)| field == name)
+        {
+            Some((_, type_)) => Ok(Translated::new(format!("{}{}", text, name), type_.clone())),
+            None => Err(Error::UndefinedName(access.member.span)),
+        }
+    }
+
+    fn binary(
+        &mut self,
+        span: Span,
+        binary: &Binary,
+        expected: Option<&CType>,
+    ) -> Result<Translated, Error> {
+        let op = binary.op;
+        match op {
+            BinaryOp::SetTo => {
+                let place = self.place(&binary.left)?;
+                let value = self.expression(&binary.right, Some(&place.type_))?;
+                if value.type_ == CType::Never {
+                    return Ok(value);
+                }
+                if place.type_.has_value() {
+                    self.line(format!("{} = {};", place.text, value.text));
+                }
+                return Ok(Translated::unit());
+            }
+            BinaryOp::And | BinaryOp::Or => {
+                let left = self.expression(&binary.left, Some(&CType::Bool))?;
+                if left.type_ == CType::Never {
+                    return Ok(left);
+                }
+                let result = self.temporary();
+                self.line(format!("bool {} = {};", result, left.text));
+                let negate = if op == BinaryOp::And { "" } else { "!" };
+                self.line(format!("if ({}{}) {{", negate, result));
+                self.indent += 1;
+                let right = self.expression(&binary.right, Some(&CType::Bool))?;
+                if right.type_.has_value() {
+                    self.line(format!("{} = {};", result, right.text));
+                }
+                self.indent -= 1;
+                self.line("}".to_string());
+                return Ok(Translated::new(result, CType::Bool));
+            }
+            _ => {}
+        }
+
+        let comparison = op == BinaryOp::IsEqualTo || op == BinaryOp::IsNotEqualTo;
+        let expected = if comparison { None } else { expected };
+        // Untyped literals take the type of the other operand.
+        let is_literal =
+            |expression: &Expression| matches!(expression.kind, ExpressionKind::Value(_));
+        let (left, right) = if is_literal(&binary.left) && !is_literal(&binary.right) {
+            let right = self.expression(&binary.right, expected)?;
+            let left = self.expression(&binary.left, Some(&right.type_))?;
+            (left, right)
+        } else {
+            let left = self.expression(&binary.left, expected)?;
+            let right = self.expression(&binary.right, Some(&left.type_))?;
+            (left, right)
+        };
+        if left.type_ == CType::Never || right.type_ == CType::Never {
+            return Ok(Translated::never());
+        }
+        let (left, right) = match left.type_ {
+            CType::Integer(integer) if !comparison => {
+                self.check_overflow(span, op, integer, left, right)
+            }
+            _ => (left, right),
+        };
+
+        if comparison {
+            let op = if op == BinaryOp::IsEqualTo {
+                "=="
+            } else {
+                "!="
+            };
+            let text = match &left.type_ {
+                CType::Bool | CType::Integer(_) | CType::Pointer(_) => {
+                    format!("({} {} {})", left.text, op, right.text)
+                }
+                CType::Enum(enum_) if self.types.is_fieldless(*enum_) => {
+                    format!("({}.tag {} {}.tag)", left.text, op, right.text)
+                }
+                _ => return Err(Error::Unsupported(span)),
+            };
+            return Ok(Translated::new(text, CType::Bool));
+        }
+
+        let text = match (&left.type_, op) {
+            (CType::Integer(integer), BinaryOp::Plus)
+            | (CType::Integer(integer), BinaryOp::Minus)
+            | (CType::Integer(integer), BinaryOp::Times) => {
+                let op = match op {
+                    BinaryOp::Plus => "+",
+                    BinaryOp::Minus => "-",
+                    _ => "*",
+                };
+                // Signed overflow is undefined in C so arithmetic is done
+                // unsigned, which wraps.
+                format!(
+                    "(({})((uint64_t){} {} (uint64_t){}))",
+                    integer.c_name(),
+                    left.text,
+                    op,
+                    right.text
+                )
+            }
+            (CType::Integer(integer), _) => {
+                let op = match op {
+                    BinaryOp::DividedBy => "/",
+                    BinaryOp::BitAnd => "&",
+                    _ => "|",
+                };
+                format!(
+                    "(({})({} {} {}))",
+                    integer.c_name(),
+                    left.text,
+                    op,
+                    right.text
+                )
+            }
+            (CType::Bool, BinaryOp::BitAnd) => format!("({} & {})", left.text, right.text),
+            (CType::Bool, BinaryOp::BitOr) => format!("({} | {})", left.text, right.text),
+            _ => return Err(Error::Unsupported(span)),
+        };
+        Ok(Translated::new(text, left.type_))
+    }
+
+    /// Emits a panic if `left op right` overflows or divides by zero.  Only
+    /// division is checked in release builds.
+    fn check_overflow(
+        &mut self,
+        span: Span,
+        op: BinaryOp,
+        integer: Integer,
+        left: Translated,
+        right: Translated,
+    ) -> (Translated, Translated) {
+        let checked = match op {
+            BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Times => self.profile.overflow_checks(),
+            BinaryOp::DividedBy => true,
+            _ => false,
+        };
+        if !checked {
+            return (left, right);
+        }
+        let left = self.materialize(left);
+        let right = self.materialize(right);
+        let (a, b) = (&left.text, &right.text);
+        let (min, max) = (integer.min(), integer.max());
+        let mut checks = Vec::new();
+        match (op, integer.is_signed()) {
+            (BinaryOp::Plus, true) => checks.push((
+                format!(
+                    "({b} > 0 && {a} > {max} - {b}) || ({b} < 0 && {a} < {min} - {b})",
+                    a = a,
+                    b = b,
+                    min = min,
+                    max = max
+                ),
+                "attempt to add with overflow",
+            )),
+            (BinaryOp::Plus, false) => checks.push((
+                format!("{} > {} - {}", a, max, b),
+                "attempt to add with overflow",
+            )),
+            (BinaryOp::Minus, true) => checks.push((
+                format!(
+                    "({b} < 0 && {a} > {max} + {b}) || ({b} > 0 && {a} < {min} + {b})",
+                    a = a,
+                    b = b,
+                    min = min,
+                    max = max
+                ),
+                "attempt to subtract with overflow",
+            )),
+            (BinaryOp::Minus, false) => checks.push((
+                format!("{} < {}", a, b),
+                "attempt to subtract with overflow",
+            )),
+            (BinaryOp::Times, true) => checks.push((
+                format!(
+                    "{a} > 0 ? ({b} > 0 ? {a} > {max} / {b} : {b} < {min} / {a}) \
+                     : ({b} > 0 ? {a} < {min} / {b} : {a} != 0 && {b} < {max} / {a})",
+                    a = a,
+                    b = b,
+                    min = min,
+                    max = max
+                ),
+                "attempt to multiply with overflow",
+            )),
+            (BinaryOp::Times, false) => checks.push((
+                format!("{b} != 0 && {a} > {max} / {b}", a = a, b = b, max = max),
+                "attempt to multiply with overflow",
+            )),
+            (_, signed) => {
+                checks.push((format!("{} == 0", b), "attempt to divide by zero"));
+                if signed {
+                    checks.push((
+                        format!("{} == {} && {} == -1", a, min, b),
+                        "attempt to divide with overflow",
+                    ));
+                }
+            }
+        }
+        for (condition, message) in checks {
+            let message = format!("{}: {}", self.diagnostic.location(span), message);
+            self.line(format!(
+                "if ({}) rust_panic({});",
+                condition,
+                string_literal(&message)
+            ));
+        }
+        (left, right)
+    }
+
+    fn call(&mut self, span: Span, call: &FunctionCall) -> Result<Translated, Error> {
+        let callee = match &call.function.kind {
+            ExpressionKind::Variable(symbol) => symbol,
+            _ => return Err(Error::Unsupported(span)),
+        };
+        let functions = self.functions;
+        let (name, parameters, return_type) = if let Some(signature) = functions.get(&callee.id) {
+            (
+                signature.name.clone(),
+                signature.parameters.clone(),
+                signature.return_type.clone(),
+            )
+        } else if let Some(&(enum_, tag)) = self.types.variants.get(&callee.id) {
+            let fields = self.types.enums[&enum_].variants[tag].fields.clone();
+            (
+                self.types.constructor_name(enum_, tag),
+                fields,
+                CType::Enum(enum_),
+            )
+        } else if self.diagnostic.file_span(callee.span) == "print_i64" {
+            (
+                "print_i64".to_string(),
+                vec![CType::Integer(Integer::I64)],
+                CType::Unit,
+            )
+        } else {
+            return Err(Error::UndefinedName(callee.span));
+        };
+        if parameters.len() != call.arguments.len() {
+            return Err(Error::Unsupported(span));
+        }
+
+        let mut arguments = Vec::new();
+        for (argument, parameter) in call.arguments.iter().zip(&parameters) {
+            let argument = self.expression(argument, Some(parameter))?;
+            if argument.type_ == CType::Never {
+                return Ok(argument);
+            }
+            arguments.push(argument.text);
+        }
+        let text = format!("{}({})", name, arguments.join(", "));
+        if return_type.has_value() {
+            // Calls are evaluated immediately to keep side effects in order.
+            Ok(self.materialize(Translated::new(text, return_type)))
+        } else {
+            self.line(format!("{};", text));
+            Ok(Translated::new(String::new(), return_type))
+        }
+    }
+
+    fn if_(&mut self, if_: &If, expected: Option<&CType>) -> Result<Translated, Error> {
+        let at = self.lines.len();
+        let indent = self.indent;
+        let result = self.temporary();
+        let scope = self.bindings.len();
+
+        let matched = self.matched_value(&if_.condition)?;
+        let condition = self.condition_for(&if_.condition, &matched)?;
+        self.line(format!("if ({}) {{", condition));
+        self.indent += 1;
+        if let (Condition::Let(let_), Some(value)) = (&if_.condition, &matched) {
+            self.bind(&value.text, &value.type_, &let_.pattern)?;
+        }
+        let then = self.block(&if_.then, expected)?;
+        self.assign(&result, &then);
+        self.bindings.truncate(scope);
+        self.indent -= 1;
+
+        let mut type_ = then.type_;
+        match &if_.else_ {
+            Some(else_) => {
+                self.line("} else {".to_string());
+                self.indent += 1;
+                let expected = if type_.has_value() {
+                    Some(&type_)
+                } else {
+                    expected
+                };
+                let else_ = match &else_.kind {
+                    ElseKind::If(if_) => self.if_(if_, expected)?,
+                    ElseKind::Block(block) => self.block(block, expected)?,
+                };
+                self.assign(&result, &else_);
+                self.indent -= 1;
+                if type_ == CType::Never {
+                    type_ = else_.type_;
+                }
+            }
+            None => type_ = CType::Unit,
+        }
+        self.line("}".to_string());
+
+        if type_.has_value() {
+            self.declare_at(at, indent, &type_, &result);
+            Ok(Translated::new(result, type_))
+        } else {
+            Ok(Translated::new(String::new(), type_))
+        }
+    }
+
+    /// Evaluates the value matched by an `if let` or `while let`.
+    fn matched_value(&mut self, condition: &Condition) -> Result<Option<Translated>, Error> {
+        match condition {
+            Condition::Expression(_) => Ok(None),
+            Condition::Let(let_) => {
+                let value = self.expression(&let_.value, None)?;
+                Ok(Some(self.materialize(value)))
+            }
+        }
+    }
+
+    fn condition_for(
+        &mut self,
+        condition: &Condition,
+        matched: &Option<Translated>,
+    ) -> Result<String, Error> {
+        match (condition, matched) {
+            (Condition::Let(let_), Some(value)) => {
+                let conditions = self.test(&value.text, &value.type_, &let_.pattern)?;
+                Ok(conjunction(&conditions))
+            }
+            (Condition::Expression(expression), _) => {
+                Ok(self.expression(expression, Some(&CType::Bool))?.text)
+            }
+            (Condition::Let(let_), None) => Err(Error::Unsupported(let_.value.span)),
+        }
+    }
+
+    fn assign(&mut self, result: &str, value: &Translated) {
+        if value.type_.has_value() {
+            self.line(format!("{} = {};", result, value.text));
+        }
+    }
+
+    fn loop_(&mut self, loop_: &Loop) -> Result<Translated, Error> {
+        let at = self.lines.len();
+        let indent = self.indent;
+        let label = self.next();
+        self.loops.push(LoopContext {
+            label,
+            result: None,
+            breaks: false,
+        });
+        self.line("for (;;) {".to_string());
+        self.indent += 1;
+        self.block(&loop_.block, None)?;
+        self.line(format!("continue_{}:;", label));
+        self.indent -= 1;
+        self.line("}".to_string());
+        self.line(format!("break_{}:;", label));
+
+        let loop_ = self.loops.pop().unwrap();
+        match loop_.result {
+            Some((name, type_)) => {
+                self.declare_at(at, indent, &type_, &name);
+                Ok(Translated::new(name, type_))
+            }
+            None if loop_.breaks => Ok(Translated::unit()),
+            None => Ok(Translated::never()),
+        }
+    }
+
+    fn while_(&mut self, while_: &While) -> Result<Translated, Error> {
+        let label = self.next();
+        self.loops.push(LoopContext {
+            label,
+            result: None,
+            breaks: true,
+        });
+        self.line("for (;;) {".to_string());
+        self.indent += 1;
+        let scope = self.bindings.len();
+        let matched = self.matched_value(&while_.condition)?;
+        let condition = self.condition_for(&while_.condition, &matched)?;
+        self.line(format!("if (!({})) goto break_{};", condition, label));
+        if let (Condition::Let(let_), Some(value)) = (&while_.condition, &matched) {
+            self.bind(&value.text, &value.type_, &let_.pattern)?;
+        }
+        self.block(&while_.block, None)?;
+        self.bindings.truncate(scope);
+        self.line(format!("continue_{}:;", label));
+        self.indent -= 1;
+        self.line("}".to_string());
+        self.line(format!("break_{}:;", label));
+        self.loops.pop();
+        Ok(Translated::unit())
+    }
+
+    /// Matches on enums, integers, and booleans become a `switch` on the tag
+    /// or value.  Each case tests the arms that can match it in order.
+    fn match_(&mut self, match_: &Match, expected: Option<&CType>) -> Result<Translated, Error> {
+        let value = self.expression(&match_.value, None)?;
+        if value.type_ == CType::Never {
+            return Ok(value);
+        }
+        let value = self.materialize(value);
+        let at = self.lines.len();
+        let indent = self.indent;
+        let result = self.temporary();
+        let end = self.next();
+        let mut type_ = expected.cloned().unwrap_or(CType::Never);
+        let mut arm_type = CType::Never;
+
+        let switch = match &value.type_ {
+            CType::Enum(_) => Some(format!("{}.tag", value.text)),
+            CType::Integer(_) | CType::Bool => Some(value.text.clone()),
+            _ => None,
+        };
+        let cases: Vec<Option<String>> = match_
+            .matches
+            .iter()
+            .map(|item| self.case(&item.pattern))
+            .collect::<Result<_, _>>()?;
+        match switch {
+            Some(switch) => {
+                let mut keys: Vec<&String> = Vec::new();
+                for key in cases.iter().flatten() {
+                    if !keys.contains(&key) {
+                        keys.push(key);
+                    }
+                }
+                self.line(format!("switch ({}) {{", switch));
+                for key in keys {
+                    self.line(format!("case {}: {{", key));
+                    self.indent += 1;
+                    let arms = match_
+                        .matches
+                        .iter()
+                        .zip(&cases)
+                        .filter(|(_, case)| case.as_ref().is_none_or(|case| case == key))
+                        .map(|(item, _)| item);
+                    self.arms(arms, &value, &result, end, &mut type_, &mut arm_type)?;
+                    self.indent -= 1;
+                    self.line("}".to_string());
+                }
+                self.line("default: {".to_string());
+                self.indent += 1;
+                let arms = match_
+                    .matches
+                    .iter()
+                    .zip(&cases)
+                    .filter(|(_, case)| case.is_none())
+                    .map(|(item, _)| item);
+                self.arms(arms, &value, &result, end, &mut type_, &mut arm_type)?;
+                self.indent -= 1;
+                self.line("}".to_string());
+                self.line("}".to_string());
+            }
+            None => {
+                let arms = match_.matches.iter();
+                self.arms(arms, &value, &result, end, &mut type_, &mut arm_type)?;
+            }
+        }
+        self.line(format!("match_{}:;", end));
+
+        if arm_type.has_value() {
+            self.declare_at(at, indent, &arm_type, &result);
+            Ok(Translated::new(result, arm_type))
+        } else {
+            Ok(Translated::new(String::new(), arm_type))
+        }
+    }
+
+    /// The `case` an arm's pattern selects or `None` if it matches anything.
+    fn case(&self, pattern: &Pattern) -> Result<Option<String>, Error> {
+        Ok(match &pattern.kind {
+            PatternKind::Named(id) if self.types.variants.contains_key(id) => {
+                Some(self.variant(*id, pattern.span)?.1.to_string())
+            }
+            PatternKind::NamedTuple(symbol, _) => {
+                Some(self.variant(symbol.id, pattern.span)?.1.to_string())
+            }
+            PatternKind::Value(Value::Integer(integer)) => Some(integer.to_string()),
+            PatternKind::Value(Value::Bool(b)) => Some((*b as u8).to_string()),
+            _ => None,
+        })
+    }
+
+    fn arms<'c>(
+        &mut self,
+        arms: impl Iterator<Item = &'c MatchItem>,
+        value: &Translated,
+        result: &str,
+        end: usize,
+        expected: &mut CType,
+        arm_type: &mut CType,
+    ) -> Result<(), Error> {
+        for item in arms {
+            let conditions = self.test(&value.text, &value.type_, &item.pattern)?;
+            self.line(format!("if ({}) {{", conjunction(&conditions)));
+            self.indent += 1;
+            let scope = self.bindings.len();
+            self.bind(&value.text, &value.type_, &item.pattern)?;
+            let expected_type = if expected.has_value() {
+                Some(expected.clone())
+            } else {
+                None
+            };
+            let arm = self.expression(&item.value, expected_type.as_ref())?;
+            self.assign(result, &arm);
+            if *arm_type == CType::Never {
+                *arm_type = arm.type_.clone();
+                if arm.type_.has_value() {
+                    *expected = arm.type_;
+                }
+            }
+            self.line(format!("goto match_{};", end));
+            self.bindings.truncate(scope);
+            self.indent -= 1;
+            self.line("}".to_string());
+        }
+        // Matches are exhaustive so this is unreachable.
+        self.line("abort();".to_string());
+        Ok(())
+    }
+}
+
+fn string_literal(string: &str) -> String {
+    let mut literal = String::from("\"");
+    for c in string.chars() {
+        if c == '"' || c == '\\' {
+            literal.push('\\');
+        }
+        literal.push(c);
+    }
+    literal.push('"');
+    literal
+}
+
+fn conjunction(conditions: &[String]) -> String {
+    if conditions.is_empty() {
+        "true".to_string()
+    } else {
+        conditions.join(" && ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{run_c, run_c_output, translate_str, translate_str_with};
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn test_translate_arithmetic_and_calls() {
+        assert_eq!(
+            run_c(
+                "fn add(a: i64, b: i64) -> i64 { a + b }
+                 fn main() { print_i64(add(2, 3) * 4 - 30 / 3); }"
+            ),
+            "10\n"
+        );
+    }
+
+    #[test]
+    fn test_translate_branches_and_loops() {
+        assert_eq!(
+            run_c(
+                "fn fib(n: i64) -> i64 { if n == 0 || n == 1 { n } else { fib(n - 1) + fib(n - 2) } }
+                 fn main() {
+                     let mut i = 0;
+                     while i != 10 { print_i64(fib(i)); i = i + 1; }
+                     let x = loop { if i == 15 { break i * 2; } i = i + 1; };
+                     print_i64(x);
+                 }"
+            ),
+            "0\n1\n1\n2\n3\n5\n8\n13\n21\n34\n30\n"
+        );
+    }
+
+    #[test]
+    fn test_translate_enums_and_match() {
+        assert_eq!(
+            run_c(
+                "enum Count { One, Many(i64) }
+                 enum Shape { Square(i64), Rect(Count, i64), Empty }
+                 fn area(s: Shape) -> i64 {
+                     match s { Square(x) => x * x, Rect(One, h) => h, Rect(Many(w), h) => w * h, Empty => 0 }
+                 }
+                 fn main() {
+                     let shapes = (Square(3), Rect(Many(2), 5), Rect(One, 7), Empty);
+                     let (a, b, c, d) = shapes;
+                     print_i64(area(a) + area(b) + area(c) + area(d));
+                     let Rect(Many(w), _) = b else { return; };
+                     if let Square(x) = a { print_i64(x * w); }
+                     loop { match d { Empty => break, _ => {} } }
+                 }"
+            ),
+            "26\n6\n"
+        );
+    }
+
+    #[test]
+    fn test_translate_pointers_and_casts() {
+        let output = run_c_output(
+            "fn set(p: &mut i64, v: i64) { *p = v; }
+             fn main() {
+                 let mut x: i64 = 1;
+                 set(&mut x, 300);
+                 print_i64(x as u8 as i64);
+                 let y: i32 = 2147483647;
+                 print_i64((y + 1) as i64);
+             }",
+            Profile::Release,
+        );
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "44\n-2147483648\n"
+        );
+    }
+
+    #[test]
+    fn test_translate_struct_definitions() {
+        let source = translate_str_with(
+            "struct Point { x: i32, y: i32 }
+             struct Line { start: Point, end: Point }
+             fn length_x(line: &Line) -> i32 { line.end.x - line.start.x }",
+            Profile::Release,
+        )
+        .unwrap();
+        assert!(source.contains("struct Point {\n    int32_t x;\n    int32_t y;\n};"));
+        assert!(source.contains("return ((int32_t)((uint64_t)line_1->end.x"));
+    }
+
+    #[test]
+    fn test_translate_overflow_checks() {
+        let program = "fn mul(a: i32, b: i32) -> i32 { a * b }
+                       fn sub(a: u8, b: u8) -> u8 { a - b }
+                       fn main() {
+                           print_i64(mul(0 - 3, 5) as i64);
+                           print_i64(sub(1, 2) as i64);
+                           print_i64(mul(65536, 65536) as i64);
+                       }";
+        let output = run_c_output(program, Profile::Debug);
+        assert_eq!(output.status.code(), Some(101));
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "-15\n");
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            "panicked at <test>:2:53: attempt to subtract with overflow\n"
+        );
+
+        let output = run_c_output(program, Profile::Release);
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "-15\n255\n0\n");
+
+        let output = run_c_output(
+            "fn div(a: i64, b: i64) -> i64 { a / b }
+             fn main() { print_i64(div(7, 2)); print_i64(div(7, 0)); }",
+            Profile::Release,
+        );
+        assert_eq!(output.status.code(), Some(101));
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "3\n");
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            "panicked at <test>:1:33: attempt to divide by zero\n"
+        );
+    }
+
+    #[test]
+    fn test_translate_inferred_integer_types() {
+        assert_eq!(
+            run_c(
+                "fn next(p: &mut i64) { *p = *p + 4294967296; }
+                 fn main() {
+                     let mut i = 0;
+                     next(&mut i);
+                     print_i64(i);
+                     let x = 2147483647;
+                     print_i64(x + 1);
+                 }"
+            ),
+            "4294967296\n2147483648\n"
+        );
+        assert_eq!(
+            translate_str("fn main() { let big = 3000000000; }"),
+            Err(Error::LiteralOutOfRange(Span {
+                file: 0,
+                start: 22,
+                end: 32,
+                expn: None,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_translate_for_is_unsupported() {
+        assert_matches!(
+            translate_str("fn main() { for x in 0 {} }"),
+            Err(Error::Unsupported(_))
+        );
+    }
+}
@@ -0,0 +1,417 @@
+use crate::Error;
+use rust_comp_core::diagnostic::Diagnostic;
+use rust_comp_syntax as syntax;
+use rust_comp_syntax::{SymbolId, TypeKind};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CType {
+    Unit,
+    /// The type of expressions that don't produce a value, like `return`.
+    Never,
+    Bool,
+    Integer(Integer),
+    /// References and raw pointers.
+    Pointer(Box<CType>),
+    /// An index into `Types::tuples`.
+    Tuple(usize),
+    Struct(SymbolId),
+    Enum(SymbolId),
+}
+
+impl CType {
+    /// Whether values of this type are stored in variables.
+    pub fn has_value(&self) -> bool {
+        *self != CType::Unit && *self != CType::Never
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Integer {
+    I8,
+    I16,
+    I32,
+    I64,
+    Isize,
+    U8,
+    U16,
+    U32,
+    U64,
+    Usize,
+}
+
+impl Integer {
+    pub fn from_name(name: &str) -> Option<Integer> {
+        Some(match name {
+            "i8" => Integer::I8,
+            "i16" => Integer::I16,
+            "i32" => Integer::I32,
+            "i64" => Integer::I64,
+            "isize" => Integer::Isize,
+            "u8" => Integer::U8,
+            "u16" => Integer::U16,
+            "u32" => Integer::U32,
+            "u64" => Integer::U64,
+            "usize" => Integer::Usize,
+            _ => return None,
+        })
+    }
+
+    pub fn is_signed(self) -> bool {
+        match self {
+            Integer::I8 | Integer::I16 | Integer::I32 | Integer::I64 | Integer::Isize => true,
+            Integer::U8 | Integer::U16 | Integer::U32 | Integer::U64 | Integer::Usize => false,
+        }
+    }
+
+    /// The macro for the minimum value.  Unsigned types don't have one.
+    pub fn min(self) -> &'static str {
+        match self {
+            Integer::I8 => "INT8_MIN",
+            Integer::I16 => "INT16_MIN",
+            Integer::I32 => "INT32_MIN",
+            Integer::I64 => "INT64_MIN",
+            Integer::Isize => "INTPTR_MIN",
+            _ => "0",
+        }
+    }
+
+    /// The macro for the maximum value.
+    pub fn max(self) -> &'static str {
+        match self {
+            Integer::I8 => "INT8_MAX",
+            Integer::I16 => "INT16_MAX",
+            Integer::I32 => "INT32_MAX",
+            Integer::I64 => "INT64_MAX",
+            Integer::Isize => "INTPTR_MAX",
+            Integer::U8 => "UINT8_MAX",
+            Integer::U16 => "UINT16_MAX",
+            Integer::U32 => "UINT32_MAX",
+            Integer::U64 => "UINT64_MAX",
+            Integer::Usize => "UINTPTR_MAX",
+        }
+    }
+
+    /// The maximum value on the 64 bit targets C is compiled for.
+    pub fn max_value(self) -> u128 {
+        match self {
+            Integer::I8 => i8::MAX as u128,
+            Integer::I16 => i16::MAX as u128,
+            Integer::I32 => i32::MAX as u128,
+            Integer::I64 | Integer::Isize => i64::MAX as u128,
+            Integer::U8 => u8::MAX as u128,
+            Integer::U16 => u16::MAX as u128,
+            Integer::U32 => u32::MAX as u128,
+            Integer::U64 | Integer::Usize => u64::MAX as u128,
+        }
+    }
+
+    pub fn c_name(self) -> &'static str {
+        match self {
+            Integer::I8 => "int8_t",
+            Integer::I16 => "int16_t",
+            Integer::I32 => "int32_t",
+            Integer::I64 => "int64_t",
+            Integer::Isize => "intptr_t",
+            Integer::U8 => "uint8_t",
+            Integer::U16 => "uint16_t",
+            Integer::U32 => "uint32_t",
+            Integer::U64 => "uint64_t",
+            Integer::Usize => "uintptr_t",
+        }
+    }
+}
+
+pub struct Struct {
+    pub name: String,
+    pub fields: Vec<(String, CType)>,
+}
+
+pub struct Enum {
+    pub name: String,
+    pub variants: Vec<Variant>,
+}
+
+pub struct Variant {
+    pub name: String,
+    pub fields: Vec<CType>,
+}
+
+/// The structs, enums, and tuples used by the program.
+pub struct Types<'a> {
+    diagnostic: &'a Diagnostic,
+    pub structs: HashMap<SymbolId, Struct>,
+    pub enums: HashMap<SymbolId, Enum>,
+    /// The enum and index of each variant.
+    pub variants: HashMap<SymbolId, (SymbolId, usize)>,
+    pub tuples: Vec<Vec<CType>>,
+    /// Structs and enums in the order they are declared.
+    order: Vec<CType>,
+}
+
+impl<'a> Types<'a> {
+    pub fn new(diagnostic: &'a Diagnostic, top_levels: &[syntax::TopLevel]) -> Result<Self, Error> {
+        let mut types = Types {
+            diagnostic,
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            variants: HashMap::new(),
+            tuples: Vec::new(),
+            order: Vec::new(),
+        };
+        // Declare every type before converting fields so they can refer to
+        // types declared later.
+        for top_level in top_levels {
+            match &top_level.kind {
+                syntax::TopLevelKind::Struct(struct_) => {
+                    let name = types.name(&struct_.name);
+                    types.structs.insert(
+                        struct_.name.id,
+                        Struct {
+                            name,
+                            fields: Vec::new(),
+                        },
+                    );
+                    types.order.push(CType::Struct(struct_.name.id));
+                }
+                syntax::TopLevelKind::Enum(enum_) => {
+                    let name = types.name(&enum_.name);
+                    types.enums.insert(
+                        enum_.name.id,
+                        Enum {
+                            name,
+                            variants: Vec::new(),
+                        },
+                    );
+                    for (i, variant) in enum_.variants.iter().enumerate() {
+                        types.variants.insert(variant.name.id, (enum_.name.id, i));
+                    }
+                    types.order.push(CType::Enum(enum_.name.id));
+                }
+                _ => (),
+            }
+        }
+        for top_level in top_levels {
+            match &top_level.kind {
+                syntax::TopLevelKind::Struct(struct_) => {
+                    let mut fields = Vec::new();
+                    for field in &struct_.fields {
+                        fields.push((types.name(&field.name), types.value_type(&field.type_)?));
+                    }
+                    types.structs.get_mut(&struct_.name.id).unwrap().fields = fields;
+                }
+                syntax::TopLevelKind::Enum(enum_) => {
+                    let mut variants = Vec::new();
+                    for variant in &enum_.variants {
+                        let fields = match &variant.data {
+                            syntax::VariantData::None => Vec::new(),
+                            syntax::VariantData::Tuple(types_) => types_
+                                .iter()
+                                .map(|type_| types.value_type(type_))
+                                .collect::<Result<_, _>>()?,
+                        };
+                        variants.push(Variant {
+                            name: types.name(&variant.name),
+                            fields,
+                        });
+                    }
+                    types.enums.get_mut(&enum_.name.id).unwrap().variants = variants;
+                }
+                _ => (),
+            }
+        }
+        Ok(types)
+    }
+
+    pub fn name(&self, symbol: &syntax::Symbol) -> String {
+        self.diagnostic.file_span(symbol.span).to_string()
+    }
+
+    pub fn convert(&mut self, type_: &syntax::Type) -> Result<CType, Error> {
+        match &type_.kind {
+            TypeKind::Named(name) => {
+                let text = self.diagnostic.file_span(name.span);
+                if text == "bool" {
+                    Ok(CType::Bool)
+                } else if let Some(integer) = Integer::from_name(text) {
+                    Ok(CType::Integer(integer))
+                } else if self.structs.contains_key(&name.id) {
+                    Ok(CType::Struct(name.id))
+                } else if self.enums.contains_key(&name.id) {
+                    Ok(CType::Enum(name.id))
+                } else {
+                    Err(Error::Unsupported(type_.span))
+                }
+            }
+            TypeKind::Ref(inner)
+            | TypeKind::RefMut(inner)
+            | TypeKind::PtrConst(inner)
+            | TypeKind::PtrMut(inner) => Ok(CType::Pointer(Box::new(self.convert(inner)?))),
+            TypeKind::Tuple(types) if types.is_empty() => Ok(CType::Unit),
+            TypeKind::Tuple(types) => {
+                let types = types
+                    .iter()
+                    .map(|type_| self.value_type(type_))
+                    .collect::<Result<_, _>>()?;
+                Ok(self.tuple(types))
+            }
+            TypeKind::Hole => Err(Error::Unsupported(type_.span)),
+        }
+    }
+
+    /// Converts the type of a field, which can't be `()` in C.
+    fn value_type(&mut self, type_: &syntax::Type) -> Result<CType, Error> {
+        let c_type = self.convert(type_)?;
+        if c_type.has_value() {
+            Ok(c_type)
+        } else {
+            Err(Error::Unsupported(type_.span))
+        }
+    }
+
+    pub fn tuple(&mut self, types: Vec<CType>) -> CType {
+        match self.tuples.iter().position(|tuple| *tuple == types) {
+            Some(index) => CType::Tuple(index),
+            None => {
+                self.tuples.push(types);
+                CType::Tuple(self.tuples.len() - 1)
+            }
+        }
+    }
+
+    pub fn c_name(&self, type_: &CType) -> String {
+        match type_ {
+            CType::Unit | CType::Never => "void".to_string(),
+            CType::Bool => "bool".to_string(),
+            CType::Integer(integer) => integer.c_name().to_string(),
+            CType::Pointer(inner) => format!("{} *", self.c_name(inner)),
+            CType::Tuple(index) => format!("struct tuple_{}", index),
+            CType::Struct(id) => format!("struct {}", self.structs[id].name),
+            CType::Enum(id) => format!("struct {}", self.enums[id].name),
+        }
+    }
+
+    /// Whether the enum only has variants without data.
+    pub fn is_fieldless(&self, enum_: SymbolId) -> bool {
+        self.enums[&enum_]
+            .variants
+            .iter()
+            .all(|variant| variant.fields.is_empty())
+    }
+
+    /// Emits the definitions of every type and the constructors of enum
+    /// variants.  Types are defined after the types they contain.
+    pub fn definitions(&self) -> String {
+        let mut output = String::new();
+        for type_ in &self.order {
+            writeln!(output, "{};", self.c_name(type_)).unwrap();
+        }
+        let mut defined = HashSet::new();
+        let tuples = (0..self.tuples.len()).map(CType::Tuple);
+        for type_ in self.order.iter().cloned().chain(tuples) {
+            self.define(&mut output, &mut defined, &type_);
+        }
+        for type_ in &self.order {
+            if let CType::Enum(id) = type_ {
+                for (tag, variant) in self.enums[id].variants.iter().enumerate() {
+                    self.constructor(&mut output, *id, tag, variant);
+                }
+            }
+        }
+        output
+    }
+
+    fn define(&self, output: &mut String, defined: &mut HashSet<String>, type_: &CType) {
+        let name = self.c_name(type_);
+        if !defined.insert(name.clone()) {
+            return;
+        }
+        let mut fields: Vec<CType> = match type_ {
+            CType::Tuple(index) => self.tuples[*index].clone(),
+            CType::Struct(id) => self.structs[id]
+                .fields
+                .iter()
+                .map(|(_, type_)| type_.clone())
+                .collect(),
+            CType::Enum(id) => self.enums[id]
+                .variants
+                .iter()
+                .flat_map(|variant| variant.fields.iter().cloned())
+                .collect(),
+            _ => return,
+        };
+        fields.retain(|field| matches!(field, CType::Tuple(_) | CType::Struct(_) | CType::Enum(_)));
+        for field in &fields {
+            self.define(output, defined, field);
+        }
+
+        writeln!(output, "{} {{", name).unwrap();
+        match type_ {
+            CType::Tuple(index) => {
+                for (i, field) in self.tuples[*index].iter().enumerate() {
+                    writeln!(output, "    {} _{};", self.c_name(field), i).unwrap();
+                }
+            }
+            CType::Struct(id) => {
+                for (field_name, field) in &self.structs[id].fields {
+                    writeln!(output, "    {} {};", self.c_name(field), field_name).unwrap();
+                }
+            }
+            CType::Enum(id) => {
+                writeln!(output, "    uint32_t tag;").unwrap();
+                if !self.is_fieldless(*id) {
+                    writeln!(output, "    union {{").unwrap();
+                    for variant in &self.enums[id].variants {
+                        if variant.fields.is_empty() {
+                            continue;
+                        }
+                        writeln!(output, "        struct {{").unwrap();
+                        for (i, field) in variant.fields.iter().enumerate() {
+                            writeln!(output, "            {} _{};", self.c_name(field), i).unwrap();
+                        }
+                        writeln!(output, "        }} {};", variant.name).unwrap();
+                    }
+                    writeln!(output, "    }} data;").unwrap();
+                }
+            }
+            _ => unreachable!(),
+        }
+        writeln!(output, "}};").unwrap();
+    }
+
+    fn constructor(&self, output: &mut String, enum_: SymbolId, tag: usize, variant: &Variant) {
+        let type_ = self.c_name(&CType::Enum(enum_));
+        let parameters = if variant.fields.is_empty() {
+            "void".to_string()
+        } else {
+            variant
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| format!("{} _{}", self.c_name(field), i))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        writeln!(
+            output,
+            "static {} {}({}) {{",
+            type_,
+            self.constructor_name(enum_, tag),
+            parameters
+        )
+        .unwrap();
+        writeln!(output, "    {} value;", type_).unwrap();
+        writeln!(output, "    value.tag = {};", tag).unwrap();
+        for i in 0..variant.fields.len() {
+            writeln!(output, "    value.data.{}._{} = _{};", variant.name, i, i).unwrap();
+        }
+        writeln!(output, "    return value;").unwrap();
+        writeln!(output, "}}").unwrap();
+    }
+
+    pub fn constructor_name(&self, enum_: SymbolId, tag: usize) -> String {
+        let enum_ = &self.enums[&enum_];
+        format!("{}_{}", enum_.name, enum_.variants[tag].name)
+    }
+}
@@ -0,0 +1,352 @@
+use crate::pos::Span;
+use crate::visit::*;
+use rust_comp_core::diagnostic::Diagnostic;
+use rust_comp_syntax::*;
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The cast expression and its target type.  The string describes the
+    /// type being casted from.
+    InvalidCast(&'static str, Span, Span),
+}
+
+/// Checks that every `as` cast whose operand type is known is allowed.
+pub fn check_casts(diagnostic: &Diagnostic, top_levels: &[TopLevel]) -> Vec<Error> {
+    let mut checker = Checker {
+        diagnostic,
+        enums: HashMap::new(),
+        bindings: Vec::new(),
+        scopes: Vec::new(),
+        errors: Vec::new(),
+    };
+    for top_level in top_levels {
+        if let TopLevelKind::Enum(enum_) = &top_level.kind {
+            let fieldless = enum_
+                .variants
+                .iter()
+                .all(|variant| variant.data == VariantData::None);
+            checker.enums.insert(enum_.name.id, fieldless);
+        }
+    }
+    walk_top_levels(&mut checker, top_levels);
+    checker.errors
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    U8,
+    Integer,
+    /// An integer literal without a suffix can become any integer type.
+    IntegerLiteral,
+    Float,
+    Bool,
+    Char,
+    Ref,
+    RefMut,
+    PtrConst,
+    PtrMut,
+    Enum(SymbolId, bool),
+}
+
+impl Kind {
+    fn is_integer(self) -> bool {
+        self == Kind::U8 || self == Kind::Integer || self == Kind::IntegerLiteral
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Kind::U8 => "`u8`",
+            Kind::Integer | Kind::IntegerLiteral => "an integer",
+            Kind::Float => "a float",
+            Kind::Bool => "`bool`",
+            Kind::Char => "`char`",
+            Kind::Ref => "a `&` reference",
+            Kind::RefMut => "a `&mut` reference",
+            Kind::PtrConst => "a `*const` pointer",
+            Kind::PtrMut => "a `*mut` pointer",
+            Kind::Enum(_, true) => "an enum",
+            Kind::Enum(_, false) => "an enum with fields",
+        }
+    }
+}
+
+fn is_valid_cast(from: Kind, to: Kind) -> bool {
+    match to {
+        Kind::U8 | Kind::Integer => match from {
+            Kind::Enum(_, fieldless) => fieldless,
+            Kind::Ref | Kind::RefMut => false,
+            _ => true,
+        },
+        Kind::IntegerLiteral => unreachable!(),
+        Kind::Float => from.is_integer() || from == Kind::Float,
+        Kind::Bool => from == Kind::Bool,
+        Kind::Char => from == Kind::U8 || from == Kind::IntegerLiteral || from == Kind::Char,
+        Kind::Ref | Kind::RefMut => from == to,
+        Kind::PtrConst => match from {
+            Kind::Ref | Kind::RefMut | Kind::PtrConst | Kind::PtrMut => true,
+            _ => from.is_integer(),
+        },
+        Kind::PtrMut => match from {
+            Kind::RefMut | Kind::PtrConst | Kind::PtrMut => true,
+            _ => from.is_integer(),
+        },
+        Kind::Enum(..) => from == to,
+    }
+}
+
+struct Checker<'a> {
+    diagnostic: &'a Diagnostic,
+    /// Whether each enum only has variants without data.
+    enums: HashMap<SymbolId, bool>,
+    /// Bindings in scope, innermost last.
+    bindings: Vec<(SymbolId, Option<Kind>)>,
+    /// The length of `bindings` when each scope was entered.
+    scopes: Vec<usize>,
+    errors: Vec<Error>,
+}
+
+impl<'a> Visitor for Checker<'a> {
+    fn visit_function(&mut self, function: &Function) {
+        for parameter in &function.parameters {
+            let kind = self.kind_of_type(&parameter.type_);
+            self.bindings.push((parameter.name.id, kind));
+        }
+        walk_function(self, function);
+        self.bindings.clear();
+    }
+
+    fn visit_let(&mut self, let_: &Let) {
+        let id = match let_.pattern.kind {
+            PatternKind::Named(id) => id,
+            _ => return walk_let(self, let_),
+        };
+        if let Some(value) = &let_.value {
+            self.visit_expression(value);
+        }
+        if let Some(else_) = &let_.else_ {
+            self.visit_block(&else_.block);
+        }
+        let kind = match (&let_.type_, &let_.value) {
+            (Some(type_), _) => self.kind_of_type(type_),
+            (None, Some(value)) => self.kind_of(value),
+            (None, None) => None,
+        };
+        self.bindings.push((id, kind));
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        walk_expression(self, expression);
+        if let ExpressionKind::Cast(cast) = &expression.kind {
+            let from = self.kind_of(&cast.expression);
+            let to = self.kind_of_type(&cast.type_);
+            if let (Some(from), Some(to)) = (from, to) {
+                if !is_valid_cast(from, to) {
+                    self.errors.push(Error::InvalidCast(
+                        from.description(),
+                        expression.span,
+                        cast.type_.span,
+                    ));
+                }
+            }
+        }
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        self.bind_pattern(pattern);
+    }
+
+    fn visit_for_var(&mut self, var: &Symbol) {
+        self.bindings.push((var.id, None));
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(self.bindings.len());
+    }
+
+    fn exit_scope(&mut self) {
+        let scope = self.scopes.pop().unwrap();
+        self.bindings.truncate(scope);
+    }
+}
+
+impl<'a> Checker<'a> {
+    fn bind_pattern(&mut self, pattern: &Pattern) {
+        match &pattern.kind {
+            PatternKind::Named(id) => self.bindings.push((*id, None)),
+            PatternKind::Tuple(patterns) | PatternKind::NamedTuple(_, patterns) => {
+                for pattern in patterns {
+                    self.bind_pattern(pattern);
+                }
+            }
+            PatternKind::Hole | PatternKind::Value(_) => (),
+        }
+    }
+
+    /// Finds the kind of type `expression` evaluates to, if it is obvious
+    /// without type inference.
+    fn kind_of(&self, expression: &Expression) -> Option<Kind> {
+        match &expression.kind {
+            ExpressionKind::Variable(symbol) => self
+                .bindings
+                .iter()
+                .rev()
+                .find(|(id, _)| *id == symbol.id)
+                .and_then(|(_, kind)| *kind),
+            ExpressionKind::Value(Value::Bool(_)) => Some(Kind::Bool),
+            ExpressionKind::Value(Value::Integer(_)) => Some(Kind::IntegerLiteral),
+            ExpressionKind::Ref(_) => Some(Kind::Ref),
+            ExpressionKind::RefMut(_) => Some(Kind::RefMut),
+            ExpressionKind::RawConst(_) => Some(Kind::PtrConst),
+            ExpressionKind::RawMut(_) => Some(Kind::PtrMut),
+            ExpressionKind::Cast(cast) => self.kind_of_type(&cast.type_),
+            _ => None,
+        }
+    }
+
+    fn kind_of_type(&self, type_: &Type) -> Option<Kind> {
+        match &type_.kind {
+            TypeKind::Named(name) => match self.diagnostic.file_span(name.span) {
+                "u8" => Some(Kind::U8),
+                "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u16" | "u32" | "u64"
+                | "u128" | "usize" => Some(Kind::Integer),
+                "f32" | "f64" => Some(Kind::Float),
+                "bool" => Some(Kind::Bool),
+                "char" => Some(Kind::Char),
+                _ => self
+                    .enums
+                    .get(&name.id)
+                    .map(|fieldless| Kind::Enum(name.id, *fieldless)),
+            },
+            TypeKind::Ref(_) => Some(Kind::Ref),
+            TypeKind::RefMut(_) => Some(Kind::RefMut),
+            TypeKind::PtrConst(_) => Some(Kind::PtrConst),
+            TypeKind::PtrMut(_) => Some(Kind::PtrMut),
+            TypeKind::Tuple(_) | TypeKind::Hole => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::convert;
+    use assert_matches::assert_matches;
+
+    fn check(file_contents: &str) -> Vec<Error> {
+        let (diagnostic, top_levels) = convert(file_contents);
+        check_casts(&diagnostic, &top_levels)
+    }
+
+    #[test]
+    fn test_numeric_casts_are_valid() {
+        assert_eq!(
+            check(
+                "fn f(a: i32, b: f64, c: u8) { a as u8; a as f32; b as i64; c as u128; 1 as f32; }"
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_bool_and_char_to_integer_are_valid() {
+        assert_eq!(check("fn f(c: char) { true as i32; c as u32; }"), vec![]);
+    }
+
+    #[test]
+    fn test_bool_as_float_is_invalid() {
+        assert_eq!(
+            check("fn f() { true as f32; }"),
+            vec![Error::InvalidCast(
+                "`bool`",
+                Span {
+                    file: 0,
+                    start: 9,
+                    end: 20,
+                    expn: None
+                },
+                Span {
+                    file: 0,
+                    start: 17,
+                    end: 20,
+                    expn: None
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn test_integer_as_bool_is_invalid() {
+        assert_matches!(
+            check("fn f(a: i32) { a as bool; }").as_slice(),
+            [Error::InvalidCast("an integer", _, _)]
+        );
+    }
+
+    #[test]
+    fn test_only_u8_casts_to_char() {
+        assert_eq!(check("fn f(a: u8) { a as char; 97 as char; }"), vec![]);
+        assert_matches!(
+            check("fn f(a: u32) { a as char; }").as_slice(),
+            [Error::InvalidCast("an integer", _, _)]
+        );
+    }
+
+    #[test]
+    fn test_pointer_casts() {
+        assert_eq!(
+            check(
+                "fn f(p: *const i32, r: &mut i32) {
+                     p as *mut u8; r as *mut i32; p as usize; 0 as *const u8; &raw const p as *const u8;
+                 }"
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_ref_as_mut_pointer_is_invalid() {
+        assert_matches!(
+            check("fn f(r: &i32) { r as *mut i32; }").as_slice(),
+            [Error::InvalidCast("a `&` reference", _, _)]
+        );
+    }
+
+    #[test]
+    fn test_pointer_as_float_is_invalid() {
+        assert_matches!(
+            check("fn f(p: *const u8) { p as f64; }").as_slice(),
+            [Error::InvalidCast("a `*const` pointer", _, _)]
+        );
+    }
+
+    #[test]
+    fn test_fieldless_enum_to_discriminant() {
+        assert_eq!(check("enum E { A, B } fn f(e: E) { e as i32; }"), vec![]);
+        assert_matches!(
+            check("enum E { A, B } fn f(e: E) { e as f32; }").as_slice(),
+            [Error::InvalidCast("an enum", _, _)]
+        );
+    }
+
+    #[test]
+    fn test_enum_with_fields_is_not_castable() {
+        assert_matches!(
+            check("enum E { A(i32), B } fn f(e: E) { e as i32; }").as_slice(),
+            [Error::InvalidCast("an enum with fields", _, _)]
+        );
+    }
+
+    #[test]
+    fn test_unknown_operand_is_not_checked() {
+        assert_eq!(check("fn f() { g() as bool; x as f32; }"), vec![]);
+    }
+
+    #[test]
+    fn test_chained_cast_uses_intermediate_type() {
+        assert_matches!(
+            check("fn f(a: bool) { a as u8 as char as bool; }").as_slice(),
+            [Error::InvalidCast("`char`", _, _)]
+        );
+    }
+}
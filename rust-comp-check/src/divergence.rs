@@ -0,0 +1,236 @@
+use crate::pos::Span;
+use crate::visit::*;
+use rust_comp_syntax::*;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The `else` block of a `let`-`else` can complete normally.
+    LetElseDoesNotDiverge(Span),
+}
+
+/// Checks that the `else` block of every `let`-`else` diverges.
+pub fn check_let_else(top_levels: &[TopLevel]) -> Vec<Error> {
+    let mut checker = Checker::default();
+    walk_top_levels(&mut checker, top_levels);
+    checker.errors
+}
+
+/// Whether evaluating `block` can never complete normally.
+pub fn block_diverges(block: &Block) -> bool {
+    block
+        .statements
+        .iter()
+        .any(|statement| match &statement.kind {
+            StatementKind::Empty => false,
+            StatementKind::Expression(expression) => expression_diverges(expression),
+            StatementKind::Let(let_) => let_.value.as_ref().is_some_and(expression_diverges),
+        })
+        || block.expression.as_deref().is_some_and(expression_diverges)
+}
+
+/// Whether evaluating `expression` can never complete normally.
+pub fn expression_diverges(expression: &Expression) -> bool {
+    match &expression.kind {
+        ExpressionKind::Variable(_) | ExpressionKind::Value(_) => false,
+        ExpressionKind::Return(_) | ExpressionKind::Break(_) | ExpressionKind::Continue => true,
+        ExpressionKind::Block(block) => block_diverges(block),
+        ExpressionKind::If(if_) => if_diverges(if_),
+        ExpressionKind::Loop(loop_) => !breaks_out(&loop_.block),
+        ExpressionKind::While(while_) => condition_diverges(&while_.condition),
+        ExpressionKind::For(for_) => expression_diverges(&for_.expr),
+        ExpressionKind::Match(match_) => {
+            expression_diverges(&match_.value)
+                || match_
+                    .matches
+                    .iter()
+                    .all(|item| expression_diverges(&item.value))
+        }
+        ExpressionKind::Binary(binary) => {
+            // The right side of `&&` and `||` isn't always evaluated.
+            expression_diverges(&binary.left)
+                || (binary.op != BinaryOp::And
+                    && binary.op != BinaryOp::Or
+                    && expression_diverges(&binary.right))
+        }
+        ExpressionKind::FunctionCall(function_call) => {
+            expression_diverges(&function_call.function)
+                || function_call.arguments.iter().any(expression_diverges)
+        }
+        ExpressionKind::MemberCall(member_call) => {
+            expression_diverges(&member_call.member.object)
+                || member_call.arguments.iter().any(expression_diverges)
+        }
+        ExpressionKind::MemberAccess(member_access) => expression_diverges(&member_access.object),
+        ExpressionKind::Tuple(expressions) => expressions.iter().any(expression_diverges),
+        ExpressionKind::Ref(expression)
+        | ExpressionKind::RefMut(expression)
+        | ExpressionKind::RawConst(expression)
+        | ExpressionKind::RawMut(expression)
+        | ExpressionKind::Deref(expression)
+        | ExpressionKind::Try(expression) => expression_diverges(expression),
+        ExpressionKind::Cast(cast) => expression_diverges(&cast.expression),
+    }
+}
+
+/// Whether evaluating `if_` can never complete normally.
+pub fn if_diverges(if_: &If) -> bool {
+    if condition_diverges(&if_.condition) {
+        return true;
+    }
+    let else_diverges = match if_.else_.as_ref().map(|else_| &else_.kind) {
+        Some(ElseKind::If(if_)) => if_diverges(if_),
+        Some(ElseKind::Block(block)) => block_diverges(block),
+        None => false,
+    };
+    else_diverges && block_diverges(&if_.then)
+}
+
+fn condition_diverges(condition: &Condition) -> bool {
+    match condition {
+        Condition::Expression(expression) => expression_diverges(expression),
+        Condition::Let(let_) => expression_diverges(&let_.value),
+    }
+}
+
+/// Whether `block` contains a `break` out of the loop it is the body of.
+fn breaks_out(block: &Block) -> bool {
+    let mut finder = BreakFinder::default();
+    finder.visit_block(block);
+    finder.found
+}
+
+#[derive(Default)]
+struct BreakFinder {
+    /// The number of loops nested inside the block being searched.
+    depth: usize,
+    found: bool,
+}
+
+impl Visitor for BreakFinder {
+    fn visit_expression(&mut self, expression: &Expression) {
+        match &expression.kind {
+            ExpressionKind::Break(_) if self.depth == 0 => self.found = true,
+            ExpressionKind::Loop(_) | ExpressionKind::While(_) | ExpressionKind::For(_) => {
+                self.depth += 1;
+                walk_expression(self, expression);
+                self.depth -= 1;
+            }
+            _ => walk_expression(self, expression),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Checker {
+    errors: Vec<Error>,
+}
+
+impl Visitor for Checker {
+    fn visit_let(&mut self, let_: &Let) {
+        walk_let(self, let_);
+        if let Some(else_) = &let_.else_ {
+            if !block_diverges(&else_.block) {
+                self.errors.push(Error::LetElseDoesNotDiverge(else_.span));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::convert;
+    use assert_matches::assert_matches;
+
+    fn check(file_contents: &str) -> Vec<Error> {
+        check_let_else(&convert(file_contents).1)
+    }
+
+    #[test]
+    fn test_let_else_return_diverges() {
+        assert_eq!(
+            check("fn f(o: Option) { let Some(x) = o else { return; }; }"),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_let_else_empty_block_does_not_diverge() {
+        assert_eq!(
+            check("fn f(o: Option) { let Some(x) = o else {}; }"),
+            vec![Error::LetElseDoesNotDiverge(Span {
+                file: 0,
+                start: 34,
+                end: 41,
+                expn: None,
+            })]
+        );
+    }
+
+    #[test]
+    fn test_let_else_break_and_continue_diverge() {
+        assert_eq!(
+            check(
+                "fn f(o: Option) {
+                     loop { let Some(x) = o else { break; }; }
+                     loop { let Some(x) = o else { continue; }; }
+                 }"
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_let_else_if_diverges_only_with_both_branches() {
+        assert_eq!(
+            check("fn f(o: Option, b: bool) { let Some(x) = o else { if b { return; } else { return; } }; }"),
+            vec![]
+        );
+        assert_matches!(
+            check("fn f(o: Option, b: bool) { let Some(x) = o else { if b { return; } }; }")
+                .as_slice(),
+            [Error::LetElseDoesNotDiverge(_)]
+        );
+    }
+
+    #[test]
+    fn test_let_else_loop_diverges_unless_broken_out_of() {
+        assert_eq!(
+            check("fn f(o: Option) { let Some(x) = o else { loop { while true { break; } } }; }"),
+            vec![]
+        );
+        assert_matches!(
+            check("fn f(o: Option) { let Some(x) = o else { loop { break; } }; }").as_slice(),
+            [Error::LetElseDoesNotDiverge(_)]
+        );
+    }
+
+    #[test]
+    fn test_let_else_match_diverges_when_all_arms_do() {
+        assert_eq!(
+            check("fn f(o: Option, b: bool) { let Some(x) = o else { match o { Some(y) => return, _ => return } }; }"),
+            vec![]
+        );
+        assert_matches!(
+            check("fn f(o: Option, b: bool) { let Some(x) = o else { match o { Some(y) => return, _ => () } }; }")
+                .as_slice(),
+            [Error::LetElseDoesNotDiverge(_)]
+        );
+    }
+
+    #[test]
+    fn test_let_else_short_circuit_does_not_diverge() {
+        assert_matches!(
+            check("fn f(o: Option, b: bool) { let Some(x) = o else { b && return; }; }").as_slice(),
+            [Error::LetElseDoesNotDiverge(_)]
+        );
+    }
+
+    #[test]
+    fn test_nested_let_else_is_checked() {
+        assert_matches!(
+            check("fn f(o: Option) { if true { let Some(x) = o else { () }; } }").as_slice(),
+            [Error::LetElseDoesNotDiverge(_)]
+        );
+    }
+}
@@ -0,0 +1,600 @@
+use crate::divergence::block_diverges;
+use crate::pos::Span;
+use crate::visit::*;
+use rust_comp_core::diagnostic::Diagnostic;
+use rust_comp_core::integer;
+use rust_comp_syntax::*;
+use std::collections::{HashMap, HashSet};
+
+/// The integer types of the bindings and integer literals of a program.
+#[derive(Debug, Default)]
+pub struct Integers {
+    types: HashMap<Span, &'static str>,
+}
+
+impl Integers {
+    /// The name of the integer type of the binding spelled at `span` or the
+    /// literal at `span`.  Returns `None` if it isn't an integer.
+    pub fn of(&self, span: Span) -> Option<&'static str> {
+        self.types.get(&span).copied()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// An integer literal too large for its type, which is named.
+    LiteralOutOfRange(&'static str, Span),
+}
+
+/// Checks that every integer literal fits in the type inferred for it.
+pub fn check_literals(diagnostic: &Diagnostic, top_levels: &[TopLevel]) -> Vec<Error> {
+    let mut checker = Checker {
+        integers: infer_integers(diagnostic, top_levels),
+        errors: Vec::new(),
+    };
+    walk_top_levels(&mut checker, top_levels);
+    checker.errors
+}
+
+struct Checker {
+    integers: Integers,
+    errors: Vec<Error>,
+}
+
+impl Visitor for Checker {
+    fn visit_expression(&mut self, expression: &Expression) {
+        if let ExpressionKind::Value(Value::Integer(value)) = expression.kind {
+            if let Some(integer) = self.integers.of(expression.span) {
+                if integer::max(integer).is_some_and(|max| value > max) {
+                    self.errors
+                        .push(Error::LiteralOutOfRange(integer, expression.span));
+                }
+            }
+        }
+        walk_expression(self, expression);
+    }
+}
+
+/// Infers the integer types of the bindings and integer literals in
+/// `top_levels` from the annotations, parameters, and operands they meet.
+/// Literals nothing constrains are `integer::DEFAULT`.
+pub fn infer_integers(diagnostic: &Diagnostic, top_levels: &[TopLevel]) -> Integers {
+    let mut inferrer = Inferrer {
+        diagnostic,
+        functions: HashMap::new(),
+        variants: ["None", "Some", "Ok", "Err"]
+            .iter()
+            .map(|name| SymbolId::of_name(name))
+            .collect(),
+        vars: Vec::new(),
+        bindings: Vec::new(),
+        return_: Term::Other,
+        loops: Vec::new(),
+        typed: Vec::new(),
+        literals: Vec::new(),
+    };
+    let print_i64 = (vec![Term::Integer("i64")], Term::Other);
+    inferrer
+        .functions
+        .insert(SymbolId::of_name("print_i64"), print_i64);
+    for top_level in top_levels {
+        match &top_level.kind {
+            TopLevelKind::Function(function) => {
+                let parameters = function
+                    .parameters
+                    .iter()
+                    .map(|parameter| inferrer.type_term(&parameter.type_))
+                    .collect();
+                let return_ = inferrer.type_term(&function.return_type);
+                inferrer
+                    .functions
+                    .insert(function.name.id, (parameters, return_));
+            }
+            TopLevelKind::Enum(enum_) => {
+                inferrer
+                    .variants
+                    .extend(enum_.variants.iter().map(|variant| variant.name.id));
+            }
+            _ => (),
+        }
+    }
+    for top_level in top_levels {
+        if let TopLevelKind::Function(function) = &top_level.kind {
+            inferrer.function(function);
+        }
+    }
+    inferrer.finish()
+}
+
+/// What is known about the type of an expression.
+#[derive(Clone, Debug)]
+enum Term {
+    /// A type variable.
+    Var(usize),
+    Integer(&'static str),
+    /// A reference or pointer.
+    Pointer(Box<Term>),
+    Tuple(Vec<Term>),
+    /// A type that isn't an integer, or whose parts aren't tracked.
+    Other,
+}
+
+struct Inferrer<'a> {
+    diagnostic: &'a Diagnostic,
+    /// The parameter and return types of each function.
+    functions: HashMap<SymbolId, (Vec<Term>, Term)>,
+    /// Enum variants, which patterns match instead of binding.
+    variants: HashSet<SymbolId>,
+    /// The term each type variable has been unified with.
+    vars: Vec<Option<Term>>,
+    /// Bindings in scope, innermost last.
+    bindings: Vec<(SymbolId, Term)>,
+    /// The return type of the function being inferred.
+    return_: Term,
+    /// The types of the values of the `loop`s being inferred, innermost
+    /// last.  `while` and `for` loops are `Other`.
+    loops: Vec<Term>,
+    /// The spans of bindings with their types.
+    typed: Vec<(Span, Term)>,
+    /// The spans of integer literals with their type variables.
+    literals: Vec<(Span, usize)>,
+}
+
+impl<'a> Inferrer<'a> {
+    fn fresh(&mut self) -> Term {
+        self.vars.push(None);
+        Term::Var(self.vars.len() - 1)
+    }
+
+    fn type_term(&mut self, type_: &Type) -> Term {
+        match &type_.kind {
+            TypeKind::Named(name) => {
+                let name = self.diagnostic.file_span(name.span);
+                match integer::NAMES.iter().find(|integer| **integer == name) {
+                    Some(integer) => Term::Integer(integer),
+                    None => Term::Other,
+                }
+            }
+            TypeKind::Ref(type_)
+            | TypeKind::RefMut(type_)
+            | TypeKind::PtrConst(type_)
+            | TypeKind::PtrMut(type_) => Term::Pointer(Box::new(self.type_term(type_))),
+            TypeKind::Tuple(types) => {
+                Term::Tuple(types.iter().map(|type_| self.type_term(type_)).collect())
+            }
+            TypeKind::Hole => self.fresh(),
+        }
+    }
+
+    /// Follows `term` through the type variables it has been unified with.
+    fn resolve(&self, term: &Term) -> Term {
+        let mut term = term.clone();
+        while let Term::Var(var) = term {
+            match &self.vars[var] {
+                Some(next) => term = next.clone(),
+                None => break,
+            }
+        }
+        term
+    }
+
+    /// Makes `a` and `b` the same type where either is unknown.  Mismatches
+    /// are ignored.
+    fn unify(&mut self, a: &Term, b: &Term) {
+        match (self.resolve(a), self.resolve(b)) {
+            (Term::Var(a), Term::Var(b)) if a == b => (),
+            (Term::Var(var), term) | (term, Term::Var(var)) if !self.occurs(var, &term) => {
+                self.vars[var] = Some(term);
+            }
+            (Term::Pointer(a), Term::Pointer(b)) => self.unify(&a, &b),
+            (Term::Tuple(a), Term::Tuple(b)) if a.len() == b.len() => {
+                for (a, b) in a.iter().zip(&b) {
+                    self.unify(a, b);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn occurs(&self, var: usize, term: &Term) -> bool {
+        match self.resolve(term) {
+            Term::Var(other) => other == var,
+            Term::Pointer(term) => self.occurs(var, &term),
+            Term::Tuple(terms) => terms.iter().any(|term| self.occurs(var, term)),
+            Term::Integer(_) | Term::Other => false,
+        }
+    }
+
+    fn lookup(&self, id: SymbolId) -> Option<&Term> {
+        self.bindings
+            .iter()
+            .rev()
+            .find(|(binding, _)| *binding == id)
+            .map(|(_, term)| term)
+    }
+
+    fn bind(&mut self, pattern: &Pattern, term: Term) {
+        match &pattern.kind {
+            PatternKind::Named(id) if !self.variants.contains(id) => {
+                self.typed.push((pattern.span, term.clone()));
+                self.bindings.push((*id, term));
+            }
+            PatternKind::Tuple(patterns) => {
+                let terms: Vec<_> = patterns.iter().map(|_| self.fresh()).collect();
+                self.unify(&term, &Term::Tuple(terms.clone()));
+                for (pattern, term) in patterns.iter().zip(terms) {
+                    self.bind(pattern, term);
+                }
+            }
+            PatternKind::NamedTuple(_, patterns) => {
+                for pattern in patterns {
+                    let term = self.fresh();
+                    self.bind(pattern, term);
+                }
+            }
+            PatternKind::Named(_) | PatternKind::Hole | PatternKind::Value(_) => (),
+        }
+    }
+
+    fn function(&mut self, function: &Function) {
+        for parameter in &function.parameters {
+            let term = self.type_term(&parameter.type_);
+            self.bindings.push((parameter.name.id, term));
+        }
+        self.return_ = self.type_term(&function.return_type);
+        let body = self.block(&function.body);
+        let return_ = self.return_.clone();
+        self.unify(&return_, &body);
+        self.bindings.clear();
+    }
+
+    fn block(&mut self, block: &Block) -> Term {
+        let len = self.bindings.len();
+        for statement in &block.statements {
+            match &statement.kind {
+                StatementKind::Empty => (),
+                StatementKind::Expression(expression) => {
+                    self.expression(expression);
+                }
+                StatementKind::Let(let_) => self.let_(let_),
+            }
+        }
+        let term = match &block.expression {
+            Some(expression) => self.expression(expression),
+            // A block that never completes can have any type.
+            None if block_diverges(block) => self.fresh(),
+            None => Term::Other,
+        };
+        self.bindings.truncate(len);
+        term
+    }
+
+    fn let_(&mut self, let_: &Let) {
+        let term = match &let_.type_ {
+            Some(type_) => self.type_term(type_),
+            None => self.fresh(),
+        };
+        if let Some(value) = &let_.value {
+            let value = self.expression(value);
+            self.unify(&term, &value);
+        }
+        if let Some(else_) = &let_.else_ {
+            self.block(&else_.block);
+        }
+        self.bind(&let_.pattern, term);
+    }
+
+    fn condition(&mut self, condition: &Condition) {
+        match condition {
+            Condition::Expression(expression) => {
+                self.expression(expression);
+            }
+            Condition::Let(let_condition) => {
+                let value = self.expression(&let_condition.value);
+                self.bind(&let_condition.pattern, value);
+            }
+        }
+    }
+
+    fn if_(&mut self, if_: &If) -> Term {
+        let len = self.bindings.len();
+        self.condition(&if_.condition);
+        let then = self.block(&if_.then);
+        self.bindings.truncate(len);
+        let else_ = match if_.else_.as_ref().map(|else_| &else_.kind) {
+            Some(ElseKind::If(if_)) => self.if_(if_),
+            Some(ElseKind::Block(block)) => self.block(block),
+            None => return Term::Other,
+        };
+        self.unify(&then, &else_);
+        then
+    }
+
+    fn expression(&mut self, expression: &Expression) -> Term {
+        match &expression.kind {
+            ExpressionKind::Variable(symbol) => {
+                self.lookup(symbol.id).cloned().unwrap_or(Term::Other)
+            }
+            ExpressionKind::Value(Value::Integer(_)) => {
+                self.vars.push(None);
+                let var = self.vars.len() - 1;
+                self.literals.push((expression.span, var));
+                Term::Var(var)
+            }
+            ExpressionKind::Value(Value::Bool(_)) => Term::Other,
+            ExpressionKind::Block(block) => self.block(block),
+            ExpressionKind::If(if_) => self.if_(if_),
+            ExpressionKind::Loop(loop_) => {
+                let term = self.fresh();
+                self.loops.push(term.clone());
+                self.block(&loop_.block);
+                self.loops.pop();
+                term
+            }
+            ExpressionKind::While(while_) => {
+                let len = self.bindings.len();
+                self.loops.push(Term::Other);
+                self.condition(&while_.condition);
+                self.block(&while_.block);
+                self.loops.pop();
+                self.bindings.truncate(len);
+                Term::Other
+            }
+            ExpressionKind::For(for_) => {
+                self.expression(&for_.expr);
+                let var = self.fresh();
+                self.bindings.push((for_.var.id, var));
+                self.loops.push(Term::Other);
+                self.block(&for_.block);
+                self.loops.pop();
+                self.bindings.pop();
+                Term::Other
+            }
+            ExpressionKind::Match(match_) => {
+                let value = self.expression(&match_.value);
+                let term = self.fresh();
+                for item in &match_.matches {
+                    let len = self.bindings.len();
+                    self.bind(&item.pattern, value.clone());
+                    let item = self.expression(&item.value);
+                    self.unify(&term, &item);
+                    self.bindings.truncate(len);
+                }
+                term
+            }
+            ExpressionKind::Binary(binary) => {
+                let left = self.expression(&binary.left);
+                let right = self.expression(&binary.right);
+                match binary.op {
+                    BinaryOp::Times
+                    | BinaryOp::DividedBy
+                    | BinaryOp::Plus
+                    | BinaryOp::Minus
+                    | BinaryOp::BitAnd
+                    | BinaryOp::BitOr => {
+                        self.unify(&left, &right);
+                        left
+                    }
+                    BinaryOp::IsEqualTo | BinaryOp::IsNotEqualTo | BinaryOp::SetTo => {
+                        self.unify(&left, &right);
+                        Term::Other
+                    }
+                    BinaryOp::And | BinaryOp::Or => Term::Other,
+                }
+            }
+            ExpressionKind::FunctionCall(function_call) => {
+                let signature = match &function_call.function.kind {
+                    ExpressionKind::Variable(symbol) if self.lookup(symbol.id).is_none() => {
+                        self.functions.get(&symbol.id).cloned()
+                    }
+                    _ => {
+                        self.expression(&function_call.function);
+                        None
+                    }
+                };
+                let arguments: Vec<_> = function_call
+                    .arguments
+                    .iter()
+                    .map(|argument| self.expression(argument))
+                    .collect();
+                match signature {
+                    Some((parameters, return_)) => {
+                        for (parameter, argument) in parameters.iter().zip(&arguments) {
+                            self.unify(parameter, argument);
+                        }
+                        return_
+                    }
+                    None => Term::Other,
+                }
+            }
+            ExpressionKind::MemberCall(member_call) => {
+                self.expression(&member_call.member.object);
+                for argument in &member_call.arguments {
+                    self.expression(argument);
+                }
+                Term::Other
+            }
+            ExpressionKind::MemberAccess(member_access) => {
+                self.expression(&member_access.object);
+                Term::Other
+            }
+            ExpressionKind::Tuple(expressions) => Term::Tuple(
+                expressions
+                    .iter()
+                    .map(|expression| self.expression(expression))
+                    .collect(),
+            ),
+            ExpressionKind::Ref(expression)
+            | ExpressionKind::RefMut(expression)
+            | ExpressionKind::RawConst(expression)
+            | ExpressionKind::RawMut(expression) => {
+                Term::Pointer(Box::new(self.expression(expression)))
+            }
+            ExpressionKind::Deref(expression) => {
+                let pointer = self.expression(expression);
+                let pointee = self.fresh();
+                self.unify(&pointer, &Term::Pointer(Box::new(pointee.clone())));
+                pointee
+            }
+            ExpressionKind::Cast(cast) => {
+                self.expression(&cast.expression);
+                self.type_term(&cast.type_)
+            }
+            ExpressionKind::Try(expression) => {
+                self.expression(expression);
+                self.fresh()
+            }
+            ExpressionKind::Return(value) => {
+                if let Some(value) = value {
+                    let value = self.expression(value);
+                    let return_ = self.return_.clone();
+                    self.unify(&return_, &value);
+                }
+                self.fresh()
+            }
+            ExpressionKind::Break(value) => {
+                if let Some(value) = value {
+                    let value = self.expression(value);
+                    if let Some(loop_) = self.loops.last().cloned() {
+                        self.unify(&loop_, &value);
+                    }
+                }
+                self.fresh()
+            }
+            ExpressionKind::Continue => self.fresh(),
+        }
+    }
+
+    fn finish(mut self) -> Integers {
+        for (_, var) in &self.literals {
+            if let Term::Var(var) = self.resolve(&Term::Var(*var)) {
+                self.vars[var] = Some(Term::Integer(integer::DEFAULT));
+            }
+        }
+        let literals = self
+            .literals
+            .iter()
+            .map(|(span, var)| (*span, Term::Var(*var)));
+        let types = self
+            .typed
+            .iter()
+            .cloned()
+            .chain(literals)
+            .filter_map(|(span, term)| match self.resolve(&term) {
+                Term::Integer(integer) => Some((span, integer)),
+                _ => None,
+            })
+            .collect();
+        Integers { types }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::convert;
+
+    /// The text of each typed binding and literal with its type, in order.
+    fn infer(file_contents: &str) -> Vec<(&str, &'static str)> {
+        let (diagnostic, top_levels) = convert(file_contents);
+        let integers = infer_integers(&diagnostic, &top_levels);
+        let mut types: Vec<_> = integers.types.into_iter().collect();
+        types.sort_by_key(|(span, _)| span.start);
+        types
+            .into_iter()
+            .map(|(span, integer)| (&file_contents[span.start..span.end], integer))
+            .collect()
+    }
+
+    #[test]
+    fn test_infer_integers() {
+        assert_eq!(
+            infer("fn f() { let x = 1; let y: u8 = 2; let z = y + 3; }"),
+            vec![
+                ("x", "i32"),
+                ("1", "i32"),
+                ("y", "u8"),
+                ("2", "u8"),
+                ("z", "u8"),
+                ("3", "u8")
+            ]
+        );
+        assert_eq!(
+            infer("fn next(p: &mut i64) {} fn f() { let mut i = 0; next(&mut i); }"),
+            vec![("i", "i64"), ("0", "i64")]
+        );
+        assert_eq!(
+            infer("fn f(p: &mut i64) { *p + 4294967296; }"),
+            vec![("4294967296", "i64")]
+        );
+        assert_eq!(
+            infer("fn f() { let m: i64 = 0 - 9 - 1; let x = 2; print_i64(x + 1); }"),
+            vec![
+                ("m", "i64"),
+                ("0", "i64"),
+                ("9", "i64"),
+                ("1", "i64"),
+                ("x", "i64"),
+                ("2", "i64"),
+                ("1", "i64")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_literals() {
+        let check = |file_contents| {
+            let (diagnostic, top_levels) = convert(file_contents);
+            check_literals(&diagnostic, &top_levels)
+        };
+        assert_eq!(
+            check("fn f() { let a = 2147483647; let b: u8 = 255; let c: i64 = 3000000000; }"),
+            vec![]
+        );
+        assert_eq!(
+            check("fn f(x: u8) { let big = 3000000000; if x == 256 {} }"),
+            vec![
+                Error::LiteralOutOfRange(
+                    "i32",
+                    Span {
+                        file: 0,
+                        start: 24,
+                        end: 34,
+                        expn: None,
+                    }
+                ),
+                Error::LiteralOutOfRange(
+                    "u8",
+                    Span {
+                        file: 0,
+                        start: 44,
+                        end: 47,
+                        expn: None,
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_infer_integers_through_control_flow() {
+        assert_eq!(
+            infer("fn f(c: bool) -> u16 { let x; if c { x = 1; } else { return 2; } x }"),
+            vec![("x", "u16"), ("1", "u16"), ("2", "u16")]
+        );
+        assert_eq!(
+            infer("fn f(o: Option) -> u64 { let v = match o { Some(y) => 1, None => loop { break 2; } }; v }"),
+            vec![("v", "u64"), ("1", "u64"), ("2", "u64")]
+        );
+        assert_eq!(
+            infer("fn f() { let (a, b) = (1, 2 as u8); let c = a as i64; }"),
+            vec![
+                ("a", "i32"),
+                ("b", "u8"),
+                ("1", "i32"),
+                ("2", "i32"),
+                ("c", "i64")
+            ]
+        );
+    }
+}
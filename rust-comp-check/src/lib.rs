@@ -0,0 +1,14 @@
+pub mod cast;
+pub mod divergence;
+pub mod integers;
+pub mod lint;
+pub mod moves;
+pub mod mutability;
+pub mod question;
+pub mod scope;
+pub mod visit;
+
+use rust_comp_core::pos;
+
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
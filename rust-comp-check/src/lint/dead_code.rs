@@ -0,0 +1,75 @@
+use super::{referenced_names, Level, Lint, LintContext, LintPass};
+use rust_comp_syntax::*;
+
+pub static DEAD_CODE: Lint = Lint {
+    name: "dead_code",
+    default_level: Level::Warn,
+    description: "private items that are never used",
+};
+
+/// Reports private functions, structs, and enums that aren't used outside
+/// of themselves.
+pub struct DeadCode;
+
+impl LintPass for DeadCode {
+    fn lints(&self) -> Vec<&'static Lint> {
+        vec![&DEAD_CODE]
+    }
+
+    fn check(&mut self, cx: &mut LintContext, top_levels: &[TopLevel]) {
+        let used: Vec<_> = top_levels
+            .iter()
+            .map(|top_level| referenced_names(cx, top_level))
+            .collect();
+        for (i, top_level) in top_levels.iter().enumerate() {
+            if top_level.visibility != Visibility::Private {
+                continue;
+            }
+            let (kind, name) = match &top_level.kind {
+                TopLevelKind::Function(function) => ("function", &function.name),
+                TopLevelKind::Struct(struct_) => ("struct", &struct_.name),
+                TopLevelKind::Enum(enum_) => ("enum", &enum_.name),
+                TopLevelKind::ModFile(_) | TopLevelKind::Use(_) => continue,
+            };
+            let name_text = cx.text(name.span);
+            if name_text == "main" {
+                continue;
+            }
+            let is_used = used
+                .iter()
+                .enumerate()
+                .any(|(j, names)| i != j && names.contains(name_text));
+            if !is_used {
+                cx.lint(
+                    &DEAD_CODE,
+                    name.span,
+                    format!("{} `{}` is never used", kind, name_text),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::messages;
+
+    #[test]
+    fn test_dead_code() {
+        assert_eq!(
+            messages(
+                "fn main() { helper(Origin); }
+                 fn helper(p: Point) { helper(p); unused(); }
+                 fn unused() {}
+                 fn recursive() { recursive(); }
+                 pub fn exported() {}
+                 enum Point { Origin }
+                 struct Unused { x: i32 }"
+            ),
+            [
+                "function `recursive` is never used",
+                "struct `Unused` is never used",
+            ]
+        );
+    }
+}
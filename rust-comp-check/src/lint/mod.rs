@@ -0,0 +1,534 @@
+mod dead_code;
+mod naming;
+mod unreachable;
+mod unused;
+
+pub use self::dead_code::{DeadCode, DEAD_CODE};
+pub use self::naming::{NamingConventions, NON_CAMEL_CASE_TYPES, NON_SNAKE_CASE};
+pub use self::unreachable::{UnreachableCode, UNREACHABLE_CODE};
+pub use self::unused::{UnusedImports, UnusedVariables, UNUSED_IMPORTS, UNUSED_VARIABLES};
+
+use crate::pos::Span;
+use crate::visit::*;
+use rust_comp_core::diagnostic::{Diagnostic, Emitter, Report, Severity};
+use rust_comp_syntax::*;
+use std::collections::{HashMap, HashSet};
+
+/// How a lint's findings are reported, from least to most severe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+    /// Like `Deny` but can't be lowered by attributes.
+    Forbid,
+}
+
+impl Level {
+    /// The name used in attributes.
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Allow => "allow",
+            Level::Warn => "warn",
+            Level::Deny => "deny",
+            Level::Forbid => "forbid",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Level> {
+        [Level::Allow, Level::Warn, Level::Deny, Level::Forbid]
+            .iter()
+            .copied()
+            .find(|level| level.name() == name)
+    }
+}
+
+/// Code that compiles but is probably a mistake.
+#[derive(Debug)]
+pub struct Lint {
+    /// The name used in attributes and flags, such as `unused_variables`.
+    pub name: &'static str,
+    pub default_level: Level,
+    pub description: &'static str,
+}
+
+/// Names in lint attributes that aren't lints.
+pub static UNKNOWN_LINTS: Lint = Lint {
+    name: "unknown_lints",
+    default_level: Level::Warn,
+    description: "unknown lints in attributes",
+};
+
+/// Checks the items of a file for some lints.  Implement this and pass it
+/// to `LintStore::register` to add lints.
+pub trait LintPass {
+    /// The lints the pass reports.
+    fn lints(&self) -> Vec<&'static Lint>;
+
+    fn check(&mut self, cx: &mut LintContext, top_levels: &[TopLevel]);
+}
+
+/// Collects what lint passes find.
+pub struct LintContext<'a> {
+    diagnostic: &'a Diagnostic,
+    findings: Vec<(&'static Lint, Report)>,
+}
+
+impl<'a> LintContext<'a> {
+    pub fn diagnostic(&self) -> &'a Diagnostic {
+        self.diagnostic
+    }
+
+    /// The code at `span`, such as the name of a symbol.
+    pub fn text(&self, span: Span) -> &'a str {
+        self.diagnostic.file_span(span)
+    }
+
+    pub fn lint(&mut self, lint: &'static Lint, span: Span, message: impl Into<String>) {
+        self.lint_report(lint, Report::warning(message, span));
+    }
+
+    /// Reports `report` for `lint`.  Its severity is replaced based on the
+    /// lint's level where it's reported.
+    pub fn lint_report(&mut self, lint: &'static Lint, report: Report) {
+        self.findings.push((lint, report));
+    }
+}
+
+/// Where a lint's level comes from.
+#[derive(Clone, Copy)]
+enum Source {
+    Default,
+    CommandLine,
+    Attribute(Span),
+}
+
+/// The lints that are checked and the levels set for them on the command
+/// line.
+pub struct LintStore {
+    passes: Vec<Box<dyn LintPass>>,
+    lints: Vec<&'static Lint>,
+    levels: HashMap<&'static str, Level>,
+}
+
+impl Default for LintStore {
+    fn default() -> Self {
+        let mut store = LintStore {
+            passes: Vec::new(),
+            lints: vec![&UNKNOWN_LINTS],
+            levels: HashMap::new(),
+        };
+        store.register(Box::new(UnusedVariables));
+        store.register(Box::new(UnusedImports));
+        store.register(Box::new(DeadCode));
+        store.register(Box::new(NamingConventions));
+        store.register(Box::new(UnreachableCode));
+        store
+    }
+}
+
+impl LintStore {
+    /// A store with the builtin lints.
+    pub fn new() -> Self {
+        LintStore::default()
+    }
+
+    pub fn register(&mut self, pass: Box<dyn LintPass>) {
+        self.lints.extend(pass.lints());
+        self.passes.push(pass);
+    }
+
+    pub fn lints(&self) -> &[&'static Lint] {
+        &self.lints
+    }
+
+    pub fn find(&self, name: &str) -> Option<&'static Lint> {
+        self.lints.iter().copied().find(|lint| lint.name == name)
+    }
+
+    /// Sets the level of the lint named `name` where attributes don't
+    /// change it.  If it's set more than once the most severe level is
+    /// kept.  Returns the name as an error if there's no such lint.
+    pub fn set_level(&mut self, name: &str, level: Level) -> Result<(), String> {
+        let lint = self.find(name).ok_or_else(|| name.to_string())?;
+        let current = self.levels.entry(lint.name).or_insert(level);
+        *current = (*current).max(level);
+        Ok(())
+    }
+
+    fn level(&self, lint: &Lint) -> (Level, Source) {
+        match self.levels.get(lint.name) {
+            Some(level) => (*level, Source::CommandLine),
+            None => (lint.default_level, Source::Default),
+        }
+    }
+
+    /// Runs every pass on the items of a file and emits what they find.
+    pub fn check(
+        &mut self,
+        diagnostic: &Diagnostic,
+        top_levels: &[TopLevel],
+        emitter: &mut Emitter,
+    ) {
+        let mut cx = LintContext {
+            diagnostic,
+            findings: Vec::new(),
+        };
+        let item_levels: Vec<_> = top_levels
+            .iter()
+            .map(|top_level| self.attribute_levels(&mut cx, emitter, top_level))
+            .collect();
+        for pass in &mut self.passes {
+            pass.check(&mut cx, top_levels);
+        }
+
+        cx.findings
+            .sort_by_key(|(_, report)| report.span.map(|span| (span.file, span.start)));
+        for (lint, mut report) in cx.findings {
+            let attribute = report
+                .span
+                .and_then(|span| {
+                    top_levels
+                        .iter()
+                        .position(|top_level| contains(top_level.span, span))
+                })
+                .and_then(|item| item_levels[item].get(lint.name));
+            let (level, source) = match attribute {
+                Some(&(level, span)) => (level, Source::Attribute(span)),
+                None => self.level(lint),
+            };
+            report.severity = match level {
+                Level::Allow => continue,
+                Level::Warn => Severity::Warning,
+                Level::Deny | Level::Forbid => Severity::Error,
+            };
+            report = match source {
+                Source::Default => report.with_note(format!(
+                    "`#[{}({})]` on by default",
+                    level.name(),
+                    lint.name
+                )),
+                Source::CommandLine => report.with_note(format!(
+                    "`{}({})` set on the command line",
+                    level.name(),
+                    lint.name
+                )),
+                Source::Attribute(span) => report.with_label(span, "the lint level is set here"),
+            };
+            emitter.emit(report);
+        }
+    }
+
+    /// The levels set by the attributes of `top_level` and the spans of the
+    /// attributes setting them.
+    fn attribute_levels(
+        &self,
+        cx: &mut LintContext,
+        emitter: &mut Emitter,
+        top_level: &TopLevel,
+    ) -> HashMap<&'static str, (Level, Span)> {
+        let mut levels: HashMap<&'static str, (Level, Span)> = HashMap::new();
+        for attribute in &top_level.attributes {
+            let level = match &attribute.path.segments[..] {
+                [name] => Level::from_name(cx.text(name.span)),
+                _ => None,
+            };
+            let level = match level {
+                Some(level) => level,
+                None => continue,
+            };
+            for path in &attribute.arguments {
+                let name = path
+                    .segments
+                    .iter()
+                    .map(|segment| cx.text(segment.span))
+                    .collect::<Vec<_>>()
+                    .join("::");
+                let span = Span {
+                    file: attribute.span.file,
+                    start: path.segments[0].span.start,
+                    end: path.segments[path.segments.len() - 1].span.end,
+                    expn: attribute.span.expn,
+                };
+                let lint = match self.find(&name) {
+                    Some(lint) => lint,
+                    None => {
+                        cx.lint(&UNKNOWN_LINTS, span, format!("unknown lint: `{}`", name));
+                        continue;
+                    }
+                };
+                let (current, current_span) = match levels.get(lint.name) {
+                    Some(&(level, span)) => (level, Some(span)),
+                    None => (self.level(lint).0, None),
+                };
+                if current == Level::Forbid && level != Level::Forbid {
+                    let mut report = Report::error(
+                        format!(
+                            "`{}({})` incompatible with `forbid({})`",
+                            level.name(),
+                            name,
+                            name
+                        ),
+                        span,
+                    )
+                    .with_code("RC0040");
+                    report = match current_span {
+                        Some(forbid) => report.with_label(forbid, "`forbid` set here"),
+                        None => report.with_note("`forbid` set on the command line"),
+                    };
+                    emitter.emit(report);
+                    continue;
+                }
+                levels.insert(lint.name, (level, attribute.span));
+            }
+        }
+        levels
+    }
+}
+
+fn contains(outer: Span, inner: Span) -> bool {
+    outer.file == inner.file && outer.start <= inner.start && inner.end <= outer.end
+}
+
+/// The names that items, patterns, and expressions in `top_level` refer to.
+/// Names are compared as text since those spelled in macros are hygienic.
+fn referenced_names<'a>(cx: &LintContext<'a>, top_level: &TopLevel) -> HashSet<&'a str> {
+    let mut collector = NameCollector {
+        cx,
+        names: HashSet::new(),
+    };
+    match &top_level.kind {
+        TopLevelKind::Function(function) => {
+            for parameter in &function.parameters {
+                collector.type_(&parameter.type_);
+            }
+            collector.type_(&function.return_type);
+            collector.visit_function(function);
+        }
+        TopLevelKind::Struct(struct_) => {
+            for field in &struct_.fields {
+                collector.type_(&field.type_);
+            }
+        }
+        TopLevelKind::Enum(enum_) => {
+            for variant in &enum_.variants {
+                if let VariantData::Tuple(types) = &variant.data {
+                    types.iter().for_each(|type_| collector.type_(type_));
+                }
+            }
+        }
+        TopLevelKind::ModFile(_) | TopLevelKind::Use(_) => {}
+    }
+    collector.names
+}
+
+struct NameCollector<'a, 'c> {
+    cx: &'c LintContext<'a>,
+    names: HashSet<&'a str>,
+}
+
+impl NameCollector<'_, '_> {
+    fn type_(&mut self, type_: &Type) {
+        match &type_.kind {
+            TypeKind::Named(name) => {
+                self.names.insert(self.cx.text(name.span));
+            }
+            TypeKind::Ref(type_)
+            | TypeKind::RefMut(type_)
+            | TypeKind::PtrConst(type_)
+            | TypeKind::PtrMut(type_) => self.type_(type_),
+            TypeKind::Tuple(types) => types.iter().for_each(|type_| self.type_(type_)),
+            TypeKind::Hole => {}
+        }
+    }
+}
+
+impl Visitor for NameCollector<'_, '_> {
+    fn visit_let(&mut self, let_: &Let) {
+        if let Some(type_) = &let_.type_ {
+            self.type_(type_);
+        }
+        walk_let(self, let_);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        match &expression.kind {
+            ExpressionKind::Variable(name) => {
+                self.names.insert(self.cx.text(name.span));
+            }
+            ExpressionKind::Cast(cast) => self.type_(&cast.type_),
+            _ => {}
+        }
+        walk_expression(self, expression);
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        match &pattern.kind {
+            PatternKind::Named(_) => {
+                self.names.insert(self.cx.text(pattern.span));
+            }
+            PatternKind::NamedTuple(name, patterns) => {
+                self.names.insert(self.cx.text(name.span));
+                patterns
+                    .iter()
+                    .for_each(|pattern| self.visit_pattern(pattern));
+            }
+            PatternKind::Tuple(patterns) => patterns
+                .iter()
+                .for_each(|pattern| self.visit_pattern(pattern)),
+            PatternKind::Hole | PatternKind::Value(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::convert;
+
+    /// The reports for linting `file_contents` with `store`.
+    pub(super) fn lint_with(store: &mut LintStore, file_contents: &str) -> Vec<Report> {
+        let (diagnostic, top_levels) = convert(file_contents);
+        let mut emitter = Emitter::new();
+        store.check(&diagnostic, &top_levels, &mut emitter);
+        emitter.reports().to_vec()
+    }
+
+    pub(super) fn lint(file_contents: &str) -> Vec<Report> {
+        lint_with(&mut LintStore::new(), file_contents)
+    }
+
+    /// The messages of the reports for `file_contents`.
+    pub(super) fn messages(file_contents: &str) -> Vec<String> {
+        lint(file_contents)
+            .into_iter()
+            .map(|report| report.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_level_from_name() {
+        assert_eq!(Level::from_name("deny"), Some(Level::Deny));
+        assert_eq!(Level::from_name("Deny"), None);
+        assert!(Level::Allow < Level::Warn && Level::Deny < Level::Forbid);
+    }
+
+    #[test]
+    fn test_default_level() {
+        let reports = lint("fn main() { let x = 1; }");
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].severity, Severity::Warning);
+        assert_eq!(
+            reports[0].children.last().unwrap().message,
+            "`#[warn(unused_variables)]` on by default"
+        );
+    }
+
+    #[test]
+    fn test_command_line_level() {
+        let mut store = LintStore::new();
+        store.set_level("unused_variables", Level::Deny).unwrap();
+        store.set_level("unused_variables", Level::Allow).unwrap();
+        assert_eq!(
+            store.set_level("unused_everything", Level::Deny),
+            Err("unused_everything".to_string())
+        );
+        let reports = lint_with(&mut store, "fn main() { let x = 1; }");
+        assert_eq!(reports[0].severity, Severity::Error);
+
+        let mut store = LintStore::new();
+        store.set_level("unused_variables", Level::Allow).unwrap();
+        assert_eq!(lint_with(&mut store, "fn main() { let x = 1; }"), []);
+    }
+
+    #[test]
+    fn test_attribute_levels() {
+        assert_eq!(
+            messages("#[allow(unused_variables)] fn main() { let x = 1; }"),
+            Vec::<String>::new()
+        );
+        let reports =
+            lint("#[deny(unused_variables)] fn main() { let x = 1; } pub fn g() { let y = 2; }");
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].severity, Severity::Error);
+        assert_eq!(
+            reports[0].labels,
+            [(
+                Span {
+                    file: 0,
+                    start: 0,
+                    end: 25,
+                    expn: None
+                },
+                "the lint level is set here".to_string()
+            )]
+        );
+        assert_eq!(reports[1].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_forbid() {
+        let mut store = LintStore::new();
+        store.set_level("unused_variables", Level::Forbid).unwrap();
+        let reports = lint_with(
+            &mut store,
+            "#[allow(unused_variables)] fn main() { let x = 1; }",
+        );
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].code, Some("RC0040"));
+        assert_eq!(reports[1].message, "unused variable: `x`");
+        assert_eq!(reports[1].severity, Severity::Error);
+
+        let reports = lint("#[forbid(dead_code)] #[allow(dead_code)] fn f() {}");
+        assert_eq!(
+            reports[0].message,
+            "`allow(dead_code)` incompatible with `forbid(dead_code)`"
+        );
+        assert_eq!(reports[1].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_unknown_lints() {
+        assert_eq!(
+            messages("#[allow(unused_varaibles)] #[inline] fn main() {}"),
+            ["unknown lint: `unused_varaibles`"]
+        );
+    }
+
+    struct NoMain;
+
+    static NO_MAIN: Lint = Lint {
+        name: "no_main",
+        default_level: Level::Deny,
+        description: "files without a `main` function",
+    };
+
+    impl LintPass for NoMain {
+        fn lints(&self) -> Vec<&'static Lint> {
+            vec![&NO_MAIN]
+        }
+
+        fn check(&mut self, cx: &mut LintContext, top_levels: &[TopLevel]) {
+            let has_main = top_levels.iter().any(|top_level| match &top_level.kind {
+                TopLevelKind::Function(function) => cx.text(function.name.span) == "main",
+                _ => false,
+            });
+            if !has_main {
+                if let Some(top_level) = top_levels.first() {
+                    cx.lint(&NO_MAIN, top_level.span, "file has no `main` function");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_register_custom_lint() {
+        let mut store = LintStore::new();
+        store.register(Box::new(NoMain));
+        assert!(store.find("no_main").is_some());
+        let reports = lint_with(&mut store, "pub fn f() {}");
+        assert_eq!(reports[0].message, "file has no `main` function");
+        assert_eq!(reports[0].severity, Severity::Error);
+        assert_eq!(lint_with(&mut store, "#[allow(no_main)] pub fn f() {}"), []);
+    }
+}
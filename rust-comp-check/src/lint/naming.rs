@@ -0,0 +1,139 @@
+use super::{Level, Lint, LintContext, LintPass};
+use rust_comp_core::diagnostic::{Applicability, Report, Suggestion};
+use rust_comp_syntax::*;
+
+pub static NON_SNAKE_CASE: Lint = Lint {
+    name: "non_snake_case",
+    default_level: Level::Warn,
+    description: "function names that aren't in `snake_case`",
+};
+
+pub static NON_CAMEL_CASE_TYPES: Lint = Lint {
+    name: "non_camel_case_types",
+    default_level: Level::Warn,
+    description: "struct, enum, and variant names that aren't in `CamelCase`",
+};
+
+/// Reports names that don't follow Rust's naming conventions.
+pub struct NamingConventions;
+
+impl LintPass for NamingConventions {
+    fn lints(&self) -> Vec<&'static Lint> {
+        vec![&NON_SNAKE_CASE, &NON_CAMEL_CASE_TYPES]
+    }
+
+    fn check(&mut self, cx: &mut LintContext, top_levels: &[TopLevel]) {
+        for top_level in top_levels {
+            match &top_level.kind {
+                TopLevelKind::Function(function) => {
+                    check_snake_case(cx, "function", &function.name)
+                }
+                TopLevelKind::Struct(struct_) => check_camel_case(cx, "struct", &struct_.name),
+                TopLevelKind::Enum(enum_) => {
+                    check_camel_case(cx, "enum", &enum_.name);
+                    for variant in &enum_.variants {
+                        check_camel_case(cx, "variant", &variant.name);
+                    }
+                }
+                TopLevelKind::ModFile(_) | TopLevelKind::Use(_) => {}
+            }
+        }
+    }
+}
+
+fn check_snake_case(cx: &mut LintContext, kind: &str, name: &Symbol) {
+    let text = cx.text(name.span);
+    if !text.chars().any(char::is_uppercase) {
+        return;
+    }
+    let report = Report::warning(
+        format!("{} `{}` should have a snake case name", kind, text),
+        name.span,
+    )
+    .with_suggestion(
+        "convert the identifier to snake case",
+        Suggestion::replace(
+            name.span,
+            to_snake_case(text),
+            Applicability::MaybeIncorrect,
+        ),
+    );
+    cx.lint_report(&NON_SNAKE_CASE, report);
+}
+
+fn check_camel_case(cx: &mut LintContext, kind: &str, name: &Symbol) {
+    let text = cx.text(name.span);
+    let trimmed = text.trim_matches('_');
+    if trimmed.is_empty() || trimmed.starts_with(char::is_uppercase) && !trimmed.contains('_') {
+        return;
+    }
+    let report = Report::warning(
+        format!("{} `{}` should have an upper camel case name", kind, text),
+        name.span,
+    )
+    .with_suggestion(
+        "convert the identifier to upper camel case",
+        Suggestion::replace(
+            name.span,
+            to_camel_case(text),
+            Applicability::MaybeIncorrect,
+        ),
+    );
+    cx.lint_report(&NON_CAMEL_CASE_TYPES, report);
+}
+
+/// `fooBar` to `foo_bar`.
+fn to_snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        if c.is_uppercase() && previous.is_some_and(|p| p.is_lowercase() || p.is_numeric()) {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+        previous = Some(c);
+    }
+    snake
+}
+
+/// `foo_bar` to `FooBar`.
+fn to_camel_case(name: &str) -> String {
+    name.split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().unwrap();
+            first.to_uppercase().chain(chars).collect::<String>()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::messages;
+    use super::*;
+
+    #[test]
+    fn test_naming_conventions() {
+        assert_eq!(
+            messages(
+                "pub fn parseFile() {} pub fn parse_file() {}
+                 pub struct point {} pub struct Point {}
+                 pub enum Color { Light_Red, Blue }"
+            ),
+            [
+                "function `parseFile` should have a snake case name",
+                "struct `point` should have an upper camel case name",
+                "variant `Light_Red` should have an upper camel case name",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_convert_case() {
+        assert_eq!(to_snake_case("parseFile"), "parse_file");
+        assert_eq!(to_snake_case("HTTPRequest2Go"), "httprequest2_go");
+        assert_eq!(to_camel_case("light_red"), "LightRed");
+        assert_eq!(to_camel_case("Light_Red"), "LightRed");
+    }
+}
@@ -0,0 +1,118 @@
+use super::{Level, Lint, LintContext, LintPass};
+use crate::divergence::expression_diverges;
+use crate::pos::Span;
+use crate::visit::*;
+use rust_comp_core::diagnostic::Report;
+use rust_comp_syntax::*;
+
+pub static UNREACHABLE_CODE: Lint = Lint {
+    name: "unreachable_code",
+    default_level: Level::Warn,
+    description: "code after an expression that never completes",
+};
+
+/// Reports the first statement or expression of a block following one
+/// that diverges.
+pub struct UnreachableCode;
+
+impl LintPass for UnreachableCode {
+    fn lints(&self) -> Vec<&'static Lint> {
+        vec![&UNREACHABLE_CODE]
+    }
+
+    fn check(&mut self, cx: &mut LintContext, top_levels: &[TopLevel]) {
+        let mut finder = Finder::default();
+        walk_top_levels(&mut finder, top_levels);
+        for (kind, span, diverging) in finder.unreachable {
+            let report = Report::warning(format!("unreachable {}", kind), span).with_label(
+                diverging,
+                "any code following this expression is unreachable",
+            );
+            cx.lint_report(&UNREACHABLE_CODE, report);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Finder {
+    /// What's unreachable, its span, and the span of the statement it
+    /// follows.
+    unreachable: Vec<(&'static str, Span, Span)>,
+}
+
+impl Visitor for Finder {
+    fn visit_block(&mut self, block: &Block) {
+        let diverging = block
+            .statements
+            .iter()
+            .position(|statement| match &statement.kind {
+                StatementKind::Empty => false,
+                StatementKind::Expression(expression) => expression_diverges(expression),
+                StatementKind::Let(let_) => let_.value.as_ref().is_some_and(expression_diverges),
+            });
+        let diverging = match diverging {
+            Some(diverging) => diverging,
+            None => return walk_block(self, block),
+        };
+        let following = block.statements[diverging + 1..]
+            .iter()
+            .find(|statement| statement.kind != StatementKind::Empty);
+        let unreachable = match (following, &block.expression) {
+            (Some(statement), _) => Some(("statement", statement.span)),
+            (None, Some(expression)) => Some(("expression", expression.span)),
+            (None, None) => None,
+        };
+        if let Some((kind, span)) = unreachable {
+            self.unreachable
+                .push((kind, span, block.statements[diverging].span));
+        }
+        // Only look inside the reachable statements.
+        for statement in &block.statements[..=diverging] {
+            match &statement.kind {
+                StatementKind::Empty => (),
+                StatementKind::Expression(expression) => self.visit_expression(expression),
+                StatementKind::Let(let_) => self.visit_let(let_),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{lint, messages};
+    use super::*;
+
+    #[test]
+    fn test_unreachable_code() {
+        assert_eq!(
+            messages(
+                "pub fn f(x: i32) -> i32 {
+                     if x == 0 { return 1; x; }
+                     loop { break; }
+                     loop { }
+                     2
+                 }
+                 pub fn g() -> i32 { return 1; ; }"
+            ),
+            ["unreachable statement", "unreachable expression"]
+        );
+    }
+
+    #[test]
+    fn test_unreachable_code_label() {
+        let reports = lint("pub fn f() -> i32 { return 1; 2 }");
+        assert_eq!(
+            reports[0].span,
+            Some(Span {
+                file: 0,
+                start: 30,
+                end: 31,
+                expn: None
+            })
+        );
+        assert_eq!(
+            reports[0].labels[0].1,
+            "any code following this expression is unreachable"
+        );
+    }
+}
@@ -0,0 +1,233 @@
+use super::{referenced_names, Level, Lint, LintContext, LintPass};
+use crate::pos::Span;
+use crate::visit::*;
+use rust_comp_core::diagnostic::{Applicability, Report, Suggestion};
+use rust_comp_syntax::*;
+
+pub static UNUSED_VARIABLES: Lint = Lint {
+    name: "unused_variables",
+    default_level: Level::Warn,
+    description: "variables that are never read",
+};
+
+pub static UNUSED_IMPORTS: Lint = Lint {
+    name: "unused_imports",
+    default_level: Level::Warn,
+    description: "`use` items whose name is never used",
+};
+
+/// Reports variables that are bound but never read.  Assigning to a
+/// variable doesn't count as reading it.
+pub struct UnusedVariables;
+
+impl LintPass for UnusedVariables {
+    fn lints(&self) -> Vec<&'static Lint> {
+        vec![&UNUSED_VARIABLES]
+    }
+
+    fn check(&mut self, cx: &mut LintContext, top_levels: &[TopLevel]) {
+        for top_level in top_levels {
+            if let TopLevelKind::Function(function) = &top_level.kind {
+                let mut visitor = Bindings {
+                    cx,
+                    scopes: vec![Vec::new()],
+                    bindings: Vec::new(),
+                };
+                for parameter in &function.parameters {
+                    visitor.bind(parameter.name.id, parameter.name.span);
+                }
+                visitor.visit_function(function);
+                let unused: Vec<Span> = visitor
+                    .bindings
+                    .into_iter()
+                    .filter(|(_, used)| !used)
+                    .map(|(span, _)| span)
+                    .collect();
+                for span in unused {
+                    let name = cx.text(span);
+                    let report = Report::warning(format!("unused variable: `{}`", name), span)
+                        .with_suggestion(
+                            "if this is intentional, prefix it with an underscore",
+                            Suggestion::replace(
+                                span,
+                                format!("_{}", name),
+                                Applicability::MachineApplicable,
+                            ),
+                        );
+                    cx.lint_report(&UNUSED_VARIABLES, report);
+                }
+            }
+        }
+    }
+}
+
+/// The variables bound in a function and whether each is read.
+struct Bindings<'a, 'c> {
+    cx: &'c LintContext<'a>,
+    /// The bindings in scope as indices into `bindings`.
+    scopes: Vec<Vec<(SymbolId, usize)>>,
+    bindings: Vec<(Span, bool)>,
+}
+
+impl Bindings<'_, '_> {
+    fn bind(&mut self, id: SymbolId, span: Span) {
+        let name = self.cx.text(span);
+        // Uppercase names are enum variants and constants.
+        if name.starts_with('_') || name.starts_with(char::is_uppercase) {
+            return;
+        }
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .push((id, self.bindings.len()));
+        self.bindings.push((span, false));
+    }
+
+    fn use_(&mut self, id: SymbolId) {
+        let binding = self
+            .scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|(binding, _)| *binding == id);
+        if let Some(&(_, index)) = binding {
+            self.bindings[index].1 = true;
+        }
+    }
+}
+
+impl Visitor for Bindings<'_, '_> {
+    fn visit_expression(&mut self, expression: &Expression) {
+        match &expression.kind {
+            ExpressionKind::Variable(name) => self.use_(name.id),
+            ExpressionKind::Binary(Binary {
+                left,
+                op: BinaryOp::SetTo,
+                right,
+            }) if matches!(left.kind, ExpressionKind::Variable(_)) => self.visit_expression(right),
+            _ => walk_expression(self, expression),
+        }
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        match &pattern.kind {
+            PatternKind::Named(id) => self.bind(*id, pattern.span),
+            PatternKind::Tuple(patterns) | PatternKind::NamedTuple(_, patterns) => patterns
+                .iter()
+                .for_each(|pattern| self.visit_pattern(pattern)),
+            PatternKind::Hole | PatternKind::Value(_) => {}
+        }
+    }
+
+    fn visit_for_var(&mut self, var: &Symbol) {
+        self.bind(var.id, var.span);
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    fn exit_scope(&mut self) {
+        self.scopes.pop();
+    }
+}
+
+/// Reports private `use` items whose name isn't used in the file.
+pub struct UnusedImports;
+
+impl LintPass for UnusedImports {
+    fn lints(&self) -> Vec<&'static Lint> {
+        vec![&UNUSED_IMPORTS]
+    }
+
+    fn check(&mut self, cx: &mut LintContext, top_levels: &[TopLevel]) {
+        let used: Vec<_> = top_levels
+            .iter()
+            .map(|top_level| referenced_names(cx, top_level))
+            .collect();
+        for top_level in top_levels {
+            let name = match (&top_level.visibility, &top_level.kind) {
+                (Visibility::Private, TopLevelKind::Use(use_)) => match &use_.suffix {
+                    UsePathSuffix::Item(name) => cx.text(name.span),
+                },
+                _ => continue,
+            };
+            if !used.iter().any(|names| names.contains(name)) {
+                let report = Report::warning(format!("unused import: `{}`", name), top_level.span)
+                    .with_suggestion(
+                        "remove the unused import",
+                        Suggestion::delete(top_level.span, Applicability::MachineApplicable),
+                    );
+                cx.lint_report(&UNUSED_IMPORTS, report);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{lint, messages};
+    use super::*;
+
+    #[test]
+    fn test_unused_variables() {
+        assert_eq!(
+            messages(
+                "pub fn f(a: i32, _b: i32, c: i32) -> i32 {
+                     let (x, y) = (1, 2);
+                     let mut z = 0;
+                     z = y;
+                     for i in c { }
+                     match Some(x) { Some(w) => 1, None => 2 }
+                 }"
+            ),
+            [
+                "unused variable: `a`",
+                "unused variable: `z`",
+                "unused variable: `i`",
+                "unused variable: `w`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unused_variables_shadowing() {
+        assert_eq!(
+            messages("pub fn f() -> i32 { let x = 1; let x = 2; x }"),
+            ["unused variable: `x`"]
+        );
+        assert_eq!(
+            messages("pub fn f() -> i32 { let x = 1; let x = x + 1; x }"),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_unused_variables_suggestion() {
+        let reports = lint("fn main() { let count = 1; }");
+        assert_eq!(
+            reports[0].all_suggestions().collect::<Vec<_>>(),
+            [&Suggestion::replace(
+                Span {
+                    file: 0,
+                    start: 16,
+                    end: 21,
+                    expn: None
+                },
+                "_count",
+                Applicability::MachineApplicable
+            )]
+        );
+    }
+
+    #[test]
+    fn test_unused_imports() {
+        assert_eq!(
+            messages(
+                "use a::b; use a::c; pub use a::d; use a::E;
+                 fn main() -> E { b() }"
+            ),
+            ["unused import: `c`"]
+        );
+    }
+}
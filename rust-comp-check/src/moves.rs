@@ -0,0 +1,774 @@
+use crate::pos::Span;
+use rust_comp_syntax::*;
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The use and where the value was moved.
+    UseAfterMove(Span, Span),
+}
+
+/// A binding holding a value that isn't `Copy` or a reference is dropped
+/// when leaving its scope.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Drop {
+    /// The scope being exited or the `return`, `break`, `continue`, or `?`
+    /// leaving it.
+    pub span: Span,
+    /// The binding's declaration.
+    pub binding: Span,
+    /// Whether the binding is only moved on some paths so a drop flag has
+    /// to record whether it still holds a value.
+    pub flag: bool,
+}
+
+/// A use that moves the value out of a binding.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Move {
+    /// The moving use.
+    pub span: Span,
+    /// The binding's declaration.
+    pub binding: Span,
+}
+
+pub struct Moves {
+    pub errors: Vec<Error>,
+    /// Drops at the same span run in order.
+    pub drops: Vec<Drop>,
+    pub moves: Vec<Move>,
+}
+
+/// Checks that bindings aren't used after their value is moved.
+pub fn check_moves(top_levels: &[TopLevel]) -> Vec<Error> {
+    analyze_moves(top_levels).errors
+}
+
+/// Finds uses of moved bindings and where bindings are dropped.
+pub fn analyze_moves(top_levels: &[TopLevel]) -> Moves {
+    let mut analyzer = Analyzer::new(top_levels);
+    for top_level in top_levels {
+        if let TopLevelKind::Function(function) = &top_level.kind {
+            analyzer.function(top_level.span, function);
+        }
+    }
+    Moves {
+        errors: analyzer.errors,
+        drops: analyzer.drops,
+        moves: analyzer.moves,
+    }
+}
+
+/// Whether values are copied or moved.  Tuples are tracked per element so
+/// destructuring binds each element with its own kind.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Kind {
+    Copy,
+    Move,
+    /// A `&mut` reference.  It's moved except when passed to a function,
+    /// where it's reborrowed.
+    RefMut,
+    Tuple(Vec<Kind>),
+}
+
+impl Kind {
+    fn is_copy(&self) -> bool {
+        match self {
+            Kind::Copy => true,
+            Kind::Move | Kind::RefMut => false,
+            Kind::Tuple(kinds) => kinds.iter().all(Kind::is_copy),
+        }
+    }
+
+    /// References aren't dropped even though `&mut` ones are moved.
+    fn needs_drop(&self) -> bool {
+        match self {
+            Kind::Move => true,
+            Kind::Copy | Kind::RefMut => false,
+            Kind::Tuple(kinds) => kinds.iter().any(Kind::needs_drop),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    /// `let x;` before it is assigned.
+    Uninitialized,
+    Initialized,
+    /// Moved on every path.
+    Moved(Span),
+    /// Initialized on some paths.  Holds a place it may have been moved.
+    MaybeMoved(Option<Span>),
+}
+
+impl State {
+    fn join(self, other: State) -> State {
+        match (self, other) {
+            _ if self == other => self,
+            (State::Moved(moved), State::Moved(_))
+            | (State::Moved(moved), State::Uninitialized)
+            | (State::Uninitialized, State::Moved(moved)) => State::Moved(moved),
+            (State::Moved(moved), _)
+            | (_, State::Moved(moved))
+            | (State::MaybeMoved(Some(moved)), _)
+            | (_, State::MaybeMoved(Some(moved))) => State::MaybeMoved(Some(moved)),
+            _ => State::MaybeMoved(None),
+        }
+    }
+}
+
+/// The states of the bindings in scope or `None` if unreachable.
+type Flow = Option<Vec<State>>;
+
+fn join(a: Flow, b: Flow) -> Flow {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.into_iter().zip(b).map(|(a, b)| a.join(b)).collect()),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+/// Structs, enums, and variants hold whether the type is `Copy`.
+enum Item<'a> {
+    Struct(bool),
+    Enum(bool),
+    Variant(bool),
+    Function(&'a Type),
+}
+
+struct Binding {
+    id: SymbolId,
+    span: Span,
+    kind: Kind,
+    state: State,
+}
+
+struct Loop {
+    /// The number of bindings in scope outside the loop.
+    bindings: usize,
+    breaks: Flow,
+    continues: Flow,
+}
+
+struct Analyzer<'a> {
+    items: HashMap<SymbolId, Item<'a>>,
+    /// Bindings in scope, innermost last.
+    bindings: Vec<Binding>,
+    loops: Vec<Loop>,
+    reachable: bool,
+    errors: Vec<Error>,
+    drops: Vec<Drop>,
+    moves: Vec<Move>,
+}
+
+impl<'a> Analyzer<'a> {
+    fn new(top_levels: &'a [TopLevel]) -> Self {
+        let mut items = HashMap::new();
+        for top_level in top_levels {
+            match &top_level.kind {
+                TopLevelKind::Function(function) => {
+                    items.insert(function.name.id, Item::Function(&function.return_type));
+                }
+                TopLevelKind::Struct(struct_) => {
+                    items.insert(struct_.name.id, Item::Struct(top_level.derives("Copy")));
+                }
+                TopLevelKind::Enum(enum_) => {
+                    let copy = top_level.derives("Copy");
+                    items.insert(enum_.name.id, Item::Enum(copy));
+                    for variant in &enum_.variants {
+                        items.insert(variant.name.id, Item::Variant(copy));
+                    }
+                }
+                _ => (),
+            }
+        }
+        Analyzer {
+            items,
+            bindings: Vec::new(),
+            loops: Vec::new(),
+            reachable: true,
+            errors: Vec::new(),
+            drops: Vec::new(),
+            moves: Vec::new(),
+        }
+    }
+
+    /// Primitives, shared references, pointers, tuples of `Copy` types, and
+    /// structs and enums with `#[derive(Copy)]` are `Copy`.  `Option` and
+    /// `Result` are treated as `Copy` because their data isn't known.
+    fn kind_of_type(&self, type_: &Type) -> Kind {
+        match &type_.kind {
+            TypeKind::Named(name) => match self.items.get(&name.id) {
+                Some(Item::Struct(false)) | Some(Item::Enum(false)) => Kind::Move,
+                _ => Kind::Copy,
+            },
+            TypeKind::Tuple(types) => {
+                Kind::Tuple(types.iter().map(|type_| self.kind_of_type(type_)).collect())
+            }
+            TypeKind::RefMut(_) => Kind::RefMut,
+            TypeKind::Ref(_) | TypeKind::PtrConst(_) | TypeKind::PtrMut(_) | TypeKind::Hole => {
+                Kind::Copy
+            }
+        }
+    }
+
+    /// Guesses the kind of the value of `expression`.  Values whose type
+    /// can't be determined are assumed to be `Copy`.
+    fn kind_of(&self, expression: &Expression) -> Kind {
+        match &expression.kind {
+            ExpressionKind::Variable(symbol) => match self.lookup(symbol.id) {
+                Some(binding) => self.bindings[binding].kind.clone(),
+                None => match self.items.get(&symbol.id) {
+                    Some(Item::Variant(false)) => Kind::Move,
+                    _ => Kind::Copy,
+                },
+            },
+            ExpressionKind::FunctionCall(function_call) => match &function_call.function.kind {
+                ExpressionKind::Variable(symbol) if self.lookup(symbol.id).is_none() => {
+                    match self.items.get(&symbol.id) {
+                        Some(Item::Variant(false)) => Kind::Move,
+                        Some(Item::Function(return_type)) => self.kind_of_type(return_type),
+                        _ => Kind::Copy,
+                    }
+                }
+                _ => Kind::Copy,
+            },
+            ExpressionKind::Tuple(expressions) => Kind::Tuple(
+                expressions
+                    .iter()
+                    .map(|expression| self.kind_of(expression))
+                    .collect(),
+            ),
+            ExpressionKind::RefMut(_) => Kind::RefMut,
+            ExpressionKind::Block(block) => match &block.expression {
+                Some(expression) => self.kind_of(expression),
+                None => Kind::Copy,
+            },
+            ExpressionKind::If(if_) => match &if_.then.expression {
+                Some(expression) => self.kind_of(expression),
+                None => Kind::Copy,
+            },
+            ExpressionKind::Match(match_) => match match_.matches.first() {
+                Some(item) => self.kind_of(&item.value),
+                None => Kind::Copy,
+            },
+            _ => Kind::Copy,
+        }
+    }
+
+    fn lookup(&self, id: SymbolId) -> Option<usize> {
+        self.bindings.iter().rposition(|binding| binding.id == id)
+    }
+
+    fn save(&self) -> Flow {
+        if self.reachable {
+            Some(self.bindings.iter().map(|binding| binding.state).collect())
+        } else {
+            None
+        }
+    }
+
+    fn restore(&mut self, flow: Flow) {
+        match flow {
+            Some(states) => {
+                self.reachable = true;
+                for (binding, state) in self.bindings.iter_mut().zip(states) {
+                    binding.state = state;
+                }
+            }
+            None => self.reachable = false,
+        }
+    }
+
+    fn bind_pattern(&mut self, pattern: &Pattern, kind: Kind, state: State) {
+        match &pattern.kind {
+            PatternKind::Named(id) => self.bindings.push(Binding {
+                id: *id,
+                span: pattern.span,
+                kind,
+                state,
+            }),
+            PatternKind::Tuple(patterns) => {
+                for (i, pattern) in patterns.iter().enumerate() {
+                    let kind = match &kind {
+                        Kind::Tuple(kinds) => kinds.get(i).cloned().unwrap_or(Kind::Copy),
+                        _ => Kind::Copy,
+                    };
+                    self.bind_pattern(pattern, kind, state);
+                }
+            }
+            PatternKind::NamedTuple(_, patterns) => {
+                for pattern in patterns {
+                    self.bind_pattern(pattern, Kind::Copy, state);
+                }
+            }
+            PatternKind::Hole | PatternKind::Value(_) => (),
+        }
+    }
+
+    /// Drops the bindings from `scope` onwards in reverse order.
+    fn drop_bindings(&mut self, scope: usize, span: Span) {
+        if !self.reachable {
+            return;
+        }
+        let drops: Vec<Drop> = self.bindings[scope..]
+            .iter()
+            .rev()
+            .filter(|binding| binding.kind.needs_drop())
+            .filter_map(|binding| {
+                let flag = match binding.state {
+                    State::Initialized => false,
+                    State::MaybeMoved(_) => true,
+                    State::Uninitialized | State::Moved(_) => return None,
+                };
+                Some(Drop {
+                    span,
+                    binding: binding.span,
+                    flag,
+                })
+            })
+            .collect();
+        for drop in drops {
+            // Loop bodies are analyzed twice.
+            match self
+                .drops
+                .iter_mut()
+                .find(|existing| existing.span == drop.span && existing.binding == drop.binding)
+            {
+                Some(existing) => existing.flag |= drop.flag,
+                None => self.drops.push(drop),
+            }
+        }
+    }
+
+    fn exit_scope(&mut self, scope: usize, span: Span) {
+        self.drop_bindings(scope, span);
+        self.bindings.truncate(scope);
+    }
+
+    fn function(&mut self, span: Span, function: &Function) {
+        self.reachable = true;
+        for parameter in &function.parameters {
+            self.bindings.push(Binding {
+                id: parameter.name.id,
+                span: parameter.span,
+                kind: self.kind_of_type(&parameter.type_),
+                state: State::Initialized,
+            });
+        }
+        self.block(&function.body, span, true);
+        self.exit_scope(0, span);
+    }
+
+    /// Analyzes `block`.  `moving` is whether its value is moved.
+    fn block(&mut self, block: &Block, span: Span, moving: bool) {
+        let scope = self.bindings.len();
+        for statement in &block.statements {
+            match &statement.kind {
+                StatementKind::Empty => (),
+                StatementKind::Expression(expression) => self.expression(expression, true),
+                StatementKind::Let(let_) => self.let_(let_),
+            }
+        }
+        if let Some(expression) = &block.expression {
+            self.expression(expression, moving);
+        }
+        self.exit_scope(scope, span);
+    }
+
+    fn let_(&mut self, let_: &Let) {
+        if let Some(value) = &let_.value {
+            self.expression(value, true);
+        }
+        if let Some(else_) = &let_.else_ {
+            let flow = self.save();
+            self.block(&else_.block, else_.span, false);
+            self.restore(flow);
+        }
+        let kind = match (&let_.type_, &let_.value) {
+            (Some(type_), _) => self.kind_of_type(type_),
+            (None, Some(value)) => self.kind_of(value),
+            (None, None) => Kind::Copy,
+        };
+        let state = if let_.value.is_some() {
+            State::Initialized
+        } else {
+            State::Uninitialized
+        };
+        self.bind_pattern(&let_.pattern, kind, state);
+    }
+
+    fn expression(&mut self, expression: &Expression, moving: bool) {
+        match &expression.kind {
+            ExpressionKind::Variable(symbol) => self.use_(symbol.id, expression.span, moving),
+            ExpressionKind::Value(_) => (),
+            ExpressionKind::Block(block) => self.block(block, expression.span, moving),
+            ExpressionKind::If(if_) => self.if_(if_, expression.span, moving),
+            ExpressionKind::Loop(loop_) => self.loop_(&loop_.block, expression.span, None, None),
+            ExpressionKind::While(while_) => self.loop_(
+                &while_.block,
+                expression.span,
+                Some(&while_.condition),
+                None,
+            ),
+            ExpressionKind::For(for_) => {
+                self.expression(&for_.expr, true);
+                self.loop_(&for_.block, expression.span, None, Some(&for_.var));
+            }
+            ExpressionKind::Match(match_) => {
+                self.expression(&match_.value, false);
+                let before = self.save();
+                let mut after = None;
+                for item in &match_.matches {
+                    self.restore(before.clone());
+                    let scope = self.bindings.len();
+                    self.bind_pattern(&item.pattern, Kind::Copy, State::Initialized);
+                    self.expression(&item.value, moving);
+                    self.exit_scope(scope, item.span);
+                    after = join(after, self.save());
+                }
+                self.restore(after);
+            }
+            ExpressionKind::Binary(binary) => match binary.op {
+                BinaryOp::SetTo => {
+                    self.expression(&binary.right, true);
+                    self.assign(&binary.left);
+                }
+                BinaryOp::And | BinaryOp::Or => {
+                    self.expression(&binary.left, false);
+                    let after_left = self.save();
+                    self.expression(&binary.right, false);
+                    let flow = join(after_left, self.save());
+                    self.restore(flow);
+                }
+                _ => {
+                    self.expression(&binary.left, false);
+                    self.expression(&binary.right, false);
+                }
+            },
+            ExpressionKind::FunctionCall(function_call) => {
+                self.expression(&function_call.function, false);
+                self.arguments(&function_call.arguments);
+            }
+            ExpressionKind::MemberCall(member_call) => {
+                self.expression(&member_call.member.object, false);
+                self.arguments(&member_call.arguments);
+            }
+            ExpressionKind::MemberAccess(member_access) => {
+                self.expression(&member_access.object, false)
+            }
+            ExpressionKind::Tuple(expressions) => {
+                for expression in expressions {
+                    self.expression(expression, true);
+                }
+            }
+            ExpressionKind::Ref(place)
+            | ExpressionKind::RefMut(place)
+            | ExpressionKind::RawConst(place)
+            | ExpressionKind::RawMut(place)
+            | ExpressionKind::Deref(place) => self.expression(place, false),
+            ExpressionKind::Cast(cast) => self.expression(&cast.expression, false),
+            ExpressionKind::Try(value) => {
+                self.expression(value, true);
+                self.drop_bindings(0, expression.span);
+            }
+            ExpressionKind::Return(value) => {
+                if let Some(value) = value {
+                    self.expression(value, true);
+                }
+                self.drop_bindings(0, expression.span);
+                self.reachable = false;
+            }
+            ExpressionKind::Break(value) => {
+                if let Some(value) = value {
+                    self.expression(value, true);
+                }
+                self.exit_loop(expression.span, true);
+            }
+            ExpressionKind::Continue => self.exit_loop(expression.span, false),
+        }
+    }
+
+    /// Moves the arguments of a call except `&mut` references, which are
+    /// reborrowed.
+    fn arguments(&mut self, arguments: &[Expression]) {
+        for argument in arguments {
+            let reborrowed = match &argument.kind {
+                ExpressionKind::Variable(symbol) => self
+                    .lookup(symbol.id)
+                    .is_some_and(|binding| self.bindings[binding].kind == Kind::RefMut),
+                _ => false,
+            };
+            self.expression(argument, !reborrowed);
+        }
+    }
+
+    fn use_(&mut self, id: SymbolId, span: Span, moving: bool) {
+        let binding = match self.lookup(id) {
+            Some(binding) => binding,
+            None => return,
+        };
+        if !self.reachable {
+            return;
+        }
+        let binding = &mut self.bindings[binding];
+        if let State::Moved(moved) | State::MaybeMoved(Some(moved)) = binding.state {
+            let error = Error::UseAfterMove(span, moved);
+            if !self.errors.contains(&error) {
+                self.errors.push(error);
+            }
+        }
+        if moving && !binding.kind.is_copy() {
+            binding.state = State::Moved(span);
+            let move_ = Move {
+                span,
+                binding: binding.span,
+            };
+            // Loop bodies are analyzed twice.
+            if !self.moves.contains(&move_) {
+                self.moves.push(move_);
+            }
+        }
+    }
+
+    /// Assigning to a binding gives it a new value.
+    fn assign(&mut self, place: &Expression) {
+        match &place.kind {
+            ExpressionKind::Variable(symbol) => {
+                if let Some(binding) = self.lookup(symbol.id) {
+                    self.bindings[binding].state = State::Initialized;
+                }
+            }
+            _ => self.expression(place, false),
+        }
+    }
+
+    fn if_(&mut self, if_: &If, span: Span, moving: bool) {
+        let scope = self.bindings.len();
+        match &if_.condition {
+            Condition::Expression(condition) => self.expression(condition, false),
+            Condition::Let(let_) => self.expression(&let_.value, false),
+        }
+        let after_condition = self.save();
+        if let Condition::Let(let_) = &if_.condition {
+            self.bind_pattern(&let_.pattern, Kind::Copy, State::Initialized);
+        }
+        self.block(&if_.then, span, moving);
+        self.exit_scope(scope, span);
+        let after_then = self.save();
+
+        self.restore(after_condition);
+        if let Some(else_) = &if_.else_ {
+            match &else_.kind {
+                ElseKind::If(if_) => self.if_(if_, else_.span, moving),
+                ElseKind::Block(block) => self.block(block, else_.span, moving),
+            }
+        }
+        let flow = join(after_then, self.save());
+        self.restore(flow);
+    }
+
+    /// Analyzes the body twice so moves in one iteration are seen by the
+    /// next.
+    fn loop_(
+        &mut self,
+        block: &Block,
+        span: Span,
+        condition: Option<&Condition>,
+        var: Option<&Symbol>,
+    ) {
+        let scope = self.bindings.len();
+        let entry = self.save();
+        self.loops.push(Loop {
+            bindings: scope,
+            breaks: None,
+            continues: None,
+        });
+        let mut head = entry.clone();
+        let mut exit = None;
+        for _ in 0..2 {
+            self.restore(head.clone());
+            match condition {
+                Some(Condition::Expression(condition)) => self.expression(condition, false),
+                Some(Condition::Let(let_)) => self.expression(&let_.value, false),
+                None => (),
+            }
+            // `loop` can only be left by `break`.
+            exit = if condition.is_some() || var.is_some() {
+                self.save()
+            } else {
+                None
+            };
+            if let Some(Condition::Let(let_)) = condition {
+                self.bind_pattern(&let_.pattern, Kind::Copy, State::Initialized);
+            }
+            if let Some(var) = var {
+                self.bind_pattern(
+                    &Pattern {
+                        span: var.span,
+                        kind: PatternKind::Named(var.id),
+                    },
+                    Kind::Copy,
+                    State::Initialized,
+                );
+            }
+            self.block(block, span, false);
+            self.exit_scope(scope, span);
+            let continues = self.loops.last_mut().unwrap().continues.take();
+            head = join(entry.clone(), join(self.save(), continues));
+        }
+        let loop_ = self.loops.pop().unwrap();
+        self.restore(join(exit, loop_.breaks));
+    }
+
+    fn exit_loop(&mut self, span: Span, break_: bool) {
+        let scope = match self.loops.last() {
+            Some(loop_) => loop_.bindings,
+            None => {
+                self.reachable = false;
+                return;
+            }
+        };
+        self.drop_bindings(scope, span);
+        let flow = self.save().map(|mut states| {
+            states.truncate(scope);
+            states
+        });
+        let loop_ = self.loops.last_mut().unwrap();
+        let target = if break_ {
+            &mut loop_.breaks
+        } else {
+            &mut loop_.continues
+        };
+        *target = join(target.take(), flow);
+        self.reachable = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::convert;
+    use assert_matches::assert_matches;
+
+    fn analyze(s: &str) -> Moves {
+        let (_, top_levels) = convert(s);
+        analyze_moves(&top_levels)
+    }
+
+    fn check(s: &str) -> Vec<Error> {
+        analyze(s).errors
+    }
+
+    /// The drops in `f`.
+    fn drops(s: &str) -> Vec<Drop> {
+        let f = s.find("fn f").unwrap();
+        let mut drops = analyze(s).drops;
+        drops.retain(|drop| drop.span.start >= f);
+        drops
+    }
+
+    #[test]
+    fn test_use_after_move_in_let() {
+        let s = "enum E { A } fn f() { let e = A; let g = e; let h = e; }";
+        let errors = check(s);
+        assert_eq!(errors.len(), 1);
+        assert_matches!(errors[0], Error::UseAfterMove(used, moved)
+            if &s[used] == "e" && &s[moved] == "e" && moved.start < used.start);
+    }
+
+    #[test]
+    fn test_use_after_move_in_argument() {
+        let s = "enum E { A } fn take(e: E) {} fn f(e: E) { take(e); take(e); }";
+        assert_matches!(check(s)[..], [Error::UseAfterMove(..)]);
+    }
+
+    #[test]
+    fn test_copy_types_are_not_moved() {
+        let s = "enum E { A }
+                 #[derive(Clone, Copy)]
+                 struct P { x: i32, y: &E }
+                 #[derive(Clone, Copy)]
+                 enum C { D }
+                 fn f(p: P, e: E) {
+                     let d = D; let g = d; let h = d;
+                     let a = 1; let b = a; let c = a;
+                     let r = &e; let s = r; let t = r;
+                     let q = p; let u = p;
+                     let v = (a, r); let w = v; let x = v;
+                 }";
+        assert_eq!(check(s), []);
+    }
+
+    #[test]
+    fn test_structs_of_moved_fields_are_moved() {
+        let s = "enum E { A } struct P { x: i32, e: E } fn f(p: P) { let q = p; let r = p; }";
+        assert_matches!(check(s)[..], [Error::UseAfterMove(..)]);
+    }
+
+    #[test]
+    fn test_structs_are_moved_unless_they_derive_copy() {
+        let s = "struct P { x: i32 } fn f(p: P) { let q = p; let r = p; }";
+        assert_matches!(check(s)[..], [Error::UseAfterMove(..)]);
+    }
+
+    #[test]
+    fn test_mutable_references_are_moved() {
+        let s = "fn f(r: &mut i32) { let s = r; let t = r; }";
+        assert_matches!(check(s)[..], [Error::UseAfterMove(..)]);
+        let s = "fn f() { let mut x = 1; let r = &mut x; let s = r; let t = r; }";
+        assert_matches!(check(s)[..], [Error::UseAfterMove(..)]);
+        // Passing one to a function reborrows it.
+        let s = "fn g(r: &mut i32) {} fn f(r: &mut i32) { g(r); g(r); }";
+        assert_eq!(check(s), []);
+    }
+
+    #[test]
+    fn test_assignment_reinitializes() {
+        let s = "enum E { A, B }
+                 fn take(e: E) {}
+                 fn f() { let mut e = A; take(e); e = B; take(e); }";
+        assert_eq!(check(s), []);
+    }
+
+    #[test]
+    fn test_use_after_move_in_loop() {
+        let s = "enum E { A } fn take(e: E) {} fn f() { let e = A; loop { take(e); } }";
+        assert_matches!(check(s)[..], [Error::UseAfterMove(used, moved)] if used == moved);
+    }
+
+    #[test]
+    fn test_use_after_move_in_one_branch() {
+        let s = "enum E { A }
+                 fn take(e: E) {}
+                 fn f(c: bool) { let e = A; if c { take(e); } else {} take(e); }";
+        assert_matches!(check(s)[..], [Error::UseAfterMove(..)]);
+    }
+
+    #[test]
+    fn test_drops_at_end_of_scope() {
+        let s = "enum E { A, B }
+                 fn take(e: E) {}
+                 fn f() { let e = A; let g = B; let h = A; take(h); }";
+        let drops = drops(s);
+        assert_eq!(drops.len(), 2);
+        assert_eq!(&s[drops[0].binding], "g");
+        assert_eq!(&s[drops[1].binding], "e");
+        assert!(!drops[0].flag && !drops[1].flag);
+    }
+
+    #[test]
+    fn test_conditional_move_uses_drop_flag() {
+        let s = "enum E { A } fn take(e: E) {} fn f(c: bool) { let e = A; if c { take(e); } }";
+        assert_eq!(check(s), []);
+        assert_matches!(drops(s)[..], [Drop { binding, flag: true, .. }] if &s[binding] == "e");
+    }
+
+    #[test]
+    fn test_drops_on_return() {
+        let s = "enum E { A }
+                 fn take(e: E) {}
+                 fn f(c: bool) { let e = A; if c { return; } take(e); }";
+        let drops = drops(s);
+        assert_matches!(drops[..], [Drop { span, flag: false, .. }] if &s[span] == "return");
+    }
+}
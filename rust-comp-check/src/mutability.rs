@@ -0,0 +1,471 @@
+use crate::divergence::{block_diverges, expression_diverges, if_diverges};
+use crate::pos::Span;
+use crate::visit::*;
+use rust_comp_syntax::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Assign,
+    BorrowMut,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The place is a binding not declared `mut`.  The second span is the
+    /// binding's declaration.
+    ImmutableBinding(Access, Span, Span),
+    /// The place is behind a `&` reference.
+    BehindRef(Access, Span),
+    /// The place is behind a `*const` pointer.
+    BehindPtrConst(Access, Span),
+}
+
+/// Checks that every assignment and mutable borrow is of a mutable place.
+pub fn check_mutability(top_levels: &[TopLevel]) -> Vec<Error> {
+    let mut checker = Checker::default();
+    walk_top_levels(&mut checker, top_levels);
+    checker.errors
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Pointer {
+    Ref,
+    RefMut,
+    PtrConst,
+    PtrMut,
+}
+
+struct Binding {
+    id: SymbolId,
+    span: Span,
+    mutable: bool,
+    /// `let x;` may be assigned once on each path even if it isn't `mut`.
+    /// Cleared once some path reaching here may have assigned it.
+    deferred: bool,
+    pointer: Option<Pointer>,
+}
+
+#[derive(Default)]
+struct Checker {
+    /// Bindings in scope, innermost last.
+    bindings: Vec<Binding>,
+    /// The length of `bindings` when each scope was entered.
+    scopes: Vec<usize>,
+    errors: Vec<Error>,
+}
+
+impl Visitor for Checker {
+    fn visit_function(&mut self, function: &Function) {
+        for parameter in &function.parameters {
+            self.bind(
+                parameter.name.id,
+                parameter.span,
+                pointer_of_type(&parameter.type_),
+            );
+        }
+        walk_function(self, function);
+        self.bindings.clear();
+    }
+
+    fn visit_let(&mut self, let_: &Let) {
+        let id = match let_.pattern.kind {
+            PatternKind::Named(id) => id,
+            _ => return walk_let(self, let_),
+        };
+        if let Some(value) = &let_.value {
+            self.visit_expression(value);
+        }
+        if let Some(else_) = &let_.else_ {
+            self.visit_block(&else_.block);
+        }
+        let pointer = match (&let_.type_, &let_.value) {
+            (Some(type_), _) => pointer_of_type(type_),
+            (None, Some(value)) => self.pointer_of(value),
+            (None, None) => None,
+        };
+        self.bindings.push(Binding {
+            id,
+            span: let_.pattern.span,
+            mutable: let_.mutable,
+            deferred: let_.value.is_none(),
+            pointer,
+        });
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        match &expression.kind {
+            ExpressionKind::If(if_) => self.visit_if(if_),
+            ExpressionKind::Match(match_) => self.visit_match(match_),
+            // A second pass finds assignments repeated by later iterations.
+            ExpressionKind::Loop(_) | ExpressionKind::While(_) | ExpressionKind::For(_) => {
+                walk_expression(self, expression);
+                walk_expression(self, expression);
+            }
+            _ => walk_expression(self, expression),
+        }
+        match &expression.kind {
+            ExpressionKind::Binary(binary) if binary.op == BinaryOp::SetTo => {
+                self.check_place(&binary.left, Access::Assign)
+            }
+            ExpressionKind::RefMut(place) | ExpressionKind::RawMut(place) => {
+                self.check_place(place, Access::BorrowMut)
+            }
+            _ => (),
+        }
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        self.bind_pattern(pattern);
+    }
+
+    fn visit_for_var(&mut self, var: &Symbol) {
+        self.bind(var.id, var.span, None);
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(self.bindings.len());
+    }
+
+    fn exit_scope(&mut self) {
+        let scope = self.scopes.pop().unwrap();
+        self.bindings.truncate(scope);
+    }
+}
+
+impl Checker {
+    /// Visits the branches of `if_` each from the state before them, then
+    /// joins the states of those that complete.
+    fn visit_if(&mut self, if_: &If) {
+        self.enter_scope();
+        walk_condition(self, &if_.condition);
+        let before = self.deferred();
+        self.visit_block(&if_.then);
+        self.exit_scope();
+        let then = (self.deferred(), block_diverges(&if_.then));
+        self.set_deferred(&before);
+        let else_diverges = match if_.else_.as_ref().map(|else_| &else_.kind) {
+            Some(ElseKind::If(if_)) => {
+                self.visit_if(if_);
+                if_diverges(if_)
+            }
+            Some(ElseKind::Block(block)) => {
+                self.visit_block(block);
+                block_diverges(block)
+            }
+            None => false,
+        };
+        let else_ = (self.deferred(), else_diverges);
+        self.join(&[then, else_]);
+    }
+
+    fn visit_match(&mut self, match_: &Match) {
+        self.visit_expression(&match_.value);
+        let before = self.deferred();
+        let mut arms = Vec::new();
+        for item in &match_.matches {
+            self.set_deferred(&before);
+            self.enter_scope();
+            self.visit_pattern(&item.pattern);
+            self.visit_expression(&item.value);
+            self.exit_scope();
+            arms.push((self.deferred(), expression_diverges(&item.value)));
+        }
+        if !arms.is_empty() {
+            self.join(&arms);
+        }
+    }
+
+    fn deferred(&self) -> Vec<bool> {
+        self.bindings
+            .iter()
+            .map(|binding| binding.deferred)
+            .collect()
+    }
+
+    /// Restores the bindings still in scope to `deferred`.
+    fn set_deferred(&mut self, deferred: &[bool]) {
+        for (binding, &deferred) in self.bindings.iter_mut().zip(deferred) {
+            binding.deferred = deferred;
+        }
+    }
+
+    /// A binding is still unassigned after branches only if it is on every
+    /// branch that completes.
+    fn join(&mut self, branches: &[(Vec<bool>, bool)]) {
+        let completing: Vec<_> = branches.iter().filter(|(_, diverges)| !diverges).collect();
+        let completing = if completing.is_empty() {
+            branches.iter().collect()
+        } else {
+            completing
+        };
+        for (i, binding) in self.bindings.iter_mut().enumerate() {
+            binding.deferred = completing.iter().all(|(deferred, _)| deferred[i]);
+        }
+    }
+
+    fn error(&mut self, error: Error) {
+        // Loop bodies are visited twice.
+        if !self.errors.contains(&error) {
+            self.errors.push(error);
+        }
+    }
+
+    fn check_place(&mut self, place: &Expression, access: Access) {
+        match &place.kind {
+            ExpressionKind::Variable(symbol) => {
+                let binding = match self.lookup_mut(symbol.id) {
+                    Some(binding) => binding,
+                    None => return,
+                };
+                if binding.mutable {
+                    return;
+                }
+                if binding.deferred && access == Access::Assign {
+                    binding.deferred = false;
+                    return;
+                }
+                let binding_span = binding.span;
+                self.error(Error::ImmutableBinding(access, place.span, binding_span));
+            }
+            ExpressionKind::Deref(pointer) => self.check_behind(pointer, place.span, access),
+            ExpressionKind::MemberAccess(member_access) => {
+                match self.pointer_of(&member_access.object) {
+                    Some(_) => self.check_behind(&member_access.object, place.span, access),
+                    None => self.check_place(&member_access.object, access),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn check_behind(&mut self, pointer: &Expression, span: Span, access: Access) {
+        match self.pointer_of(pointer) {
+            Some(Pointer::Ref) => self.error(Error::BehindRef(access, span)),
+            Some(Pointer::PtrConst) => self.error(Error::BehindPtrConst(access, span)),
+            Some(Pointer::RefMut) | Some(Pointer::PtrMut) | None => (),
+        }
+    }
+
+    /// Finds the kind of pointer `expression` evaluates to, if it is obvious
+    /// without type inference.
+    fn pointer_of(&self, expression: &Expression) -> Option<Pointer> {
+        match &expression.kind {
+            ExpressionKind::Variable(symbol) => self.lookup(symbol.id)?.pointer,
+            ExpressionKind::Ref(_) => Some(Pointer::Ref),
+            ExpressionKind::RefMut(_) => Some(Pointer::RefMut),
+            ExpressionKind::RawConst(_) => Some(Pointer::PtrConst),
+            ExpressionKind::RawMut(_) => Some(Pointer::PtrMut),
+            ExpressionKind::Cast(cast) => pointer_of_type(&cast.type_),
+            _ => None,
+        }
+    }
+
+    fn bind(&mut self, id: SymbolId, span: Span, pointer: Option<Pointer>) {
+        self.bindings.push(Binding {
+            id,
+            span,
+            mutable: false,
+            deferred: false,
+            pointer,
+        });
+    }
+
+    fn bind_pattern(&mut self, pattern: &Pattern) {
+        match &pattern.kind {
+            PatternKind::Named(id) => self.bind(*id, pattern.span, None),
+            PatternKind::Tuple(patterns) | PatternKind::NamedTuple(_, patterns) => {
+                for pattern in patterns {
+                    self.bind_pattern(pattern);
+                }
+            }
+            PatternKind::Hole | PatternKind::Value(_) => (),
+        }
+    }
+
+    fn lookup(&self, id: SymbolId) -> Option<&Binding> {
+        self.bindings.iter().rev().find(|binding| binding.id == id)
+    }
+
+    fn lookup_mut(&mut self, id: SymbolId) -> Option<&mut Binding> {
+        self.bindings
+            .iter_mut()
+            .rev()
+            .find(|binding| binding.id == id)
+    }
+}
+
+fn pointer_of_type(type_: &Type) -> Option<Pointer> {
+    match type_.kind {
+        TypeKind::Ref(_) => Some(Pointer::Ref),
+        TypeKind::RefMut(_) => Some(Pointer::RefMut),
+        TypeKind::PtrConst(_) => Some(Pointer::PtrConst),
+        TypeKind::PtrMut(_) => Some(Pointer::PtrMut),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::convert;
+    use assert_matches::assert_matches;
+
+    fn check(file_contents: &str) -> Vec<Error> {
+        check_mutability(&convert(file_contents).1)
+    }
+
+    #[test]
+    fn test_assign_to_mut_binding() {
+        assert_eq!(check("fn f() { let mut x = 1; x = 2; }"), vec![]);
+    }
+
+    #[test]
+    fn test_assign_to_immutable_binding() {
+        assert_eq!(
+            check("fn f() { let x = 1; x = 2; }"),
+            vec![Error::ImmutableBinding(
+                Access::Assign,
+                Span {
+                    file: 0,
+                    start: 20,
+                    end: 21,
+                    expn: None
+                },
+                Span {
+                    file: 0,
+                    start: 13,
+                    end: 14,
+                    expn: None
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn test_assign_to_parameter() {
+        assert_matches!(
+            check("fn f(x: i32) { x = 2; }").as_slice(),
+            [Error::ImmutableBinding(Access::Assign, _, _)]
+        );
+    }
+
+    #[test]
+    fn test_deferred_initialization_is_assignable_once() {
+        assert_matches!(
+            check("fn f() { let x; x = 1; x = 2; }").as_slice(),
+            [Error::ImmutableBinding(
+                Access::Assign,
+                Span { start: 23, .. },
+                _
+            )]
+        );
+    }
+
+    #[test]
+    fn test_deferred_initialization_per_branch() {
+        assert_eq!(
+            check("fn f(c: bool) { let x: i64; if c { x = 1; } else { x = 2; } }"),
+            vec![]
+        );
+        assert_eq!(
+            check("fn f(o: Option) { let x; match o { Some(y) => x = y, None => x = 0 } }"),
+            vec![]
+        );
+        assert_eq!(
+            check("fn f(c: bool) { let x; if c { x = 1; return; } x = 2; }"),
+            vec![]
+        );
+        assert_matches!(
+            check("fn f(c: bool) { let x; if c { x = 1; } x = 2; }").as_slice(),
+            [Error::ImmutableBinding(
+                Access::Assign,
+                Span { start: 39, .. },
+                _
+            )]
+        );
+        assert_matches!(
+            check("fn f() { let x; loop { x = 1; } }").as_slice(),
+            [Error::ImmutableBinding(
+                Access::Assign,
+                Span { start: 23, .. },
+                _
+            )]
+        );
+    }
+
+    #[test]
+    fn test_shadowed_binding_uses_innermost() {
+        assert_eq!(
+            check("fn f() { let x = 1; { let mut x = 2; x = 3; } }"),
+            vec![]
+        );
+        assert_matches!(
+            check("fn f() { let mut x = 1; { let x = 2; x = 3; } }").as_slice(),
+            [Error::ImmutableBinding(..)]
+        );
+    }
+
+    #[test]
+    fn test_assign_through_ref() {
+        assert_matches!(
+            check("fn f(r: &i32) { *r = 1; }").as_slice(),
+            [Error::BehindRef(Access::Assign, _)]
+        );
+    }
+
+    #[test]
+    fn test_assign_through_ref_mut() {
+        assert_eq!(check("fn f(r: &mut i32) { *r = 1; }"), vec![]);
+    }
+
+    #[test]
+    fn test_assign_through_inferred_ref() {
+        assert_matches!(
+            check("fn f() { let mut x = 1; let r = &x; *r = 2; }").as_slice(),
+            [Error::BehindRef(Access::Assign, _)]
+        );
+    }
+
+    #[test]
+    fn test_assign_field_through_ref() {
+        assert_matches!(
+            check("fn f(p: &Point) { p.x = 1; }").as_slice(),
+            [Error::BehindRef(Access::Assign, _)]
+        );
+    }
+
+    #[test]
+    fn test_assign_field_of_immutable_binding() {
+        assert_matches!(
+            check("fn f(p: Point) { p.x = 1; }").as_slice(),
+            [Error::ImmutableBinding(Access::Assign, _, _)]
+        );
+    }
+
+    #[test]
+    fn test_assign_through_ptr_const() {
+        assert_matches!(
+            check("fn f(p: *const i32) { *p = 1; *(p as *mut i32) = 1; }").as_slice(),
+            [Error::BehindPtrConst(Access::Assign, _)]
+        );
+    }
+
+    #[test]
+    fn test_borrow_immutable_binding_as_mut() {
+        assert_matches!(
+            check("fn f() { let x = 1; let r = &mut x; let p = &raw mut x; }").as_slice(),
+            [
+                Error::ImmutableBinding(Access::BorrowMut, _, _),
+                Error::ImmutableBinding(Access::BorrowMut, _, _),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_borrow_mut_through_ref() {
+        assert_matches!(
+            check("fn f(r: &i32) { let m = &mut *r; }").as_slice(),
+            [Error::BehindRef(Access::BorrowMut, _)]
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use crate::pos::Span;
+use crate::visit::*;
+use rust_comp_syntax::*;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The `?` expression and the return type of the function containing it.
+    IncompatibleReturnType(Span, Span),
+}
+
+/// Checks that `?` is only used in functions returning `Result` or `Option`.
+pub fn check_try(top_levels: &[TopLevel]) -> Vec<Error> {
+    let mut checker = Checker::default();
+    walk_top_levels(&mut checker, top_levels);
+    checker.errors
+}
+
+/// Replaces each `?` with a `match` that returns early on failure:
+///
+/// * `e?` in a `Result` function becomes
+///   `match e { Ok(v) => v, Err(e) => return Err(e) }`.
+/// * `e?` in an `Option` function becomes
+///   `match e { Some(v) => v, None => return None }`.
+///
+/// `?` in other functions is left as is; `check_try` reports them.
+pub fn desugar_try(top_levels: &mut [TopLevel]) {
+    for top_level in top_levels {
+        if let TopLevelKind::Function(function) = &mut top_level.kind {
+            if let Some(family) = family_of(&function.return_type) {
+                Desugarer { family }.visit_block_mut(&mut function.body);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Family {
+    Result,
+    Option,
+}
+
+fn family_of(type_: &Type) -> Option<Family> {
+    match &type_.kind {
+        TypeKind::Named(name) if name.id == SymbolId::of_name("Result") => Some(Family::Result),
+        TypeKind::Named(name) if name.id == SymbolId::of_name("Option") => Some(Family::Option),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct Checker {
+    /// The family and span of the current function's return type.
+    return_type: Option<(Option<Family>, Span)>,
+    errors: Vec<Error>,
+}
+
+impl Visitor for Checker {
+    fn visit_function(&mut self, function: &Function) {
+        let return_type = &function.return_type;
+        self.return_type = Some((family_of(return_type), return_type.span));
+        walk_function(self, function);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        walk_expression(self, expression);
+        if let ExpressionKind::Try(_) = expression.kind {
+            if let Some((None, return_type_span)) = self.return_type {
+                self.errors.push(Error::IncompatibleReturnType(
+                    expression.span,
+                    return_type_span,
+                ));
+            }
+        }
+    }
+}
+
+struct Desugarer {
+    family: Family,
+}
+
+impl VisitorMut for Desugarer {
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression);
+        if let ExpressionKind::Try(_) = expression.kind {
+            let kind = std::mem::replace(&mut expression.kind, ExpressionKind::Continue);
+            if let ExpressionKind::Try(operand) = kind {
+                expression.kind = ExpressionKind::Match(self.desugar(*operand, expression.span));
+            }
+        }
+    }
+}
+
+impl Desugarer {
+    fn desugar(&self, operand: Expression, span: Span) -> Match {
+        // These names can't be spelled in source so they can't collide with
+        // the user's names.
+        let value = SymbolId::of_name("?value");
+        let error = SymbolId::of_name("?error");
+        let symbol = |name| Symbol {
+            span,
+            id: SymbolId::of_name(name),
+        };
+        let pattern = |kind| Pattern { span, kind };
+        let expression = |kind| Expression { span, kind };
+        let variable = |id| expression(ExpressionKind::Variable(Symbol { span, id }));
+
+        let (success, failure) = match self.family {
+            Family::Result => ("Ok", "Err"),
+            Family::Option => ("Some", "None"),
+        };
+        let (failure_pattern, failure_value) = match self.family {
+            Family::Result => (
+                PatternKind::NamedTuple(symbol(failure), vec![pattern(PatternKind::Named(error))]),
+                ExpressionKind::FunctionCall(FunctionCall {
+                    function: Box::new(variable(symbol(failure).id)),
+                    arguments: vec![variable(error)],
+                }),
+            ),
+            Family::Option => (
+                PatternKind::Named(symbol(failure).id),
+                ExpressionKind::Variable(symbol(failure)),
+            ),
+        };
+        Match {
+            value: Box::new(operand),
+            matches: vec![
+                MatchItem {
+                    span,
+                    pattern: pattern(PatternKind::NamedTuple(
+                        symbol(success),
+                        vec![pattern(PatternKind::Named(value))],
+                    )),
+                    value: variable(value),
+                },
+                MatchItem {
+                    span,
+                    pattern: pattern(failure_pattern),
+                    value: expression(ExpressionKind::Return(Some(Box::new(expression(
+                        failure_value,
+                    ))))),
+                },
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::convert;
+    use assert_matches::assert_matches;
+
+    fn check(file_contents: &str) -> Vec<Error> {
+        check_try(&convert(file_contents).1)
+    }
+
+    fn desugar(file_contents: &str) -> Expression {
+        let mut top_levels = convert(file_contents).1;
+        desugar_try(&mut top_levels);
+        match top_levels.pop().unwrap().kind {
+            TopLevelKind::Function(function) => *function.body.expression.unwrap(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_try_in_result_and_option_functions() {
+        assert_eq!(check("fn f() -> Result { g()? }"), vec![]);
+        assert_eq!(check("fn f() -> Option { g()? }"), vec![]);
+    }
+
+    #[test]
+    fn test_try_in_unit_function() {
+        assert_eq!(
+            check("fn f() { g()?; }"),
+            vec![Error::IncompatibleReturnType(
+                Span {
+                    file: 0,
+                    start: 9,
+                    end: 13,
+                    expn: None,
+                },
+                Span {
+                    file: 0,
+                    start: 7,
+                    end: 8,
+                    expn: None,
+                },
+            )]
+        );
+    }
+
+    #[test]
+    fn test_try_return_type_is_per_function() {
+        assert_matches!(
+            check("fn f() -> Option { g()? } fn h() -> i32 { if let Some(x) = g()? { x } }")
+                .as_slice(),
+            [Error::IncompatibleReturnType(_, _)]
+        );
+    }
+
+    #[test]
+    fn test_desugar_try_in_result_function() {
+        let expression = desugar("fn f() -> Result { g()? }");
+        assert_matches!(expression.kind, ExpressionKind::Match(match_) => {
+            assert_matches!(match_.value.kind, ExpressionKind::FunctionCall(_));
+            assert_matches!(&match_.matches[0].pattern.kind, PatternKind::NamedTuple(ok, _) => {
+                assert_eq!(ok.id, SymbolId::of_name("Ok"));
+            });
+            assert_matches!(&match_.matches[1].pattern.kind, PatternKind::NamedTuple(err, _) => {
+                assert_eq!(err.id, SymbolId::of_name("Err"));
+            });
+            assert_matches!(&match_.matches[1].value.kind, ExpressionKind::Return(Some(value)) => {
+                assert_matches!(value.kind, ExpressionKind::FunctionCall(_));
+            });
+        });
+    }
+
+    #[test]
+    fn test_desugar_try_in_option_function() {
+        let expression = desugar("fn f() -> Option { g()? }");
+        assert_matches!(expression.kind, ExpressionKind::Match(match_) => {
+            assert_matches!(&match_.matches[1].pattern.kind, PatternKind::Named(none) => {
+                assert_eq!(*none, SymbolId::of_name("None"));
+            });
+            assert_matches!(&match_.matches[1].value.kind, ExpressionKind::Return(Some(value)) => {
+                assert_matches!(&value.kind, ExpressionKind::Variable(none) => {
+                    assert_eq!(none.id, SymbolId::of_name("None"));
+                });
+            });
+        });
+    }
+
+    #[test]
+    fn test_desugar_nested_try() {
+        let expression = desugar("fn f() -> Option { g()?.h()? }");
+        assert_matches!(expression.kind, ExpressionKind::Match(match_) => {
+            assert_matches!(match_.value.kind, ExpressionKind::MemberCall(member_call) => {
+                assert_matches!(member_call.member.object.kind, ExpressionKind::Match(_));
+            });
+        });
+    }
+}
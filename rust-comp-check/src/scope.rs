@@ -0,0 +1,297 @@
+use crate::pos::Span;
+use crate::visit::*;
+use rust_comp_core::diagnostic::Diagnostic;
+use rust_comp_core::suggest;
+use rust_comp_syntax::*;
+use std::collections::HashSet;
+
+/// The values every file can name without defining them: the variants of
+/// `Option` and `Result`, and the `print_i64` builtin.
+const PRELUDE: &[&str] = &["None", "Some", "Ok", "Err", "print_i64"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// A variable that names no binding in scope, item, or prelude value.
+    UndefinedName(Span),
+}
+
+/// Checks that every variable resolves to a binding in scope, a function or
+/// enum variant, something imported with `use`, or a prelude value.
+pub fn check_names(top_levels: &[TopLevel]) -> Vec<Error> {
+    let mut items: HashSet<SymbolId> = PRELUDE.iter().map(|name| SymbolId::of_name(name)).collect();
+    for top_level in top_levels {
+        match &top_level.kind {
+            TopLevelKind::Function(function) => {
+                items.insert(function.name.id);
+            }
+            TopLevelKind::Enum(enum_) => {
+                items.extend(enum_.variants.iter().map(|variant| variant.name.id));
+            }
+            TopLevelKind::Use(use_) => match &use_.suffix {
+                UsePathSuffix::Item(name) => {
+                    items.insert(name.id);
+                }
+            },
+            TopLevelKind::Struct(_) | TopLevelKind::ModFile(_) => {}
+        }
+    }
+    let mut resolver = Resolver {
+        items,
+        scopes: Vec::new(),
+        errors: Vec::new(),
+    };
+    for top_level in top_levels {
+        if let TopLevelKind::Function(function) = &top_level.kind {
+            resolver.scopes.push(
+                function
+                    .parameters
+                    .iter()
+                    .map(|parameter| parameter.name.id)
+                    .collect(),
+            );
+            resolver.visit_function(function);
+            resolver.scopes.pop();
+        }
+    }
+    resolver.errors
+}
+
+/// The name in scope most similar to the variable at `span`, which
+/// couldn't be resolved.
+pub fn similar_name<'a>(
+    diagnostic: &'a Diagnostic,
+    top_levels: &[TopLevel],
+    span: Span,
+) -> Option<&'a str> {
+    let names = names_in_scope(diagnostic, top_levels, span)?;
+    suggest::best_match(diagnostic.file_span(span), names)
+}
+
+/// The names of the prelude values, the items, and the variables in scope
+/// at the variable at `span`, innermost variables last.  Returns `None` if
+/// there's no variable at `span`.
+pub fn names_in_scope<'a>(
+    diagnostic: &'a Diagnostic,
+    top_levels: &[TopLevel],
+    span: Span,
+) -> Option<Vec<&'a str>> {
+    let mut finder = ScopeFinder {
+        diagnostic,
+        span,
+        scopes: Vec::new(),
+        found: None,
+    };
+    for top_level in top_levels {
+        if let TopLevelKind::Function(function) = &top_level.kind {
+            if finder.found.is_none() && contains(top_level.span, span) {
+                finder.scopes.push(
+                    function
+                        .parameters
+                        .iter()
+                        .map(|parameter| diagnostic.file_span(parameter.name.span))
+                        .collect(),
+                );
+                finder.visit_function(function);
+            }
+        }
+    }
+    let mut names = item_names(diagnostic, top_levels);
+    names.extend(finder.found?);
+    Some(names)
+}
+
+fn item_names<'a>(diagnostic: &'a Diagnostic, top_levels: &[TopLevel]) -> Vec<&'a str> {
+    let mut names = PRELUDE.to_vec();
+    let mut spans = Vec::new();
+    for top_level in top_levels {
+        match &top_level.kind {
+            TopLevelKind::Function(function) => spans.push(function.name.span),
+            TopLevelKind::Struct(struct_) => spans.push(struct_.name.span),
+            TopLevelKind::Enum(enum_) => {
+                spans.push(enum_.name.span);
+                spans.extend(enum_.variants.iter().map(|variant| variant.name.span));
+            }
+            TopLevelKind::Use(use_) => match &use_.suffix {
+                UsePathSuffix::Item(name) => spans.push(name.span),
+            },
+            TopLevelKind::ModFile(_) => {}
+        }
+    }
+    names.extend(spans.into_iter().map(|span| diagnostic.file_span(span)));
+    names
+}
+
+fn contains(outer: Span, inner: Span) -> bool {
+    outer.file == inner.file && outer.start <= inner.start && inner.end <= outer.end
+}
+
+struct ScopeFinder<'a> {
+    diagnostic: &'a Diagnostic,
+    span: Span,
+    scopes: Vec<Vec<&'a str>>,
+    found: Option<Vec<&'a str>>,
+}
+
+impl<'a> ScopeFinder<'a> {
+    fn bind(&mut self, span: Span) {
+        let name = self.diagnostic.file_span(span);
+        self.scopes.last_mut().unwrap().push(name);
+    }
+}
+
+impl Visitor for ScopeFinder<'_> {
+    fn visit_expression(&mut self, expression: &Expression) {
+        match &expression.kind {
+            ExpressionKind::Variable(name) if name.span == self.span => {
+                self.found = Some(self.scopes.concat());
+            }
+            _ => walk_expression(self, expression),
+        }
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        match &pattern.kind {
+            PatternKind::Named(_) => self.bind(pattern.span),
+            PatternKind::Tuple(patterns) | PatternKind::NamedTuple(_, patterns) => patterns
+                .iter()
+                .for_each(|pattern| self.visit_pattern(pattern)),
+            PatternKind::Hole | PatternKind::Value(_) => {}
+        }
+    }
+
+    fn visit_for_var(&mut self, var: &Symbol) {
+        self.bind(var.span);
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    fn exit_scope(&mut self) {
+        self.scopes.pop();
+    }
+}
+
+struct Resolver {
+    items: HashSet<SymbolId>,
+    scopes: Vec<Vec<SymbolId>>,
+    errors: Vec<Error>,
+}
+
+impl Resolver {
+    fn bind(&mut self, id: SymbolId) {
+        self.scopes.last_mut().unwrap().push(id);
+    }
+}
+
+impl Visitor for Resolver {
+    fn visit_expression(&mut self, expression: &Expression) {
+        match &expression.kind {
+            ExpressionKind::Variable(name) => {
+                if !self.items.contains(&name.id)
+                    && !self.scopes.iter().any(|scope| scope.contains(&name.id))
+                {
+                    self.errors.push(Error::UndefinedName(name.span));
+                }
+            }
+            _ => walk_expression(self, expression),
+        }
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        match &pattern.kind {
+            PatternKind::Named(id) => self.bind(*id),
+            PatternKind::Tuple(patterns) | PatternKind::NamedTuple(_, patterns) => patterns
+                .iter()
+                .for_each(|pattern| self.visit_pattern(pattern)),
+            PatternKind::Hole | PatternKind::Value(_) => {}
+        }
+    }
+
+    fn visit_for_var(&mut self, var: &Symbol) {
+        self.bind(var.id);
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    fn exit_scope(&mut self) {
+        self.scopes.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::convert;
+
+    #[test]
+    fn test_names_in_scope() {
+        let file_contents = "fn f(a: i32) {
+                                 let b = 1;
+                                 { let c = 2; }
+                                 let d = e;
+                                 let f = 3;
+                             }
+                             enum E { V }";
+        let (diagnostic, top_levels) = convert(file_contents);
+        let start = file_contents.find("e;").unwrap();
+        let span = Span {
+            file: 0,
+            start,
+            end: start + 1,
+            expn: None,
+        };
+        assert_eq!(
+            names_in_scope(&diagnostic, &top_levels, span),
+            Some(vec![
+                "None",
+                "Some",
+                "Ok",
+                "Err",
+                "print_i64",
+                "f",
+                "E",
+                "V",
+                "a",
+                "b"
+            ])
+        );
+        assert_eq!(
+            names_in_scope(&diagnostic, &top_levels, Span { end: start, ..span }),
+            None
+        );
+    }
+
+    #[test]
+    fn test_similar_name() {
+        let file_contents = "fn area(length: i32, width: i32) -> i32 { lenght * width }";
+        let (diagnostic, top_levels) = convert(file_contents);
+        let start = file_contents.find("lenght").unwrap();
+        let span = Span {
+            file: 0,
+            start,
+            end: start + 6,
+            expn: None,
+        };
+        assert_eq!(similar_name(&diagnostic, &top_levels, span), Some("length"));
+    }
+
+    #[test]
+    fn test_check_names() {
+        let file_contents = "fn f(a: i32) -> i32 {
+                                 let b = a;
+                                 { let c = b; }
+                                 for i in c { print_i64(i); }
+                                 match Some(b) { Some(d) => d, None => e }
+                             }
+                             fn g() -> i32 { f(i) }";
+        let (diagnostic, top_levels) = convert(file_contents);
+        let names: Vec<_> = check_names(&top_levels)
+            .into_iter()
+            .map(|Error::UndefinedName(span)| diagnostic.file_span(span))
+            .collect();
+        assert_eq!(names, ["c", "e", "i"]);
+    }
+}
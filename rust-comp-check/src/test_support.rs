@@ -0,0 +1,26 @@
+//! Takes code through the front end for the tests of this crate and the
+//! crates after it.
+
+use crate::question;
+use rust_comp_core::diagnostic::Diagnostic;
+use rust_comp_front::{expand, lex, parse, parse_to_syntax};
+use rust_comp_syntax::TopLevel;
+
+/// Lexes, parses, expands, and converts `file_contents` as file 0.  Panics
+/// if it doesn't parse.
+pub fn convert(file_contents: &str) -> (Diagnostic, Vec<TopLevel>) {
+    let (tokens, eofpos) = lex::read_tokens(0, file_contents).unwrap();
+    let mut top_levels = parse::parse(file_contents, &tokens, eofpos).unwrap();
+    let expansions = expand::expand(file_contents, &mut top_levels).unwrap();
+    let mut diagnostic = Diagnostic::with_contents("test", file_contents.to_string());
+    diagnostic.source_map_mut().set_expansions(0, expansions);
+    let top_levels = parse_to_syntax::Context::new(&diagnostic).convert_top_levels(&top_levels);
+    (diagnostic, top_levels)
+}
+
+/// Like `convert` but also desugars `?` as the backends expect.
+pub fn desugar(file_contents: &str) -> (Diagnostic, Vec<TopLevel>) {
+    let (diagnostic, mut top_levels) = convert(file_contents);
+    question::desugar_try(&mut top_levels);
+    (diagnostic, top_levels)
+}
@@ -0,0 +1,274 @@
+use rust_comp_syntax::*;
+
+/// Walks the syntax tree of a function.  Each method defaults to visiting
+/// the children of the node so implementations only need to override the
+/// nodes they are interested in.
+pub trait Visitor: Sized {
+    fn visit_function(&mut self, function: &Function) {
+        walk_function(self, function)
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        walk_block(self, block)
+    }
+
+    fn visit_let(&mut self, let_: &Let) {
+        walk_let(self, let_)
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        walk_expression(self, expression)
+    }
+
+    /// Called for each pattern that introduces bindings into the current
+    /// scope.
+    fn visit_pattern(&mut self, _pattern: &Pattern) {}
+
+    fn visit_for_var(&mut self, _var: &Symbol) {}
+
+    fn enter_scope(&mut self) {}
+
+    fn exit_scope(&mut self) {}
+}
+
+pub fn walk_top_levels<V: Visitor>(visitor: &mut V, top_levels: &[TopLevel]) {
+    for top_level in top_levels {
+        if let TopLevelKind::Function(function) = &top_level.kind {
+            visitor.visit_function(function);
+        }
+    }
+}
+
+pub fn walk_function<V: Visitor>(visitor: &mut V, function: &Function) {
+    visitor.visit_block(&function.body);
+}
+
+pub fn walk_block<V: Visitor>(visitor: &mut V, block: &Block) {
+    visitor.enter_scope();
+    for statement in &block.statements {
+        match &statement.kind {
+            StatementKind::Empty => (),
+            StatementKind::Expression(expression) => visitor.visit_expression(expression),
+            StatementKind::Let(let_) => visitor.visit_let(let_),
+        }
+    }
+    if let Some(expression) = &block.expression {
+        visitor.visit_expression(expression);
+    }
+    visitor.exit_scope();
+}
+
+pub fn walk_let<V: Visitor>(visitor: &mut V, let_: &Let) {
+    if let Some(value) = &let_.value {
+        visitor.visit_expression(value);
+    }
+    if let Some(else_) = &let_.else_ {
+        visitor.visit_block(&else_.block);
+    }
+    visitor.visit_pattern(&let_.pattern);
+}
+
+pub fn walk_expression<V: Visitor>(visitor: &mut V, expression: &Expression) {
+    match &expression.kind {
+        ExpressionKind::Variable(_) | ExpressionKind::Value(_) | ExpressionKind::Continue => (),
+        ExpressionKind::Block(block) => visitor.visit_block(block),
+        ExpressionKind::If(if_) => walk_if(visitor, if_),
+        ExpressionKind::Loop(loop_) => visitor.visit_block(&loop_.block),
+        ExpressionKind::While(while_) => {
+            visitor.enter_scope();
+            walk_condition(visitor, &while_.condition);
+            visitor.visit_block(&while_.block);
+            visitor.exit_scope();
+        }
+        ExpressionKind::For(for_) => {
+            visitor.visit_expression(&for_.expr);
+            visitor.enter_scope();
+            visitor.visit_for_var(&for_.var);
+            visitor.visit_block(&for_.block);
+            visitor.exit_scope();
+        }
+        ExpressionKind::Match(match_) => {
+            visitor.visit_expression(&match_.value);
+            for item in &match_.matches {
+                visitor.enter_scope();
+                visitor.visit_pattern(&item.pattern);
+                visitor.visit_expression(&item.value);
+                visitor.exit_scope();
+            }
+        }
+        ExpressionKind::Binary(binary) => {
+            visitor.visit_expression(&binary.left);
+            visitor.visit_expression(&binary.right);
+        }
+        ExpressionKind::FunctionCall(function_call) => {
+            visitor.visit_expression(&function_call.function);
+            for argument in &function_call.arguments {
+                visitor.visit_expression(argument);
+            }
+        }
+        ExpressionKind::MemberCall(member_call) => {
+            visitor.visit_expression(&member_call.member.object);
+            for argument in &member_call.arguments {
+                visitor.visit_expression(argument);
+            }
+        }
+        ExpressionKind::MemberAccess(member_access) => {
+            visitor.visit_expression(&member_access.object)
+        }
+        ExpressionKind::Tuple(expressions) => {
+            for expression in expressions {
+                visitor.visit_expression(expression);
+            }
+        }
+        ExpressionKind::Ref(expression)
+        | ExpressionKind::RefMut(expression)
+        | ExpressionKind::RawConst(expression)
+        | ExpressionKind::RawMut(expression)
+        | ExpressionKind::Deref(expression)
+        | ExpressionKind::Try(expression) => visitor.visit_expression(expression),
+        ExpressionKind::Cast(cast) => visitor.visit_expression(&cast.expression),
+        ExpressionKind::Return(value) | ExpressionKind::Break(value) => {
+            if let Some(value) = value {
+                visitor.visit_expression(value);
+            }
+        }
+    }
+}
+
+fn walk_if<V: Visitor>(visitor: &mut V, if_: &If) {
+    visitor.enter_scope();
+    walk_condition(visitor, &if_.condition);
+    visitor.visit_block(&if_.then);
+    visitor.exit_scope();
+    match if_.else_.as_ref().map(|else_| &else_.kind) {
+        Some(ElseKind::If(if_)) => walk_if(visitor, if_),
+        Some(ElseKind::Block(block)) => visitor.visit_block(block),
+        None => (),
+    }
+}
+
+pub fn walk_condition<V: Visitor>(visitor: &mut V, condition: &Condition) {
+    match condition {
+        Condition::Expression(expression) => visitor.visit_expression(expression),
+        Condition::Let(let_) => {
+            visitor.visit_expression(&let_.value);
+            visitor.visit_pattern(&let_.pattern);
+        }
+    }
+}
+
+/// Like `Visitor` but allows replacing nodes.  There are no scopes or
+/// bindings: passes using this run after checking.
+pub trait VisitorMut: Sized {
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        walk_block_mut(self, block)
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression)
+    }
+}
+
+pub fn walk_top_levels_mut<V: VisitorMut>(visitor: &mut V, top_levels: &mut [TopLevel]) {
+    for top_level in top_levels {
+        if let TopLevelKind::Function(function) = &mut top_level.kind {
+            visitor.visit_block_mut(&mut function.body);
+        }
+    }
+}
+
+pub fn walk_block_mut<V: VisitorMut>(visitor: &mut V, block: &mut Block) {
+    for statement in &mut block.statements {
+        match &mut statement.kind {
+            StatementKind::Empty => (),
+            StatementKind::Expression(expression) => visitor.visit_expression_mut(expression),
+            StatementKind::Let(let_) => {
+                if let Some(value) = &mut let_.value {
+                    visitor.visit_expression_mut(value);
+                }
+                if let Some(else_) = &mut let_.else_ {
+                    visitor.visit_block_mut(&mut else_.block);
+                }
+            }
+        }
+    }
+    if let Some(expression) = &mut block.expression {
+        visitor.visit_expression_mut(expression);
+    }
+}
+
+pub fn walk_expression_mut<V: VisitorMut>(visitor: &mut V, expression: &mut Expression) {
+    match &mut expression.kind {
+        ExpressionKind::Variable(_) | ExpressionKind::Value(_) | ExpressionKind::Continue => (),
+        ExpressionKind::Block(block) => visitor.visit_block_mut(block),
+        ExpressionKind::If(if_) => walk_if_mut(visitor, if_),
+        ExpressionKind::Loop(loop_) => visitor.visit_block_mut(&mut loop_.block),
+        ExpressionKind::While(while_) => {
+            walk_condition_mut(visitor, &mut while_.condition);
+            visitor.visit_block_mut(&mut while_.block);
+        }
+        ExpressionKind::For(for_) => {
+            visitor.visit_expression_mut(&mut for_.expr);
+            visitor.visit_block_mut(&mut for_.block);
+        }
+        ExpressionKind::Match(match_) => {
+            visitor.visit_expression_mut(&mut match_.value);
+            for item in &mut match_.matches {
+                visitor.visit_expression_mut(&mut item.value);
+            }
+        }
+        ExpressionKind::Binary(binary) => {
+            visitor.visit_expression_mut(&mut binary.left);
+            visitor.visit_expression_mut(&mut binary.right);
+        }
+        ExpressionKind::FunctionCall(function_call) => {
+            visitor.visit_expression_mut(&mut function_call.function);
+            for argument in &mut function_call.arguments {
+                visitor.visit_expression_mut(argument);
+            }
+        }
+        ExpressionKind::MemberCall(member_call) => {
+            visitor.visit_expression_mut(&mut member_call.member.object);
+            for argument in &mut member_call.arguments {
+                visitor.visit_expression_mut(argument);
+            }
+        }
+        ExpressionKind::MemberAccess(member_access) => {
+            visitor.visit_expression_mut(&mut member_access.object)
+        }
+        ExpressionKind::Tuple(expressions) => {
+            for expression in expressions {
+                visitor.visit_expression_mut(expression);
+            }
+        }
+        ExpressionKind::Ref(expression)
+        | ExpressionKind::RefMut(expression)
+        | ExpressionKind::RawConst(expression)
+        | ExpressionKind::RawMut(expression)
+        | ExpressionKind::Deref(expression)
+        | ExpressionKind::Try(expression) => visitor.visit_expression_mut(expression),
+        ExpressionKind::Cast(cast) => visitor.visit_expression_mut(&mut cast.expression),
+        ExpressionKind::Return(value) | ExpressionKind::Break(value) => {
+            if let Some(value) = value {
+                visitor.visit_expression_mut(value);
+            }
+        }
+    }
+}
+
+fn walk_if_mut<V: VisitorMut>(visitor: &mut V, if_: &mut If) {
+    walk_condition_mut(visitor, &mut if_.condition);
+    visitor.visit_block_mut(&mut if_.then);
+    match if_.else_.as_mut().map(|else_| &mut else_.kind) {
+        Some(ElseKind::If(if_)) => walk_if_mut(visitor, if_),
+        Some(ElseKind::Block(block)) => visitor.visit_block_mut(block),
+        None => (),
+    }
+}
+
+fn walk_condition_mut<V: VisitorMut>(visitor: &mut V, condition: &mut Condition) {
+    match condition {
+        Condition::Expression(expression) => visitor.visit_expression_mut(expression),
+        Condition::Let(let_) => visitor.visit_expression_mut(&mut let_.value),
+    }
+}
@@ -0,0 +1,71 @@
+/// Every error code with its explanation, sorted by code.
+pub const CODES: &[(&str, &str)] = &[
+    ("RC0001", include_str!("codes/RC0001.md")),
+    ("RC0002", include_str!("codes/RC0002.md")),
+    ("RC0003", include_str!("codes/RC0003.md")),
+    ("RC0004", include_str!("codes/RC0004.md")),
+    ("RC0005", include_str!("codes/RC0005.md")),
+    ("RC0006", include_str!("codes/RC0006.md")),
+    ("RC0007", include_str!("codes/RC0007.md")),
+    ("RC0008", include_str!("codes/RC0008.md")),
+    ("RC0009", include_str!("codes/RC0009.md")),
+    ("RC0010", include_str!("codes/RC0010.md")),
+    ("RC0011", include_str!("codes/RC0011.md")),
+    ("RC0012", include_str!("codes/RC0012.md")),
+    ("RC0013", include_str!("codes/RC0013.md")),
+    ("RC0014", include_str!("codes/RC0014.md")),
+    ("RC0015", include_str!("codes/RC0015.md")),
+    ("RC0016", include_str!("codes/RC0016.md")),
+    ("RC0017", include_str!("codes/RC0017.md")),
+    ("RC0018", include_str!("codes/RC0018.md")),
+    ("RC0019", include_str!("codes/RC0019.md")),
+    ("RC0020", include_str!("codes/RC0020.md")),
+    ("RC0021", include_str!("codes/RC0021.md")),
+    ("RC0022", include_str!("codes/RC0022.md")),
+    ("RC0023", include_str!("codes/RC0023.md")),
+    ("RC0024", include_str!("codes/RC0024.md")),
+    ("RC0025", include_str!("codes/RC0025.md")),
+    ("RC0026", include_str!("codes/RC0026.md")),
+    ("RC0027", include_str!("codes/RC0027.md")),
+    ("RC0028", include_str!("codes/RC0028.md")),
+    ("RC0029", include_str!("codes/RC0029.md")),
+    ("RC0030", include_str!("codes/RC0030.md")),
+    ("RC0031", include_str!("codes/RC0031.md")),
+    ("RC0032", include_str!("codes/RC0032.md")),
+    ("RC0033", include_str!("codes/RC0033.md")),
+    ("RC0034", include_str!("codes/RC0034.md")),
+    ("RC0035", include_str!("codes/RC0035.md")),
+    ("RC0036", include_str!("codes/RC0036.md")),
+    ("RC0037", include_str!("codes/RC0037.md")),
+    ("RC0038", include_str!("codes/RC0038.md")),
+    ("RC0039", include_str!("codes/RC0039.md")),
+    ("RC0040", include_str!("codes/RC0040.md")),
+    ("RC0041", include_str!("codes/RC0041.md")),
+];
+
+/// The explanation of `code`, such as `RC0001`.
+pub fn explanation(code: &str) -> Option<&'static str> {
+    CODES
+        .binary_search_by_key(&code, |(code, _)| code)
+        .ok()
+        .map(|index| CODES[index].1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_sorted() {
+        for pair in CODES.windows(2) {
+            assert!(pair[0].0 < pair[1].0, "{} before {}", pair[0].0, pair[1].0);
+        }
+    }
+
+    #[test]
+    fn test_explanation() {
+        assert!(explanation("RC0004").unwrap().contains("fn f(x: i32)"));
+        assert_eq!(explanation("RC9999"), None);
+        assert_eq!(explanation("rc0004"), None);
+    }
+}
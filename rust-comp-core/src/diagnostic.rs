@@ -1,126 +1,1171 @@
+use crate::codes;
 use crate::pos::*;
+use crate::source_map::{FileName, SourceMap};
 use std::fmt;
 use std::io;
+use std::str::FromStr;
 
+/// How serious a report is.  Only errors stop compilation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+impl Severity {
+    /// The lowercase name used for notes and in JSON.
+    pub fn level(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+            Severity::Help => "help",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "Error",
+            Severity::Warning => "Warning",
+            Severity::Note => "Note",
+            Severity::Help => "Help",
+        })
+    }
+}
+
+/// A message about the program being compiled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    pub severity: Severity,
+    /// The code explaining the message in detail, such as `RC0001`.  See
+    /// `codes`.
+    pub code: Option<&'static str>,
+    pub message: String,
+    /// The code the message is about, if it's about specific code.
+    pub span: Option<Span>,
+    /// Other code related to the message, each with what it has to do with
+    /// it.
+    pub labels: Vec<(Span, String)>,
+    /// Notes and help attached to the message.
+    pub children: Vec<Report>,
+    /// Edits to the code that fix the problem.
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Report {
+    pub fn new(severity: Severity, message: impl Into<String>, span: Option<Span>) -> Self {
+        Report {
+            severity,
+            code: None,
+            message: message.into(),
+            span,
+            labels: Vec::new(),
+            children: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>, span: Span) -> Self {
+        Report::new(Severity::Error, message, Some(span))
+    }
+
+    pub fn warning(message: impl Into<String>, span: Span) -> Self {
+        Report::new(Severity::Warning, message, Some(span))
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, label: impl Into<String>) -> Self {
+        self.labels.push((span, label.into()));
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.children.push(Report::new(Severity::Note, note, None));
+        self
+    }
+
+    pub fn with_span_note(mut self, span: Span, note: impl Into<String>) -> Self {
+        self.children
+            .push(Report::new(Severity::Note, note, Some(span)));
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.children.push(Report::new(Severity::Help, help, None));
+        self
+    }
+
+    /// Adds help describing `suggestion`.
+    pub fn with_suggestion(mut self, help: impl Into<String>, suggestion: Suggestion) -> Self {
+        let mut child = Report::new(Severity::Help, help, None);
+        child.suggestions.push(suggestion);
+        self.children.push(child);
+        self
+    }
+
+    /// The suggestions of the report and its children.
+    pub fn all_suggestions(&self) -> impl Iterator<Item = &Suggestion> {
+        self.suggestions
+            .iter()
+            .chain(self.children.iter().flat_map(|child| &child.suggestions))
+    }
+}
+
+/// How sure a suggestion is to fix the problem.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Applicability {
+    /// The suggestion is definitely what was meant and can be applied
+    /// without asking, such as by `--fix`.
+    MachineApplicable,
+    /// The suggestion is probably what was meant but might not compile or
+    /// might change the meaning of the code.
+    MaybeIncorrect,
+    /// The suggestion contains placeholders like `...` that have to be
+    /// filled in.
+    HasPlaceholders,
+    Unspecified,
+}
+
+impl Applicability {
+    /// The name used in JSON.
+    pub fn name(self) -> &'static str {
+        match self {
+            Applicability::MachineApplicable => "MachineApplicable",
+            Applicability::MaybeIncorrect => "MaybeIncorrect",
+            Applicability::HasPlaceholders => "HasPlaceholders",
+            Applicability::Unspecified => "Unspecified",
+        }
+    }
+}
+
+/// An edit to the code: an insertion if `span` is empty, a deletion if
+/// `replacement` is, and otherwise a replacement.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Suggestion {
+    pub span: Span,
+    pub replacement: String,
+    pub applicability: Applicability,
+}
+
+impl Suggestion {
+    pub fn insert(
+        file: usize,
+        index: usize,
+        text: impl Into<String>,
+        applicability: Applicability,
+    ) -> Self {
+        let span = Span {
+            file,
+            start: index,
+            end: index,
+            expn: None,
+        };
+        Suggestion::replace(span, text, applicability)
+    }
+
+    pub fn replace(span: Span, text: impl Into<String>, applicability: Applicability) -> Self {
+        Suggestion {
+            span,
+            replacement: text.into(),
+            applicability,
+        }
+    }
+
+    pub fn delete(span: Span, applicability: Applicability) -> Self {
+        Suggestion::replace(span, "", applicability)
+    }
+}
+
+/// `contents` with `suggestions` applied and how many were.  Suggestions
+/// overlapping an earlier one, including insertions at the same place, are
+/// skipped.
+pub fn apply_suggestions(contents: &str, suggestions: &[&Suggestion]) -> (String, usize) {
+    let mut suggestions = suggestions.to_vec();
+    suggestions.sort_by_key(|suggestion| (suggestion.span.start, suggestion.span.end));
+    suggestions.dedup();
+    let mut fixed = String::with_capacity(contents.len());
+    let mut applied = 0;
+    // The span of the last suggestion applied.
+    let mut last: Option<Span> = None;
+    for suggestion in suggestions {
+        let span = suggestion.span;
+        let overlaps = last.is_some_and(|last| {
+            span.start < last.end || (span.start == last.end && span.start == last.start)
+        });
+        if overlaps
+            || span.end > contents.len()
+            || !contents.is_char_boundary(span.start)
+            || !contents.is_char_boundary(span.end)
+        {
+            continue;
+        }
+        fixed.push_str(&contents[last.map_or(0, |last| last.end)..span.start]);
+        fixed.push_str(&suggestion.replacement);
+        applied += 1;
+        last = Some(span);
+    }
+    fixed.push_str(&contents[last.map_or(0, |last| last.end)..]);
+    (fixed, applied)
+}
+
+/// How reports are printed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+    /// Messages with snippets of the code for people to read.
+    #[default]
+    Human,
+    /// One JSON object per line in the format rustc uses.  See
+    /// `diagnostics.md`.
+    Json,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ErrorFormat, String> {
+        match s {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(format!("expected `human` or `json`, found `{}`", s)),
+        }
+    }
+}
+
+/// When to color reports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Only when stderr is a terminal.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether reports written to stderr are colored.
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => atty::is(atty::Stream::Stderr),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ColorChoice, String> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!(
+                "expected `auto`, `always`, or `never`, found `{}`",
+                s
+            )),
+        }
+    }
+}
+
+/// How reports written for people look.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Style {
+    /// Whether to color the output with ANSI escape codes.
+    pub color: bool,
+    /// The number of columns lines are trimmed to fit in.
+    pub width: usize,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            color: false,
+            width: DEFAULT_WIDTH,
+        }
+    }
+}
+
+impl Style {
+    /// The style for writing to stderr, which is as wide as the terminal
+    /// if `COLUMNS` says how wide that is.
+    pub fn stderr(color: ColorChoice) -> Self {
+        let width = std::env::var("COLUMNS")
+            .ok()
+            .and_then(|columns| columns.parse().ok())
+            .unwrap_or(DEFAULT_WIDTH);
+        Style {
+            color: color.enabled(),
+            width,
+        }
+    }
+
+    fn paint(self, text: &str, color: &str) -> String {
+        if self.color && !text.is_empty() {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// The width lines are trimmed to when the terminal's isn't known.
+const DEFAULT_WIDTH: usize = 140;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const LINE_NUMBER: &str = "\x1b[1;34m";
+const INSERTION: &str = "\x1b[1;32m";
+const DELETION: &str = "\x1b[1;31m";
+const REPLACEMENT: &str = "\x1b[1;33m";
+
+fn severity_color(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "\x1b[1;31m",
+        Severity::Warning => "\x1b[1;33m",
+        Severity::Note => "\x1b[1;32m",
+        Severity::Help => "\x1b[1;36m",
+    }
+}
+
+/// Collects reports so that compilation can continue after one and they
+/// can be printed together.
+#[derive(Debug, Default)]
+pub struct Emitter {
+    format: ErrorFormat,
+    style: Style,
+    reports: Vec<Report>,
+}
+
+impl Emitter {
+    pub fn new() -> Self {
+        Emitter::default()
+    }
+
+    pub fn with_format(format: ErrorFormat) -> Self {
+        Emitter {
+            format,
+            style: Style::default(),
+            reports: Vec::new(),
+        }
+    }
+
+    /// Sets how reports are written for people.
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn emit(&mut self, report: Report) {
+        self.reports.push(report);
+    }
+
+    pub fn reports(&self) -> &[Report] {
+        &self.reports
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.reports
+            .iter()
+            .filter(|report| report.severity == severity)
+            .count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.count(Severity::Error) != 0
+    }
+
+    /// The suggestions that can be applied without asking.
+    pub fn machine_applicable(&self) -> Vec<&Suggestion> {
+        self.reports
+            .iter()
+            .flat_map(Report::all_suggestions)
+            .filter(|suggestion| suggestion.applicability == Applicability::MachineApplicable)
+            .collect()
+    }
+
+    /// Prints the collected reports to stderr and forgets them.
+    pub fn flush(&mut self, diagnostic: &Diagnostic) {
+        // If writing to stderr fails there's nowhere to report it.
+        let _ = self.write(io::stderr().lock(), diagnostic);
+        self.reports.clear();
+    }
+
+    /// Writes the collected reports followed, for people, by how to explain
+    /// their codes.
+    fn write(&self, mut stream: impl io::Write, diagnostic: &Diagnostic) -> io::Result<()> {
+        for report in &self.reports {
+            match self.format {
+                ErrorFormat::Human => {
+                    diagnostic.write_styled_report(&mut stream, report, self.style)?
+                }
+                ErrorFormat::Json => diagnostic.write_json_report(&mut stream, report)?,
+            }
+        }
+        if self.format == ErrorFormat::Human {
+            let mut codes: Vec<&str> = self
+                .reports
+                .iter()
+                .filter(|report| report.severity == Severity::Error)
+                .filter_map(|report| report.code)
+                .filter(|code| codes::explanation(code).is_some())
+                .collect();
+            codes.sort_unstable();
+            codes.dedup();
+            match codes[..] {
+                [] => {}
+                [code] => writeln!(
+                    stream,
+                    "For more information about this error, try `rust-comp --explain {}`.",
+                    code
+                )?,
+                _ => {
+                    writeln!(
+                        stream,
+                        "Some errors have detailed explanations: {}.",
+                        codes.join(", ")
+                    )?;
+                    writeln!(
+                        stream,
+                        "For more information about an error, try `rust-comp --explain {}`.",
+                        codes[0]
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes reports about the files in a source map.
 pub struct Diagnostic {
-    files_names: Vec<String>,
-    files_contents: Vec<String>,
-    files_lines: Vec<Vec<usize>>,
+    source_map: SourceMap,
 }
 
 impl Diagnostic {
-    pub fn new(files_names: Vec<String>) -> Self {
-        Diagnostic {
-            files_names,
-            files_contents: Vec::new(),
-            files_lines: Vec::new(),
-        }
+    pub fn new(source_map: SourceMap) -> Self {
+        Diagnostic { source_map }
+    }
+
+    /// A diagnostic for one file not on disk, which is file 0.
+    pub fn with_contents(name: &str, contents: String) -> Self {
+        let mut source_map = SourceMap::new();
+        source_map.add_file(FileName::Virtual(name.to_string()), contents);
+        Diagnostic::new(source_map)
     }
 
-    pub fn print_pos_error(&self, format_args: fmt::Arguments, pos: Pos) {
-        self.print_span_error(format_args, span_for_pos(pos));
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
     }
 
-    pub fn print_span_error(&self, format_args: std::fmt::Arguments, span: Span) {
-        // At this point we are already going to quit.  If writing to stderr
-        // errors, we don't want to write anything else to it.  Thus we'll just
-        // ignore it.
-        let _ = self.write_span_error(io::stderr().lock(), format_args, span);
+    pub fn source_map_mut(&mut self) -> &mut SourceMap {
+        &mut self.source_map
     }
 
-    fn write_span_error(
+    /// Writes `report` and its children without colors.
+    pub fn write_report(&self, stream: impl io::Write, report: &Report) -> io::Result<()> {
+        self.write_styled_report(stream, report, Style::default())
+    }
+
+    /// Writes `report` and its children.  The labels are drawn in the same
+    /// snippet as the report's span.
+    pub fn write_styled_report(
         &self,
         mut stream: impl io::Write,
-        format_args: fmt::Arguments,
-        span: Span,
+        report: &Report,
+        style: Style,
     ) -> io::Result<()> {
-        let file_lines = &self.files_lines[span.file];
-        let line = match file_lines.binary_search(&span.start) {
-            Ok(line) => line,
-            Err(line) => line - 1,
-        };
-        let line_start = file_lines[line];
-        let line_end = file_lines[line + 1];
-        let column = span.start - line_start;
+        match report.span {
+            Some(span) => {
+                let mut annotations = vec![Annotation {
+                    span,
+                    label: "here",
+                    primary: true,
+                }];
+                annotations.extend(report.labels.iter().map(|(span, label)| Annotation {
+                    span: *span,
+                    label,
+                    primary: false,
+                }));
+                let heading = heading(report, style);
+                self.write_message(&mut stream, report.severity, &heading, &annotations, style)?
+            }
+            None => writeln!(stream, "{}", heading(report, style))?,
+        }
+        for child in &report.children {
+            match child.span {
+                Some(span) => {
+                    let annotation = Annotation {
+                        span,
+                        label: "here",
+                        primary: true,
+                    };
+                    let heading = heading(child, style);
+                    self.write_message(&mut stream, child.severity, &heading, &[annotation], style)?
+                }
+                None => {
+                    let level = style.paint(child.severity.level(), BOLD);
+                    writeln!(stream, "  = {}: {}", level, child.message)?;
+                    for suggestion in &child.suggestions {
+                        self.write_suggestion(&mut stream, suggestion, style)?;
+                    }
+                    writeln!(stream)?;
+                }
+            }
+        }
+        Ok(())
+    }
 
+    /// Writes `report` as a JSON object on one line.
+    pub fn write_json_report(&self, mut stream: impl io::Write, report: &Report) -> io::Result<()> {
+        let mut rendered = Vec::new();
+        self.write_report(&mut rendered, report)?;
+        let rendered = String::from_utf8_lossy(&rendered);
         writeln!(
             stream,
-            "{}:{}:{}:",
-            self.files_names[span.file],
-            line + 1,
-            column + 1
-        )?;
-        writeln!(stream, "  >> Error: {}", format_args)?;
-        writeln!(stream)?;
+            "{{\"$message_type\":\"diagnostic\",{},\"rendered\":{}}}",
+            self.json_fields(report),
+            json_string(&rendered)
+        )
+    }
 
-        let file_contents = &self.files_contents[span.file];
-        // TODO: replace with log10
-        let line_string = format!("{}", line + 1);
-        let line_len = line_string.len();
-        writeln!(
-            stream,
-            "{} | {}",
-            line_string,
-            &file_contents[line_start..line_end]
-        )?;
+    /// The fields of a report's JSON object other than `rendered`.
+    fn json_fields(&self, report: &Report) -> String {
+        let spans =
+            report
+                .span
+                .map(|span| self.json_span(span, true, None, None))
+                .into_iter()
+                .chain(
+                    report
+                        .labels
+                        .iter()
+                        .map(|(span, label)| self.json_span(*span, false, Some(label), None)),
+                )
+                .chain(report.suggestions.iter().map(|suggestion| {
+                    self.json_span(suggestion.span, true, None, Some(suggestion))
+                }))
+                .collect::<Vec<_>>();
+        let children = report
+            .children
+            .iter()
+            .map(|child| format!("{{{},\"rendered\":null}}", self.json_fields(child)))
+            .collect::<Vec<_>>();
+        let code = match report.code {
+            Some(code) => format!(
+                "{{\"code\":{},\"explanation\":{}}}",
+                json_string(code),
+                codes::explanation(code).map_or("null".to_string(), json_string)
+            ),
+            None => "null".to_string(),
+        };
+        format!(
+            "\"message\":{},\"code\":{},\"level\":\"{}\",\"spans\":[{}],\"children\":[{}]",
+            json_string(&report.message),
+            code,
+            report.severity.level(),
+            spans.join(","),
+            children.join(",")
+        )
+    }
 
-        for _ in 0..column + line_len + 3 {
-            write!(stream, " ")?;
+    fn json_span(
+        &self,
+        span: Span,
+        primary: bool,
+        label: Option<&str>,
+        suggestion: Option<&Suggestion>,
+    ) -> String {
+        let file = span.file;
+        let line_start = self.line_of(file, span.start);
+        let line_end = self.line_of(file, span.end);
+        // Columns count characters from 1.
+        let column = |index: usize| self.source_map.lookup(Pos { file, index }).column;
+        let text = (line_start..=line_end)
+            .map(|line| {
+                let text = self.line_text(file, line);
+                let start = if line == line_start {
+                    column(span.start)
+                } else {
+                    1
+                };
+                let end = if line == line_end {
+                    column(span.end)
+                } else {
+                    text.chars().count() + 1
+                };
+                format!(
+                    "{{\"text\":{},\"highlight_start\":{},\"highlight_end\":{}}}",
+                    json_string(text),
+                    start,
+                    end
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"file_name\":{},\"byte_start\":{},\"byte_end\":{},\"line_start\":{},\"line_end\":{},\
+             \"column_start\":{},\"column_end\":{},\"is_primary\":{},\"text\":[{}],\"label\":{},\
+             \"suggested_replacement\":{},\"suggestion_applicability\":{},\"expansion\":{}}}",
+            json_string(&self.source_map.file(file).name.to_string()),
+            span.start,
+            span.end,
+            line_start + 1,
+            line_end + 1,
+            column(span.start),
+            column(span.end),
+            primary,
+            text.join(","),
+            label.map_or("null".to_string(), json_string),
+            suggestion.map_or("null".to_string(), |suggestion| json_string(
+                &suggestion.replacement
+            )),
+            suggestion.map_or("null".to_string(), |suggestion| json_string(
+                suggestion.applicability.name()
+            )),
+            self.json_expansion(span)
+        )
+    }
+
+    /// The macro expansion `span` is part of as a JSON object, or `null`.
+    fn json_expansion(&self, span: Span) -> String {
+        let expansion = match self.source_map.expansion_of(span) {
+            Some(expansion) => expansion,
+            None => return "null".to_string(),
+        };
+        format!(
+            "{{\"span\":{},\"macro_decl_name\":{},\"def_site_span\":{}}}",
+            self.json_span(expansion.call_site, false, None, None),
+            json_string(&expansion.macro_name),
+            self.json_span(expansion.def_site, false, None, None)
+        )
+    }
+
+    /// Writes `heading` at the first annotation and a snippet of the code
+    /// around every annotation.
+    fn write_message(
+        &self,
+        mut stream: impl io::Write,
+        severity: Severity,
+        heading: &str,
+        annotations: &[Annotation],
+        style: Style,
+    ) -> io::Result<()> {
+        writeln!(stream, "{}:", self.location(annotations[0].span))?;
+        writeln!(stream, "  >> {}", heading)?;
+        writeln!(stream)?;
+
+        // The line numbers of every file share a gutter.
+        let gutter = annotations
+            .iter()
+            .map(|annotation| self.line_of(annotation.span.file, last(annotation.span)) + 1)
+            .max()
+            .unwrap()
+            .to_string()
+            .len();
+        let mut files: Vec<usize> = Vec::new();
+        for annotation in annotations {
+            if !files.contains(&annotation.span.file) {
+                files.push(annotation.span.file);
+            }
         }
-        for _ in span.start..span.end {
-            write!(stream, "^")?;
+        for (i, &file) in files.iter().enumerate() {
+            let annotations: Vec<&Annotation> = annotations
+                .iter()
+                .filter(|annotation| annotation.span.file == file)
+                .collect();
+            if i != 0 {
+                let location = self.location(annotations[0].span);
+                let arrow = style.paint("-->", LINE_NUMBER);
+                writeln!(
+                    stream,
+                    "{:gutter$} {} {}",
+                    "",
+                    arrow,
+                    location,
+                    gutter = gutter
+                )?;
+            }
+            self.write_snippet(&mut stream, severity, file, &annotations, gutter, style)?;
         }
-        writeln!(stream, " here")?;
         writeln!(stream)?;
         Ok(())
     }
 
-    pub fn files(&self) -> usize {
-        self.files_names.len()
+    /// Writes the lines of `file` that `annotations` cover with their spans
+    /// underlined.  A span covering several lines is drawn as a line in the
+    /// margin from its start to its end.
+    fn write_snippet(
+        &self,
+        mut stream: impl io::Write,
+        severity: Severity,
+        file: usize,
+        annotations: &[&Annotation],
+        gutter: usize,
+        style: Style,
+    ) -> io::Result<()> {
+        struct Marked<'a> {
+            start_line: usize,
+            start_column: usize,
+            end_line: usize,
+            end_column: usize,
+            width: usize,
+            marker: char,
+            color: &'static str,
+            label: &'a str,
+        }
+
+        let mut marked: Vec<Marked> = annotations
+            .iter()
+            .map(|annotation| {
+                let start = annotation.span.start;
+                let last = last(annotation.span);
+                let start_line = self.line_of(file, start);
+                let end_line = self.line_of(file, last);
+                let start_column = self.column(file, start_line, start);
+                let end_column = self.column(file, end_line, last);
+                let width = if start_line == end_line {
+                    display_width(self.text(file, start, last + 1)).max(1)
+                } else {
+                    1
+                };
+                let (marker, color) = if annotation.primary {
+                    ('^', severity_color(severity))
+                } else {
+                    ('-', LINE_NUMBER)
+                };
+                Marked {
+                    start_line,
+                    start_column,
+                    end_line,
+                    end_column,
+                    width,
+                    marker,
+                    color,
+                    label: annotation.label,
+                }
+            })
+            .collect();
+        marked.sort_by_key(|marked| (marked.start_line, marked.start_column));
+        let multiline: Vec<&Marked> = marked
+            .iter()
+            .filter(|marked| marked.start_line != marked.end_line)
+            .collect();
+
+        let mut lines = Vec::new();
+        for marked in &marked {
+            lines.push(marked.start_line);
+            lines.push(marked.end_line);
+            // Short spans show every line and long ones their ends.
+            if marked.end_line - marked.start_line <= 3 {
+                lines.extend(marked.start_line + 1..marked.end_line);
+            }
+        }
+        lines.sort_unstable();
+        lines.dedup();
+
+        let margin_width = if multiline.is_empty() {
+            0
+        } else {
+            multiline.len() + 1
+        };
+        let widest = lines
+            .iter()
+            .map(|&line| display_width(self.line_text(file, line)))
+            .max()
+            .unwrap_or(0);
+        let first = marked
+            .iter()
+            .map(|marked| marked.start_column.min(marked.end_column))
+            .min()
+            .unwrap_or(0);
+        let window = Window::new(
+            widest,
+            first,
+            style.width.saturating_sub(gutter + 3 + margin_width),
+        );
+
+        // Whether the margin line of each multi-line span is drawn.
+        let mut open = vec![false; multiline.len()];
+        let margin = |open: &[bool]| -> String {
+            let mut margin = String::new();
+            for (o, marked) in open.iter().zip(&multiline) {
+                if *o {
+                    margin.push_str(&style.paint("|", marked.color));
+                } else {
+                    margin.push(' ');
+                }
+            }
+            if !open.is_empty() {
+                margin.push(' ');
+            }
+            margin
+        };
+        let line_number = |line: Option<usize>| {
+            let number = line.map_or(String::new(), |line| (line + 1).to_string());
+            style.paint(
+                &format!("{:>gutter$} |", number, gutter = gutter),
+                LINE_NUMBER,
+            )
+        };
+        let mut write_row = |row: String| writeln!(stream, "{}", row.trim_end());
+        for (i, &line) in lines.iter().enumerate() {
+            if i != 0 && lines[i - 1] + 1 != line {
+                write_row("...".to_string())?;
+            }
+            write_row(format!(
+                "{} {}{}",
+                line_number(Some(line)),
+                margin(&open),
+                window.trim(&expand_tabs(self.line_text(file, line)))
+            ))?;
+            for marked in marked
+                .iter()
+                .filter(|marked| marked.start_line == line && marked.end_line == line)
+            {
+                let underline = format!(
+                    "{} {}",
+                    marked.marker.to_string().repeat(marked.width),
+                    marked.label
+                );
+                write_row(format!(
+                    "{} {}{:column$}{}",
+                    line_number(None),
+                    margin(&open),
+                    "",
+                    style.paint(&underline, marked.color),
+                    column = window.column(marked.start_column)
+                ))?;
+            }
+            for (j, marked) in multiline.iter().enumerate() {
+                if marked.end_line == line {
+                    let underline = format!(
+                        "|{}{} {}",
+                        "_".repeat(open.len() - j + window.column(marked.end_column)),
+                        marked.marker,
+                        marked.label
+                    );
+                    write_row(format!(
+                        "{} {}{}",
+                        line_number(None),
+                        margin(&open[..j]),
+                        style.paint(&underline, marked.color)
+                    ))?;
+                    open[j] = false;
+                }
+            }
+            for (j, marked) in multiline.iter().enumerate() {
+                if marked.start_line == line {
+                    let underline = format!(
+                        " {}{}",
+                        "_".repeat(open.len() - j + window.column(marked.start_column)),
+                        marked.marker
+                    );
+                    write_row(format!(
+                        "{} {}{}",
+                        line_number(None),
+                        margin(&open[..j]),
+                        style.paint(&underline, marked.color)
+                    ))?;
+                    open[j] = true;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the line `suggestion` changes as it would be after the change
+    /// with the change marked.  Changes spanning lines aren't shown.
+    fn write_suggestion(
+        &self,
+        mut stream: impl io::Write,
+        suggestion: &Suggestion,
+        style: Style,
+    ) -> io::Result<()> {
+        let span = suggestion.span;
+        let file = span.file;
+        let line = self.line_of(file, span.start);
+        if self.line_of(file, span.end) != line || suggestion.replacement.contains('\n') {
+            return Ok(());
+        }
+        let line_start = self.source_map.file(file).line_start(line);
+        let line_end = line_start + self.line_text(file, line).len();
+        let before = self.text(file, line_start, span.start);
+        let after = self.text(file, span.end, line_end);
+        let (fixed, marker, color, marked) = if suggestion.replacement.is_empty() {
+            // Deletions are shown on the line before it.
+            let deleted = self.text(file, span.start, span.end);
+            (
+                format!("{}{}{}", before, deleted, after),
+                '-',
+                DELETION,
+                deleted,
+            )
+        } else if span.start == span.end {
+            let fixed = format!("{}{}{}", before, suggestion.replacement, after);
+            (fixed, '+', INSERTION, suggestion.replacement.as_str())
+        } else {
+            let fixed = format!("{}{}{}", before, suggestion.replacement, after);
+            (fixed, '~', REPLACEMENT, suggestion.replacement.as_str())
+        };
+        let gutter = (line + 1).to_string().len();
+        let window = Window::new(
+            display_width(&fixed),
+            display_width(before),
+            style.width.saturating_sub(gutter + 3),
+        );
+        let markers = marker.to_string().repeat(display_width(marked).max(1));
+        writeln!(stream)?;
+        writeln!(
+            stream,
+            "{} {}",
+            style.paint(
+                &format!("{:>gutter$} |", line + 1, gutter = gutter),
+                LINE_NUMBER
+            ),
+            window.trim(&expand_tabs(&fixed)).trim_end()
+        )?;
+        writeln!(
+            stream,
+            "{} {:column$}{}",
+            style.paint(&format!("{:gutter$} |", "", gutter = gutter), LINE_NUMBER),
+            "",
+            style.paint(&markers, color),
+            column = window.column(display_width(before))
+        )
     }
 
-    pub fn file_name(&self, file: usize) -> &str {
-        &self.files_names[file]
+    /// The index of the line containing the byte at `index`.
+    fn line_of(&self, file: usize, index: usize) -> usize {
+        self.source_map.file(file).line_of(index)
     }
 
-    pub fn add_file_contents(&mut self, contents: String) {
-        self.files_lines.push(file_lines(&contents));
-        self.files_contents.push(contents);
+    /// The text of `line` without its line ending.
+    fn line_text(&self, file: usize, line: usize) -> &str {
+        self.source_map.file(file).line_text(line)
+    }
+
+    /// The width of the text on `line` before the byte at `index` when
+    /// printed.
+    fn column(&self, file: usize, line: usize, index: usize) -> usize {
+        let file = self.source_map.file(file);
+        display_width(file.text(file.line_start(line), index))
+    }
+
+    /// The text between two bytes of `file`, clamped to its contents.
+    fn text(&self, file: usize, start: usize, end: usize) -> &str {
+        self.source_map.file(file).text(start, end)
+    }
+
+    /// Formats where `span` starts as `file:line:column`.
+    pub fn location(&self, span: Span) -> String {
+        let loc = self.source_map.lookup(Pos {
+            file: span.file,
+            index: span.start,
+        });
+        format!(
+            "{}:{}:{}",
+            self.source_map.file(span.file).name,
+            loc.line,
+            loc.column
+        )
+    }
+
+    pub fn files(&self) -> usize {
+        self.source_map.files().len()
     }
 
     pub fn file_contents(&self, file: usize) -> &str {
-        &self.files_contents[file]
+        self.source_map.file(file).contents()
     }
 
     pub fn file_span(&self, span: Span) -> &str {
-        &self.files_contents[span.file][span.start..span.end]
+        self.source_map.span_text(span)
     }
 
     pub fn file_lines(&self, file: usize) -> usize {
-        self.files_lines[file].len()
+        self.source_map.file(file).lines()
     }
 }
 
-fn span_for_pos(pos: Pos) -> Span {
-    Span {
-        file: pos.file,
-        start: pos.index,
-        end: pos.index + 1,
+/// The severity, code, and message of `report`, such as
+/// `Error[RC0013]: cannot assign twice to immutable variable`.
+fn heading(report: &Report, style: Style) -> String {
+    let severity = match report.code {
+        Some(code) => format!("{}[{}]", report.severity, code),
+        None => report.severity.to_string(),
+    };
+    format!(
+        "{}: {}",
+        style.paint(&severity, severity_color(report.severity)),
+        style.paint(&report.message, BOLD)
+    )
+}
+
+/// A span to underline in a snippet.
+struct Annotation<'a> {
+    span: Span,
+    label: &'a str,
+    /// Whether the span is what the message is about rather than related
+    /// code.
+    primary: bool,
+}
+
+/// The index of the last character in `span`.  Empty spans mark the
+/// character after them.
+fn last(span: Span) -> usize {
+    if span.end > span.start {
+        span.end - 1
+    } else {
+        span.start
     }
 }
 
-fn file_lines(s: &str) -> Vec<usize> {
-    let mut result = Vec::new();
-    result.push(0);
-    for (i, c) in s.chars().enumerate() {
-        if c == '\n' {
-            result.push(i + 1);
+/// The columns of long lines that are shown.
+#[derive(Clone, Copy)]
+struct Window {
+    /// The number of columns cut off the start of lines.
+    left: usize,
+    width: usize,
+}
+
+/// The fewest columns of a line that are shown.
+const MIN_WINDOW_WIDTH: usize = 40;
+/// The number of columns shown before the first span in trimmed lines.
+const TRIM_CONTEXT: usize = 16;
+const ELLIPSIS: &str = "...";
+
+impl Window {
+    /// The window showing `first` for lines up to `widest` columns.
+    fn new(widest: usize, first: usize, width: usize) -> Self {
+        let width = width.max(MIN_WINDOW_WIDTH);
+        let left = if widest <= width {
+            0
+        } else {
+            first.saturating_sub(TRIM_CONTEXT)
+        };
+        Window { left, width }
+    }
+
+    /// Where `column` of a line is once it's trimmed.
+    fn column(self, column: usize) -> usize {
+        if self.left == 0 {
+            column
+        } else {
+            column.saturating_sub(self.left) + ELLIPSIS.len()
         }
     }
-    if result[result.len() - 1] != s.len() {
-        result.push(s.len());
+
+    /// The part of `text`, which has no tabs, that's shown, with `...` where
+    /// it's cut off.
+    fn trim(self, text: &str) -> String {
+        if self.left == 0 && display_width(text) <= self.width {
+            return text.to_string();
+        }
+        let mut trimmed = String::new();
+        let mut column = 0;
+        let mut chars = text.chars();
+        if self.left != 0 {
+            trimmed.push_str(ELLIPSIS);
+            for c in chars.by_ref() {
+                column += char_width(c);
+                if column >= self.left {
+                    // Keep the columns after the cut of a wide character.
+                    trimmed.push_str(&" ".repeat(column - self.left));
+                    break;
+                }
+            }
+        }
+        let rest = chars.as_str();
+        let width = self.width - trimmed.len();
+        if display_width(rest) <= width {
+            trimmed.push_str(rest);
+        } else {
+            let mut shown = 0;
+            for c in rest.chars() {
+                shown += char_width(c);
+                if shown > width - ELLIPSIS.len() {
+                    break;
+                }
+                trimmed.push(c);
+            }
+            trimmed.push_str(ELLIPSIS);
+        }
+        trimmed
+    }
+}
+
+/// How many columns a tab is expanded to.
+const TAB_WIDTH: usize = 4;
+
+fn expand_tabs(text: &str) -> String {
+    text.replace('\t', &" ".repeat(TAB_WIDTH))
+}
+
+/// The number of columns `text` takes up in a terminal.
+fn display_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x09 => TAB_WIDTH,
+        // Combining marks and zero width spaces and joiners.
+        0x0300..=0x036F | 0x200B..=0x200F | 0xFE00..=0xFE0F => 0,
+        // East Asian wide and fullwidth characters and emoji.
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x2FFFD
+        | 0x30000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+/// `s` as a JSON string literal.
+fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// The span of the character at `pos`.
+pub fn span_for_pos(pos: Pos) -> Span {
+    Span {
+        file: pos.file,
+        start: pos.index,
+        end: pos.index + 1,
+        expn: None,
     }
-    result
 }
 
 pub fn print_duration(name: &str, duration: std::time::Duration) {
@@ -136,51 +1181,51 @@ pub fn print_duration(name: &str, duration: std::time::Duration) {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_new() {
-        let diagnostic = Diagnostic::new(vec!["abc".to_string()]);
-        assert_eq!(diagnostic.files_names, ["abc".to_string()]);
-        assert_eq!(diagnostic.files_contents.len(), 0);
-        assert_eq!(diagnostic.files_lines.len(), 0);
+    /// A diagnostic for files on disk with these names and contents.
+    fn diagnostic(files: &[(&str, &str)]) -> Diagnostic {
+        let mut source_map = SourceMap::new();
+        for (name, contents) in files {
+            source_map.add_file(FileName::Real(name.to_string()), contents.to_string());
+        }
+        Diagnostic::new(source_map)
     }
 
     #[test]
-    fn test_add_file_contents() {
-        let mut diagnostic = Diagnostic::new(vec!["file1".to_string()]);
-        diagnostic.add_file_contents("oh man xx\nare they\nready for a miracle?".to_string());
+    fn test_files() {
+        let diagnostic = diagnostic(&[("a.rs", "fn main() {}\n"), ("b.rs", "// é\nfn f() {}")]);
+        assert_eq!(diagnostic.files(), 2);
+        assert_eq!(diagnostic.file_contents(1), "// é\nfn f() {}");
+        assert_eq!(diagnostic.file_lines(0), 1);
+        assert_eq!(diagnostic.file_lines(1), 2);
         assert_eq!(
-            diagnostic.files_contents,
-            ["oh man xx\nare they\nready for a miracle?".to_string()]
+            diagnostic.location(Span {
+                file: 1,
+                start: 9,
+                end: 10,
+                expn: None
+            }),
+            "b.rs:2:4"
+        );
+        assert_eq!(
+            Diagnostic::with_contents("test", "x".to_string()).location(Span {
+                file: 0,
+                start: 0,
+                end: 1,
+                expn: None
+            }),
+            "<test>:1:1"
         );
-        assert_eq!(diagnostic.files_lines, [vec![0, 10, 19, 39]]);
-    }
-
-    #[test]
-    fn test_file_lines_empty_file() {
-        assert_eq!(file_lines(""), vec![0]);
-    }
-
-    #[test]
-    fn test_file_lines_no_trailing_newline() {
-        assert_eq!(file_lines("abc\ndef"), vec![0, 4, 7]);
-    }
-
-    #[test]
-    fn test_file_lines_trailing_newline() {
-        assert_eq!(file_lines("abc\ndef\n"), vec![0, 4, 8]);
     }
 
     #[test]
     fn test_write_pos_error_emulated() {
-        let mut diagnostic = Diagnostic::new(vec!["file1".to_string()]);
-        diagnostic.add_file_contents("oh man xx\nare they\nready for a miracle?".to_string());
+        let diagnostic = diagnostic(&[("file1", "oh man xx\nare they\nready for a miracle?")]);
 
         let mut buffer = Vec::new();
         diagnostic
-            .write_span_error(
+            .write_report(
                 &mut buffer,
-                format_args!("error message"),
-                span_for_pos(Pos { file: 0, index: 3 }),
+                &Report::error("error message", span_for_pos(Pos { file: 0, index: 3 })),
             )
             .unwrap();
 
@@ -191,28 +1236,43 @@ mod tests {
   >> Error: error message
 
 1 | oh man xx
-
-       ^ here
+  |    ^ here
 
 "
         );
     }
 
+    #[test]
+    fn test_location() {
+        let diagnostic = diagnostic(&[("file1", "oh man xx\nare they")]);
+        assert_eq!(
+            diagnostic.location(Span {
+                file: 0,
+                start: 14,
+                end: 18,
+                expn: None,
+            }),
+            "file1:2:5"
+        );
+    }
+
     #[test]
     fn test_write_span_error() {
-        let mut diagnostic = Diagnostic::new(vec!["file1".to_string()]);
-        diagnostic.add_file_contents("oh man xx\nare they\nready for a miracle?".to_string());
+        let diagnostic = diagnostic(&[("file1", "oh man xx\nare they\nready for a miracle?")]);
 
         let mut buffer = Vec::new();
         diagnostic
-            .write_span_error(
+            .write_report(
                 &mut buffer,
-                format_args!("error message"),
-                Span {
-                    file: 0,
-                    start: 3,
-                    end: 6,
-                },
+                &Report::error(
+                    "error message",
+                    Span {
+                        file: 0,
+                        start: 3,
+                        end: 6,
+                        expn: None,
+                    },
+                ),
             )
             .unwrap();
 
@@ -223,10 +1283,483 @@ mod tests {
   >> Error: error message
 
 1 | oh man xx
+  |    ^^^ here
+
+"
+        );
+    }
+
+    #[test]
+    fn test_write_report_with_children() {
+        let diagnostic = diagnostic(&[("file1", "let x = 1;\nx = 2;")]);
+        let report = Report::warning(
+            "value assigned is never read",
+            Span {
+                file: 0,
+                start: 11,
+                end: 12,
+                expn: None,
+            },
+        )
+        .with_label(
+            Span {
+                file: 0,
+                start: 4,
+                end: 5,
+                expn: None,
+            },
+            "variable declared here",
+        )
+        .with_help("maybe it is overwritten before being read?");
+
+        let mut buffer = Vec::new();
+        diagnostic.write_report(&mut buffer, &report).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "file1:2:1:
+  >> Warning: value assigned is never read
+
+1 | let x = 1;
+  |     - variable declared here
+2 | x = 2;
+  | ^ here
+
+  = help: maybe it is overwritten before being read?
+
+"
+        );
+
+        let mut buffer = Vec::new();
+        let report = Report::new(Severity::Error, "`main` function not found", None);
+        diagnostic.write_report(&mut buffer, &report).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "Error: `main` function not found\n"
+        );
+    }
+
+    #[test]
+    fn test_emitter() {
+        let span = span_for_pos(Pos { file: 0, index: 0 });
+        let mut emitter = Emitter::new();
+        emitter.emit(Report::warning("unused variable", span));
+        assert!(!emitter.has_errors());
+        emitter.emit(Report::error("expected `;`", span).with_note("statements end in `;`"));
+        assert!(emitter.has_errors());
+        assert_eq!(emitter.count(Severity::Warning), 1);
+        assert_eq!(emitter.reports()[1].children[0].severity, Severity::Note);
+    }
+
+    #[test]
+    fn test_emitter_explain_codes() {
+        let diagnostic = diagnostic(&[("file1", "x = 1;")]);
+        let write = |emitter: &Emitter| {
+            let mut buffer = Vec::new();
+            emitter.write(&mut buffer, &diagnostic).unwrap();
+            String::from_utf8(buffer).unwrap()
+        };
+        let mut emitter = Emitter::new();
+        emitter.emit(
+            Report::error("cannot assign twice to immutable variable", span(0, 0, 1))
+                .with_code("RC0013"),
+        );
+        assert_eq!(
+            write(&emitter),
+            "file1:1:1:
+  >> Error[RC0013]: cannot assign twice to immutable variable
+
+1 | x = 1;
+  | ^ here
+
+For more information about this error, try `rust-comp --explain RC0013`.
+"
+        );
+        emitter.emit(
+            Report::new(Severity::Error, "`main` function not found", None).with_code("RC0029"),
+        );
+        emitter.emit(
+            Report::error("cannot assign twice to immutable variable", span(0, 0, 1))
+                .with_code("RC0013"),
+        );
+        assert!(write(&emitter).ends_with(
+            "^ here
+
+Some errors have detailed explanations: RC0013, RC0029.
+For more information about an error, try `rust-comp --explain RC0013`.
+"
+        ));
+        let mut emitter = Emitter::with_format(ErrorFormat::Json);
+        emitter.emit(
+            Report::error("cannot assign twice to immutable variable", span(0, 0, 1))
+                .with_code("RC0013"),
+        );
+        let json = write(&emitter);
+        assert!(json.contains(
+            "\"code\":{\"code\":\"RC0013\",\"explanation\":\"A variable that isn't `mut`"
+        ));
+        assert!(!json.contains("--explain"));
+    }
+
+    fn span(file: usize, start: usize, end: usize) -> Span {
+        Span {
+            file,
+            start,
+            end,
+            expn: None,
+        }
+    }
+
+    fn write_report(diagnostic: &Diagnostic, report: &Report) -> String {
+        let mut buffer = Vec::new();
+        diagnostic.write_report(&mut buffer, report).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_write_multiline_span() {
+        let diagnostic = diagnostic(&[(
+            "file1",
+            "fn f() {\n    let x = {\n        1\n    };\n    g(x);\n}\n",
+        )]);
+        let report = Report::error("use of moved value", span(0, 46, 47))
+            .with_label(span(0, 21, 38), "value moved here");
+        assert_eq!(
+            write_report(&diagnostic, &report),
+            "file1:5:7:
+  >> Error: use of moved value
+
+2 |       let x = {
+  |  _____________-
+3 | |         1
+4 | |     };
+  | |_____- value moved here
+5 |       g(x);
+  |         ^ here
+
+"
+        );
+    }
+
+    #[test]
+    fn test_write_long_multiline_span() {
+        let diagnostic = diagnostic(&[("file1", "{\n1\n2\n3\n4\n5\n6\n7\n8\n9\n}")]);
+        let report = Report::error("mismatched types", span(0, 0, 21));
+        assert_eq!(
+            write_report(&diagnostic, &report),
+            "file1:1:1:
+  >> Error: mismatched types
+
+ 1 |   {
+   |  _^
+...
+11 | | }
+   | |_^ here
+
+"
+        );
+    }
+
+    #[test]
+    fn test_write_tabs_and_wide_characters() {
+        let diagnostic = diagnostic(&[("file1", "\tlet s = \"日本\"; x")]);
+        let report = Report::error("cannot find value in this scope", span(0, 19, 20))
+            .with_label(span(0, 9, 17), "string here");
+        assert_eq!(
+            write_report(&diagnostic, &report),
+            "file1:1:16:
+  >> Error: cannot find value in this scope
 
-       ^^^ here
+1 |     let s = \"日本\"; x
+  |             ------ string here
+  |                     ^ here
 
 "
         );
     }
+
+    #[test]
+    fn test_write_crlf_and_non_ascii() {
+        let diagnostic = diagnostic(&[(
+            "file1",
+            "\u{feff}// ça marche\r\nfn café() {\r\n    let x = {\r\n        1\r\n    };\r\n    ç(x);\r\n}\r\n",
+        )]);
+        let report = Report::error("use of moved value", span(0, 73, 74))
+            .with_label(span(0, 44, 63), "value moved here")
+            .with_suggestion(
+                "insert `;`",
+                Suggestion::insert(0, 56, ";", Applicability::MachineApplicable),
+            );
+        assert_eq!(
+            write_report(&diagnostic, &report),
+            "file1:6:7:
+  >> Error: use of moved value
+
+3 |       let x = {
+  |  _____________-
+4 | |         1
+5 | |     };
+  | |_____- value moved here
+6 |       ç(x);
+  |         ^ here
+
+  = help: insert `;`
+
+4 |         1;
+  |          +
+
+"
+        );
+        let mut buffer = Vec::new();
+        diagnostic.write_json_report(&mut buffer, &report).unwrap();
+        assert!(!String::from_utf8(buffer).unwrap().contains("\\r"));
+        // The byte order mark isn't a column.
+        assert_eq!(diagnostic.location(span(0, 6, 8)), "file1:1:4");
+    }
+
+    #[test]
+    fn test_write_spans_in_different_files() {
+        let diagnostic = diagnostic(&[
+            ("a.rs", "fn main() { f(1); }"),
+            ("b.rs", "\n\n\n\n\n\n\n\n\nfn f() {}"),
+        ]);
+        let report = Report::error(
+            "function called with the wrong number of arguments",
+            span(0, 12, 16),
+        )
+        .with_label(span(1, 9, 18), "function defined here");
+        assert_eq!(
+            write_report(&diagnostic, &report),
+            "a.rs:1:13:
+  >> Error: function called with the wrong number of arguments
+
+ 1 | fn main() { f(1); }
+   |             ^^^^ here
+   --> b.rs:10:1
+10 | fn f() {}
+   | --------- function defined here
+
+"
+        );
+    }
+
+    #[test]
+    fn test_write_json_report() {
+        let diagnostic = diagnostic(&[("src/main.rs", "fn main() {\n    let \"é\" = x;\n}")]);
+        let report = Report::error("cannot find value in this scope", span(0, 27, 28))
+            .with_label(span(0, 20, 24), "pattern")
+            .with_help("declare it with `let`");
+        let mut buffer = Vec::new();
+        diagnostic.write_json_report(&mut buffer, &report).unwrap();
+        let json = String::from_utf8(buffer).unwrap();
+        let rendered = json_string(&write_report(&diagnostic, &report));
+        assert_eq!(
+            json,
+            format!(
+                "{{\"$message_type\":\"diagnostic\",\"message\":\"cannot find value in this scope\",\
+                 \"code\":null,\"level\":\"error\",\"spans\":[\
+                 {{\"file_name\":\"src/main.rs\",\"byte_start\":27,\"byte_end\":28,\"line_start\":2,\
+                 \"line_end\":2,\"column_start\":15,\"column_end\":16,\"is_primary\":true,\
+                 \"text\":[{{\"text\":\"    let \\\"é\\\" = x;\",\"highlight_start\":15,\"highlight_end\":16}}],\
+                 \"label\":null,\"suggested_replacement\":null,\"suggestion_applicability\":null,\
+                 \"expansion\":null}},\
+                 {{\"file_name\":\"src/main.rs\",\"byte_start\":20,\"byte_end\":24,\"line_start\":2,\
+                 \"line_end\":2,\"column_start\":9,\"column_end\":12,\"is_primary\":false,\
+                 \"text\":[{{\"text\":\"    let \\\"é\\\" = x;\",\"highlight_start\":9,\"highlight_end\":12}}],\
+                 \"label\":\"pattern\",\"suggested_replacement\":null,\"suggestion_applicability\":null,\
+                 \"expansion\":null}}],\
+                 \"children\":[{{\"message\":\"declare it with `let`\",\"code\":null,\"level\":\"help\",\
+                 \"spans\":[],\"children\":[],\"rendered\":null}}],\"rendered\":{}}}\n",
+                rendered
+            )
+        );
+    }
+
+    #[test]
+    fn test_write_suggestion() {
+        let diagnostic = diagnostic(&[("file1", "fn f(x i32) {\n    let y = 1\n}")]);
+        let report = Report::error("expected `:`", span(0, 7, 10)).with_suggestion(
+            "insert `:`",
+            Suggestion::insert(0, 6, ":", Applicability::MachineApplicable),
+        );
+        assert_eq!(
+            write_report(&diagnostic, &report),
+            "file1:1:8:
+  >> Error: expected `:`
+
+1 | fn f(x i32) {
+  |        ^^^ here
+
+  = help: insert `:`
+
+1 | fn f(x: i32) {
+  |       +
+
+"
+        );
+
+        let report = Report::error("unnecessary parentheses", span(0, 22, 23)).with_suggestion(
+            "replace the value",
+            Suggestion::replace(span(0, 26, 27), "(2)", Applicability::MaybeIncorrect),
+        );
+        assert!(write_report(&diagnostic, &report).ends_with(
+            "  = help: replace the value
+
+2 |     let y = (2)
+  |             ~~~
+
+"
+        ));
+
+        let report = Report::error("unused variable", span(0, 22, 23)).with_suggestion(
+            "remove it",
+            Suggestion::delete(span(0, 22, 23), Applicability::Unspecified),
+        );
+        assert!(write_report(&diagnostic, &report).ends_with(
+            "  = help: remove it
+
+2 |     let y = 1
+  |         -
+
+"
+        ));
+    }
+
+    #[test]
+    fn test_json_suggestion() {
+        let diagnostic = diagnostic(&[("file1", "let x = 1\n")]);
+        let report = Report::error("expected `;`", span(0, 10, 11)).with_suggestion(
+            "insert `;`",
+            Suggestion::insert(0, 9, ";", Applicability::MachineApplicable),
+        );
+        let mut buffer = Vec::new();
+        diagnostic.write_json_report(&mut buffer, &report).unwrap();
+        let json = String::from_utf8(buffer).unwrap();
+        assert!(json.contains(
+            "\"byte_start\":9,\"byte_end\":9,\"line_start\":1,\"line_end\":1,\"column_start\":10,\
+             \"column_end\":10,\"is_primary\":true,"
+        ));
+        assert!(json.contains(
+            "\"suggested_replacement\":\";\",\"suggestion_applicability\":\"MachineApplicable\""
+        ));
+    }
+
+    #[test]
+    fn test_apply_suggestions() {
+        let contents = "fn f(x i32) { g() }";
+        let insert = Suggestion::insert(0, 6, ":", Applicability::MachineApplicable);
+        let replace = Suggestion::replace(span(0, 14, 15), "h", Applicability::MachineApplicable);
+        let delete = Suggestion::delete(span(0, 13, 17), Applicability::MachineApplicable);
+        let other_insert = Suggestion::insert(0, 6, ";", Applicability::MachineApplicable);
+        assert_eq!(
+            apply_suggestions(contents, &[&replace, &insert, &insert]),
+            ("fn f(x: i32) { h() }".to_string(), 2)
+        );
+        assert_eq!(
+            apply_suggestions(contents, &[&delete, &replace, &insert, &other_insert]),
+            ("fn f(x: i32) { }".to_string(), 2)
+        );
+        assert_eq!(apply_suggestions(contents, &[]), (contents.to_string(), 0));
+    }
+
+    #[test]
+    fn test_emitter_machine_applicable() {
+        let mut emitter = Emitter::new();
+        let insert = Suggestion::insert(0, 6, ":", Applicability::MachineApplicable);
+        let maybe = Suggestion::insert(0, 6, ")", Applicability::MaybeIncorrect);
+        emitter.emit(
+            Report::error("expected `:`", span(0, 7, 10))
+                .with_suggestion("insert `:`", insert.clone()),
+        );
+        emitter.emit(
+            Report::error("expected `)`", span(0, 7, 10)).with_suggestion("insert `)`", maybe),
+        );
+        assert_eq!(emitter.machine_applicable(), [&insert]);
+    }
+
+    #[test]
+    fn test_write_colored_report() {
+        let diagnostic = diagnostic(&[("file1", "x = 1;")]);
+        let report = Report::error("cannot assign twice to immutable variable", span(0, 0, 1))
+            .with_code("RC0013")
+            .with_help("make the variable mutable");
+        let style = Style {
+            color: true,
+            width: DEFAULT_WIDTH,
+        };
+        let mut buffer = Vec::new();
+        diagnostic
+            .write_styled_report(&mut buffer, &report, style)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "file1:1:1:
+  >> \x1b[1;31mError[RC0013]\x1b[0m: \x1b[1mcannot assign twice to immutable variable\x1b[0m
+
+\x1b[1;34m1 |\x1b[0m x = 1;
+\x1b[1;34m  |\x1b[0m \x1b[1;31m^ here\x1b[0m
+
+  = \x1b[1mhelp\x1b[0m: make the variable mutable
+
+"
+        );
+    }
+
+    #[test]
+    fn test_write_long_line() {
+        let line = format!(
+            "let x = [{}]; let y = x;  // {}",
+            "0, ".repeat(30),
+            "z".repeat(30)
+        );
+        let diagnostic = diagnostic(&[("file1", &line)]);
+        let report = Report::error("use of moved value", span(0, 110, 111));
+        let style = Style {
+            color: false,
+            width: 50,
+        };
+        let mut buffer = Vec::new();
+        diagnostic
+            .write_styled_report(&mut buffer, &report, style)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "file1:1:111:
+  >> Error: use of moved value
+
+1 | ..., 0, ]; let y = x;  // zzzzzzzzzzzzzzzzz...
+  |                    ^ here
+
+"
+        );
+        let style = Style {
+            color: false,
+            width: 200,
+        };
+        let mut buffer = Vec::new();
+        diagnostic
+            .write_styled_report(&mut buffer, &report, style)
+            .unwrap();
+        let rendered = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            rendered.lines().nth(3),
+            Some(format!("1 | {}", diagnostic.file_contents(0)).as_str())
+        );
+    }
+
+    #[test]
+    fn test_color_choice_from_str() {
+        assert_eq!("auto".parse(), Ok(ColorChoice::Auto));
+        assert_eq!("always".parse(), Ok(ColorChoice::Always));
+        assert_eq!("never".parse(), Ok(ColorChoice::Never));
+        assert!("sometimes".parse::<ColorChoice>().is_err());
+        assert!(ColorChoice::Always.enabled());
+        assert!(!ColorChoice::Never.enabled());
+    }
+
+    #[test]
+    fn test_error_format_from_str() {
+        assert_eq!("human".parse(), Ok(ErrorFormat::Human));
+        assert_eq!("json".parse(), Ok(ErrorFormat::Json));
+        assert!("xml".parse::<ErrorFormat>().is_err());
+    }
 }
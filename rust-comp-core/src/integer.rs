@@ -0,0 +1,41 @@
+/// The names of the integer types.
+pub const NAMES: &[&str] = &[
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+];
+
+/// The type of an integer literal nothing else constrains, as in rustc.
+/// Every backend falls back to it.
+pub const DEFAULT: &str = "i32";
+
+/// The largest value of the integer type named `name`.
+pub fn max(name: &str) -> Option<u128> {
+    if !NAMES.contains(&name) {
+        return None;
+    }
+    let bits = match &name[1..] {
+        "8" => 8,
+        "16" => 16,
+        "32" => 32,
+        "64" | "size" => 64,
+        _ => 128,
+    };
+    let signed = name.starts_with('i') as u32;
+    Some(u128::MAX >> (128 - bits + signed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max() {
+        assert_eq!(max("i8"), Some(127));
+        assert_eq!(max("u8"), Some(255));
+        assert_eq!(max("i32"), Some(i32::MAX as u128));
+        assert_eq!(max("usize"), Some(u64::MAX as u128));
+        assert_eq!(max("u128"), Some(u128::MAX));
+        assert_eq!(max("f32"), None);
+        assert_eq!(max(""), None);
+        assert!(NAMES.contains(&DEFAULT));
+    }
+}
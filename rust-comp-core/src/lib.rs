@@ -1,2 +1,7 @@
+pub mod codes;
 pub mod diagnostic;
+pub mod integer;
 pub mod pos;
+pub mod profile;
+pub mod source_map;
+pub mod suggest;
@@ -1,12 +1,18 @@
 use std::ops::{Deref, DerefMut, Index};
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub struct Span {
     pub file: usize,
     pub start: usize,
     pub end: usize,
+    /// The macro expansion whose transcriber the span was copied from.
+    pub expn: Option<ExpnId>,
 }
 
+/// Identifies a macro expansion among those of the file it's in.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct ExpnId(pub u32);
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct FilePos<'a> {
     pub file_name: &'a str,
@@ -0,0 +1,42 @@
+use std::str::FromStr;
+
+/// How arithmetic is compiled, matching rustc's default profiles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    /// Overflow panics.
+    Debug,
+    /// Overflow wraps.
+    Release,
+}
+
+impl Profile {
+    /// Whether `+`, `-`, and `*` panic on overflow.  Division by zero and
+    /// dividing the minimum value by `-1` panic in every profile.
+    pub fn overflow_checks(self) -> bool {
+        self == Profile::Debug
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Profile, String> {
+        match s {
+            "debug" => Ok(Profile::Debug),
+            "release" => Ok(Profile::Release),
+            _ => Err(format!("expected `debug` or `release`, found `{}`", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("debug".parse(), Ok(Profile::Debug));
+        assert_eq!("release".parse(), Ok(Profile::Release));
+        assert!("fast".parse::<Profile>().is_err());
+    }
+}
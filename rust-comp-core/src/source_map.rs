@@ -0,0 +1,332 @@
+use crate::pos::{Pos, Span};
+use std::fmt;
+
+/// A byte offset into every file of a `SourceMap` laid end to end.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BytePos(pub usize);
+
+/// Where the contents of a file came from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileName {
+    /// A path on disk.
+    Real(String),
+    /// Code that isn't on disk, such as a test or code generated by a
+    /// program embedding the compiler.  Printed in angle brackets.
+    Virtual(String),
+}
+
+impl fmt::Display for FileName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FileName::Real(path) => write!(f, "{}", path),
+            FileName::Virtual(name) => write!(f, "<{}>", name),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub name: FileName,
+    /// The global position of the first byte.
+    pub start_pos: BytePos,
+    contents: String,
+    /// The byte offset of the start of each line.
+    line_starts: Vec<usize>,
+    /// The macro expansions in the file, indexed by `ExpnId`.
+    expansions: Vec<ExpnData>,
+}
+
+impl SourceFile {
+    fn new(name: FileName, start_pos: BytePos, contents: String) -> Self {
+        let line_starts = line_starts(&contents);
+        SourceFile {
+            name,
+            start_pos,
+            contents,
+            line_starts,
+            expansions: Vec::new(),
+        }
+    }
+
+    pub fn contents(&self) -> &str {
+        &self.contents
+    }
+
+    pub fn lines(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// The byte offset of the start of `line`, counting from 0.
+    pub fn line_start(&self, line: usize) -> usize {
+        self.line_starts[line]
+    }
+
+    /// The line, counting from 0, containing the byte at `index`.  The end
+    /// of the file is on the last line.
+    pub fn line_of(&self, index: usize) -> usize {
+        match self.line_starts.binary_search(&index) {
+            Ok(line) => line,
+            // Before the start of the first line is a byte order mark.
+            Err(line) => line.saturating_sub(1),
+        }
+    }
+
+    /// The text of `line` without its line ending.
+    pub fn line_text(&self, line: usize) -> &str {
+        let end = self
+            .line_starts
+            .get(line + 1)
+            .copied()
+            .unwrap_or(self.contents.len());
+        self.text(self.line_starts[line], end)
+            .trim_end_matches(&['\n', '\r'][..])
+    }
+
+    /// The text between two bytes, clamped to the contents and to
+    /// character boundaries.
+    pub fn text(&self, start: usize, end: usize) -> &str {
+        let end = floor_char_boundary(&self.contents, end);
+        let start = floor_char_boundary(&self.contents, start.min(end));
+        &self.contents[start..end]
+    }
+}
+
+/// The byte at or before `index` that starts a character.
+fn floor_char_boundary(s: &str, index: usize) -> usize {
+    let mut index = index.min(s.len());
+    while !s.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// A position in a file as people count it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Loc {
+    pub file: usize,
+    /// Counts from 1.
+    pub line: usize,
+    /// Counts characters from 1.
+    pub column: usize,
+    /// Counts UTF-16 code units from 1, as editors using the Language
+    /// Server Protocol do.
+    pub column_utf16: usize,
+}
+
+/// Where a macro was used and defined.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpnData {
+    /// The macro invocation.
+    pub call_site: Span,
+    /// The transcriber of the macro rule that was used.
+    pub def_site: Span,
+    /// The name of the macro, such as `vec!`.
+    pub macro_name: String,
+}
+
+/// The files being compiled and the macro expansions in them.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap::default()
+    }
+
+    /// Adds a file and returns its index, which is used as `Span::file`.
+    pub fn add_file(&mut self, name: FileName, contents: String) -> usize {
+        let start_pos = match self.files.last() {
+            // Leave a byte between files so the end of one isn't the start
+            // of the next.
+            Some(last) => BytePos(last.start_pos.0 + last.contents.len() + 1),
+            None => BytePos(0),
+        };
+        self.files.push(SourceFile::new(name, start_pos, contents));
+        self.files.len() - 1
+    }
+
+    /// Records the macro expansions of `file` as returned by expanding it.
+    pub fn set_expansions(&mut self, file: usize, expansions: Vec<ExpnData>) {
+        self.files[file].expansions = expansions;
+    }
+
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+
+    pub fn file(&self, file: usize) -> &SourceFile {
+        &self.files[file]
+    }
+
+    /// The text of `span`, clamped like `SourceFile::text`.
+    pub fn span_text(&self, span: Span) -> &str {
+        self.files[span.file].text(span.start, span.end)
+    }
+
+    pub fn byte_pos(&self, pos: Pos) -> BytePos {
+        BytePos(self.files[pos.file].start_pos.0 + pos.index)
+    }
+
+    /// The file and offset of a global position.
+    pub fn lookup_pos(&self, pos: BytePos) -> Pos {
+        let file = match self.files.binary_search_by_key(&pos, |file| file.start_pos) {
+            Ok(file) => file,
+            Err(file) => file - 1,
+        };
+        Pos {
+            file,
+            index: pos.0 - self.files[file].start_pos.0,
+        }
+    }
+
+    pub fn lookup(&self, pos: Pos) -> Loc {
+        let file = &self.files[pos.file];
+        let line = file.line_of(pos.index);
+        let before = file.text(file.line_start(line), pos.index);
+        Loc {
+            file: pos.file,
+            line: line + 1,
+            column: before.chars().count() + 1,
+            column_utf16: before.encode_utf16().count() + 1,
+        }
+    }
+
+    /// The expansion whose transcriber `span` was copied from.
+    pub fn expansion_of(&self, span: Span) -> Option<&ExpnData> {
+        span.expn
+            .map(|id| &self.files[span.file].expansions[id.0 as usize])
+    }
+}
+
+/// The byte offset of the start of each line of `s`.  The first line starts
+/// after a byte order mark so it isn't counted as a column.
+fn line_starts(s: &str) -> Vec<usize> {
+    let mut starts = vec![if s.starts_with('\u{feff}') { 3 } else { 0 }];
+    starts.extend(
+        s.bytes()
+            .enumerate()
+            .filter(|&(i, byte)| byte == b'\n' && i + 1 < s.len())
+            .map(|(i, _)| i + 1),
+    );
+    starts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pos::ExpnId;
+
+    fn source_map(files: &[&str]) -> SourceMap {
+        let mut source_map = SourceMap::new();
+        for (i, contents) in files.iter().enumerate() {
+            let name = FileName::Virtual(i.to_string());
+            assert_eq!(source_map.add_file(name, contents.to_string()), i);
+        }
+        source_map
+    }
+
+    #[test]
+    fn test_line_starts() {
+        assert_eq!(line_starts(""), [0]);
+        assert_eq!(line_starts("abc\ndef"), [0, 4]);
+        assert_eq!(line_starts("abc\ndef\n"), [0, 4]);
+        assert_eq!(line_starts("\n\n"), [0, 1]);
+        assert_eq!(line_starts("// é\nfn"), [0, 6]);
+        assert_eq!(line_starts("\u{feff}fn\r\nf"), [3, 7]);
+    }
+
+    #[test]
+    fn test_line_text() {
+        let source_map = source_map(&["abc\r\ndé\n"]);
+        let file = source_map.file(0);
+        assert_eq!(file.lines(), 2);
+        assert_eq!(file.line_text(0), "abc");
+        assert_eq!(file.line_text(1), "dé");
+        assert_eq!(file.line_of(8), 1);
+        assert_eq!(file.text(0, 7), "abc\r\nd");
+    }
+
+    #[test]
+    fn test_global_positions() {
+        let source_map = source_map(&["abc", "", "de"]);
+        let files: Vec<_> = source_map.files().iter().map(|f| f.start_pos).collect();
+        assert_eq!(files, [BytePos(0), BytePos(4), BytePos(5)]);
+        for (file, index) in [(0, 0), (0, 3), (1, 0), (2, 0), (2, 2)] {
+            let pos = Pos { file, index };
+            assert_eq!(source_map.lookup_pos(source_map.byte_pos(pos)), pos);
+        }
+    }
+
+    #[test]
+    fn test_span_text() {
+        let source_map = source_map(&["é = 1"]);
+        let span = |start, end| Span {
+            file: 0,
+            start,
+            end,
+            expn: None,
+        };
+        assert_eq!(source_map.span_text(span(0, 2)), "é");
+        assert_eq!(source_map.span_text(span(1, 3)), "é ");
+        assert_eq!(source_map.span_text(span(5, 9)), "1");
+    }
+
+    #[test]
+    fn test_lookup() {
+        let source_map = source_map(&["fn f() {}\n// 𝄞 é\nx"]);
+        let loc = |index| source_map.lookup(Pos { file: 0, index });
+        assert_eq!(
+            loc(3),
+            Loc {
+                file: 0,
+                line: 1,
+                column: 4,
+                column_utf16: 4
+            }
+        );
+        // `𝄞` is 4 bytes and 2 UTF-16 code units and `é` is 2 bytes and 1.
+        assert_eq!(loc(18).line, 2);
+        assert_eq!((loc(18).column, loc(18).column_utf16), (6, 7));
+        assert_eq!((loc(21).line, loc(21).column), (3, 1));
+    }
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(
+            FileName::Real("src/main.rs".to_string()).to_string(),
+            "src/main.rs"
+        );
+        assert_eq!(FileName::Virtual("test".to_string()).to_string(), "<test>");
+    }
+
+    #[test]
+    fn test_expansions() {
+        let mut source_map = source_map(&["macro_rules! m { () => { 1 } } fn f() { m!() + m!() }"]);
+        let span = |start, end, expn| Span {
+            file: 0,
+            start,
+            end,
+            expn,
+        };
+        let expansion = |call_site| ExpnData {
+            call_site,
+            def_site: span(17, 28, None),
+            macro_name: "m!".to_string(),
+        };
+        source_map.set_expansions(
+            0,
+            vec![expansion(span(39, 43, None)), expansion(span(46, 50, None))],
+        );
+        // Both uses copy the `1` from the same transcriber.
+        let call_site = |expn| {
+            source_map
+                .expansion_of(span(25, 26, expn))
+                .map(|expansion| expansion.call_site)
+        };
+        assert_eq!(call_site(Some(ExpnId(0))), Some(span(39, 43, None)));
+        assert_eq!(call_site(Some(ExpnId(1))), Some(span(46, 50, None)));
+        assert_eq!(call_site(None), None);
+    }
+}
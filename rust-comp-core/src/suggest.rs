@@ -0,0 +1,85 @@
+/// The number of characters that have to be inserted, deleted, replaced,
+/// or swapped with their neighbor to turn `a` into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // `distances[i][j]` is the distance between the first `i` characters of
+    // `a` and the first `j` of `b`.
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+/// The candidate most likely meant by someone who wrote `name`, if any is
+/// close enough.  A candidate differing only in case is always close
+/// enough.  Ties go to the earliest candidate.
+pub fn best_match<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max_distance = name.chars().count().max(3) / 3;
+    let mut best: Option<(usize, &str)> = None;
+    for candidate in candidates {
+        if candidate == name {
+            continue;
+        }
+        let distance = if candidate.eq_ignore_ascii_case(name) {
+            0
+        } else {
+            edit_distance(name, candidate)
+        };
+        if distance <= max_distance && best.is_none_or(|(best, _)| distance < best) {
+            best = Some((distance, candidate));
+        }
+    }
+    best.map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("retrun", "return"), 1);
+        assert_eq!(edit_distance("strcut", "struct"), 1);
+        assert_eq!(edit_distance("lenght", "length"), 1);
+        assert_eq!(edit_distance("héllo", "hello"), 1);
+    }
+
+    #[test]
+    fn test_best_match() {
+        let candidates = ["length", "len", "width"];
+        assert_eq!(
+            best_match("lenght", candidates.iter().copied()),
+            Some("length")
+        );
+        assert_eq!(best_match("le", candidates.iter().copied()), Some("len"));
+        assert_eq!(best_match("height", candidates.iter().copied()), None);
+        assert_eq!(best_match("len", candidates.iter().copied()), None);
+        assert_eq!(
+            best_match("LENGTH", candidates.iter().copied()),
+            Some("length")
+        );
+    }
+}
@@ -0,0 +1,338 @@
+use super::rule::*;
+use crate::parse::block::expect_block;
+use crate::parse::expression::expect_expression;
+use crate::parse::parser::Parser;
+use crate::parse::path::expect_path;
+use crate::parse::pattern::expect_pattern;
+use crate::parse::top_level::expect_top_level;
+use crate::parse::type_::expect_type;
+use crate::parse::visibility::expect_visibility;
+use crate::parse::Error;
+use crate::pos::Pos;
+use crate::token::*;
+use std::collections::HashMap;
+
+pub type Bindings<'a> = HashMap<&'a str, Binding>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Binding {
+    Fragment(FragmentKind, Vec<Token>),
+    Repetition(Vec<Binding>),
+}
+
+/// Matches all of `tokens` against `matchers`.
+pub fn match_rule<'a>(
+    file_contents: &'a str,
+    matchers: &[Matcher],
+    tokens: &[Token],
+    eofpos: Pos,
+) -> Option<Bindings<'a>> {
+    let mut state = MatchState {
+        file_contents,
+        tokens,
+        eofpos,
+        index: 0,
+    };
+    let mut bindings = Bindings::new();
+    if state.match_sequence(matchers, &mut bindings) && state.index == tokens.len() {
+        Some(bindings)
+    } else {
+        None
+    }
+}
+
+pub fn tokens_equal(file_contents: &str, a: &Token, b: &Token) -> bool {
+    a.kind == b.kind
+        && match a.kind {
+            TokenKind::Label | TokenKind::Integer | TokenKind::String => {
+                file_contents[a.span] == file_contents[b.span]
+            }
+            _ => true,
+        }
+}
+
+pub fn metavariables<'a>(file_contents: &'a str, matchers: &[Matcher], names: &mut Vec<&'a str>) {
+    for matcher in matchers {
+        match matcher {
+            Matcher::Token(_) => (),
+            Matcher::Fragment(name, _) => names.push(&file_contents[*name]),
+            Matcher::Repetition(repetition) => {
+                metavariables(file_contents, &repetition.elements, names)
+            }
+        }
+    }
+}
+
+const LITERALS: [TokenKind; 4] = [
+    TokenKind::Integer,
+    TokenKind::String,
+    TokenKind::True,
+    TokenKind::False,
+];
+
+struct MatchState<'a, 't> {
+    file_contents: &'a str,
+    tokens: &'t [Token],
+    eofpos: Pos,
+    index: usize,
+}
+
+impl<'a, 't> MatchState<'a, 't> {
+    fn match_sequence(&mut self, matchers: &[Matcher], bindings: &mut Bindings<'a>) -> bool {
+        for matcher in matchers {
+            match matcher {
+                Matcher::Token(expected) => {
+                    if !self.eat(expected) {
+                        return false;
+                    }
+                }
+                Matcher::Fragment(name, kind) => match self.match_fragment(*kind) {
+                    Some(tokens) => {
+                        bindings
+                            .insert(&self.file_contents[*name], Binding::Fragment(*kind, tokens));
+                    }
+                    None => return false,
+                },
+                Matcher::Repetition(repetition) => {
+                    if !self.match_repetition(repetition, bindings) {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    fn match_repetition(
+        &mut self,
+        repetition: &Repetition<Matcher>,
+        bindings: &mut Bindings<'a>,
+    ) -> bool {
+        let mut iterations = Vec::new();
+        loop {
+            let start = self.index;
+            if !iterations.is_empty() {
+                if let Some(separator) = &repetition.separator {
+                    if !self.eat(separator) {
+                        break;
+                    }
+                }
+            }
+            let mut iteration = Bindings::new();
+            if !self.match_sequence(&repetition.elements, &mut iteration) || self.index == start {
+                self.index = start;
+                break;
+            }
+            iterations.push(iteration);
+        }
+
+        if repetition.op == KleeneOp::OneOrMore && iterations.is_empty() {
+            return false;
+        }
+
+        let mut names = Vec::new();
+        metavariables(self.file_contents, &repetition.elements, &mut names);
+        for name in names {
+            let values = iterations
+                .iter_mut()
+                .map(|iteration| iteration.remove(name).unwrap())
+                .collect();
+            bindings.insert(name, Binding::Repetition(values));
+        }
+        true
+    }
+
+    fn match_fragment(&mut self, kind: FragmentKind) -> Option<Vec<Token>> {
+        let start = self.index;
+        let matched = match kind {
+            FragmentKind::Block => self.parse_with(expect_block),
+            FragmentKind::Expr => self.parse_with(expect_expression),
+            FragmentKind::Ident => self.eat_kind(|kind| kind == TokenKind::Label),
+            FragmentKind::Item => self.parse_with(expect_top_level),
+            FragmentKind::Literal => self.eat_kind(|kind| LITERALS.contains(&kind)),
+            FragmentKind::Pat => self.parse_with(expect_pattern),
+            FragmentKind::Path => self.parse_with(expect_path),
+            FragmentKind::Tt => self.skip_token_tree(),
+            FragmentKind::Ty => self.parse_with(expect_type),
+            FragmentKind::Vis => self.parse_with(expect_visibility),
+        };
+        if matched {
+            Some(self.tokens[start..self.index].to_vec())
+        } else {
+            self.index = start;
+            None
+        }
+    }
+
+    fn parse_with<T, F>(&mut self, f: F) -> bool
+    where
+        F: FnOnce(&mut Parser) -> Result<T, Error>,
+    {
+        let mut parser = Parser::new(self.file_contents, self.tokens, self.eofpos);
+        parser.index = self.index;
+        match f(&mut parser) {
+            Ok(_) => {
+                self.index = parser.index;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn skip_token_tree(&mut self) -> bool {
+        let mut depth = 0;
+        loop {
+            let kind = match self.tokens.get(self.index) {
+                Some(token) => token.kind,
+                None => return false,
+            };
+            match kind {
+                TokenKind::OpenParen | TokenKind::OpenSquare | TokenKind::OpenCurly => depth += 1,
+                TokenKind::CloseParen | TokenKind::CloseSquare | TokenKind::CloseCurly => {
+                    if depth == 0 {
+                        return false;
+                    }
+                    depth -= 1;
+                }
+                _ => (),
+            }
+            self.index += 1;
+            if depth == 0 {
+                return true;
+            }
+        }
+    }
+
+    fn eat(&mut self, expected: &Token) -> bool {
+        let file_contents = self.file_contents;
+        match self.tokens.get(self.index) {
+            Some(token) if tokens_equal(file_contents, expected, token) => {
+                self.index += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn eat_kind<F>(&mut self, f: F) -> bool
+    where
+        F: FnOnce(TokenKind) -> bool,
+    {
+        match self.tokens.get(self.index) {
+            Some(token) if f(token.kind) => {
+                self.index += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lex::read_tokens;
+    use crate::parse::{parse, TopLevelKind};
+    use assert_matches::assert_matches;
+
+    /// Matches the arguments of `m!(...)` in `contents` against the first
+    /// rule of the `macro_rules!` that precedes it.
+    fn match_first_rule(contents: &str) -> Option<Bindings<'_>> {
+        let (tokens, eofpos) = read_tokens(0, contents).unwrap();
+        let top_levels = parse(contents, &tokens, eofpos).unwrap();
+        let rule = match &top_levels[0].kind {
+            TopLevelKind::MacroRules(macro_rules) => {
+                compile_rule(contents, &macro_rules.rules[0]).unwrap()
+            }
+            _ => unreachable!(),
+        };
+        let arguments = match &top_levels[1].kind {
+            TopLevelKind::Macro(macro_, _) => &macro_.invocation.arguments,
+            _ => unreachable!(),
+        };
+        let mut input = Vec::new();
+        crate::parse::macro_::flatten_token_trees(&arguments.trees, &mut input);
+        match_rule(contents, &rule.matchers, &input, eofpos)
+    }
+
+    #[test]
+    fn test_match_rule_literal_tokens() {
+        let bindings = match_first_rule("macro_rules! m { (a, 1) => {} } m!(a, 1);");
+        assert_eq!(bindings, Some(Bindings::new()));
+    }
+
+    #[test]
+    fn test_match_rule_literal_label_mismatch() {
+        let bindings = match_first_rule("macro_rules! m { (a) => {} } m!(b);");
+        assert_eq!(bindings, None);
+    }
+
+    #[test]
+    fn test_match_rule_leftover_tokens_fail() {
+        let bindings = match_first_rule("macro_rules! m { (a) => {} } m!(a a);");
+        assert_eq!(bindings, None);
+    }
+
+    #[test]
+    fn test_match_rule_expr_fragment_takes_whole_expression() {
+        let bindings = match_first_rule("macro_rules! m { ($e:expr) => {} } m!(a + b * c);");
+        assert_matches!(bindings.unwrap().get("e"), Some(Binding::Fragment(FragmentKind::Expr, tokens)) => {
+            assert_eq!(tokens.len(), 5);
+        });
+    }
+
+    #[test]
+    fn test_match_rule_ident_fragment_rejects_integer() {
+        let bindings = match_first_rule("macro_rules! m { ($i:ident) => {} } m!(1);");
+        assert_eq!(bindings, None);
+    }
+
+    #[test]
+    fn test_match_rule_tt_fragment_takes_delimited_group() {
+        let bindings = match_first_rule("macro_rules! m { ($t:tt x) => {} } m!((a b) x);");
+        assert_matches!(bindings.unwrap().get("t"), Some(Binding::Fragment(FragmentKind::Tt, tokens)) => {
+            assert_eq!(tokens.len(), 4);
+        });
+    }
+
+    #[test]
+    fn test_match_rule_repetition_with_separator() {
+        let bindings = match_first_rule("macro_rules! m { ($($e:expr),*) => {} } m!(1, 2, 3);");
+        assert_matches!(bindings.unwrap().get("e"), Some(Binding::Repetition(values)) => {
+            assert_eq!(values.len(), 3);
+        });
+    }
+
+    #[test]
+    fn test_match_rule_repetition_zero_times() {
+        let bindings = match_first_rule("macro_rules! m { ($($e:expr),*) => {} } m!();");
+        assert_eq!(
+            bindings.unwrap().get("e"),
+            Some(&Binding::Repetition(vec![]))
+        );
+    }
+
+    #[test]
+    fn test_match_rule_one_or_more_rejects_empty() {
+        let bindings = match_first_rule("macro_rules! m { ($($e:expr),+) => {} } m!();");
+        assert_eq!(bindings, None);
+    }
+
+    #[test]
+    fn test_match_rule_trailing_separator_is_not_consumed() {
+        let bindings = match_first_rule("macro_rules! m { ($($e:expr),*) => {} } m!(1, 2,);");
+        assert_eq!(bindings, None);
+    }
+
+    #[test]
+    fn test_match_rule_nested_repetition() {
+        let bindings =
+            match_first_rule("macro_rules! m { ($([$($i:ident)*])*) => {} } m!([a b] [c]);");
+        assert_matches!(bindings.unwrap().get("i"), Some(Binding::Repetition(outer)) => {
+            assert_matches!(outer.as_slice(), [Binding::Repetition(a), Binding::Repetition(b)] => {
+                assert_eq!(a.len(), 2);
+                assert_eq!(b.len(), 1);
+            });
+        });
+    }
+}
@@ -0,0 +1,660 @@
+mod matcher;
+mod rule;
+mod transcriber;
+
+use self::matcher::match_rule;
+use self::rule::{compile_rule, Rule};
+use self::transcriber::transcribe;
+use crate::parse::block::expect_block_body;
+use crate::parse::expression::expect_expression;
+use crate::parse::macro_::flatten_token_trees;
+use crate::parse::parser::Parser;
+use crate::parse::pattern::expect_pattern;
+use crate::parse::top_level::expect_top_level;
+use crate::parse::type_::expect_type;
+use crate::parse::*;
+use crate::pos::*;
+use crate::token::Token;
+use rust_comp_core::source_map::ExpnData;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    Parse(crate::parse::Error),
+    UndefinedMacro(Span),
+    NoRulesMatched(Span),
+    UnknownFragmentSpecifier(Span),
+    UnboundMetavariable(Span),
+    InvalidRepetition(Span),
+    RecursionLimit(Span),
+}
+
+const RECURSION_LIMIT: usize = 64;
+
+/// Expands every macro invocation in `top_levels`, storing the result in the
+/// invocation's `expansion`.  Macros must be defined before they are used.
+/// Returns where each expansion came from, outermost first, indexed by the
+/// `ExpnId`s of the spans copied from transcribers.
+pub fn expand(file_contents: &str, top_levels: &mut [TopLevel]) -> Result<Vec<ExpnData>, Error> {
+    let mut expander = Expander {
+        file_contents,
+        macros: HashMap::new(),
+        contexts: 0,
+        depth: 0,
+        expansions: Vec::new(),
+    };
+    expander.expand_top_levels(top_levels)?;
+    Ok(expander.expansions)
+}
+
+pub fn invocation_span(invocation: &MacroInvocation) -> Span {
+    let start = invocation
+        .path
+        .prefix_separator
+        .unwrap_or(invocation.path.segments[0]);
+    Span {
+        file: start.file,
+        start: start.start,
+        end: invocation.arguments.close_span.end,
+        expn: start.expn,
+    }
+}
+
+struct Expander<'a> {
+    file_contents: &'a str,
+    macros: HashMap<&'a str, Rc<Vec<Rule>>>,
+    contexts: u32,
+    depth: usize,
+    expansions: Vec<ExpnData>,
+}
+
+impl<'a> Expander<'a> {
+    fn expand_top_levels(&mut self, top_levels: &mut [TopLevel]) -> Result<(), Error> {
+        for top_level in top_levels {
+            self.expand_top_level(top_level)?;
+        }
+        Ok(())
+    }
+
+    fn expand_top_level(&mut self, top_level: &mut TopLevel) -> Result<(), Error> {
+        match &mut top_level.kind {
+            TopLevelKind::Function(f) => self.expand_function(f),
+            TopLevelKind::Struct(s) => {
+                for field in &mut s.fields {
+                    self.expand_type(&mut field.type_)?;
+                }
+                Ok(())
+            }
+            TopLevelKind::Enum(e) => {
+                for variant in &mut e.variants {
+                    if let VariantData::Tuple(tuple) = &mut variant.data {
+                        for type_ in &mut tuple.types {
+                            self.expand_type(type_)?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+            TopLevelKind::ModFile(_) | TopLevelKind::Use(_) => Ok(()),
+            TopLevelKind::MacroRules(macro_rules) => self.define(macro_rules),
+            TopLevelKind::Macro(macro_, _) => self.expand_macro(
+                macro_,
+                |parser| {
+                    let mut top_levels = Vec::new();
+                    while parser.peek().is_some() {
+                        top_levels.push(expect_top_level(parser)?);
+                    }
+                    Ok(top_levels)
+                },
+                |this, top_levels| this.expand_top_levels(top_levels),
+            ),
+        }
+    }
+
+    fn define(&mut self, macro_rules: &MacroRules) -> Result<(), Error> {
+        let rules = macro_rules
+            .rules
+            .iter()
+            .map(|rule| compile_rule(self.file_contents, rule))
+            .collect::<Result<_, _>>()?;
+        self.macros
+            .insert(&self.file_contents[macro_rules.name], Rc::new(rules));
+        Ok(())
+    }
+
+    fn expand_function(&mut self, f: &mut Function) -> Result<(), Error> {
+        for parameter in &mut f.parameters {
+            self.expand_type(&mut parameter.type_)?;
+        }
+        if let Some(return_type) = &mut f.return_type {
+            self.expand_type(&mut return_type.type_)?;
+        }
+        self.expand_block(&mut f.body)
+    }
+
+    fn expand_block(&mut self, block: &mut Block) -> Result<(), Error> {
+        for statement in &mut block.statements {
+            self.expand_statement(statement)?;
+        }
+        if let Some(expression) = &mut block.expression {
+            self.expand_expression(expression)?;
+        }
+        Ok(())
+    }
+
+    fn expand_statement(&mut self, statement: &mut Statement) -> Result<(), Error> {
+        match &mut statement.kind {
+            StatementKind::Empty => Ok(()),
+            StatementKind::Expression(Expression::Macro(macro_)) => {
+                self.expand_statement_macro(macro_)
+            }
+            StatementKind::Expression(expression) => self.expand_expression(expression),
+            StatementKind::Let(let_) => {
+                self.expand_pattern(&mut let_.pattern)?;
+                if let Some(type_) = &mut let_.type_ {
+                    self.expand_type(&mut type_.type_)?;
+                }
+                if let Some(value) = &mut let_.value {
+                    self.expand_expression(&mut value.value)?;
+                }
+                if let Some(else_) = &mut let_.else_ {
+                    self.expand_block(&mut else_.block)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Macros in statement position may expand to several statements.  They
+    /// are collected into a block expression.
+    fn expand_statement_macro(&mut self, macro_: &mut Macro<Expression>) -> Result<(), Error> {
+        let open_curly_span = macro_.invocation.arguments.open_span;
+        let close_curly_span = macro_.invocation.arguments.close_span;
+        self.expand_macro(
+            macro_,
+            |parser| {
+                let (statements, expression) = expect_block_body(parser)?;
+                Ok(Expression::Block(Block {
+                    open_curly_span,
+                    statements,
+                    expression: expression.map(Box::new),
+                    close_curly_span,
+                }))
+            },
+            |this, expression| this.expand_expression(expression),
+        )
+    }
+
+    fn expand_expression(&mut self, expression: &mut Expression) -> Result<(), Error> {
+        match expression {
+            Expression::Variable(_)
+            | Expression::Bool(_)
+            | Expression::Integer(_)
+            | Expression::Continue(_) => Ok(()),
+            Expression::Paren(paren) => self.expand_expression(&mut paren.expression),
+            Expression::Block(block) => self.expand_block(block),
+            Expression::If(if_) => self.expand_if(if_),
+            Expression::Loop(loop_) => self.expand_block(&mut loop_.block),
+            Expression::While(while_) => {
+                self.expand_condition(&mut while_.condition)?;
+                self.expand_block(&mut while_.block)
+            }
+            Expression::For(for_) => {
+                self.expand_expression(&mut for_.expr)?;
+                self.expand_block(&mut for_.block)
+            }
+            Expression::Match(match_) => {
+                self.expand_expression(&mut match_.value)?;
+                for item in &mut match_.matches {
+                    self.expand_pattern(&mut item.pattern)?;
+                    self.expand_expression(&mut item.value)?;
+                }
+                Ok(())
+            }
+            Expression::Binary(binary) => {
+                self.expand_expression(&mut binary.left)?;
+                self.expand_expression(&mut binary.right)
+            }
+            Expression::FunctionCall(function_call) => {
+                self.expand_expression(&mut function_call.function)?;
+                for argument in &mut function_call.arguments {
+                    self.expand_expression(argument)?;
+                }
+                Ok(())
+            }
+            Expression::MemberAccess(member_access) => {
+                self.expand_expression(&mut member_access.object)
+            }
+            Expression::Tuple(tuple) => {
+                for expression in &mut tuple.expressions {
+                    self.expand_expression(expression)?;
+                }
+                Ok(())
+            }
+            Expression::Ref(RefExpression { expression, .. })
+            | Expression::RefMut(RefMutExpression { expression, .. })
+            | Expression::RawConst(RawConstExpression { expression, .. })
+            | Expression::RawMut(RawMutExpression { expression, .. })
+            | Expression::Deref(Deref { expression, .. })
+            | Expression::Try(Try { expression, .. }) => self.expand_expression(expression),
+            Expression::Cast(cast) => {
+                self.expand_expression(&mut cast.expression)?;
+                self.expand_type(&mut cast.type_)
+            }
+            Expression::Return(Return { value, .. }) | Expression::Break(Break { value, .. }) => {
+                match value {
+                    Some(value) => self.expand_expression(value),
+                    None => Ok(()),
+                }
+            }
+            Expression::Macro(macro_) => self.expand_macro(macro_, expect_expression, |this, e| {
+                this.expand_expression(e)
+            }),
+        }
+    }
+
+    fn expand_condition(&mut self, condition: &mut Condition) -> Result<(), Error> {
+        match condition {
+            Condition::Expression(expression) => self.expand_expression(expression),
+            Condition::Let(let_) => {
+                self.expand_pattern(&mut let_.pattern)?;
+                self.expand_expression(&mut let_.value)
+            }
+        }
+    }
+
+    fn expand_if(&mut self, if_: &mut If) -> Result<(), Error> {
+        self.expand_condition(&mut if_.condition)?;
+        self.expand_block(&mut if_.then)?;
+        match if_.else_.as_mut().map(|else_| &mut else_.kind) {
+            Some(ElseKind::If(if_)) => self.expand_if(if_),
+            Some(ElseKind::Block(block)) => self.expand_block(block),
+            None => Ok(()),
+        }
+    }
+
+    fn expand_pattern(&mut self, pattern: &mut Pattern) -> Result<(), Error> {
+        match pattern {
+            Pattern::Named(_) | Pattern::Hole(_) => Ok(()),
+            Pattern::Tuple(tuple) | Pattern::NamedTuple(_, tuple) => {
+                for pattern in &mut tuple.patterns {
+                    self.expand_pattern(pattern)?;
+                }
+                Ok(())
+            }
+            Pattern::Paren(paren) => self.expand_pattern(&mut paren.pattern),
+            Pattern::Macro(macro_) => {
+                self.expand_macro(macro_, expect_pattern, |this, p| this.expand_pattern(p))
+            }
+        }
+    }
+
+    fn expand_type(&mut self, type_: &mut Type) -> Result<(), Error> {
+        match type_ {
+            Type::Named(_) | Type::Hole(_) => Ok(()),
+            Type::Ref(RefType { type_, .. })
+            | Type::RefMut(RefMutType { type_, .. })
+            | Type::PtrConst(PtrConstType { type_, .. })
+            | Type::PtrMut(PtrMutType { type_, .. })
+            | Type::Paren(ParenType { type_, .. }) => self.expand_type(type_),
+            Type::Tuple(tuple) => {
+                for type_ in &mut tuple.types {
+                    self.expand_type(type_)?;
+                }
+                Ok(())
+            }
+            Type::Macro(macro_) => {
+                self.expand_macro(macro_, expect_type, |this, t| this.expand_type(t))
+            }
+        }
+    }
+
+    /// Transcribes the invocation, parses the result with `parse`, and then
+    /// expands the macros inside of it with `expand`.
+    fn expand_macro<T, P, E>(
+        &mut self,
+        macro_: &mut Macro<T>,
+        parse: P,
+        expand: E,
+    ) -> Result<(), Error>
+    where
+        P: FnOnce(&mut Parser) -> Result<T, crate::parse::Error>,
+        E: FnOnce(&mut Self, &mut T) -> Result<(), Error>,
+    {
+        let call_site = invocation_span(&macro_.invocation);
+        if self.depth == RECURSION_LIMIT {
+            return Err(Error::RecursionLimit(call_site));
+        }
+
+        let (def_site, tokens) = self.transcribe_invocation(&macro_.invocation)?;
+        let name = *macro_.invocation.path.segments.last().unwrap();
+        self.expansions.push(ExpnData {
+            call_site,
+            def_site,
+            macro_name: format!("{}!", &self.file_contents[name]),
+        });
+        let close_span = macro_.invocation.arguments.close_span;
+        let eofpos = Pos {
+            file: close_span.file,
+            index: close_span.start,
+        };
+        let mut parser = Parser::new(self.file_contents, &tokens, eofpos);
+        let mut value = parse(&mut parser).map_err(Error::Parse)?;
+        if parser.index < tokens.len() {
+            return Err(Error::Parse(crate::parse::Error::Expected(
+                "end of macro expansion",
+                parser.span(),
+            )));
+        }
+
+        self.contexts += 1;
+        let context = SyntaxContext(self.contexts);
+        self.depth += 1;
+        let result = expand(self, &mut value);
+        self.depth -= 1;
+        result?;
+
+        macro_.expansion = Some(Box::new(Expansion {
+            context,
+            def_site,
+            value,
+        }));
+        Ok(())
+    }
+
+    fn transcribe_invocation(
+        &mut self,
+        invocation: &MacroInvocation,
+    ) -> Result<(Span, Vec<Token>), Error> {
+        let name = *invocation.path.segments.last().unwrap();
+        let rules = match self.macros.get(&self.file_contents[name]) {
+            Some(rules) => rules.clone(),
+            None => return Err(Error::UndefinedMacro(name)),
+        };
+
+        let mut input = Vec::new();
+        flatten_token_trees(&invocation.arguments.trees, &mut input);
+        let close_span = invocation.arguments.close_span;
+        let eofpos = Pos {
+            file: close_span.file,
+            index: close_span.start,
+        };
+
+        for rule in rules.iter() {
+            if let Some(bindings) = match_rule(self.file_contents, &rule.matchers, &input, eofpos) {
+                let mut tokens = Vec::new();
+                // The expansion is recorded with the next id once it's transcribed.
+                let expn = ExpnId(self.expansions.len() as u32);
+                transcribe(
+                    self.file_contents,
+                    &rule.transcribers,
+                    &bindings,
+                    expn,
+                    &mut tokens,
+                )?;
+                return Ok((rule.def_site, tokens));
+            }
+        }
+        Err(Error::NoRulesMatched(invocation_span(invocation)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lex::read_tokens;
+    use crate::parse::parse;
+    use assert_matches::assert_matches;
+
+    fn parse_and_expand(contents: &str) -> Result<Vec<TopLevel>, Error> {
+        let (tokens, eofpos) = read_tokens(0, contents).unwrap();
+        let mut top_levels = parse(contents, &tokens, eofpos).unwrap();
+        expand(contents, &mut top_levels)?;
+        Ok(top_levels)
+    }
+
+    fn function_body(top_level: &TopLevel) -> &Block {
+        match &top_level.kind {
+            TopLevelKind::Function(f) => &f.body,
+            _ => panic!("expected function"),
+        }
+    }
+
+    #[test]
+    fn test_expand_expression_macro() {
+        let top_levels =
+            parse_and_expand("macro_rules! two { () => { 1 + 1 } } fn f() { two!() }").unwrap();
+        let body = function_body(&top_levels[1]);
+        assert_matches!(body.expression.as_deref(), Some(Expression::Macro(Macro {
+            expansion: Some(expansion),
+            ..
+        })) => {
+            assert_eq!(expansion.context, SyntaxContext(1));
+            assert_matches!(expansion.value, Expression::Binary(_));
+        });
+    }
+
+    #[test]
+    fn test_expand_records_each_use() {
+        let contents = "macro_rules! x { () => { x } } fn f() { x!() + x!() }";
+        let (tokens, eofpos) = read_tokens(0, contents).unwrap();
+        let mut top_levels = parse(contents, &tokens, eofpos).unwrap();
+        let expansions = expand(contents, &mut top_levels).unwrap();
+        let call_sites: Vec<_> = expansions
+            .iter()
+            .map(|expansion| &contents[expansion.call_site])
+            .collect();
+        assert_eq!(call_sites, ["x!()", "x!()"]);
+        assert_ne!(expansions[0].call_site, expansions[1].call_site);
+
+        let body = function_body(&top_levels[1]);
+        let variable = |expression: &Expression| match expression {
+            Expression::Macro(Macro {
+                expansion: Some(expansion),
+                ..
+            }) => match &expansion.value {
+                Expression::Variable(variable) => variable.name,
+                _ => panic!("expected variable"),
+            },
+            _ => panic!("expected macro"),
+        };
+        assert_matches!(body.expression.as_deref(), Some(Expression::Binary(binary)) => {
+            let (left, right) = (variable(&binary.left), variable(&binary.right));
+            assert_eq!((left.start, right.start), (25, 25));
+            assert_eq!(left.expn, Some(ExpnId(0)));
+            assert_eq!(right.expn, Some(ExpnId(1)));
+        });
+    }
+
+    #[test]
+    fn test_expand_statement_macro_expands_to_block() {
+        let top_levels = parse_and_expand(
+            "macro_rules! decl { ($i:ident) => { let $i = 1; let y = $i; } } fn f() { decl!(x); }",
+        )
+        .unwrap();
+        let body = function_body(&top_levels[1]);
+        assert_matches!(&body.statements[0].kind, StatementKind::Expression(Expression::Macro(Macro {
+            expansion: Some(expansion),
+            ..
+        })) => {
+            assert_matches!(&expansion.value, Expression::Block(block) => {
+                assert_eq!(block.statements.len(), 2);
+            });
+        });
+    }
+
+    #[test]
+    fn test_expand_item_macro() {
+        let top_levels = parse_and_expand(
+            "macro_rules! func { ($name:ident) => { fn $name() {} } } func!(g); func! { h }",
+        )
+        .unwrap();
+        for top_level in &top_levels[1..] {
+            assert_matches!(&top_level.kind, TopLevelKind::Macro(Macro {
+                expansion: Some(expansion),
+                ..
+            }, _) => {
+                assert_matches!(expansion.value.as_slice(), [TopLevel {
+                    kind: TopLevelKind::Function(_),
+                    ..
+                }]);
+            });
+        }
+    }
+
+    #[test]
+    fn test_expand_pattern_and_type_macros() {
+        let top_levels = parse_and_expand(
+            "macro_rules! t { () => { i32 } } macro_rules! p { () => { (a, _) } }
+             fn f(x: t!()) { match x { p!() => a } }",
+        )
+        .unwrap();
+        assert_matches!(&top_levels[2].kind, TopLevelKind::Function(f) => {
+            assert_matches!(&f.parameters[0].type_, Type::Macro(Macro {
+                expansion: Some(expansion),
+                ..
+            }) => {
+                assert_matches!(expansion.value, Type::Named(_));
+            });
+            assert_matches!(&f.body.statements[0].kind, StatementKind::Expression(Expression::Match(m)) => {
+                assert_matches!(&m.matches[0].pattern, Pattern::Macro(Macro {
+                    expansion: Some(expansion),
+                    ..
+                }) => {
+                    assert_matches!(expansion.value, Pattern::Tuple(_));
+                });
+            });
+        });
+    }
+
+    #[test]
+    fn test_expand_returns_expansions() {
+        let contents = "macro_rules! one { () => { 1 } } macro_rules! two { () => { one!() } }
+                        fn f() { two!() }";
+        let (tokens, eofpos) = read_tokens(0, contents).unwrap();
+        let mut top_levels = parse(contents, &tokens, eofpos).unwrap();
+        let expansions = expand(contents, &mut top_levels).unwrap();
+        let text = |span: Span| &contents[span];
+        let expansions: Vec<_> = expansions
+            .iter()
+            .map(|e| (text(e.call_site), text(e.def_site), e.macro_name.as_str()))
+            .collect();
+        assert_eq!(
+            expansions,
+            [
+                ("two!()", "{ one!() }", "two!"),
+                ("one!()", "{ 1 }", "one!"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_nested_macros_get_fresh_contexts() {
+        let top_levels = parse_and_expand(
+            "macro_rules! one { () => { 1 } } macro_rules! two { () => { one!() + one!() } }
+             fn f() { two!() }",
+        )
+        .unwrap();
+        let body = function_body(&top_levels[2]);
+        assert_matches!(body.expression.as_deref(), Some(Expression::Macro(Macro {
+            expansion: Some(expansion),
+            ..
+        })) => {
+            assert_matches!(&expansion.value, Expression::Binary(Binary { left, right, .. }) => {
+                assert_matches!((&**left, &**right), (
+                    Expression::Macro(Macro { expansion: Some(l), .. }),
+                    Expression::Macro(Macro { expansion: Some(r), .. }),
+                ) => {
+                    assert_ne!(l.context, r.context);
+                    assert_ne!(l.context, expansion.context);
+                });
+            });
+        });
+    }
+
+    #[test]
+    fn test_expand_macro_rules_tried_in_order() {
+        let top_levels = parse_and_expand(
+            "macro_rules! m { (a) => { true }; ($e:expr) => { $e } } fn f() { m!(a) }",
+        )
+        .unwrap();
+        let body = function_body(&top_levels[1]);
+        assert_matches!(body.expression.as_deref(), Some(Expression::Macro(Macro {
+            expansion: Some(expansion),
+            ..
+        })) => {
+            assert_matches!(expansion.value, Expression::Bool(_));
+        });
+    }
+
+    #[test]
+    fn test_expand_undefined_macro() {
+        assert_eq!(
+            parse_and_expand("fn f() { println!(\"hi\"); }"),
+            Err(Error::UndefinedMacro(Span {
+                file: 0,
+                start: 9,
+                end: 16,
+                expn: None
+            }))
+        );
+    }
+
+    #[test]
+    fn test_expand_macro_used_before_definition() {
+        assert_matches!(
+            parse_and_expand("fn f() { m!() } macro_rules! m { () => {} }"),
+            Err(Error::UndefinedMacro(_))
+        );
+    }
+
+    #[test]
+    fn test_expand_no_rules_matched() {
+        assert_eq!(
+            parse_and_expand("macro_rules! m { (a) => {} } fn f() { m!(b) }"),
+            Err(Error::NoRulesMatched(Span {
+                file: 0,
+                start: 38,
+                end: 43,
+                expn: None
+            }))
+        );
+    }
+
+    #[test]
+    fn test_expand_leftover_tokens_in_expression_position() {
+        assert_matches!(
+            parse_and_expand("macro_rules! m { () => { 1 2 } } fn f() { m!() }"),
+            Err(Error::Parse(crate::parse::Error::Expected(
+                "end of macro expansion",
+                _
+            )))
+        );
+    }
+
+    #[test]
+    fn test_expand_infinite_recursion() {
+        assert_matches!(
+            parse_and_expand("macro_rules! m { () => { m!() } } fn f() { m!() }"),
+            Err(Error::RecursionLimit(_))
+        );
+    }
+
+    #[test]
+    fn test_invocation_span() {
+        let contents = "::a::b!(x)";
+        let (tokens, eofpos) = read_tokens(0, contents).unwrap();
+        let mut parser = Parser::new(contents, &tokens, eofpos);
+        let invocation = crate::parse::macro_::expect_macro_invocation(&mut parser).unwrap();
+        assert_eq!(
+            invocation_span(&invocation),
+            Span {
+                file: 0,
+                start: 0,
+                end: 10,
+                expn: None
+            }
+        );
+    }
+}
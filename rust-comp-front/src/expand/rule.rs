@@ -0,0 +1,361 @@
+use super::Error;
+use crate::parse::macro_::{close_token, open_token};
+use crate::parse::{self, DelimitedTokenTree, Delimiter, MacroRule, TokenTree};
+use crate::pos::Span;
+use crate::token::*;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rule {
+    pub matchers: Vec<Matcher>,
+    pub transcribers: Vec<Transcriber>,
+    pub def_site: Span,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Matcher {
+    Token(Token),
+    Fragment(Span, FragmentKind),
+    Repetition(Repetition<Matcher>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Transcriber {
+    Token(Token),
+    Variable(Span),
+    Repetition(Repetition<Transcriber>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Repetition<T> {
+    pub dollar_span: Span,
+    pub elements: Vec<T>,
+    pub separator: Option<Token>,
+    pub op: KleeneOp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KleeneOp {
+    ZeroOrMore,
+    OneOrMore,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FragmentKind {
+    Block,
+    Expr,
+    Ident,
+    Item,
+    Literal,
+    Pat,
+    Path,
+    Tt,
+    Ty,
+    Vis,
+}
+
+pub fn compile_rule(file_contents: &str, rule: &MacroRule) -> Result<Rule, Error> {
+    Ok(Rule {
+        matchers: compile_matchers(file_contents, &rule.matcher.trees)?,
+        transcribers: compile_transcribers(&rule.transcriber.trees)?,
+        def_site: Span {
+            file: rule.transcriber.open_span.file,
+            start: rule.transcriber.open_span.start,
+            end: rule.transcriber.close_span.end,
+            expn: rule.transcriber.open_span.expn,
+        },
+    })
+}
+
+fn compile_matchers(file_contents: &str, trees: &[TokenTree]) -> Result<Vec<Matcher>, Error> {
+    let mut matchers = Vec::new();
+    let mut i = 0;
+    while i < trees.len() {
+        match &trees[i] {
+            TokenTree::Token(Token {
+                kind: TokenKind::Dollar,
+                span: dollar_span,
+            }) => match &trees.get(i + 1) {
+                Some(TokenTree::Token(Token {
+                    kind: TokenKind::Label,
+                    span: name,
+                })) => {
+                    let kind = expect_fragment_specifier(file_contents, trees, i + 2, *name)?;
+                    matchers.push(Matcher::Fragment(*name, kind));
+                    i += 4;
+                }
+                Some(TokenTree::Delimited(delimited))
+                    if delimited.delimiter == Delimiter::Paren =>
+                {
+                    let (separator, op, suffix_len) =
+                        expect_repetition_suffix(trees, i + 2, delimited.close_span)?;
+                    matchers.push(Matcher::Repetition(Repetition {
+                        dollar_span: *dollar_span,
+                        elements: compile_matchers(file_contents, &delimited.trees)?,
+                        separator,
+                        op,
+                    }));
+                    i += 2 + suffix_len;
+                }
+                _ => return Err(expected_after_dollar(*dollar_span)),
+            },
+            TokenTree::Token(token) => {
+                matchers.push(Matcher::Token(token.clone()));
+                i += 1;
+            }
+            TokenTree::Delimited(delimited) => {
+                let (open, close) = delimiter_tokens(delimited);
+                matchers.push(Matcher::Token(open));
+                matchers.extend(compile_matchers(file_contents, &delimited.trees)?);
+                matchers.push(Matcher::Token(close));
+                i += 1;
+            }
+        }
+    }
+    Ok(matchers)
+}
+
+fn compile_transcribers(trees: &[TokenTree]) -> Result<Vec<Transcriber>, Error> {
+    let mut transcribers = Vec::new();
+    let mut i = 0;
+    while i < trees.len() {
+        match &trees[i] {
+            TokenTree::Token(Token {
+                kind: TokenKind::Dollar,
+                span: dollar_span,
+            }) => match &trees.get(i + 1) {
+                Some(TokenTree::Token(Token {
+                    kind: TokenKind::Label,
+                    span: name,
+                })) => {
+                    transcribers.push(Transcriber::Variable(*name));
+                    i += 2;
+                }
+                Some(TokenTree::Delimited(delimited))
+                    if delimited.delimiter == Delimiter::Paren =>
+                {
+                    let (separator, op, suffix_len) =
+                        expect_repetition_suffix(trees, i + 2, delimited.close_span)?;
+                    transcribers.push(Transcriber::Repetition(Repetition {
+                        dollar_span: *dollar_span,
+                        elements: compile_transcribers(&delimited.trees)?,
+                        separator,
+                        op,
+                    }));
+                    i += 2 + suffix_len;
+                }
+                _ => return Err(expected_after_dollar(*dollar_span)),
+            },
+            TokenTree::Token(token) => {
+                transcribers.push(Transcriber::Token(token.clone()));
+                i += 1;
+            }
+            TokenTree::Delimited(delimited) => {
+                let (open, close) = delimiter_tokens(delimited);
+                transcribers.push(Transcriber::Token(open));
+                transcribers.extend(compile_transcribers(&delimited.trees)?);
+                transcribers.push(Transcriber::Token(close));
+                i += 1;
+            }
+        }
+    }
+    Ok(transcribers)
+}
+
+fn expect_fragment_specifier(
+    file_contents: &str,
+    trees: &[TokenTree],
+    i: usize,
+    name: Span,
+) -> Result<FragmentKind, Error> {
+    match (trees.get(i), trees.get(i + 1)) {
+        (
+            Some(TokenTree::Token(Token {
+                kind: TokenKind::Colon,
+                ..
+            })),
+            Some(TokenTree::Token(Token {
+                kind: TokenKind::Label,
+                span,
+            })),
+        ) => fragment_kind(&file_contents[*span]).ok_or(Error::UnknownFragmentSpecifier(*span)),
+        _ => Err(Error::Parse(parse::Error::Expected(
+            "fragment specifier",
+            name,
+        ))),
+    }
+}
+
+fn fragment_kind(specifier: &str) -> Option<FragmentKind> {
+    match specifier {
+        "block" => Some(FragmentKind::Block),
+        "expr" => Some(FragmentKind::Expr),
+        "ident" => Some(FragmentKind::Ident),
+        "item" => Some(FragmentKind::Item),
+        "literal" => Some(FragmentKind::Literal),
+        "pat" => Some(FragmentKind::Pat),
+        "path" => Some(FragmentKind::Path),
+        "tt" => Some(FragmentKind::Tt),
+        "ty" => Some(FragmentKind::Ty),
+        "vis" => Some(FragmentKind::Vis),
+        _ => None,
+    }
+}
+
+/// Parses the optional separator and the Kleene operator following `$(...)`.
+/// Returns the number of trees they used.
+fn expect_repetition_suffix(
+    trees: &[TokenTree],
+    i: usize,
+    close_span: Span,
+) -> Result<(Option<Token>, KleeneOp, usize), Error> {
+    let token = |i| match trees.get(i) {
+        Some(TokenTree::Token(token)) => Some(token.clone()),
+        _ => None,
+    };
+    match (token(i), token(i + 1)) {
+        (Some(op), _) if kleene_op(op.kind).is_some() => Ok((None, kleene_op(op.kind).unwrap(), 1)),
+        (Some(separator), Some(op)) if kleene_op(op.kind).is_some() => {
+            Ok((Some(separator), kleene_op(op.kind).unwrap(), 2))
+        }
+        _ => Err(Error::Parse(parse::Error::Expected(
+            "`*` or `+` after repetition",
+            close_span,
+        ))),
+    }
+}
+
+fn kleene_op(kind: TokenKind) -> Option<KleeneOp> {
+    match kind {
+        TokenKind::Star => Some(KleeneOp::ZeroOrMore),
+        TokenKind::Plus => Some(KleeneOp::OneOrMore),
+        _ => None,
+    }
+}
+
+fn delimiter_tokens(delimited: &DelimitedTokenTree) -> (Token, Token) {
+    (
+        Token {
+            kind: open_token(delimited.delimiter),
+            span: delimited.open_span,
+        },
+        Token {
+            kind: close_token(delimited.delimiter),
+            span: delimited.close_span,
+        },
+    )
+}
+
+fn expected_after_dollar(dollar_span: Span) -> Error {
+    Error::Parse(parse::Error::Expected(
+        "metavariable or repetition after `$`",
+        dollar_span,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lex::read_tokens;
+    use crate::parse::parse;
+    use crate::parse::TopLevelKind;
+    use assert_matches::assert_matches;
+
+    fn compile(contents: &str) -> Result<Rule, Error> {
+        let (tokens, eofpos) = read_tokens(0, contents).unwrap();
+        let top_levels = parse(contents, &tokens, eofpos).unwrap();
+        match &top_levels[0].kind {
+            TopLevelKind::MacroRules(macro_rules) => compile_rule(contents, &macro_rules.rules[0]),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_compile_rule_fragment() {
+        let rule = compile("macro_rules! m { ($e:expr) => { $e } }").unwrap();
+        assert_matches!(
+            rule.matchers.as_slice(),
+            [Matcher::Fragment(_, FragmentKind::Expr)]
+        );
+        assert_matches!(rule.transcribers.as_slice(), [Transcriber::Variable(_)]);
+    }
+
+    #[test]
+    fn test_compile_rule_def_site_covers_transcriber() {
+        let rule = compile("macro_rules! m { () => { 1 } }").unwrap();
+        assert_eq!(
+            rule.def_site,
+            Span {
+                file: 0,
+                start: 23,
+                end: 28,
+                expn: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_compile_rule_repetition_with_separator() {
+        let rule = compile("macro_rules! m { ($($e:expr),*) => {} }").unwrap();
+        assert_matches!(rule.matchers.as_slice(), [Matcher::Repetition(Repetition {
+            elements,
+            separator: Some(Token { kind: TokenKind::Comma, .. }),
+            op: KleeneOp::ZeroOrMore,
+            ..
+        })] => {
+            assert_eq!(elements.len(), 1);
+        });
+    }
+
+    #[test]
+    fn test_compile_rule_repetition_one_or_more_no_separator() {
+        let rule = compile("macro_rules! m { ($($i:ident)+) => {} }").unwrap();
+        assert_matches!(
+            rule.matchers.as_slice(),
+            [Matcher::Repetition(Repetition {
+                separator: None,
+                op: KleeneOp::OneOrMore,
+                ..
+            })]
+        );
+    }
+
+    #[test]
+    fn test_compile_rule_delimiters_are_flattened() {
+        let rule = compile("macro_rules! m { ([$i:ident]) => {} }").unwrap();
+        assert_matches!(
+            rule.matchers.as_slice(),
+            [
+                Matcher::Token(Token {
+                    kind: TokenKind::OpenSquare,
+                    ..
+                }),
+                Matcher::Fragment(_, FragmentKind::Ident),
+                Matcher::Token(Token {
+                    kind: TokenKind::CloseSquare,
+                    ..
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_rule_unknown_fragment_specifier() {
+        assert_eq!(
+            compile("macro_rules! m { ($e:bogus) => {} }"),
+            Err(Error::UnknownFragmentSpecifier(Span {
+                file: 0,
+                start: 21,
+                end: 26,
+                expn: None
+            }))
+        );
+    }
+
+    #[test]
+    fn test_compile_rule_repetition_without_operator() {
+        assert_matches!(
+            compile("macro_rules! m { ($($e:expr)) => {} }"),
+            Err(Error::Parse(parse::Error::Expected(_, _)))
+        );
+    }
+}
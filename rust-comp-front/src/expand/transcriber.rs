@@ -0,0 +1,237 @@
+use super::matcher::*;
+use super::rule::*;
+use super::Error;
+use crate::pos::{ExpnId, Span};
+use crate::token::*;
+
+/// Pushes the tokens of `transcribers` with `bindings` substituted.  The
+/// tokens copied from the transcribers are marked as part of `expn`.
+pub fn transcribe(
+    file_contents: &str,
+    transcribers: &[Transcriber],
+    bindings: &Bindings,
+    expn: ExpnId,
+    tokens: &mut Vec<Token>,
+) -> Result<(), Error> {
+    for transcriber in transcribers {
+        match transcriber {
+            Transcriber::Token(token) => tokens.push(in_expansion(token, expn)),
+            Transcriber::Variable(name) => match bindings.get(&file_contents[*name]) {
+                Some(Binding::Fragment(kind, fragment)) => push_fragment(*kind, fragment, tokens),
+                Some(Binding::Repetition(_)) => return Err(Error::InvalidRepetition(*name)),
+                None => return Err(Error::UnboundMetavariable(*name)),
+            },
+            Transcriber::Repetition(repetition) => {
+                transcribe_repetition(file_contents, repetition, bindings, expn, tokens)?
+            }
+        }
+    }
+    Ok(())
+}
+
+fn transcribe_repetition(
+    file_contents: &str,
+    repetition: &Repetition<Transcriber>,
+    bindings: &Bindings,
+    expn: ExpnId,
+    tokens: &mut Vec<Token>,
+) -> Result<(), Error> {
+    let mut names = Vec::new();
+    variables(file_contents, &repetition.elements, &mut names);
+    let repeating: Vec<_> = names
+        .into_iter()
+        .filter_map(|name| match bindings.get(name) {
+            Some(Binding::Repetition(values)) => Some((name, values)),
+            _ => None,
+        })
+        .collect();
+
+    let count = match repeating.first() {
+        Some((_, values)) => values.len(),
+        None => return Err(Error::InvalidRepetition(repetition.dollar_span)),
+    };
+    if repeating.iter().any(|(_, values)| values.len() != count) {
+        return Err(Error::InvalidRepetition(repetition.dollar_span));
+    }
+
+    for i in 0..count {
+        if i > 0 {
+            if let Some(separator) = &repetition.separator {
+                tokens.push(in_expansion(separator, expn));
+            }
+        }
+        let mut iteration = bindings.clone();
+        for (name, values) in &repeating {
+            iteration.insert(name, values[i].clone());
+        }
+        transcribe(
+            file_contents,
+            &repetition.elements,
+            &iteration,
+            expn,
+            tokens,
+        )?;
+    }
+    Ok(())
+}
+
+fn in_expansion(token: &Token, expn: ExpnId) -> Token {
+    Token {
+        kind: token.kind,
+        span: Span {
+            expn: Some(expn),
+            ..token.span
+        },
+    }
+}
+
+/// Expressions are parenthesized so that they keep their precedence when
+/// substituted next to operators.
+fn push_fragment(kind: FragmentKind, fragment: &[Token], tokens: &mut Vec<Token>) {
+    if kind == FragmentKind::Expr && fragment.len() > 1 {
+        tokens.push(Token {
+            kind: TokenKind::OpenParen,
+            span: fragment[0].span,
+        });
+        tokens.extend_from_slice(fragment);
+        tokens.push(Token {
+            kind: TokenKind::CloseParen,
+            span: fragment[fragment.len() - 1].span,
+        });
+    } else {
+        tokens.extend_from_slice(fragment);
+    }
+}
+
+fn variables<'a>(file_contents: &'a str, transcribers: &[Transcriber], names: &mut Vec<&'a str>) {
+    for transcriber in transcribers {
+        match transcriber {
+            Transcriber::Token(_) => (),
+            Transcriber::Variable(name) => names.push(&file_contents[*name]),
+            Transcriber::Repetition(repetition) => {
+                variables(file_contents, &repetition.elements, names)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lex::read_tokens;
+    use crate::parse::macro_::flatten_token_trees;
+    use crate::parse::{parse, TopLevelKind};
+    use crate::pos::Span;
+
+    /// Expands `m!(...)` with the first rule of the preceding `macro_rules!`
+    /// and returns the text of the resulting tokens.  Parentheses are shown by
+    /// kind because inserted ones borrow the span of the fragment.
+    fn expand_first_rule(contents: &str) -> Result<Vec<&str>, Error> {
+        let (tokens, eofpos) = read_tokens(0, contents).unwrap();
+        let top_levels = parse(contents, &tokens, eofpos).unwrap();
+        let rule = match &top_levels[0].kind {
+            TopLevelKind::MacroRules(macro_rules) => {
+                compile_rule(contents, &macro_rules.rules[0]).unwrap()
+            }
+            _ => unreachable!(),
+        };
+        let arguments = match &top_levels[1].kind {
+            TopLevelKind::Macro(macro_, _) => &macro_.invocation.arguments,
+            _ => unreachable!(),
+        };
+        let mut input = Vec::new();
+        flatten_token_trees(&arguments.trees, &mut input);
+        let bindings = match_rule(contents, &rule.matchers, &input, eofpos).unwrap();
+        let mut output = Vec::new();
+        transcribe(
+            contents,
+            &rule.transcribers,
+            &bindings,
+            ExpnId(0),
+            &mut output,
+        )?;
+        Ok(output
+            .iter()
+            .map(|t| match t.kind {
+                TokenKind::OpenParen => "(",
+                TokenKind::CloseParen => ")",
+                _ => &contents[t.span],
+            })
+            .collect())
+    }
+
+    #[test]
+    fn test_transcribe_substitutes_ident() {
+        assert_eq!(
+            expand_first_rule("macro_rules! m { ($i:ident) => { let $i = 1; } } m!(x);"),
+            Ok(vec!["let", "x", "=", "1", ";"])
+        );
+    }
+
+    #[test]
+    fn test_transcribe_parenthesizes_expr() {
+        assert_eq!(
+            expand_first_rule("macro_rules! m { ($e:expr) => { $e * 2 } } m!(a + b);"),
+            Ok(vec!["(", "a", "+", "b", ")", "*", "2"])
+        );
+    }
+
+    #[test]
+    fn test_transcribe_repetition_with_separator() {
+        assert_eq!(
+            expand_first_rule("macro_rules! m { ($($e:expr),*) => { f($($e);*) } } m!(1, 2);"),
+            Ok(vec!["f", "(", "1", ";", "2", ")"])
+        );
+    }
+
+    #[test]
+    fn test_transcribe_repetition_mixed_with_non_repeating() {
+        assert_eq!(
+            expand_first_rule(
+                "macro_rules! m { ($f:ident $($e:expr)*) => { $($f($e))* } } m!(g 1 2);"
+            ),
+            Ok(vec!["g", "(", "1", ")", "g", "(", "2", ")"])
+        );
+    }
+
+    #[test]
+    fn test_transcribe_unbound_metavariable() {
+        assert_eq!(
+            expand_first_rule("macro_rules! m { () => { $y } } m!();"),
+            Err(Error::UnboundMetavariable(Span {
+                file: 0,
+                start: 26,
+                end: 27,
+                expn: None
+            }))
+        );
+    }
+
+    #[test]
+    fn test_transcribe_repeating_variable_outside_repetition() {
+        assert_eq!(
+            expand_first_rule("macro_rules! m { ($($e:expr)*) => { $e } } m!(1);"),
+            Err(Error::InvalidRepetition(Span {
+                file: 0,
+                start: 37,
+                end: 38,
+                expn: None
+            }))
+        );
+    }
+
+    #[test]
+    fn test_transcribe_repetition_lengths_differ() {
+        assert_eq!(
+            expand_first_rule(
+                "macro_rules! m { ($($a:ident)* ; $($b:ident)*) => { $($a $b)* } } m!(x y ; z);"
+            ),
+            Err(Error::InvalidRepetition(Span {
+                file: 0,
+                start: 52,
+                end: 53,
+                expn: None
+            }))
+        );
+    }
+}
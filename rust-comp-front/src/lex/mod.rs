@@ -3,64 +3,82 @@ use self::tagged_iter::TaggedIter;
 
 use crate::pos::*;
 use crate::token::*;
+use rust_comp_core::suggest;
 use std::collections::HashMap;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Error {
     UnterminatedBlockComment(Pos),
     UnrecognizedControlChar(Pos),
+    UnterminatedString(Pos),
 }
 
+/// The spellings of every token other than labels and integers.
+const KEYWORDS: &[(&str, TokenKind)] = &[
+    ("!", TokenKind::Bang),
+    ("!=", TokenKind::NotEquals),
+    ("#", TokenKind::Pound),
+    ("$", TokenKind::Dollar),
+    ("&", TokenKind::Ampersand),
+    ("&&", TokenKind::And),
+    ("(", TokenKind::OpenParen),
+    (")", TokenKind::CloseParen),
+    ("*", TokenKind::Star),
+    ("+", TokenKind::Plus),
+    (",", TokenKind::Comma),
+    ("-", TokenKind::Minus),
+    ("->", TokenKind::ThinArrow),
+    (".", TokenKind::Dot),
+    ("/", TokenKind::ForwardSlash),
+    (":", TokenKind::Colon),
+    ("::", TokenKind::ColonColon),
+    (";", TokenKind::Semicolon),
+    ("?", TokenKind::Question),
+    ("=", TokenKind::Set),
+    ("==", TokenKind::Equals),
+    ("=>", TokenKind::FatArrow),
+    ("[", TokenKind::OpenSquare),
+    ("]", TokenKind::CloseSquare),
+    ("_", TokenKind::Underscore),
+    ("as", TokenKind::As),
+    ("break", TokenKind::Break),
+    ("const", TokenKind::Const),
+    ("continue", TokenKind::Continue),
+    ("else", TokenKind::Else),
+    ("enum", TokenKind::Enum),
+    ("false", TokenKind::False),
+    ("fn", TokenKind::Fn),
+    ("for", TokenKind::For),
+    ("if", TokenKind::If),
+    ("in", TokenKind::In),
+    ("let", TokenKind::Let),
+    ("loop", TokenKind::Loop),
+    ("match", TokenKind::Match),
+    ("mod", TokenKind::Mod),
+    ("mut", TokenKind::Mut),
+    ("pub", TokenKind::Pub),
+    ("return", TokenKind::Return),
+    ("struct", TokenKind::Struct),
+    ("true", TokenKind::True),
+    ("use", TokenKind::Use),
+    ("while", TokenKind::While),
+    ("{", TokenKind::OpenCurly),
+    ("|", TokenKind::Bar),
+    ("||", TokenKind::Or),
+    ("}", TokenKind::CloseCurly),
+];
+
 pub fn read_tokens<'a>(file: usize, contents: &str) -> Result<(Vec<Token>, Pos), Error> {
-    let mut keywords = HashMap::new();
-    keywords.insert("!=", TokenKind::NotEquals);
-    keywords.insert("&", TokenKind::Ampersand);
-    keywords.insert("&&", TokenKind::And);
-    keywords.insert("(", TokenKind::OpenParen);
-    keywords.insert(")", TokenKind::CloseParen);
-    keywords.insert("*", TokenKind::Star);
-    keywords.insert("+", TokenKind::Plus);
-    keywords.insert(",", TokenKind::Comma);
-    keywords.insert("-", TokenKind::Minus);
-    keywords.insert("->", TokenKind::ThinArrow);
-    keywords.insert(".", TokenKind::Dot);
-    keywords.insert("/", TokenKind::ForwardSlash);
-    keywords.insert(":", TokenKind::Colon);
-    keywords.insert("::", TokenKind::ColonColon);
-    keywords.insert(";", TokenKind::Semicolon);
-    keywords.insert("=", TokenKind::Set);
-    keywords.insert("==", TokenKind::Equals);
-    keywords.insert("=>", TokenKind::FatArrow);
-    keywords.insert("_", TokenKind::Underscore);
-    keywords.insert("const", TokenKind::Const);
-    keywords.insert("else", TokenKind::Else);
-    keywords.insert("enum", TokenKind::Enum);
-    keywords.insert("false", TokenKind::False);
-    keywords.insert("fn", TokenKind::Fn);
-    keywords.insert("for", TokenKind::For);
-    keywords.insert("if", TokenKind::If);
-    keywords.insert("in", TokenKind::In);
-    keywords.insert("let", TokenKind::Let);
-    keywords.insert("loop", TokenKind::Loop);
-    keywords.insert("match", TokenKind::Match);
-    keywords.insert("mod", TokenKind::Mod);
-    keywords.insert("mut", TokenKind::Mut);
-    keywords.insert("pub", TokenKind::Pub);
-    keywords.insert("struct", TokenKind::Struct);
-    keywords.insert("true", TokenKind::True);
-    keywords.insert("use", TokenKind::Use);
-    keywords.insert("while", TokenKind::While);
-    keywords.insert("{", TokenKind::OpenCurly);
-    keywords.insert("|", TokenKind::Bar);
-    keywords.insert("||", TokenKind::Or);
-    keywords.insert("}", TokenKind::CloseCurly);
+    let keywords: HashMap<_, _> = KEYWORDS.iter().copied().collect();
 
     let mut tagged_iter = TaggedIter::new(file, contents);
+    skip_bom_and_shebang(&mut tagged_iter);
     let mut tokens = Vec::new();
     let mut span = Span {
         file: tagged_iter.pos().file,
         start: tagged_iter.pos().index,
         end: tagged_iter.pos().index,
+        expn: None,
     };
 
     loop {
@@ -83,6 +101,20 @@ pub fn read_tokens<'a>(file: usize, contents: &str) -> Result<(Vec<Token>, Pos),
                 span.start = tagged_iter.pos().index;
             }
 
+            Some('"') => {
+                flush_temp(&keywords, &mut tokens, tagged_iter.contents(), span);
+                span.start = span.end;
+
+                skip_string(&mut tagged_iter)?;
+                span.end = tagged_iter.pos().index;
+
+                tokens.push(Token {
+                    kind: TokenKind::String,
+                    span,
+                });
+                span.start = span.end;
+            }
+
             Some(ch) if is_symbol(ch) => {
                 if span.start != span.end {
                     // there is still a previous token, flush it
@@ -110,8 +142,72 @@ pub fn read_tokens<'a>(file: usize, contents: &str) -> Result<(Vec<Token>, Pos),
     Ok((tokens, tagged_iter.pos()))
 }
 
+/// Words used instead of a keyword in other languages.
+const KEYWORD_ALIASES: &[(&str, &str)] = &[("func", "fn"), ("function", "fn")];
+
+/// The keyword probably meant by the word at `span` or, if it starts a
+/// statement, the word before `span`.  Returns the span of the misspelled
+/// word and the keyword.
+pub fn keyword_suggestion(contents: &str, span: Span) -> Option<(Span, &'static str)> {
+    let start = span.start.min(contents.len());
+    let word_end = |end: usize| Span {
+        file: span.file,
+        start: contents[..end].trim_end_matches(is_word).len(),
+        end,
+        expn: span.expn,
+    };
+    let at = Span {
+        file: span.file,
+        start,
+        end: contents[start..]
+            .find(|c: char| !is_word(c))
+            .map_or(contents.len(), |i| start + i),
+        expn: span.expn,
+    };
+    let before = word_end(contents[..start].trim_end().len());
+    let before_statement = contents[..before.start]
+        .trim_end()
+        .chars()
+        .last()
+        .is_none_or(|c| "{};".contains(c));
+    let words = Some(at)
+        .into_iter()
+        .chain(Some(before).filter(|_| before_statement));
+    for word in words {
+        let text = &contents[word];
+        if text.chars().count() < 3 || text.starts_with(|c: char| c.is_ascii_digit()) {
+            continue;
+        }
+        if KEYWORDS.iter().any(|(keyword, _)| *keyword == text) {
+            continue;
+        }
+        let candidates = KEYWORDS
+            .iter()
+            .map(|(keyword, _)| *keyword)
+            .filter(|keyword| keyword.starts_with(is_word))
+            .chain(KEYWORD_ALIASES.iter().map(|(alias, _)| *alias));
+        let alias = KEYWORD_ALIASES.iter().find(|(alias, _)| *alias == text);
+        let keyword = match alias {
+            Some((_, keyword)) => Some(*keyword),
+            None => suggest::best_match(text, candidates),
+        };
+        if let Some(keyword) = keyword {
+            let keyword = KEYWORD_ALIASES
+                .iter()
+                .find(|(alias, _)| *alias == keyword)
+                .map_or(keyword, |(_, keyword)| keyword);
+            return Some((word, keyword));
+        }
+    }
+    None
+}
+
+fn is_word(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
 fn is_symbol(ch: char) -> bool {
-    let symbols = "!&()*+,-./:;=>{|}";
+    let symbols = "!#$&()*+,-./:;=>?[]{|}";
     ch.is_ascii() && symbols.as_bytes().binary_search(&(ch as u8)).is_ok()
 }
 
@@ -125,6 +221,22 @@ fn handle_multicharacter_symbol(ch: char, tagged_iter: &mut TaggedIter) {
     }
 }
 
+/// Skips a byte order mark and a `#!` line naming the program to run the file
+/// with.  `#![...]` is an inner attribute rather than a shebang.
+fn skip_bom_and_shebang(tagged_iter: &mut TaggedIter) {
+    if tagged_iter.peek() == Some('\u{feff}') {
+        tagged_iter.advance();
+    }
+    let rest = &tagged_iter.contents()[tagged_iter.pos().index..];
+    if let Some(rest) = rest.strip_prefix("#!") {
+        if !rest.trim_start().starts_with('[') {
+            while tagged_iter.peek().is_some() && tagged_iter.peek() != Some('\n') {
+                tagged_iter.advance();
+            }
+        }
+    }
+}
+
 fn skip_comments(
     keywords: &HashMap<&str, TokenKind>,
     tokens: &mut Vec<Token>,
@@ -190,6 +302,27 @@ fn skip_block_comment(tagged_iter: &mut TaggedIter) -> Result<(), Error> {
     Ok(())
 }
 
+fn skip_string(tagged_iter: &mut TaggedIter) -> Result<(), Error> {
+    let pos = tagged_iter.pos();
+    tagged_iter.advance();
+    loop {
+        match tagged_iter.peek() {
+            None => return Err(Error::UnterminatedString(pos)),
+            Some('"') => break,
+            Some('\\') => {
+                tagged_iter.advance();
+                if tagged_iter.peek().is_none() {
+                    return Err(Error::UnterminatedString(pos));
+                }
+            }
+            Some(_) => (),
+        }
+        tagged_iter.advance();
+    }
+    tagged_iter.advance();
+    Ok(())
+}
+
 fn skip_whitespace(tagged_iter: &mut TaggedIter) {
     tagged_iter.advance();
     loop {
@@ -236,6 +369,48 @@ fn flush_temp_nonempty(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_keyword_suggestion() {
+        let span = |start, end| Span {
+            file: 0,
+            start,
+            end,
+            expn: None,
+        };
+        assert_eq!(
+            keyword_suggestion("strcut Foo {}", span(0, 6)),
+            Some((span(0, 6), "struct"))
+        );
+        assert_eq!(
+            keyword_suggestion("fucntion f() {}", span(0, 8)),
+            Some((span(0, 8), "fn"))
+        );
+        assert_eq!(
+            keyword_suggestion("function f() {}", span(0, 8)),
+            Some((span(0, 8), "fn"))
+        );
+        assert_eq!(
+            keyword_suggestion("fn f() { retrun 1; }", span(16, 17)),
+            Some((span(9, 15), "return"))
+        );
+        assert_eq!(
+            keyword_suggestion("—strcut Foo {}", span(3, 9)),
+            Some((span(3, 9), "struct"))
+        );
+        // A keyword at the error can follow a misspelled one.
+        assert_eq!(
+            keyword_suggestion("fn f() { whiel true {} }", span(15, 19)),
+            Some((span(9, 14), "while"))
+        );
+        // Only words starting statements are checked before the error.
+        assert_eq!(
+            keyword_suggestion("fn f() { g(foo 1) }", span(15, 16)),
+            None
+        );
+        assert_eq!(keyword_suggestion("fn f() { x y }", span(11, 12)), None);
+        assert_eq!(keyword_suggestion("struct Foo {}", span(0, 6)), None);
+    }
+
     #[test]
     fn test_read_tokens_empty_file_number_1() {
         assert_eq!(read_tokens(1, ""), Ok((vec![], Pos { file: 1, index: 0 })));
@@ -249,6 +424,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_read_tokens_as() {
+        assert_eq!(
+            read_tokens(0, "as"),
+            Ok((
+                vec![Token {
+                    kind: TokenKind::As,
+                    span: Span {
+                        file: 0,
+                        start: 0,
+                        end: 2,
+                        expn: None
+                    },
+                }],
+                Pos { file: 0, index: 2 }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_read_tokens_break() {
+        assert_eq!(
+            read_tokens(0, "break"),
+            Ok((
+                vec![Token {
+                    kind: TokenKind::Break,
+                    span: Span {
+                        file: 0,
+                        start: 0,
+                        end: 5,
+                        expn: None
+                    },
+                }],
+                Pos { file: 0, index: 5 }
+            ))
+        );
+    }
+
     #[test]
     fn test_read_tokens_const() {
         assert_eq!(
@@ -259,7 +472,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 5
+                        end: 5,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 5 }
@@ -267,6 +481,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_read_tokens_continue() {
+        assert_eq!(
+            read_tokens(0, "continue"),
+            Ok((
+                vec![Token {
+                    kind: TokenKind::Continue,
+                    span: Span {
+                        file: 0,
+                        start: 0,
+                        end: 8,
+                        expn: None
+                    },
+                }],
+                Pos { file: 0, index: 8 }
+            ))
+        );
+    }
+
     #[test]
     fn test_read_tokens_else() {
         assert_eq!(
@@ -277,7 +510,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 4
+                        end: 4,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 4 }
@@ -295,7 +529,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 4
+                        end: 4,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 4 }
@@ -313,7 +548,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 5
+                        end: 5,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 5 }
@@ -331,7 +567,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 2
+                        end: 2,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 2 }
@@ -349,7 +586,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 3
+                        end: 3,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 3 }
@@ -367,7 +605,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 2
+                        end: 2,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 3 }
@@ -385,7 +624,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 3
+                        end: 3,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 3 }
@@ -403,7 +643,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 3
+                        end: 3,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 3 }
@@ -421,7 +662,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 6
+                        end: 6,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 6 }
@@ -439,7 +681,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 2
+                        end: 2,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 2 }
@@ -457,7 +700,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 2
+                        end: 2,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 2 }
@@ -475,7 +719,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 3
+                        end: 3,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 3 }
@@ -493,7 +738,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 4
+                        end: 4,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 4 }
@@ -511,7 +757,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 5
+                        end: 5,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 5 }
@@ -529,7 +776,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 3
+                        end: 3,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 3 }
@@ -547,7 +795,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 3
+                        end: 3,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 3 }
@@ -565,7 +814,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 3
+                        end: 3,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 3 }
@@ -573,6 +823,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_read_tokens_return() {
+        assert_eq!(
+            read_tokens(0, "return"),
+            Ok((
+                vec![Token {
+                    kind: TokenKind::Return,
+                    span: Span {
+                        file: 0,
+                        start: 0,
+                        end: 6,
+                        expn: None
+                    },
+                }],
+                Pos { file: 0, index: 6 }
+            ))
+        );
+    }
+
     #[test]
     fn test_read_tokens_true() {
         assert_eq!(
@@ -583,7 +852,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 4
+                        end: 4,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 4 }
@@ -601,7 +871,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 3
+                        end: 3,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 3 }
@@ -619,7 +890,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 5
+                        end: 5,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 5 }
@@ -637,7 +909,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 6
+                        end: 6,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 6 }
@@ -656,7 +929,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 0,
-                            end: 1
+                            end: 1,
+                            expn: None
                         },
                     },
                     Token {
@@ -664,7 +938,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 1,
-                            end: 2
+                            end: 2,
+                            expn: None
                         },
                     },
                     Token {
@@ -672,7 +947,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 2,
-                            end: 3
+                            end: 3,
+                            expn: None
                         },
                     },
                     Token {
@@ -680,7 +956,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 3,
-                            end: 4
+                            end: 4,
+                            expn: None
                         },
                     },
                     Token {
@@ -688,7 +965,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 4,
-                            end: 5
+                            end: 5,
+                            expn: None
                         },
                     },
                     Token {
@@ -696,7 +974,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 5,
-                            end: 6
+                            end: 6,
+                            expn: None
                         },
                     },
                     Token {
@@ -704,7 +983,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 6,
-                            end: 7
+                            end: 7,
+                            expn: None
                         },
                     },
                 ],
@@ -724,7 +1004,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 0,
-                            end: 1
+                            end: 1,
+                            expn: None
                         },
                     },
                     Token {
@@ -732,7 +1013,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 1,
-                            end: 2
+                            end: 2,
+                            expn: None
                         },
                     },
                 ],
@@ -752,7 +1034,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 0,
-                            end: 1
+                            end: 1,
+                            expn: None
                         },
                     },
                     Token {
@@ -760,7 +1043,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 1,
-                            end: 2
+                            end: 2,
+                            expn: None
                         },
                     },
                     Token {
@@ -768,7 +1052,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 2,
-                            end: 3
+                            end: 3,
+                            expn: None
                         },
                     },
                 ],
@@ -787,7 +1072,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 2
+                        end: 2,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 2 }
@@ -806,7 +1092,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 0,
-                            end: 2
+                            end: 2,
+                            expn: None
                         },
                     },
                     Token {
@@ -814,7 +1101,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 2,
-                            end: 3
+                            end: 3,
+                            expn: None
                         },
                     }
                 ],
@@ -833,7 +1121,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 2
+                        end: 2,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 2 }
@@ -851,7 +1140,8 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 0,
-                        end: 2
+                        end: 2,
+                        expn: None
                     },
                 }],
                 Pos { file: 0, index: 2 }
@@ -870,6 +1160,7 @@ mod tests {
                         file: 0,
                         start: 0,
                         end: 1,
+                        expn: None,
                     },
                 }],
                 Pos { file: 0, index: 1 }
@@ -877,6 +1168,70 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_read_tokens_question_after_label() {
+        assert_eq!(
+            read_tokens(0, "a?"),
+            Ok((
+                vec![
+                    Token {
+                        kind: TokenKind::Label,
+                        span: Span {
+                            file: 0,
+                            start: 0,
+                            end: 1,
+                            expn: None,
+                        },
+                    },
+                    Token {
+                        kind: TokenKind::Question,
+                        span: Span {
+                            file: 0,
+                            start: 1,
+                            end: 2,
+                            expn: None,
+                        },
+                    },
+                ],
+                Pos { file: 0, index: 2 }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_read_tokens_attribute() {
+        let kinds = |contents| {
+            read_tokens(0, contents)
+                .unwrap()
+                .0
+                .into_iter()
+                .map(|token| token.kind)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            kinds("#[allow(x)]"),
+            [
+                TokenKind::Pound,
+                TokenKind::OpenSquare,
+                TokenKind::Label,
+                TokenKind::OpenParen,
+                TokenKind::Label,
+                TokenKind::CloseParen,
+                TokenKind::CloseSquare,
+            ]
+        );
+        assert_eq!(
+            kinds("#![a]"),
+            [
+                TokenKind::Pound,
+                TokenKind::Bang,
+                TokenKind::OpenSquare,
+                TokenKind::Label,
+                TokenKind::CloseSquare,
+            ]
+        );
+    }
+
     #[test]
     fn test_read_tokens_plus() {
         assert_eq!(
@@ -888,6 +1243,7 @@ mod tests {
                         file: 0,
                         start: 0,
                         end: 1,
+                        expn: None,
                     },
                 }],
                 Pos { file: 0, index: 1 }
@@ -906,6 +1262,7 @@ mod tests {
                         file: 0,
                         start: 0,
                         end: 1,
+                        expn: None,
                     },
                 }],
                 Pos { file: 0, index: 1 }
@@ -924,6 +1281,7 @@ mod tests {
                         file: 0,
                         start: 0,
                         end: 1,
+                        expn: None,
                     },
                 }],
                 Pos { file: 0, index: 1 }
@@ -942,6 +1300,7 @@ mod tests {
                         file: 0,
                         start: 0,
                         end: 1,
+                        expn: None,
                     },
                 }],
                 Pos { file: 0, index: 1 }
@@ -960,6 +1319,7 @@ mod tests {
                         file: 0,
                         start: 0,
                         end: 1,
+                        expn: None,
                     },
                 }],
                 Pos { file: 0, index: 1 }
@@ -978,6 +1338,7 @@ mod tests {
                         file: 0,
                         start: 0,
                         end: 2,
+                        expn: None,
                     },
                 }],
                 Pos { file: 0, index: 2 }
@@ -996,6 +1357,7 @@ mod tests {
                         file: 0,
                         start: 0,
                         end: 2,
+                        expn: None,
                     },
                 }],
                 Pos { file: 0, index: 2 }
@@ -1014,6 +1376,7 @@ mod tests {
                         file: 0,
                         start: 0,
                         end: 2,
+                        expn: None,
                     },
                 }],
                 Pos { file: 0, index: 2 }
@@ -1032,6 +1395,7 @@ mod tests {
                         file: 0,
                         start: 0,
                         end: 1,
+                        expn: None,
                     },
                 }],
                 Pos { file: 0, index: 1 }
@@ -1050,6 +1414,7 @@ mod tests {
                         file: 0,
                         start: 0,
                         end: 2,
+                        expn: None,
                     },
                 }],
                 Pos { file: 0, index: 2 }
@@ -1068,6 +1433,7 @@ mod tests {
                         file: 0,
                         start: 0,
                         end: 1,
+                        expn: None,
                     },
                 }],
                 Pos { file: 0, index: 1 }
@@ -1086,6 +1452,7 @@ mod tests {
                         file: 0,
                         start: 0,
                         end: 2,
+                        expn: None,
                     },
                 }],
                 Pos { file: 0, index: 2 }
@@ -1104,7 +1471,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 0,
-                            end: 3
+                            end: 3,
+                            expn: None
                         },
                     },
                     Token {
@@ -1112,7 +1480,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 12,
-                            end: 13
+                            end: 13,
+                            expn: None
                         },
                     }
                 ],
@@ -1132,7 +1501,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 0,
-                            end: 3
+                            end: 3,
+                            expn: None
                         },
                     },
                     Token {
@@ -1140,7 +1510,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 12,
-                            end: 13
+                            end: 13,
+                            expn: None
                         },
                     }
                 ],
@@ -1160,7 +1531,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 0,
-                            end: 3
+                            end: 3,
+                            expn: None
                         },
                     },
                     Token {
@@ -1168,7 +1540,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 18,
-                            end: 19
+                            end: 19,
+                            expn: None
                         },
                     }
                 ],
@@ -1188,7 +1561,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 0,
-                            end: 3
+                            end: 3,
+                            expn: None
                         },
                     },
                     Token {
@@ -1196,7 +1570,8 @@ mod tests {
                         span: Span {
                             file: 0,
                             start: 15,
-                            end: 18
+                            end: 18,
+                            expn: None
                         },
                     }
                 ],
@@ -1228,4 +1603,239 @@ mod tests {
             Err(Error::UnrecognizedControlChar(Pos { file: 0, index: 0 }))
         );
     }
+
+    #[test]
+    fn test_read_tokens_bang() {
+        assert_eq!(
+            read_tokens(0, "!"),
+            Ok((
+                vec![Token {
+                    kind: TokenKind::Bang,
+                    span: Span {
+                        file: 0,
+                        start: 0,
+                        end: 1,
+                        expn: None
+                    },
+                }],
+                Pos { file: 0, index: 1 }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_read_tokens_dollar_label() {
+        assert_eq!(
+            read_tokens(0, "$x"),
+            Ok((
+                vec![
+                    Token {
+                        kind: TokenKind::Dollar,
+                        span: Span {
+                            file: 0,
+                            start: 0,
+                            end: 1,
+                            expn: None
+                        },
+                    },
+                    Token {
+                        kind: TokenKind::Label,
+                        span: Span {
+                            file: 0,
+                            start: 1,
+                            end: 2,
+                            expn: None
+                        },
+                    }
+                ],
+                Pos { file: 0, index: 2 }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_read_tokens_square_brackets() {
+        assert_eq!(
+            read_tokens(0, "[]"),
+            Ok((
+                vec![
+                    Token {
+                        kind: TokenKind::OpenSquare,
+                        span: Span {
+                            file: 0,
+                            start: 0,
+                            end: 1,
+                            expn: None
+                        },
+                    },
+                    Token {
+                        kind: TokenKind::CloseSquare,
+                        span: Span {
+                            file: 0,
+                            start: 1,
+                            end: 2,
+                            expn: None
+                        },
+                    }
+                ],
+                Pos { file: 0, index: 2 }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_read_tokens_string() {
+        assert_eq!(
+            read_tokens(0, "f(\"a b\")"),
+            Ok((
+                vec![
+                    Token {
+                        kind: TokenKind::Label,
+                        span: Span {
+                            file: 0,
+                            start: 0,
+                            end: 1,
+                            expn: None
+                        },
+                    },
+                    Token {
+                        kind: TokenKind::OpenParen,
+                        span: Span {
+                            file: 0,
+                            start: 1,
+                            end: 2,
+                            expn: None
+                        },
+                    },
+                    Token {
+                        kind: TokenKind::String,
+                        span: Span {
+                            file: 0,
+                            start: 2,
+                            end: 7,
+                            expn: None
+                        },
+                    },
+                    Token {
+                        kind: TokenKind::CloseParen,
+                        span: Span {
+                            file: 0,
+                            start: 7,
+                            end: 8,
+                            expn: None
+                        },
+                    }
+                ],
+                Pos { file: 0, index: 8 }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_read_tokens_string_escaped_quote_and_comment() {
+        assert_eq!(
+            read_tokens(0, r#""\" // x""#),
+            Ok((
+                vec![Token {
+                    kind: TokenKind::String,
+                    span: Span {
+                        file: 0,
+                        start: 0,
+                        end: 9,
+                        expn: None
+                    },
+                }],
+                Pos { file: 0, index: 9 }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_read_tokens_unterminated_string() {
+        assert_eq!(
+            read_tokens(0, "x \"abc\\\""),
+            Err(Error::UnterminatedString(Pos { file: 0, index: 2 }))
+        );
+    }
+
+    #[test]
+    fn test_read_tokens_bom_and_shebang() {
+        let kinds = |contents| {
+            let (tokens, _) = read_tokens(0, contents).unwrap();
+            tokens
+                .into_iter()
+                .map(|token| (token.kind, token.span.start))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(kinds("\u{feff}fn"), [(TokenKind::Fn, 3)]);
+        assert_eq!(kinds("#!/usr/bin/env rust-comp\nfn"), [(TokenKind::Fn, 25)]);
+        assert_eq!(
+            kinds("\u{feff}#!/usr/bin/env rust-comp\r\nfn"),
+            [(TokenKind::Fn, 29)]
+        );
+        // An inner attribute isn't a shebang.
+        assert_eq!(
+            kinds("#![x]")[..2],
+            [(TokenKind::Pound, 0), (TokenKind::Bang, 1)]
+        );
+        assert_eq!(kinds("fn #!x")[0], (TokenKind::Fn, 0));
+        assert_eq!(kinds("fn #!x")[1], (TokenKind::Pound, 3));
+    }
+
+    #[test]
+    fn test_read_tokens_unicode_identifiers_and_crlf() {
+        assert_eq!(
+            read_tokens(0, "// ça\r\nlet café = 日本;\r\n"),
+            Ok((
+                vec![
+                    Token {
+                        kind: TokenKind::Let,
+                        span: Span {
+                            file: 0,
+                            start: 8,
+                            end: 11,
+                            expn: None
+                        },
+                    },
+                    Token {
+                        kind: TokenKind::Label,
+                        span: Span {
+                            file: 0,
+                            start: 12,
+                            end: 17,
+                            expn: None
+                        },
+                    },
+                    Token {
+                        kind: TokenKind::Set,
+                        span: Span {
+                            file: 0,
+                            start: 18,
+                            end: 19,
+                            expn: None
+                        },
+                    },
+                    Token {
+                        kind: TokenKind::Label,
+                        span: Span {
+                            file: 0,
+                            start: 20,
+                            end: 26,
+                            expn: None
+                        },
+                    },
+                    Token {
+                        kind: TokenKind::Semicolon,
+                        span: Span {
+                            file: 0,
+                            start: 26,
+                            end: 27,
+                            expn: None
+                        },
+                    },
+                ],
+                Pos { file: 0, index: 29 }
+            ))
+        );
+    }
 }
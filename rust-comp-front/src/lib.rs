@@ -1,3 +1,4 @@
+pub mod expand;
 pub mod lex;
 pub mod parse;
 pub mod parse_to_syntax;
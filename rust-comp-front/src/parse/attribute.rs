@@ -0,0 +1,98 @@
+use super::combinator::{many, many_comma_separated};
+use super::parser::Parser;
+use super::path::expect_path;
+use super::tree::*;
+use super::Error;
+use crate::token::TokenKind;
+
+pub fn expect_attributes(parser: &mut Parser) -> Result<Vec<Attribute>, Error> {
+    many(parser, expect_attribute)
+}
+
+/// Parses `#[path]` or `#[path(path, ...)]`.
+pub fn expect_attribute(parser: &mut Parser) -> Result<Attribute, Error> {
+    let pound_span = parser.expect_token(TokenKind::Pound)?;
+    let open_square_span = parser.expect_token(TokenKind::OpenSquare)?;
+    let path = expect_path(parser)?;
+    let arguments = match parser.expect_token(TokenKind::OpenParen) {
+        Ok(open_paren_span) => {
+            let (paths, comma_spans) = many_comma_separated(parser, expect_path)?;
+            let close_paren_span = parser.expect_token(TokenKind::CloseParen)?;
+            Some(AttributeArguments {
+                open_paren_span,
+                paths,
+                comma_spans,
+                close_paren_span,
+            })
+        }
+        Err(_) => None,
+    };
+    let close_square_span = parser.expect_token(TokenKind::CloseSquare)?;
+    Ok(Attribute {
+        pound_span,
+        open_square_span,
+        path,
+        arguments,
+        close_square_span,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test::parse;
+    use super::*;
+    use crate::pos::Span;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn test_expect_attribute_without_arguments() {
+        let (index, len, attribute) = parse(expect_attribute, "#[test]");
+        assert_eq!(index, len);
+        assert_matches!(attribute, Ok(Attribute { path, arguments: None, .. }) => {
+            assert_eq!(path.segments.len(), 1);
+        });
+    }
+
+    #[test]
+    fn test_expect_attribute_with_arguments() {
+        let (index, len, attribute) = parse(expect_attribute, "#[allow(dead_code, clippy::all,)]");
+        assert_eq!(index, len);
+        assert_matches!(attribute, Ok(Attribute { arguments: Some(arguments), .. }) => {
+            assert_eq!(arguments.paths.len(), 2);
+            assert_eq!(arguments.paths[1].segments.len(), 2);
+            assert_eq!(arguments.comma_spans.len(), 2);
+            assert_eq!(
+                arguments.close_paren_span,
+                Span {
+                    file: 0,
+                    start: 31,
+                    end: 32, expn: None
+                }
+            );
+        });
+    }
+
+    #[test]
+    fn test_expect_attributes() {
+        let (index, _, attributes) = parse(expect_attributes, "#[a] #[b(c)] fn");
+        assert_eq!(index, 11);
+        assert_eq!(attributes.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_expect_attribute_unclosed() {
+        let (_, _, attribute) = parse(expect_attribute, "#[allow(x) fn");
+        assert_eq!(
+            attribute,
+            Err(Error::ExpectedToken(
+                TokenKind::CloseSquare,
+                Span {
+                    file: 0,
+                    start: 11,
+                    end: 13,
+                    expn: None
+                }
+            ))
+        );
+    }
+}
@@ -8,7 +8,20 @@ use crate::token::TokenKind;
 
 pub fn expect_block(parser: &mut Parser) -> Result<Block, Error> {
     let open_curly_span = parser.expect_token(TokenKind::OpenCurly)?;
+    let (statements, expression) = expect_block_body(parser)?;
+    let close_curly_span = parser.expect_token(TokenKind::CloseCurly)?;
+
+    Ok(Block {
+        open_curly_span,
+        statements,
+        expression: expression.map(Box::new),
+        close_curly_span,
+    })
+}
 
+pub fn expect_block_body(
+    parser: &mut Parser,
+) -> Result<(Vec<Statement>, Option<Expression>), Error> {
     let mut statements = Vec::new();
     let expression;
     loop {
@@ -36,14 +49,7 @@ pub fn expect_block(parser: &mut Parser) -> Result<Block, Error> {
             }
         }
     }
-    let close_curly_span = parser.expect_token(TokenKind::CloseCurly)?;
-
-    Ok(Block {
-        open_curly_span,
-        statements,
-        expression: expression.map(Box::new),
-        close_curly_span,
-    })
+    Ok((statements, expression))
 }
 
 #[cfg(test)]
@@ -74,7 +80,8 @@ mod tests {
                     semicolon_span: Some(Span {
                         file: 0,
                         start: 1,
-                        end: 2
+                        end: 2,
+                        expn: None
                     }),
                 },
                 Statement {
@@ -82,7 +89,8 @@ mod tests {
                     semicolon_span: Some(Span {
                         file: 0,
                         start: 2,
-                        end: 3
+                        end: 3,
+                        expn: None
                     }),
                 },
             ]
@@ -102,13 +110,15 @@ mod tests {
                     name: Span {
                         file: 0,
                         start: 1,
-                        end: 2
+                        end: 2,
+                        expn: None
                     }
                 })),
                 semicolon_span: Some(Span {
                     file: 0,
                     start: 2,
-                    end: 3
+                    end: 3,
+                    expn: None
                 }),
             }]
         );
@@ -118,7 +128,8 @@ mod tests {
                 name: Span {
                     file: 0,
                     start: 3,
-                    end: 4
+                    end: 4,
+                    expn: None
                 }
             })))
         );
@@ -137,6 +148,7 @@ mod tests {
                     file: 0,
                     start: 5,
                     end: 6,
+                    expn: None,
                 }
             )
         );
@@ -149,12 +161,13 @@ mod tests {
         assert_eq!(index, len - 1);
         assert_eq!(
             error,
-            Error::ExpectedToken(
-                TokenKind::Label,
+            Error::Expected(
+                "pattern",
                 Span {
                     file: 0,
                     start: 6,
                     end: 7,
+                    expn: None,
                 }
             )
         );
@@ -173,6 +186,7 @@ mod tests {
                     file: 0,
                     start: 9,
                     end: 10,
+                    expn: None,
                 }
             )
         );
@@ -56,24 +56,28 @@ mod tests {
                 enum_span: Span {
                     file: 0,
                     start: 0,
-                    end: 4
+                    end: 4,
+                    expn: None
                 },
                 name: Span {
                     file: 0,
                     start: 5,
-                    end: 6
+                    end: 6,
+                    expn: None
                 },
                 open_curly_span: Span {
                     file: 0,
                     start: 7,
-                    end: 8
+                    end: 8,
+                    expn: None
                 },
                 variants: vec![],
                 comma_spans: vec![],
                 close_curly_span: Span {
                     file: 0,
                     start: 8,
-                    end: 9
+                    end: 9,
+                    expn: None
                 },
             })
         );
@@ -91,7 +95,8 @@ mod tests {
                     name: Span {
                         file: 0,
                         start: 8,
-                        end: 9
+                        end: 9,
+                        expn: None
                     },
                     data: VariantData::None,
                 },
@@ -99,7 +104,8 @@ mod tests {
                     name: Span {
                         file: 0,
                         start: 11,
-                        end: 12
+                        end: 12,
+                        expn: None
                     },
                     data: VariantData::None,
                 }
@@ -111,12 +117,14 @@ mod tests {
                 Span {
                     file: 0,
                     start: 9,
-                    end: 10
+                    end: 10,
+                    expn: None
                 },
                 Span {
                     file: 0,
                     start: 12,
-                    end: 13
+                    end: 13,
+                    expn: None
                 },
             ]
         );
@@ -132,26 +140,30 @@ mod tests {
                 name: Span {
                     file: 0,
                     start: 0,
-                    end: 3
+                    end: 3,
+                    expn: None
                 },
                 data: VariantData::Tuple(TupleType {
                     open_paren_span: Span {
                         file: 0,
                         start: 3,
-                        end: 4
+                        end: 4,
+                        expn: None
                     },
                     types: vec![Type::Named(NamedType {
                         name: Span {
                             file: 0,
                             start: 4,
-                            end: 7
+                            end: 7,
+                            expn: None
                         }
                     })],
                     comma_spans: vec![],
                     close_paren_span: Span {
                         file: 0,
                         start: 7,
-                        end: 8
+                        end: 8,
+                        expn: None
                     },
                 })
             })
@@ -1,9 +1,13 @@
 use super::block::expect_block;
 use super::combinator::*;
+use super::macro_::*;
 use super::match_::expect_match;
 use super::parser::Parser;
+use super::pattern::expect_pattern;
 use super::tree::*;
+use super::type_::expect_type;
 use super::Error;
+use crate::pos::Span;
 use crate::token::*;
 
 type Precedence = i8;
@@ -15,6 +19,9 @@ pub fn expect_expression(parser: &mut Parser) -> Result<Expression, Error> {
 
 fn expect_expression_basic(parser: &mut Parser) -> Result<Expression, Error> {
     match parser.peek_kind() {
+        Some(TokenKind::Label) | Some(TokenKind::ColonColon) if is_macro_invocation(parser) => {
+            expect_macro_expression(parser)
+        }
         Some(TokenKind::Label) => expect_variable_expression(parser),
         Some(TokenKind::OpenParen) => expect_paren_expression(parser),
         Some(TokenKind::OpenCurly) => expect_block_expression(parser),
@@ -26,10 +33,106 @@ fn expect_expression_basic(parser: &mut Parser) -> Result<Expression, Error> {
         Some(TokenKind::True) => expect_true_expression(parser),
         Some(TokenKind::False) => expect_false_expression(parser),
         Some(TokenKind::Integer) => expect_integer_expression(parser),
+        Some(TokenKind::Ampersand) => expect_ref_expression(parser),
+        Some(TokenKind::And) => expect_double_ref_expression(parser),
+        Some(TokenKind::Star) => expect_deref_expression(parser),
+        Some(TokenKind::Return) => expect_return_expression(parser),
+        Some(TokenKind::Break) => expect_break_expression(parser),
+        Some(TokenKind::Continue) => expect_continue_expression(parser),
         _ => Err(Error::Expected("expression", parser.span())),
     }
 }
 
+/// Parses the operand of a prefix operator.  Member accesses and function
+/// calls bind tighter than the operator so they are parsed here.
+fn expect_prefix_operand(parser: &mut Parser) -> Result<Box<Expression>, Error> {
+    let mut expression = expect_expression_basic(parser)?;
+    loop {
+        expression = match parser.peek_kind() {
+            Some(TokenKind::Dot) => expect_member_access(parser, expression)?,
+            Some(TokenKind::OpenParen) => expect_function_call(parser, expression)?,
+            Some(TokenKind::Question) => expect_try(parser, expression)?,
+            _ => return Ok(Box::new(expression)),
+        }
+    }
+}
+
+fn expect_ref_expression(parser: &mut Parser) -> Result<Expression, Error> {
+    let ref_span = parser.expect_token(TokenKind::Ampersand)?;
+    ref_expression(parser, ref_span)
+}
+
+fn expect_double_ref_expression(parser: &mut Parser) -> Result<Expression, Error> {
+    let and_span = parser.expect_token(TokenKind::And)?;
+    debug_assert_eq!(and_span.start + 2, and_span.end);
+    let first_ref_span = Span {
+        file: and_span.file,
+        start: and_span.start,
+        end: and_span.start + 1,
+        expn: and_span.expn,
+    };
+    let second_ref_span = Span {
+        file: and_span.file,
+        start: and_span.start + 1,
+        end: and_span.start + 2,
+        expn: and_span.expn,
+    };
+    Ok(Expression::Ref(RefExpression {
+        ref_span: first_ref_span,
+        expression: Box::new(ref_expression(parser, second_ref_span)?),
+    }))
+}
+
+/// Parses the rest of a reference expression after the `&`.
+fn ref_expression(parser: &mut Parser, ref_span: Span) -> Result<Expression, Error> {
+    if is_raw_ref(parser) {
+        let raw_span = parser.expect_token(TokenKind::Label)?;
+        if let Ok(const_span) = parser.expect_token(TokenKind::Const) {
+            Ok(Expression::RawConst(RawConstExpression {
+                ref_span,
+                raw_span,
+                const_span,
+                expression: expect_prefix_operand(parser)?,
+            }))
+        } else {
+            let mut_span = parser.expect_token(TokenKind::Mut)?;
+            Ok(Expression::RawMut(RawMutExpression {
+                ref_span,
+                raw_span,
+                mut_span,
+                expression: expect_prefix_operand(parser)?,
+            }))
+        }
+    } else if let Ok(mut_span) = parser.expect_token(TokenKind::Mut) {
+        Ok(Expression::RefMut(RefMutExpression {
+            ref_span,
+            mut_span,
+            expression: expect_prefix_operand(parser)?,
+        }))
+    } else {
+        Ok(Expression::Ref(RefExpression {
+            ref_span,
+            expression: expect_prefix_operand(parser)?,
+        }))
+    }
+}
+
+/// `raw` is only a keyword when followed by `const` or `mut`.
+fn is_raw_ref(parser: &Parser) -> bool {
+    parser.peek_kind() == Some(TokenKind::Label)
+        && parser.file_span(parser.span()) == "raw"
+        && (parser.peek_kind_nth(1) == Some(TokenKind::Const)
+            || parser.peek_kind_nth(1) == Some(TokenKind::Mut))
+}
+
+fn expect_deref_expression(parser: &mut Parser) -> Result<Expression, Error> {
+    let star_span = parser.expect_token(TokenKind::Star)?;
+    Ok(Expression::Deref(Deref {
+        star_span,
+        expression: expect_prefix_operand(parser)?,
+    }))
+}
+
 fn expression_chain(parser: &mut Parser, mut expr: Expression) -> Result<Expression, Error> {
     let mut stack: Vec<(Expression, Token, Precedence)> = Vec::new();
     let mut max_precedence = 20;
@@ -52,32 +155,31 @@ fn expression_chain(parser: &mut Parser, mut expr: Expression) -> Result<Express
             stack.push((expr, token, cont));
             expr = next;
         } else if token.kind == TokenKind::Dot {
-            parser.index += 1;
-            let member = parser.expect_token(TokenKind::Label)?;
             if 2 <= max_precedence {
                 max_precedence = 1;
             } else {
                 expr = consolidate_stack(expr, &mut max_precedence, &mut stack, 2);
             }
-            expr = Expression::MemberAccess(MemberAccess {
-                object: Box::new(expr),
-                dot_span: token.span,
-                member,
-            });
+            expr = expect_member_access(parser, expr)?;
+        } else if token.kind == TokenKind::Question {
+            if 2 <= max_precedence {
+                max_precedence = 1;
+            } else {
+                expr = consolidate_stack(expr, &mut max_precedence, &mut stack, 2);
+            }
+            expr = expect_try(parser, expr)?;
         } else if token.kind == TokenKind::OpenParen {
             if max_precedence < 3 {
                 expr = consolidate_stack(expr, &mut max_precedence, &mut stack, 3);
             }
-            let open_paren_span = parser.expect_token(TokenKind::OpenParen).unwrap();
-            let (arguments, comma_spans) = many_comma_separated(parser, expect_expression)?;
-            let close_paren_span = parser.expect_token(TokenKind::CloseParen)?;
-            expr = Expression::FunctionCall(FunctionCall {
-                function: Box::new(expr),
-                open_paren_span,
-                arguments,
-                comma_spans,
-                close_paren_span,
-            });
+            expr = expect_function_call(parser, expr)?;
+        } else if token.kind == TokenKind::As {
+            if 5 <= max_precedence {
+                max_precedence = 4;
+            } else {
+                expr = consolidate_stack(expr, &mut max_precedence, &mut stack, 5);
+            }
+            expr = expect_cast(parser, expr)?;
         } else {
             break;
         }
@@ -86,6 +188,47 @@ fn expression_chain(parser: &mut Parser, mut expr: Expression) -> Result<Express
     Ok(collapse_stack(expr, stack))
 }
 
+fn expect_member_access(parser: &mut Parser, object: Expression) -> Result<Expression, Error> {
+    let dot_span = parser.expect_token(TokenKind::Dot)?;
+    let member = parser.expect_token(TokenKind::Label)?;
+    Ok(Expression::MemberAccess(MemberAccess {
+        object: Box::new(object),
+        dot_span,
+        member,
+    }))
+}
+
+fn expect_function_call(parser: &mut Parser, function: Expression) -> Result<Expression, Error> {
+    let open_paren_span = parser.expect_token(TokenKind::OpenParen)?;
+    let (arguments, comma_spans) = many_comma_separated(parser, expect_expression)?;
+    let close_paren_span = parser.expect_token(TokenKind::CloseParen)?;
+    Ok(Expression::FunctionCall(FunctionCall {
+        function: Box::new(function),
+        open_paren_span,
+        arguments,
+        comma_spans,
+        close_paren_span,
+    }))
+}
+
+fn expect_cast(parser: &mut Parser, expression: Expression) -> Result<Expression, Error> {
+    let as_span = parser.expect_token(TokenKind::As)?;
+    let type_ = expect_type(parser)?;
+    Ok(Expression::Cast(Cast {
+        expression: Box::new(expression),
+        as_span,
+        type_,
+    }))
+}
+
+fn expect_try(parser: &mut Parser, expression: Expression) -> Result<Expression, Error> {
+    let question_span = parser.expect_token(TokenKind::Question)?;
+    Ok(Expression::Try(Try {
+        expression: Box::new(expression),
+        question_span,
+    }))
+}
+
 fn consolidate_stack(
     mut expr: Expression,
     max_precedence: &mut Precedence,
@@ -219,7 +362,7 @@ fn expect_if_expression<'a>(parser: &mut Parser) -> Result<Expression, Error> {
 
 fn expect_if_expression_<'a>(parser: &mut Parser) -> Result<If, Error> {
     let if_span = parser.expect_token(TokenKind::If)?;
-    let condition = expect_expression(parser)?;
+    let condition = expect_condition(parser)?;
     let then = expect_block(parser)?;
     let else_ = if parser.peek_kind() == Some(TokenKind::Else) {
         Some(Box::new(expect_else_expression(parser)?))
@@ -228,12 +371,28 @@ fn expect_if_expression_<'a>(parser: &mut Parser) -> Result<If, Error> {
     };
     Ok(If {
         if_span,
-        condition: Box::new(condition),
+        condition,
         then,
         else_,
     })
 }
 
+fn expect_condition(parser: &mut Parser) -> Result<Condition, Error> {
+    if let Ok(let_span) = parser.expect_token(TokenKind::Let) {
+        let pattern = expect_pattern(parser)?;
+        let set_span = parser.expect_token(TokenKind::Set)?;
+        let value = expect_expression(parser)?;
+        Ok(Condition::Let(Box::new(LetCondition {
+            let_span,
+            pattern,
+            set_span,
+            value: Box::new(value),
+        })))
+    } else {
+        expect_expression(parser).map(|e| Condition::Expression(Box::new(e)))
+    }
+}
+
 fn expect_else_expression<'a>(parser: &mut Parser) -> Result<Else, Error> {
     let else_span = parser.expect_token(TokenKind::Else)?;
     match parser.peek_kind() {
@@ -246,11 +405,11 @@ fn expect_else_expression<'a>(parser: &mut Parser) -> Result<Else, Error> {
 
 fn expect_while_expression<'a>(parser: &mut Parser) -> Result<Expression, Error> {
     let while_span = parser.expect_token(TokenKind::While)?;
-    let condition = expect_expression(parser)?;
+    let condition = expect_condition(parser)?;
     let block = expect_block(parser)?;
     Ok(Expression::While(While {
         while_span,
-        condition: Box::new(condition),
+        condition,
         block,
     }))
 }
@@ -280,6 +439,48 @@ fn expect_match_expression<'a>(parser: &mut Parser) -> Result<Expression, Error>
     expect_match(parser).map(Expression::Match)
 }
 
+fn expect_macro_expression(parser: &mut Parser) -> Result<Expression, Error> {
+    expect_macro(parser).map(Expression::Macro)
+}
+
+fn expect_return_expression(parser: &mut Parser) -> Result<Expression, Error> {
+    let return_span = parser.expect_token(TokenKind::Return)?;
+    Ok(Expression::Return(Return {
+        return_span,
+        value: expect_optional_value(parser)?,
+    }))
+}
+
+fn expect_break_expression(parser: &mut Parser) -> Result<Expression, Error> {
+    let break_span = parser.expect_token(TokenKind::Break)?;
+    Ok(Expression::Break(Break {
+        break_span,
+        value: expect_optional_value(parser)?,
+    }))
+}
+
+fn expect_continue_expression(parser: &mut Parser) -> Result<Expression, Error> {
+    let continue_span = parser.expect_token(TokenKind::Continue)?;
+    Ok(Expression::Continue(Continue { continue_span }))
+}
+
+/// Parses the value of a `return` or `break` unless the next token ends it.
+fn expect_optional_value(parser: &mut Parser) -> Result<Option<Box<Expression>>, Error> {
+    const ENDS_VALUE: [Option<TokenKind>; 6] = [
+        None,
+        Some(TokenKind::Semicolon),
+        Some(TokenKind::Comma),
+        Some(TokenKind::CloseParen),
+        Some(TokenKind::CloseSquare),
+        Some(TokenKind::CloseCurly),
+    ];
+    if ENDS_VALUE.contains(&parser.peek_kind()) {
+        Ok(None)
+    } else {
+        expect_expression(parser).map(|e| Some(Box::new(e)))
+    }
+}
+
 fn expect_true_expression<'a>(parser: &mut Parser) -> Result<Expression, Error> {
     Ok(Expression::Bool(Bool {
         span: parser.expect_token(TokenKind::True)?,
@@ -323,7 +524,8 @@ mod tests {
                 name: Span {
                     file: 0,
                     start: 0,
-                    end: 2
+                    end: 2,
+                    expn: None
                 }
             })
         );
@@ -340,7 +542,8 @@ mod tests {
                 span: Span {
                     file: 0,
                     start: 0,
-                    end: 4
+                    end: 4,
+                    expn: None
                 },
                 value: true,
             })
@@ -358,7 +561,8 @@ mod tests {
                 span: Span {
                     file: 0,
                     start: 0,
-                    end: 5
+                    end: 5,
+                    expn: None
                 },
                 value: false,
             })
@@ -376,19 +580,22 @@ mod tests {
                 open_paren_span: Span {
                     file: 0,
                     start: 0,
-                    end: 1
+                    end: 1,
+                    expn: None
                 },
                 expression: Box::new(Expression::Variable(Variable {
                     name: Span {
                         file: 0,
                         start: 1,
-                        end: 3
+                        end: 3,
+                        expn: None
                     }
                 })),
                 close_paren_span: Span {
                     file: 0,
                     start: 3,
-                    end: 4
+                    end: 4,
+                    expn: None
                 },
             })
         );
@@ -405,14 +612,16 @@ mod tests {
                 open_paren_span: Span {
                     file: 0,
                     start: 0,
-                    end: 1
+                    end: 1,
+                    expn: None
                 },
                 expressions: vec![],
                 comma_spans: vec![],
                 close_paren_span: Span {
                     file: 0,
                     start: 1,
-                    end: 2
+                    end: 2,
+                    expn: None
                 }
             })
         );
@@ -434,7 +643,7 @@ mod tests {
                     name: Span {
                         file: 0,
                         start: 1,
-                        end: 3
+                        end: 3, expn: None
                     }
                 })]
             );
@@ -443,7 +652,7 @@ mod tests {
                 [Span {
                     file: 0,
                     start: 3,
-                    end: 4
+                    end: 4, expn: None
                 }]
             );
         });
@@ -466,14 +675,14 @@ mod tests {
                         name: Span {
                             file: 0,
                             start: 1,
-                            end: 3
+                            end: 3, expn: None
                         }
                     }),
                     Expression::Variable(Variable {
                         name: Span {
                             file: 0,
                             start: 5,
-                            end: 7
+                            end: 7, expn: None
                         }
                     })
                 ]
@@ -483,7 +692,7 @@ mod tests {
                 [Span {
                     file: 0,
                     start: 3,
-                    end: 4
+                    end: 4, expn: None
                 }]
             );
         });
@@ -500,7 +709,8 @@ mod tests {
                 Span {
                     file: 0,
                     start: 0,
-                    end: 2
+                    end: 2,
+                    expn: None
                 },
             ))
         );
@@ -516,14 +726,16 @@ mod tests {
                 open_curly_span: Span {
                     file: 0,
                     start: 0,
-                    end: 1
+                    end: 1,
+                    expn: None
                 },
                 statements: vec![],
                 expression: None,
                 close_curly_span: Span {
                     file: 0,
                     start: 1,
-                    end: 2
+                    end: 2,
+                    expn: None
                 },
             }))
         );
@@ -588,7 +800,7 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 2,
-                        end: 3,
+                        end: 3, expn: None,
                     },
                     kind: TokenKind::Plus,
                 }
@@ -607,7 +819,7 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 2,
-                        end: 3
+                        end: 3, expn: None
                     },
                     kind: TokenKind::Minus,
                 }
@@ -626,7 +838,7 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 2,
-                        end: 3
+                        end: 3, expn: None
                     },
                     kind: TokenKind::Ampersand,
                 }
@@ -645,7 +857,7 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 2,
-                        end: 3
+                        end: 3, expn: None
                     },
                     kind: TokenKind::Bar,
                 }
@@ -664,7 +876,7 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 2,
-                        end: 3
+                        end: 3, expn: None
                     },
                     kind: TokenKind::Star,
                 }
@@ -683,7 +895,7 @@ mod tests {
                     span: Span {
                         file: 0,
                         start: 2,
-                        end: 3
+                        end: 3, expn: None
                     },
                     kind: TokenKind::ForwardSlash,
                 }
@@ -786,10 +998,10 @@ mod tests {
             member,
         })) => {
             assert_eq!(*object, Expression::Variable(Variable {
-                name: Span { file: 0, start: 0, end: 1 }
+                name: Span { file: 0, start: 0, end: 1, expn: None }
             }));
-            assert_eq!(dot_span, Span { file: 0, start: 1, end: 2 });
-            assert_eq!(member, Span { file: 0, start: 2, end: 3 });
+            assert_eq!(dot_span, Span { file: 0, start: 1, end: 2, expn: None });
+            assert_eq!(member, Span { file: 0, start: 2, end: 3, expn: None });
         });
     }
 
@@ -801,4 +1013,196 @@ mod tests {
             assert_matches!(*function, Expression::MemberAccess(_));
         });
     }
+
+    #[test]
+    fn test_expect_expression_macro_in_binary() {
+        let (index, len, expression) = parse(expect_expression, "vec![1, 2] + x");
+        assert_eq!(index, len);
+        assert_matches!(expression, Ok(Expression::Binary(Binary { left, .. })) => {
+            assert_matches!(*left, Expression::Macro(Macro { invocation, expansion: None }) => {
+                assert_eq!(invocation.arguments.delimiter, Delimiter::Square);
+            });
+        });
+    }
+
+    #[test]
+    fn test_expect_expression_macro_method_call() {
+        let (index, len, expression) = parse(expect_expression, "m!().f()");
+        assert_eq!(index, len);
+        assert_matches!(expression, Ok(Expression::FunctionCall(FunctionCall { function, .. })) => {
+            assert_matches!(*function, Expression::MemberAccess(_));
+        });
+    }
+
+    #[test]
+    fn test_expect_expression_ref() {
+        let (index, len, expression) = parse(expect_expression, "&x");
+        assert_eq!(index, len);
+        assert_matches!(expression, Ok(Expression::Ref(RefExpression { expression, .. })) => {
+            assert_matches!(*expression, Expression::Variable(_));
+        });
+    }
+
+    #[test]
+    fn test_expect_expression_ref_mut() {
+        let (index, len, expression) = parse(expect_expression, "&mut x");
+        assert_eq!(index, len);
+        assert_matches!(expression, Ok(Expression::RefMut(_)));
+    }
+
+    #[test]
+    fn test_expect_expression_double_ref_splits_and() {
+        let (index, len, expression) = parse(expect_expression, "&&mut x");
+        assert_eq!(index, len);
+        assert_matches!(expression, Ok(Expression::Ref(RefExpression { ref_span, expression })) => {
+            assert_eq!(ref_span, Span { file: 0, start: 0, end: 1, expn: None });
+            assert_matches!(*expression, Expression::RefMut(RefMutExpression { ref_span, .. }) => {
+                assert_eq!(ref_span, Span { file: 0, start: 1, end: 2, expn: None });
+            });
+        });
+    }
+
+    #[test]
+    fn test_expect_expression_raw_const_and_raw_mut() {
+        let (index, len, expression) = parse(expect_expression, "&raw const x");
+        assert_eq!(index, len);
+        assert_matches!(expression, Ok(Expression::RawConst(_)));
+
+        let (index, len, expression) = parse(expect_expression, "&raw mut x");
+        assert_eq!(index, len);
+        assert_matches!(expression, Ok(Expression::RawMut(_)));
+    }
+
+    #[test]
+    fn test_expect_expression_ref_to_variable_named_raw() {
+        let (index, len, expression) = parse(expect_expression, "&raw");
+        assert_eq!(index, len);
+        assert_matches!(expression, Ok(Expression::Ref(RefExpression { expression, .. })) => {
+            assert_matches!(*expression, Expression::Variable(_));
+        });
+    }
+
+    #[test]
+    fn test_expect_expression_deref_looser_than_member_access() {
+        let (index, len, expression) = parse(expect_expression, "*a.b(c)");
+        assert_eq!(index, len);
+        assert_matches!(expression, Ok(Expression::Deref(Deref { expression, .. })) => {
+            assert_matches!(*expression, Expression::FunctionCall(_));
+        });
+    }
+
+    #[test]
+    fn test_expect_expression_deref_tighter_than_binary() {
+        let (index, len, expression) = parse(expect_expression, "*p = *q * 2");
+        assert_eq!(index, len);
+        assert_matches!(expression, Ok(Expression::Binary(Binary { left, op, right })) => {
+            assert_eq!(op.kind, TokenKind::Set);
+            assert_matches!(*left, Expression::Deref(_));
+            assert_matches!(*right, Expression::Binary(Binary { left, op, .. }) => {
+                assert_eq!(op.kind, TokenKind::Star);
+                assert_matches!(*left, Expression::Deref(_));
+            });
+        });
+    }
+
+    #[test]
+    fn test_expect_expression_cast_tighter_than_binary() {
+        let (index, len, expression) = parse(expect_expression, "a + b as u8 * c");
+        assert_eq!(index, len);
+        assert_matches!(expression, Ok(Expression::Binary(Binary { right, .. })) => {
+            assert_matches!(*right, Expression::Binary(Binary { left, .. }) => {
+                assert_matches!(*left, Expression::Cast(_));
+            });
+        });
+    }
+
+    #[test]
+    fn test_expect_expression_cast_looser_than_deref() {
+        let (index, len, expression) = parse(expect_expression, "*p as *const u8 as usize");
+        assert_eq!(index, len);
+        assert_matches!(expression, Ok(Expression::Cast(Cast { expression, type_, .. })) => {
+            assert_matches!(type_, Type::Named(_));
+            assert_matches!(*expression, Expression::Cast(Cast { expression, type_, .. }) => {
+                assert_matches!(type_, Type::PtrConst(_));
+                assert_matches!(*expression, Expression::Deref(_));
+            });
+        });
+    }
+
+    #[test]
+    fn test_expect_expression_if_let() {
+        let (index, len, expression) = parse(expect_expression, "if let Some(x) = y { x }");
+        assert_eq!(index, len);
+        assert_matches!(expression, Ok(Expression::If(If {
+            condition: Condition::Let(let_),
+            ..
+        })) => {
+            assert_matches!(let_.pattern, Pattern::NamedTuple(_, _));
+            assert_matches!(*let_.value, Expression::Variable(_));
+        });
+    }
+
+    #[test]
+    fn test_expect_expression_while_let() {
+        let (index, len, expression) = parse(expect_expression, "while let (a, _) = f() {}");
+        assert_eq!(index, len);
+        assert_matches!(
+            expression,
+            Ok(Expression::While(While {
+                condition: Condition::Let(let_),
+                ..
+            })) => assert_matches!(let_.pattern, Pattern::Tuple(_))
+        );
+    }
+
+    #[test]
+    fn test_expect_expression_return_with_value() {
+        let (index, len, expression) = parse(expect_expression, "return a + b");
+        assert_eq!(index, len);
+        assert_matches!(expression, Ok(Expression::Return(Return { value: Some(value), .. })) => {
+            assert_matches!(*value, Expression::Binary(_));
+        });
+    }
+
+    #[test]
+    fn test_expect_expression_break_without_value_ends_at_delimiter() {
+        let (index, len, expression) = parse(expect_expression, "f(break, continue)");
+        assert_eq!(index, len);
+        assert_matches!(expression, Ok(Expression::FunctionCall(FunctionCall { arguments, .. })) => {
+            assert_matches!(arguments[0], Expression::Break(Break { value: None, .. }));
+            assert_matches!(arguments[1], Expression::Continue(_));
+        });
+    }
+
+    #[test]
+    fn test_expect_expression_try() {
+        let (index, len, expression) = parse(expect_expression, "a?");
+        assert_eq!(index, len);
+        assert_matches!(expression, Ok(Expression::Try(Try { expression, question_span })) => {
+            assert_matches!(*expression, Expression::Variable(_));
+            assert_eq!(question_span, Span { file: 0, start: 1, end: 2, expn: None });
+        });
+    }
+
+    #[test]
+    fn test_expect_expression_try_chains_with_member_call() {
+        let (index, len, expression) = parse(expect_expression, "a.b()?.c");
+        assert_eq!(index, len);
+        assert_matches!(expression, Ok(Expression::MemberAccess(MemberAccess { object, .. })) => {
+            assert_matches!(*object, Expression::Try(Try { expression, .. }) => {
+                assert_matches!(*expression, Expression::FunctionCall(_));
+            });
+        });
+    }
+
+    #[test]
+    fn test_expect_expression_try_tighter_than_binary_and_deref() {
+        let (index, len, expression) = parse(expect_expression, "a + *b?");
+        assert_eq!(index, len);
+        assert_matches!(expression, Ok(Expression::Binary(Binary { right, .. })) => {
+            assert_matches!(*right, Expression::Deref(Deref { expression, .. }) => {
+                assert_matches!(*expression, Expression::Try(_));
+            });
+        });
+    }
 }
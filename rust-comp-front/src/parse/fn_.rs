@@ -78,38 +78,44 @@ mod tests {
                 fn_span: Span {
                     file: 0,
                     start: 0,
-                    end: 2
+                    end: 2,
+                    expn: None
                 },
                 name: Span {
                     file: 0,
                     start: 3,
-                    end: 4
+                    end: 4,
+                    expn: None
                 },
                 open_paren_span: Span {
                     file: 0,
                     start: 5,
-                    end: 6
+                    end: 6,
+                    expn: None
                 },
                 parameters: vec![],
                 comma_spans: vec![],
                 close_paren_span: Span {
                     file: 0,
                     start: 6,
-                    end: 7
+                    end: 7,
+                    expn: None
                 },
                 return_type: None,
                 body: Block {
                     open_curly_span: Span {
                         file: 0,
                         start: 8,
-                        end: 9
+                        end: 9,
+                        expn: None
                     },
                     statements: vec![],
                     expression: None,
                     close_curly_span: Span {
                         file: 0,
                         start: 9,
-                        end: 10
+                        end: 10,
+                        expn: None
                     },
                 }
             })
@@ -136,7 +142,8 @@ mod tests {
             [Span {
                 file: 0,
                 start: 11,
-                end: 12
+                end: 12,
+                expn: None
             }]
         );
     }
@@ -151,18 +158,21 @@ mod tests {
                 name: Span {
                     file: 0,
                     start: 0,
-                    end: 1
+                    end: 1,
+                    expn: None
                 },
                 colon_span: Span {
                     file: 0,
                     start: 1,
-                    end: 2
+                    end: 2,
+                    expn: None
                 },
                 type_: Type::Named(NamedType {
                     name: Span {
                         file: 0,
                         start: 3,
-                        end: 6
+                        end: 6,
+                        expn: None
                     }
                 })
             })
@@ -0,0 +1,276 @@
+use super::error::Error;
+use super::parser::Parser;
+use super::path::expect_path;
+use super::tree::*;
+use crate::token::*;
+
+pub fn is_macro_invocation(parser: &Parser) -> bool {
+    let mut n = 0;
+    if parser.peek_kind_nth(n) == Some(TokenKind::ColonColon) {
+        n += 1;
+    }
+    while parser.peek_kind_nth(n) == Some(TokenKind::Label) {
+        match parser.peek_kind_nth(n + 1) {
+            Some(TokenKind::Bang) => return true,
+            Some(TokenKind::ColonColon) => n += 2,
+            _ => return false,
+        }
+    }
+    false
+}
+
+pub fn is_macro_rules(parser: &Parser) -> bool {
+    parser.peek_kind() == Some(TokenKind::Label)
+        && parser.file_span(parser.span()) == "macro_rules"
+        && parser.peek_kind_nth(1) == Some(TokenKind::Bang)
+}
+
+pub fn expect_macro<T>(parser: &mut Parser) -> Result<Macro<T>, Error> {
+    Ok(Macro {
+        invocation: Box::new(expect_macro_invocation(parser)?),
+        expansion: None,
+    })
+}
+
+pub fn expect_macro_invocation(parser: &mut Parser) -> Result<MacroInvocation, Error> {
+    let path = expect_path(parser)?;
+    let bang_span = parser.expect_token(TokenKind::Bang)?;
+    let arguments = expect_delimited_token_tree(parser)?;
+    Ok(MacroInvocation {
+        path,
+        bang_span,
+        arguments,
+    })
+}
+
+pub fn expect_macro_rules(parser: &mut Parser) -> Result<MacroRules, Error> {
+    let macro_rules_span = parser.expect_token(TokenKind::Label)?;
+    let bang_span = parser.expect_token(TokenKind::Bang)?;
+    let name = parser.expect_token(TokenKind::Label)?;
+    let open_curly_span = parser.expect_token(TokenKind::OpenCurly)?;
+    let mut rules = Vec::new();
+    let mut semicolon_spans = Vec::new();
+    while parser.peek_kind() != Some(TokenKind::CloseCurly) {
+        rules.push(expect_macro_rule(parser)?);
+        match parser.expect_token(TokenKind::Semicolon) {
+            Ok(span) => semicolon_spans.push(span),
+            Err(_) => break,
+        }
+    }
+    let close_curly_span = parser.expect_token(TokenKind::CloseCurly)?;
+    Ok(MacroRules {
+        macro_rules_span,
+        bang_span,
+        name,
+        open_curly_span,
+        rules,
+        semicolon_spans,
+        close_curly_span,
+    })
+}
+
+fn expect_macro_rule(parser: &mut Parser) -> Result<MacroRule, Error> {
+    let matcher = expect_delimited_token_tree(parser)?;
+    let fat_arrow_span = parser.expect_token(TokenKind::FatArrow)?;
+    let transcriber = expect_delimited_token_tree(parser)?;
+    Ok(MacroRule {
+        matcher,
+        fat_arrow_span,
+        transcriber,
+    })
+}
+
+pub fn expect_delimited_token_tree(parser: &mut Parser) -> Result<DelimitedTokenTree, Error> {
+    let delimiter = match parser.peek_kind() {
+        Some(TokenKind::OpenParen) => Delimiter::Paren,
+        Some(TokenKind::OpenSquare) => Delimiter::Square,
+        Some(TokenKind::OpenCurly) => Delimiter::Curly,
+        _ => return Err(Error::Expected("`(`, `[`, or `{`", parser.span())),
+    };
+    let open_span = parser.expect_token(open_token(delimiter))?;
+    let mut trees = Vec::new();
+    while let Some(token) = parser.peek() {
+        match token.kind {
+            TokenKind::OpenParen | TokenKind::OpenSquare | TokenKind::OpenCurly => {
+                trees.push(TokenTree::Delimited(expect_delimited_token_tree(parser)?))
+            }
+            TokenKind::CloseParen | TokenKind::CloseSquare | TokenKind::CloseCurly => break,
+            _ => {
+                parser.index += 1;
+                trees.push(TokenTree::Token(token));
+            }
+        }
+    }
+    let close_span = parser.expect_token(close_token(delimiter))?;
+    Ok(DelimitedTokenTree {
+        delimiter,
+        open_span,
+        trees,
+        close_span,
+    })
+}
+
+pub fn open_token(delimiter: Delimiter) -> TokenKind {
+    match delimiter {
+        Delimiter::Paren => TokenKind::OpenParen,
+        Delimiter::Square => TokenKind::OpenSquare,
+        Delimiter::Curly => TokenKind::OpenCurly,
+    }
+}
+
+pub fn close_token(delimiter: Delimiter) -> TokenKind {
+    match delimiter {
+        Delimiter::Paren => TokenKind::CloseParen,
+        Delimiter::Square => TokenKind::CloseSquare,
+        Delimiter::Curly => TokenKind::CloseCurly,
+    }
+}
+
+/// Flattens the trees back into the tokens they were parsed from, including
+/// the tokens of nested delimiters.
+pub fn flatten_token_trees(trees: &[TokenTree], tokens: &mut Vec<Token>) {
+    for tree in trees {
+        match tree {
+            TokenTree::Token(token) => tokens.push(token.clone()),
+            TokenTree::Delimited(delimited) => {
+                tokens.push(Token {
+                    kind: open_token(delimited.delimiter),
+                    span: delimited.open_span,
+                });
+                flatten_token_trees(&delimited.trees, tokens);
+                tokens.push(Token {
+                    kind: close_token(delimited.delimiter),
+                    span: delimited.close_span,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test::parse;
+    use super::*;
+    use crate::lex::read_tokens;
+    use crate::pos::Span;
+    use assert_matches::assert_matches;
+
+    fn is_macro_invocation_str(contents: &str) -> bool {
+        let (tokens, eofpos) = read_tokens(0, contents).unwrap();
+        is_macro_invocation(&Parser::new(contents, &tokens, eofpos))
+    }
+
+    #[test]
+    fn test_is_macro_invocation_label_bang() {
+        assert!(is_macro_invocation_str("f!()"));
+    }
+
+    #[test]
+    fn test_is_macro_invocation_path_bang() {
+        assert!(is_macro_invocation_str("::a::b!()"));
+    }
+
+    #[test]
+    fn test_is_macro_invocation_not_equals_is_not_macro() {
+        assert!(!is_macro_invocation_str("a != b"));
+    }
+
+    #[test]
+    fn test_is_macro_invocation_function_call_is_not_macro() {
+        assert!(!is_macro_invocation_str("f()"));
+    }
+
+    #[test]
+    fn test_expect_macro_invocation_square() {
+        let (index, len, invocation) = parse(expect_macro_invocation, "vec![1, 2]");
+        assert_eq!(index, len);
+        let invocation = invocation.unwrap();
+        assert_eq!(invocation.path.segments.len(), 1);
+        assert_eq!(invocation.arguments.delimiter, Delimiter::Square);
+        assert_eq!(invocation.arguments.trees.len(), 3);
+    }
+
+    #[test]
+    fn test_expect_macro_invocation_string_argument() {
+        let (index, len, invocation) = parse(expect_macro_invocation, "println!(\"hi\")");
+        assert_eq!(index, len);
+        assert_matches!(
+            invocation.unwrap().arguments.trees.as_slice(),
+            [TokenTree::Token(Token {
+                kind: TokenKind::String,
+                ..
+            })]
+        );
+    }
+
+    #[test]
+    fn test_expect_delimited_token_tree_nested() {
+        let (index, len, tree) = parse(expect_delimited_token_tree, "{ a ( b [ ] ) c }");
+        assert_eq!(index, len);
+        let tree = tree.unwrap();
+        assert_eq!(tree.delimiter, Delimiter::Curly);
+        assert_matches!(tree.trees.as_slice(), [
+            TokenTree::Token(_),
+            TokenTree::Delimited(DelimitedTokenTree { delimiter: Delimiter::Paren, trees, .. }),
+            TokenTree::Token(_),
+        ] => {
+            assert_matches!(trees.as_slice(), [
+                TokenTree::Token(_),
+                TokenTree::Delimited(DelimitedTokenTree { delimiter: Delimiter::Square, .. }),
+            ]);
+        });
+    }
+
+    #[test]
+    fn test_expect_delimited_token_tree_mismatched_close() {
+        let (_, _, tree) = parse(expect_delimited_token_tree, "( ]");
+        assert_eq!(
+            tree,
+            Err(Error::ExpectedToken(
+                TokenKind::CloseParen,
+                Span {
+                    file: 0,
+                    start: 2,
+                    end: 3,
+                    expn: None
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_expect_delimited_token_tree_unclosed() {
+        let (_, _, tree) = parse(expect_delimited_token_tree, "( a");
+        assert_matches!(tree, Err(Error::ExpectedToken(TokenKind::CloseParen, _)));
+    }
+
+    #[test]
+    fn test_expect_macro_rules_two_rules() {
+        let (index, len, macro_rules) = parse(
+            expect_macro_rules,
+            "macro_rules! m { () => { 0 }; ($e:expr) => { $e }; }",
+        );
+        assert_eq!(index, len);
+        let macro_rules = macro_rules.unwrap();
+        assert_eq!(macro_rules.rules.len(), 2);
+        assert_eq!(macro_rules.semicolon_spans.len(), 2);
+        assert_eq!(macro_rules.rules[1].matcher.trees.len(), 4);
+    }
+
+    #[test]
+    fn test_expect_macro_rules_no_trailing_semicolon() {
+        let (index, len, macro_rules) = parse(expect_macro_rules, "macro_rules! m { () => {} }");
+        assert_eq!(index, len);
+        assert_eq!(macro_rules.unwrap().rules.len(), 1);
+    }
+
+    #[test]
+    fn test_flatten_token_trees() {
+        let contents = "( a [ b ] )";
+        let (tokens, _) = read_tokens(0, contents).unwrap();
+        let (_, _, tree) = parse(expect_delimited_token_tree, contents);
+        let mut flattened = Vec::new();
+        flatten_token_trees(&tree.unwrap().trees, &mut flattened);
+        assert_eq!(flattened, &tokens[1..tokens.len() - 1]);
+    }
+}
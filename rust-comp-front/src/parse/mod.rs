@@ -1,17 +1,19 @@
-mod block;
+pub(crate) mod attribute;
+pub(crate) mod block;
 mod combinator;
 mod enum_;
-mod expression;
+pub(crate) mod expression;
 mod fn_;
+pub(crate) mod macro_;
 mod match_;
-mod parser;
-mod path;
-mod pattern;
-mod statement;
+pub(crate) mod parser;
+pub(crate) mod path;
+pub(crate) mod pattern;
+pub(crate) mod statement;
 mod struct_;
-mod top_level;
-mod type_;
-mod visibility;
+pub(crate) mod top_level;
+pub(crate) mod type_;
+pub(crate) mod visibility;
 
 #[cfg(test)]
 mod test;
@@ -32,6 +32,7 @@ impl<'a, 't> Parser<'a, 't> {
             file: self.eofpos.file,
             start: self.eofpos.index,
             end: self.eofpos.index + 1,
+            expn: None,
         }
     }
 
@@ -60,6 +61,10 @@ impl<'a, 't> Parser<'a, 't> {
         self.peek().map(|t| t.kind)
     }
 
+    pub fn peek_kind_nth(&self, n: usize) -> Option<TokenKind> {
+        self.tokens.get(self.index + n).map(|t| t.kind)
+    }
+
     pub fn file_span(&self, span: Span) -> &'a str {
         &self.file_contents[span.start..span.end]
     }
@@ -80,7 +85,8 @@ mod tests {
             Span {
                 file: 0,
                 start: 0,
-                end: 2
+                end: 2,
+                expn: None
             }
         );
     }
@@ -102,11 +108,22 @@ mod tests {
             Span {
                 file: eofpos.file,
                 start: eofpos.index,
-                end: eofpos.index + 1
+                end: eofpos.index + 1,
+                expn: None
             }
         );
     }
 
+    #[test]
+    fn test_peek_kind_nth() {
+        let contents = "fn f";
+        let (tokens, eofpos) = read_tokens(0, contents).unwrap();
+        let parser = Parser::new(contents, &tokens, eofpos);
+        assert_eq!(parser.peek_kind_nth(0), Some(TokenKind::Fn));
+        assert_eq!(parser.peek_kind_nth(1), Some(TokenKind::Label));
+        assert_eq!(parser.peek_kind_nth(2), None);
+    }
+
     #[test]
     fn test_expect_token_out_of_bounds() {
         let contents = "";
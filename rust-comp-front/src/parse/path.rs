@@ -35,12 +35,14 @@ mod tests {
                 segments: vec![Span {
                     file: 0,
                     start: 2,
-                    end: 3
+                    end: 3,
+                    expn: None
                 }],
                 prefix_separator: Some(Span {
                     file: 0,
                     start: 0,
-                    end: 2
+                    end: 2,
+                    expn: None
                 }),
                 separator_spans: vec![]
             })
@@ -57,7 +59,8 @@ mod tests {
                 segments: vec![Span {
                     file: 0,
                     start: 0,
-                    end: 1
+                    end: 1,
+                    expn: None
                 }],
                 prefix_separator: None,
                 separator_spans: vec![]
@@ -76,19 +79,22 @@ mod tests {
                     Span {
                         file: 0,
                         start: 0,
-                        end: 1
+                        end: 1,
+                        expn: None
                     },
                     Span {
                         file: 0,
                         start: 3,
-                        end: 4
+                        end: 4,
+                        expn: None
                     }
                 ],
                 prefix_separator: None,
                 separator_spans: vec![Span {
                     file: 0,
                     start: 1,
-                    end: 3
+                    end: 3,
+                    expn: None
                 }]
             })
         );
@@ -1,11 +1,15 @@
 use super::combinator::*;
 use super::error::Error;
+use super::macro_::*;
 use super::parser::Parser;
 use super::tree::*;
 use crate::token::TokenKind;
 
 pub fn expect_pattern(parser: &mut Parser) -> Result<Pattern, Error> {
     match parser.peek_kind() {
+        Some(TokenKind::Label) | Some(TokenKind::ColonColon) if is_macro_invocation(parser) => {
+            expect_macro(parser).map(Pattern::Macro)
+        }
         Some(TokenKind::Label) => expect_named_pattern(parser),
         Some(TokenKind::Underscore) => expect_hole_pattern(parser),
         Some(TokenKind::OpenParen) => expect_paren_pattern(parser),
@@ -69,7 +73,8 @@ mod tests {
                 Span {
                     file: 0,
                     start: 0,
-                    end: 1
+                    end: 1,
+                    expn: None
                 }
             ))
         );
@@ -84,7 +89,8 @@ mod tests {
             Ok(Pattern::Named(Span {
                 file: 0,
                 start: 0,
-                end: 3
+                end: 3,
+                expn: None
             }))
         );
     }
@@ -98,7 +104,8 @@ mod tests {
             Ok(Pattern::Hole(Span {
                 file: 0,
                 start: 0,
-                end: 1
+                end: 1,
+                expn: None
             }))
         );
     }
@@ -113,7 +120,7 @@ mod tests {
                 Span {
                     file: 0,
                     start: 0,
-                    end: 3
+                    end: 3, expn: None
                 }
             );
             assert_eq!(tuple.patterns.len(), 1);
@@ -140,19 +147,32 @@ mod tests {
                 open_paren_span: Span {
                     file: 0,
                     start: 0,
-                    end: 1
+                    end: 1,
+                    expn: None
                 },
                 pattern: Box::new(Pattern::Named(Span {
                     file: 0,
                     start: 1,
-                    end: 4
+                    end: 4,
+                    expn: None
                 })),
                 close_paren_span: Span {
                     file: 0,
                     start: 4,
-                    end: 5
+                    end: 5,
+                    expn: None
                 },
             }))
         );
     }
+
+    #[test]
+    fn test_expect_pattern_macro() {
+        let (index, len, pattern) = parse(expect_pattern, "(p![a], b)");
+        assert_eq!(index, len);
+        assert_matches!(pattern, Ok(Pattern::Tuple(TuplePattern { patterns, .. })) => {
+            assert_matches!(patterns[0], Pattern::Macro(_));
+            assert_matches!(patterns[1], Pattern::Named(_));
+        });
+    }
 }
@@ -1,5 +1,7 @@
+use super::block::expect_block;
 use super::expression::expect_expression;
 use super::parser::Parser;
+use super::pattern::expect_pattern;
 use super::tree::*;
 use super::type_::expect_type;
 use super::Error;
@@ -24,13 +26,12 @@ pub fn expect_statement<'a>(parser: &mut Parser) -> Result<Statement, Error> {
 
 fn expect_let_statement<'a>(parser: &mut Parser) -> Result<Statement, Error> {
     let let_span = parser.expect_token(TokenKind::Let)?;
+    let mut_span = parser.expect_token(TokenKind::Mut).ok();
 
-    let name = if let Ok(name) = parser.expect_token(TokenKind::Label) {
-        Ok(name)
-    } else if let Ok(underscore_span) = parser.expect_token(TokenKind::Underscore) {
-        Err(underscore_span)
+    let pattern = if mut_span.is_some() {
+        Pattern::Named(parser.expect_token(TokenKind::Label)?)
     } else {
-        return Err(Error::ExpectedToken(TokenKind::Label, parser.span()));
+        expect_pattern(parser)?
     };
 
     let type_ = if let Ok(colon_span) = parser.expect_token(TokenKind::Colon) {
@@ -51,14 +52,25 @@ fn expect_let_statement<'a>(parser: &mut Parser) -> Result<Statement, Error> {
         None
     };
 
+    let else_ = match (&value, parser.expect_token(TokenKind::Else)) {
+        (Some(_), Ok(else_span)) => Some(Box::new(LetElse {
+            else_span,
+            block: expect_block(parser)?,
+        })),
+        (None, Ok(else_span)) => return Err(Error::ExpectedToken(TokenKind::Set, else_span)),
+        (_, Err(_)) => None,
+    };
+
     let semicolon_span = parser.expect_token(TokenKind::Semicolon)?;
 
     Ok(Statement {
         kind: StatementKind::Let(Let {
             let_span,
-            name,
+            mut_span,
+            pattern,
             type_,
             value,
+            else_,
         }),
         semicolon_span: Some(semicolon_span),
     })
@@ -100,6 +112,17 @@ pub fn needs_semicolon(expression: &Expression) -> bool {
         Expression::Bool(_) => true,
         Expression::Integer(_) => true,
         Expression::Tuple(_) => true,
+        Expression::Ref(_) => true,
+        Expression::RefMut(_) => true,
+        Expression::RawConst(_) => true,
+        Expression::RawMut(_) => true,
+        Expression::Deref(_) => true,
+        Expression::Cast(_) => true,
+        Expression::Try(_) => true,
+        Expression::Return(_) => true,
+        Expression::Break(_) => true,
+        Expression::Continue(_) => true,
+        Expression::Macro(ref m) => m.invocation.arguments.delimiter != Delimiter::Curly,
     }
 }
 
@@ -122,7 +145,8 @@ mod tests {
                 Span {
                     file: 0,
                     start: 0,
-                    end: 1
+                    end: 1,
+                    expn: None
                 }
             )
         );
@@ -142,12 +166,12 @@ mod tests {
         assert_eq!(index, len);
         assert_matches!(statement, Ok(Statement {
             kind: StatementKind::Let(Let {
-                name, type_, value, ..
+                pattern, type_, value, ..
             }),
             ..
         }) =>
         {
-            assert!(name.is_ok());
+            assert_matches!(pattern, Pattern::Named(_));
             assert!(type_.is_some());
             assert!(value.is_some());
         });
@@ -159,12 +183,12 @@ mod tests {
         assert_eq!(index, len);
         assert_matches!(statement, Ok(Statement {
             kind: StatementKind::Let(Let {
-                name, type_, value, ..
+                pattern, type_, value, ..
             }),
             ..
         }) =>
         {
-            assert!(name.is_err());
+            assert_matches!(pattern, Pattern::Hole(_));
             assert!(type_.is_none());
             assert!(value.is_some());
         });
@@ -176,17 +200,94 @@ mod tests {
         assert_eq!(index, len);
         assert_matches!(statement, Ok(Statement {
             kind: StatementKind::Let(Let {
-                name, type_, value, ..
+                pattern, type_, value, ..
             }),
             ..
         }) =>
         {
-            assert!(name.is_ok());
+            assert_matches!(pattern, Pattern::Named(_));
             assert!(type_.is_none());
             assert!(value.is_none());
         });
     }
 
+    #[test]
+    fn test_let_statement_mut() {
+        let (index, len, statement) = parse(expect_let_statement, "let mut x = y;");
+        assert_eq!(index, len);
+        assert_matches!(statement, Ok(Statement {
+            kind: StatementKind::Let(Let {
+                mut_span, pattern, ..
+            }),
+            ..
+        }) =>
+        {
+            assert_eq!(
+                mut_span,
+                Some(Span {
+                    file: 0,
+                    start: 4,
+                    end: 7, expn: None
+                })
+            );
+            assert_matches!(pattern, Pattern::Named(_));
+        });
+    }
+
+    #[test]
+    fn test_let_statement_else() {
+        let (index, len, statement) =
+            parse(expect_let_statement, "let Some(x) = y else { return; };");
+        assert_eq!(index, len);
+        assert_matches!(statement, Ok(Statement {
+            kind: StatementKind::Let(Let {
+                pattern, else_, ..
+            }),
+            ..
+        }) =>
+        {
+            assert_matches!(pattern, Pattern::NamedTuple(_, _));
+            assert_matches!(else_.as_deref(), Some(LetElse { block, .. }) => {
+                assert_eq!(block.statements.len(), 1);
+            });
+        });
+    }
+
+    #[test]
+    fn test_let_statement_else_without_value_should_error() {
+        let (_, _, statement) = parse(expect_let_statement, "let x else {};");
+        assert_matches!(statement, Err(Error::ExpectedToken(TokenKind::Set, _)));
+    }
+
+    #[test]
+    fn test_let_statement_tuple_pattern() {
+        let (index, len, statement) = parse(expect_let_statement, "let (a, _) = y;");
+        assert_eq!(index, len);
+        assert_matches!(
+            statement,
+            Ok(Statement {
+                kind: StatementKind::Let(Let {
+                    pattern: Pattern::Tuple(_),
+                    ..
+                }),
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn test_expect_expression_statement_deref_assignment() {
+        let (index, len, statement) = parse(expect_statement, "*p = 1;");
+        assert_eq!(index, len);
+        assert_matches!(statement, Ok(Statement {
+            kind: StatementKind::Expression(Expression::Binary(Binary { left, .. })),
+            semicolon_span: Some(_),
+        }) =>
+        {
+            assert_matches!(*left, Expression::Deref(_));
+        });
+    }
+
     #[test]
     fn test_let_statement_let_if_else_error_no_semicolon() {
         let (index, len, statement) = parse(expect_let_statement, "let x = if b {} else {}");
@@ -199,6 +300,7 @@ mod tests {
                     file: 0,
                     start: 23,
                     end: 24,
+                    expn: None,
                 }
             ))
         );
@@ -217,6 +319,7 @@ mod tests {
                     file: 0,
                     start: 2,
                     end: 3,
+                    expn: None,
                 }
             )
         );
@@ -235,6 +338,7 @@ mod tests {
                     file: 0,
                     start: 4,
                     end: 5,
+                    expn: None,
                 }
             )
         );
@@ -253,6 +357,7 @@ mod tests {
                     file: 0,
                     start: 5,
                     end: 6,
+                    expn: None,
                 }
             )
         );
@@ -271,6 +376,7 @@ mod tests {
                     file: 0,
                     start: 8,
                     end: 9,
+                    expn: None,
                 }
             )
         );
@@ -289,6 +395,7 @@ mod tests {
                     file: 0,
                     start: 5,
                     end: 6,
+                    expn: None,
                 }
             )
         );
@@ -328,4 +435,20 @@ mod tests {
         assert_eq!(index, len - 1);
         assert!(statement.is_ok());
     }
+
+    #[test]
+    fn test_expect_expression_statement_curly_macro_doesnt_consume_semicolon() {
+        let (index, len, statement) = parse(expect_expression_statement, "m! { b };");
+        assert_eq!(index, len - 1);
+        assert!(statement.is_ok());
+    }
+
+    #[test]
+    fn test_expect_expression_statement_paren_macro_no_semicolon_should_error() {
+        let (_, _, statement) = parse(expect_expression_statement, "m!(b)");
+        assert_matches!(
+            statement,
+            Err(Error::ExpectedToken(TokenKind::Semicolon, _))
+        );
+    }
 }
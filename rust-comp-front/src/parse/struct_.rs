@@ -53,24 +53,28 @@ mod tests {
                 struct_span: Span {
                     file: 0,
                     start: 0,
-                    end: 6
+                    end: 6,
+                    expn: None
                 },
                 name: Span {
                     file: 0,
                     start: 7,
-                    end: 8
+                    end: 8,
+                    expn: None
                 },
                 open_curly_span: Span {
                     file: 0,
                     start: 9,
-                    end: 10
+                    end: 10,
+                    expn: None
                 },
                 fields: vec![],
                 comma_spans: vec![],
                 close_curly_span: Span {
                     file: 0,
                     start: 10,
-                    end: 11
+                    end: 11,
+                    expn: None
                 },
             }
         );
@@ -92,7 +96,7 @@ mod tests {
                 Visibility::Public(Span {
                     file: 0,
                     start: 10,
-                    end: 13
+                    end: 13, expn: None
                 })
             );
             assert_eq!(comma_spans.len(), 0);
@@ -1,5 +1,7 @@
+use super::attribute::expect_attributes;
 use super::enum_::expect_enum;
 use super::fn_::expect_fn;
+use super::macro_::*;
 use super::parser::Parser;
 use super::path::expect_path;
 use super::struct_::expect_struct;
@@ -9,6 +11,7 @@ use super::Error;
 use crate::token::*;
 
 pub fn expect_top_level<'a>(parser: &mut Parser) -> Result<TopLevel, Error> {
+    let attributes = expect_attributes(parser)?;
     let visibility = expect_visibility(parser)?;
     let kind = match parser.peek_kind() {
         Some(TokenKind::Fn) => expect_toplevel_fn(parser),
@@ -16,9 +19,19 @@ pub fn expect_top_level<'a>(parser: &mut Parser) -> Result<TopLevel, Error> {
         Some(TokenKind::Enum) => expect_toplevel_enum(parser),
         Some(TokenKind::Mod) => expect_mod(parser),
         Some(TokenKind::Use) => expect_use(parser),
+        Some(TokenKind::Label) if is_macro_rules(parser) => {
+            expect_macro_rules(parser).map(TopLevelKind::MacroRules)
+        }
+        Some(TokenKind::Label) | Some(TokenKind::ColonColon) if is_macro_invocation(parser) => {
+            expect_macro_item(parser)
+        }
         _ => Err(Error::Expected("top level declaration", parser.span())),
     }?;
-    Ok(TopLevel { visibility, kind })
+    Ok(TopLevel {
+        attributes,
+        visibility,
+        kind,
+    })
 }
 
 fn expect_toplevel_fn<'a>(parser: &mut Parser) -> Result<TopLevelKind, Error> {
@@ -44,6 +57,16 @@ fn expect_mod<'a>(parser: &mut Parser) -> Result<TopLevelKind, Error> {
     }))
 }
 
+fn expect_macro_item(parser: &mut Parser) -> Result<TopLevelKind, Error> {
+    let macro_ = expect_macro(parser)?;
+    let semicolon_span = if macro_.invocation.arguments.delimiter == Delimiter::Curly {
+        None
+    } else {
+        Some(parser.expect_token(TokenKind::Semicolon)?)
+    };
+    Ok(TopLevelKind::Macro(macro_, semicolon_span))
+}
+
 fn expect_use<'a>(parser: &mut Parser) -> Result<TopLevelKind, Error> {
     let use_span = parser.expect_token(TokenKind::Use)?;
     let path = expect_use_path(parser)?;
@@ -81,6 +104,7 @@ mod tests {
         assert_matches!(top_level, Ok(TopLevel {
             visibility,
             kind: TopLevelKind::ModFile(_),
+            ..
         }) =>
         {
             assert_eq!(
@@ -88,12 +112,26 @@ mod tests {
                 Visibility::Public(Span {
                     file: 0,
                     start: 0,
-                    end: 3
+                    end: 3, expn: None
                 })
             );
         });
     }
 
+    #[test]
+    fn test_expect_top_level_attributes() {
+        let (index, len, top_level) = parse(expect_top_level, "#[allow(dead_code)] pub fn f() {}");
+        assert_eq!(index, len);
+        assert_matches!(top_level, Ok(TopLevel {
+            attributes,
+            visibility: Visibility::Public(_),
+            kind: TopLevelKind::Function(_),
+        }) =>
+        {
+            assert_eq!(attributes.len(), 1);
+        });
+    }
+
     #[test]
     fn test_expect_top_level_only_visibility_fails() {
         let (index, len, top_level) = parse(expect_top_level, "pub");
@@ -106,7 +144,8 @@ mod tests {
                 Span {
                     file: 0,
                     start: 3,
-                    end: 4
+                    end: 4,
+                    expn: None
                 }
             )
         );
@@ -137,9 +176,49 @@ mod tests {
                 UsePathSuffix::Item(Span {
                     file: 0,
                     start: 10,
-                    end: 11
+                    end: 11, expn: None
                 })
             );
         });
     }
+
+    #[test]
+    fn test_expect_top_level_macro_rules() {
+        let (index, len, top_level) =
+            parse(expect_top_level, "macro_rules! m { ($e:expr) => { $e } }");
+        assert_eq!(index, len);
+        assert_matches!(
+            top_level,
+            Ok(TopLevel {
+                kind: TopLevelKind::MacroRules(_),
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn test_expect_top_level_macro_paren_needs_semicolon() {
+        let (index, len, top_level) = parse(expect_top_level, "m!(a);");
+        assert_eq!(index, len);
+        assert_matches!(
+            top_level,
+            Ok(TopLevel {
+                kind: TopLevelKind::Macro(_, Some(_)),
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn test_expect_top_level_macro_curly_no_semicolon() {
+        let (index, len, top_level) = parse(expect_top_level, "m! { a }");
+        assert_eq!(index, len);
+        assert_matches!(
+            top_level,
+            Ok(TopLevel {
+                kind: TopLevelKind::Macro(_, None),
+                ..
+            })
+        );
+    }
 }
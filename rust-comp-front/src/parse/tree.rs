@@ -3,10 +3,29 @@ use crate::token::Token;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct TopLevel {
+    pub attributes: Vec<Attribute>,
     pub visibility: Visibility,
     pub kind: TopLevelKind,
 }
 
+/// An attribute like `#[allow(dead_code)]` on the item after it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Attribute {
+    pub pound_span: Span,
+    pub open_square_span: Span,
+    pub path: Path,
+    pub arguments: Option<AttributeArguments>,
+    pub close_square_span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AttributeArguments {
+    pub open_paren_span: Span,
+    pub paths: Vec<Path>,
+    pub comma_spans: Vec<Span>,
+    pub close_paren_span: Span,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Visibility {
     Private,
@@ -23,12 +42,16 @@ pub struct PathVisibility {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[allow(clippy::large_enum_variant)]
 pub enum TopLevelKind {
     Function(Function),
     Struct(Struct),
     Enum(Enum),
     ModFile(ModFile),
     Use(Use),
+    MacroRules(MacroRules),
+    /// The span is of the trailing semicolon (unless the arguments are curly).
+    Macro(Macro<Vec<TopLevel>>, Option<Span>),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -161,6 +184,17 @@ pub enum Expression {
     Bool(Bool),
     Integer(Integer),
     Tuple(Tuple),
+    Ref(RefExpression),
+    RefMut(RefMutExpression),
+    RawConst(RawConstExpression),
+    RawMut(RawMutExpression),
+    Deref(Deref),
+    Cast(Cast),
+    Try(Try),
+    Return(Return),
+    Break(Break),
+    Continue(Continue),
+    Macro(Macro<Expression>),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -178,11 +212,25 @@ pub struct ParenExpression {
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct If {
     pub if_span: Span,
-    pub condition: Box<Expression>,
+    pub condition: Condition,
     pub then: Block,
     pub else_: Option<Box<Else>>,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Condition {
+    Expression(Box<Expression>),
+    Let(Box<LetCondition>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LetCondition {
+    pub let_span: Span,
+    pub pattern: Pattern,
+    pub set_span: Span,
+    pub value: Box<Expression>,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Else {
     pub else_span: Span,
@@ -204,7 +252,7 @@ pub struct Loop {
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct While {
     pub while_span: Span,
-    pub condition: Box<Expression>,
+    pub condition: Condition,
     pub block: Block,
 }
 
@@ -241,6 +289,7 @@ pub enum Pattern {
     Tuple(TuplePattern),
     Paren(ParenPattern),
     NamedTuple(Span, TuplePattern),
+    Macro(Macro<Pattern>),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -301,13 +350,80 @@ pub struct Tuple {
     pub close_paren_span: Span,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RefExpression {
+    pub ref_span: Span,
+    pub expression: Box<Expression>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RefMutExpression {
+    pub ref_span: Span,
+    pub mut_span: Span,
+    pub expression: Box<Expression>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawConstExpression {
+    pub ref_span: Span,
+    pub raw_span: Span,
+    pub const_span: Span,
+    pub expression: Box<Expression>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawMutExpression {
+    pub ref_span: Span,
+    pub raw_span: Span,
+    pub mut_span: Span,
+    pub expression: Box<Expression>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Deref {
+    pub star_span: Span,
+    pub expression: Box<Expression>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cast {
+    pub expression: Box<Expression>,
+    pub as_span: Span,
+    pub type_: Type,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Try {
+    pub expression: Box<Expression>,
+    pub question_span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Return {
+    pub return_span: Span,
+    pub value: Option<Box<Expression>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Break {
+    pub break_span: Span,
+    pub value: Option<Box<Expression>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Continue {
+    pub continue_span: Span,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Let {
     pub let_span: Span,
-    /// Normal name => Ok, _ => Err.
-    pub name: Result<Span, Span>,
+    /// Only present when `pattern` is `Pattern::Named`.
+    pub mut_span: Option<Span>,
+    pub pattern: Pattern,
     pub type_: Option<LetType>,
     pub value: Option<LetValue>,
+    pub else_: Option<Box<LetElse>>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -322,6 +438,12 @@ pub struct LetValue {
     pub value: Expression,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LetElse {
+    pub else_span: Span,
+    pub block: Block,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Block {
     pub open_curly_span: Span,
@@ -340,6 +462,7 @@ pub enum Type {
     Tuple(TupleType),
     Paren(ParenType),
     Hole(HoleType),
+    Macro(Macro<Type>),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -393,3 +516,70 @@ pub struct ParenType {
 pub struct HoleType {
     pub underscore_span: Span,
 }
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MacroRules {
+    pub macro_rules_span: Span,
+    pub bang_span: Span,
+    pub name: Span,
+    pub open_curly_span: Span,
+    pub rules: Vec<MacroRule>,
+    pub semicolon_spans: Vec<Span>,
+    pub close_curly_span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MacroRule {
+    pub matcher: DelimitedTokenTree,
+    pub fat_arrow_span: Span,
+    pub transcriber: DelimitedTokenTree,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Macro<T> {
+    pub invocation: Box<MacroInvocation>,
+    /// Filled in by `expand::expand`.
+    pub expansion: Option<Box<Expansion<T>>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MacroInvocation {
+    pub path: Path,
+    pub bang_span: Span,
+    pub arguments: DelimitedTokenTree,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Expansion<T> {
+    pub context: SyntaxContext,
+    /// The transcriber of the rule that matched.  Names spelled inside of it
+    /// belong to `context` instead of the invocation's context.
+    pub def_site: Span,
+    pub value: T,
+}
+
+/// Distinguishes the names introduced by each macro expansion.  The root
+/// context is `SyntaxContext(0)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SyntaxContext(pub u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delimiter {
+    Paren,
+    Square,
+    Curly,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DelimitedTokenTree {
+    pub delimiter: Delimiter,
+    pub open_span: Span,
+    pub trees: Vec<TokenTree>,
+    pub close_span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TokenTree {
+    Token(Token),
+    Delimited(DelimitedTokenTree),
+}
@@ -1,4 +1,5 @@
 use super::combinator::*;
+use super::macro_::*;
 use super::parser::Parser;
 use super::tree::*;
 use super::Error;
@@ -11,6 +12,9 @@ pub fn expect_type<'a>(parser: &mut Parser) -> Result<Type, Error> {
         Some(TokenKind::Star) => expect_pointer_type(parser),
         Some(TokenKind::OpenParen) => expect_paren_type(parser),
         Some(TokenKind::Underscore) => expect_hole_type(parser),
+        Some(TokenKind::Label) | Some(TokenKind::ColonColon) if is_macro_invocation(parser) => {
+            expect_macro(parser).map(Type::Macro)
+        }
         Some(TokenKind::Label) => expect_named_type(parser),
         _ => Err(Error::Expected("type", parser.span())),
     }
@@ -24,11 +28,13 @@ fn expect_double_ref_type(parser: &mut Parser) -> Result<Type, Error> {
         file: and_span.file,
         start: and_span.start,
         end: and_span.start + 1,
+        expn: and_span.expn,
     };
     let second_ref_span = Span {
         file: and_span.file,
         start: and_span.start + 1,
         end: and_span.start + 2,
+        expn: and_span.expn,
     };
 
     let inner_type = if let Ok(mut_span) = parser.expect_token(TokenKind::Mut) {
@@ -136,7 +142,8 @@ mod tests {
                 name: Span {
                     file: 0,
                     start: 0,
-                    end: 3
+                    end: 3,
+                    expn: None
                 }
             }))
         );
@@ -201,6 +208,7 @@ mod tests {
                     file: 0,
                     start: 1,
                     end: 4,
+                    expn: None,
                 }
             )
         );
@@ -249,4 +257,13 @@ mod tests {
         assert_eq!(index, len);
         assert_matches!(type_, Ok(Type::Hole(_)));
     }
+
+    #[test]
+    fn test_expect_type_macro() {
+        let (index, len, type_) = parse(expect_type, "&t!(a)");
+        assert_eq!(index, len);
+        assert_matches!(type_, Ok(Type::Ref(RefType { type_, .. })) => {
+            assert_matches!(*type_, Type::Macro(Macro { expansion: None, .. }));
+        });
+    }
 }
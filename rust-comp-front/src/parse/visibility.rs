@@ -74,6 +74,7 @@ mod tests {
                     file: 0,
                     start: 8,
                     end: 9,
+                    expn: None,
                 }
             ))
         );
@@ -1,3 +1,4 @@
+use crate::expand::invocation_span;
 use crate::parse;
 use crate::pos::Span;
 use crate::token::TokenKind;
@@ -6,11 +7,33 @@ use rust_comp_syntax as syntax;
 
 pub struct Context<'a> {
     diagnostic: &'a Diagnostic,
+    /// The definition site and context of each expansion being converted.
+    expansions: Vec<(Span, parse::SyntaxContext)>,
 }
 
 impl<'a> Context<'a> {
     pub fn new(diagnostic: &'a Diagnostic) -> Self {
-        Context { diagnostic }
+        Context {
+            diagnostic,
+            expansions: Vec::new(),
+        }
+    }
+
+    /// Converts expanded top levels, replacing item macros with the items they
+    /// expanded to.  Macro definitions are dropped.
+    pub fn convert_top_levels(&mut self, top_levels: &[parse::TopLevel]) -> Vec<syntax::TopLevel> {
+        let mut result = Vec::new();
+        for top_level in top_levels {
+            match &top_level.kind {
+                parse::TopLevelKind::MacroRules(_) => (),
+                parse::TopLevelKind::Macro(m, _) => {
+                    let items = self.convert_expansion(m, |this, tls| this.convert_top_levels(tls));
+                    result.extend(items);
+                }
+                _ => result.push(self.convert_top_level(top_level)),
+            }
+        }
+        result
     }
 
     pub fn convert_top_level(&mut self, top_level: &parse::TopLevel) -> syntax::TopLevel {
@@ -38,15 +61,35 @@ impl<'a> Context<'a> {
                 semicolon_span,
                 ..
             }) => (*use_span, *semicolon_span),
+            MacroRules(_) | Macro(..) => unreachable!("macros are flattened by convert_top_levels"),
         };
         let kind = self.convert_top_level_kind(&top_level.kind);
+        let attributes = top_level
+            .attributes
+            .iter()
+            .map(|a| self.convert_attribute(a))
+            .collect();
         syntax::TopLevel {
             span: span_encompassing(ks, ke),
+            attributes,
             visibility,
             kind,
         }
     }
 
+    pub fn convert_attribute(&mut self, attribute: &parse::Attribute) -> syntax::Attribute {
+        syntax::Attribute {
+            span: span_encompassing(attribute.pound_span, attribute.close_square_span),
+            path: self.convert_path(&attribute.path),
+            arguments: attribute
+                .arguments
+                .iter()
+                .flat_map(|arguments| &arguments.paths)
+                .map(|p| self.convert_path(p))
+                .collect(),
+        }
+    }
+
     pub fn convert_visibility(&mut self, visibility: &parse::Visibility) -> syntax::Visibility {
         use parse::Visibility::*;
         match visibility {
@@ -72,6 +115,7 @@ impl<'a> Context<'a> {
             Enum(e) => syntax::TopLevelKind::Enum(self.convert_enum(e)),
             ModFile(m) => syntax::TopLevelKind::ModFile(self.convert_mod_file(m)),
             Use(u) => syntax::TopLevelKind::Use(self.convert_use(u)),
+            MacroRules(_) | Macro(..) => unreachable!("macros are flattened by convert_top_levels"),
         }
     }
 
@@ -187,7 +231,7 @@ impl<'a> Context<'a> {
         let type_ = self.convert_type(&p.type_);
         syntax::Parameter {
             span: span_encompassing(p.name, type_.span),
-            name: self.convert_symbol(p.name),
+            name: self.convert_local_symbol(p.name),
             type_,
         }
     }
@@ -229,7 +273,7 @@ impl<'a> Context<'a> {
         match e {
             Variable(parse::Variable { name }) => syntax::Expression {
                 span: *name,
-                kind: syntax::ExpressionKind::Variable(self.convert_symbol(*name)),
+                kind: syntax::ExpressionKind::Variable(self.convert_local_symbol(*name)),
             },
             Paren(parse::ParenExpression { expression, .. }) => {
                 self.convert_expression(&expression)
@@ -309,17 +353,119 @@ impl<'a> Context<'a> {
                 span: span_encompassing(t.open_paren_span, t.close_paren_span),
                 kind: syntax::ExpressionKind::Tuple(self.convert_tuple(t)),
             },
+            Ref(r) => {
+                let expression = self.convert_expression(&r.expression);
+                syntax::Expression {
+                    span: span_encompassing(r.ref_span, expression.span),
+                    kind: syntax::ExpressionKind::Ref(Box::new(expression)),
+                }
+            }
+            RefMut(r) => {
+                let expression = self.convert_expression(&r.expression);
+                syntax::Expression {
+                    span: span_encompassing(r.ref_span, expression.span),
+                    kind: syntax::ExpressionKind::RefMut(Box::new(expression)),
+                }
+            }
+            RawConst(r) => {
+                let expression = self.convert_expression(&r.expression);
+                syntax::Expression {
+                    span: span_encompassing(r.ref_span, expression.span),
+                    kind: syntax::ExpressionKind::RawConst(Box::new(expression)),
+                }
+            }
+            RawMut(r) => {
+                let expression = self.convert_expression(&r.expression);
+                syntax::Expression {
+                    span: span_encompassing(r.ref_span, expression.span),
+                    kind: syntax::ExpressionKind::RawMut(Box::new(expression)),
+                }
+            }
+            Deref(d) => {
+                let expression = self.convert_expression(&d.expression);
+                syntax::Expression {
+                    span: span_encompassing(d.star_span, expression.span),
+                    kind: syntax::ExpressionKind::Deref(Box::new(expression)),
+                }
+            }
+            Cast(c) => {
+                let sc = self.convert_cast(c);
+                syntax::Expression {
+                    span: span_encompassing(sc.expression.span, sc.type_.span),
+                    kind: syntax::ExpressionKind::Cast(sc),
+                }
+            }
+            Try(t) => {
+                let expression = self.convert_expression(&t.expression);
+                syntax::Expression {
+                    span: span_encompassing(expression.span, t.question_span),
+                    kind: syntax::ExpressionKind::Try(Box::new(expression)),
+                }
+            }
+            Return(r) => {
+                let value = r
+                    .value
+                    .as_ref()
+                    .map(|v| Box::new(self.convert_expression(v)));
+                syntax::Expression {
+                    span: match &value {
+                        Some(v) => span_encompassing(r.return_span, v.span),
+                        None => r.return_span,
+                    },
+                    kind: syntax::ExpressionKind::Return(value),
+                }
+            }
+            Break(b) => {
+                let value = b
+                    .value
+                    .as_ref()
+                    .map(|v| Box::new(self.convert_expression(v)));
+                syntax::Expression {
+                    span: match &value {
+                        Some(v) => span_encompassing(b.break_span, v.span),
+                        None => b.break_span,
+                    },
+                    kind: syntax::ExpressionKind::Break(value),
+                }
+            }
+            Continue(c) => syntax::Expression {
+                span: c.continue_span,
+                kind: syntax::ExpressionKind::Continue,
+            },
+            Macro(m) => syntax::Expression {
+                span: invocation_span(&m.invocation),
+                ..self.convert_expansion(m, |this, e| this.convert_expression(e))
+            },
+        }
+    }
+
+    pub fn convert_cast(&mut self, c: &parse::Cast) -> syntax::Cast {
+        syntax::Cast {
+            expression: Box::new(self.convert_expression(&c.expression)),
+            type_: self.convert_type(&c.type_),
         }
     }
 
     pub fn convert_if(&mut self, i: &parse::If) -> syntax::If {
         syntax::If {
-            condition: Box::new(self.convert_expression(&i.condition)),
+            condition: self.convert_condition(&i.condition),
             then: self.convert_block(&i.then),
             else_: i.else_.as_ref().map(|e| Box::new(self.convert_else(e))),
         }
     }
 
+    pub fn convert_condition(&mut self, c: &parse::Condition) -> syntax::Condition {
+        match c {
+            parse::Condition::Expression(e) => {
+                syntax::Condition::Expression(Box::new(self.convert_expression(e)))
+            }
+            parse::Condition::Let(l) => syntax::Condition::Let(syntax::LetCondition {
+                pattern: self.convert_pattern(&l.pattern),
+                value: Box::new(self.convert_expression(&l.value)),
+            }),
+        }
+    }
+
     pub fn convert_else(&mut self, e: &parse::Else) -> syntax::Else {
         let kind = self.convert_else_kind(&e.kind);
         syntax::Else {
@@ -350,14 +496,14 @@ impl<'a> Context<'a> {
 
     pub fn convert_while(&mut self, w: &parse::While) -> syntax::While {
         syntax::While {
-            condition: Box::new(self.convert_expression(&w.condition)),
+            condition: self.convert_condition(&w.condition),
             block: self.convert_block(&w.block),
         }
     }
 
     pub fn convert_for(&mut self, w: &parse::For) -> syntax::For {
         syntax::For {
-            var: self.convert_symbol(w.var),
+            var: self.convert_local_symbol(w.var),
             expr: Box::new(self.convert_expression(&w.expr)),
             block: self.convert_block(&w.block),
         }
@@ -389,7 +535,7 @@ impl<'a> Context<'a> {
         match p {
             Named(s) => syntax::Pattern {
                 span: *s,
-                kind: syntax::PatternKind::Named(self.convert_symbol_id(*s)),
+                kind: syntax::PatternKind::Named(self.convert_local_symbol_id(*s)),
             },
             Hole(s) => syntax::Pattern {
                 span: *s,
@@ -407,6 +553,10 @@ impl<'a> Context<'a> {
                     self.convert_tuple_pattern(t),
                 ),
             },
+            Macro(m) => syntax::Pattern {
+                span: invocation_span(&m.invocation),
+                ..self.convert_expansion(m, |this, p| this.convert_pattern(p))
+            },
         }
     }
 
@@ -470,30 +620,47 @@ impl<'a> Context<'a> {
 
     pub fn convert_let(&mut self, l: &parse::Let) -> syntax::Let {
         syntax::Let {
-            name_span: match l.name {
-                Ok(s) => s,
-                Err(s) => s,
-            },
-            name: l.name.ok().map(|n| self.convert_symbol_id(n)),
+            pattern: self.convert_pattern(&l.pattern),
+            mutable: l.mut_span.is_some(),
             type_: l.type_.as_ref().map(|lt| self.convert_type(&lt.type_)),
             value: l
                 .value
                 .as_ref()
                 .map(|lv| self.convert_expression(&lv.value)),
+            else_: l.else_.as_ref().map(|le| {
+                Box::new(syntax::LetElse {
+                    span: span_encompassing(le.else_span, le.block.close_curly_span),
+                    block: self.convert_block(&le.block),
+                })
+            }),
         }
     }
 
     pub fn convert_block(&mut self, b: &parse::Block) -> syntax::Block {
+        let mut statements: Vec<_> = b
+            .statements
+            .iter()
+            .map(|s| self.convert_statement(s))
+            .collect();
+        let mut expression = b
+            .expression
+            .as_ref()
+            .map(|e| Box::new(self.convert_expression(e)));
+        // A trailing `if`, `match`, or loop without a semicolon is parsed as
+        // a statement but is the value of the block.
+        if expression.is_none()
+            && b.statements
+                .last()
+                .is_some_and(|s| s.semicolon_span.is_none())
+        {
+            // Statements without a semicolon are always expressions.
+            if let Some(syntax::StatementKind::Expression(e)) = statements.pop().map(|s| s.kind) {
+                expression = Some(Box::new(e));
+            }
+        }
         syntax::Block {
-            statements: b
-                .statements
-                .iter()
-                .map(|s| self.convert_statement(s))
-                .collect(),
-            expression: b
-                .expression
-                .as_ref()
-                .map(|e| Box::new(self.convert_expression(e))),
+            statements,
+            expression,
         }
     }
 
@@ -556,6 +723,10 @@ impl<'a> Context<'a> {
                 span: *underscore_span,
                 kind: syntax::TypeKind::Hole,
             },
+            Macro(m) => syntax::Type {
+                span: invocation_span(&m.invocation),
+                ..self.convert_expansion(m, |this, t| this.convert_type(t))
+            },
         }
     }
 
@@ -567,12 +738,58 @@ impl<'a> Context<'a> {
     }
 
     pub fn convert_symbol_id(&mut self, span: Span) -> syntax::SymbolId {
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
+        self.hash_symbol(span, parse::SyntaxContext(0))
+    }
+
+    /// Local names are hygienic: a name spelled inside of a macro definition
+    /// is distinct from the same name spelled by the macro's caller.
+    pub fn convert_local_symbol(&mut self, span: Span) -> syntax::Symbol {
+        syntax::Symbol {
+            span,
+            id: self.convert_local_symbol_id(span),
+        }
+    }
+
+    pub fn convert_local_symbol_id(&mut self, span: Span) -> syntax::SymbolId {
+        let context = self.context_of(span);
+        self.hash_symbol(span, context)
+    }
+
+    fn hash_symbol(&self, span: Span, context: parse::SyntaxContext) -> syntax::SymbolId {
         let name = self.diagnostic.file_span(span);
-        let mut hasher = DefaultHasher::new();
-        name.hash(&mut hasher);
-        syntax::SymbolId(hasher.finish())
+        if context == parse::SyntaxContext(0) {
+            syntax::SymbolId::of_name(name)
+        } else {
+            syntax::SymbolId::of_hygienic_name(name, context.0)
+        }
+    }
+
+    fn context_of(&self, span: Span) -> parse::SyntaxContext {
+        self.expansions
+            .iter()
+            .rev()
+            .find(|(def_site, _)| {
+                def_site.file == span.file
+                    && def_site.start <= span.start
+                    && span.end <= def_site.end
+            })
+            .map(|(_, context)| *context)
+            .unwrap_or(parse::SyntaxContext(0))
+    }
+
+    fn convert_expansion<T, U, F>(&mut self, m: &parse::Macro<T>, f: F) -> U
+    where
+        F: FnOnce(&mut Self, &T) -> U,
+    {
+        let expansion = m
+            .expansion
+            .as_ref()
+            .expect("macros must be expanded before conversion");
+        self.expansions
+            .push((expansion.def_site, expansion.context));
+        let result = f(self, &expansion.value);
+        self.expansions.pop();
+        result
     }
 }
 
@@ -593,6 +810,7 @@ fn span_encompassing(start: Span, end: Span) -> Span {
         file: start.file,
         start: start.start,
         end: end.end,
+        expn: start.expn,
     }
 }
 
@@ -608,8 +826,7 @@ mod tests {
         let file_contents = "fn f() { a.b() }";
         let (tokens, eofpos) = read_tokens(0, file_contents).unwrap();
         let top_levels = parse(file_contents, &tokens, eofpos).unwrap();
-        let mut diagnostic = Diagnostic::new(vec!["".to_string()]);
-        diagnostic.add_file_contents(file_contents.to_string());
+        let diagnostic = Diagnostic::with_contents("test", file_contents.to_string());
 
         let top_level = Context::new(&diagnostic).convert_top_level(&top_levels[0]);
 
@@ -628,13 +845,43 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_attributes_are_converted() {
+        let top_levels = convert_expanded("#[allow(dead_code, unused)] #[inline] fn f() {}");
+        let attributes = &top_levels[0].attributes;
+        assert_eq!(attributes.len(), 2);
+        assert_eq!(
+            attributes[0].span,
+            Span {
+                file: 0,
+                start: 0,
+                end: 27,
+                expn: None
+            }
+        );
+        assert_eq!(attributes[0].arguments.len(), 2);
+        assert_eq!(
+            attributes[0].arguments[0].segments[0].id,
+            syntax::SymbolId::of_name("dead_code")
+        );
+        assert_eq!(attributes[1].arguments, []);
+        assert_eq!(
+            top_levels[0].span,
+            Span {
+                file: 0,
+                start: 38,
+                end: 47,
+                expn: None
+            }
+        );
+    }
+
     #[test]
     fn test_function_call_on_member() {
         let file_contents = "fn f() { (a.b)() }";
         let (tokens, eofpos) = read_tokens(0, file_contents).unwrap();
         let top_levels = parse(file_contents, &tokens, eofpos).unwrap();
-        let mut diagnostic = Diagnostic::new(vec!["".to_string()]);
-        diagnostic.add_file_contents(file_contents.to_string());
+        let diagnostic = Diagnostic::with_contents("test", file_contents.to_string());
 
         let top_level = Context::new(&diagnostic).convert_top_level(&top_levels[0]);
 
@@ -652,4 +899,149 @@ mod tests {
             }
         );
     }
+
+    fn convert_expanded(file_contents: &str) -> Vec<syntax::TopLevel> {
+        let (tokens, eofpos) = read_tokens(0, file_contents).unwrap();
+        let mut top_levels = parse(file_contents, &tokens, eofpos).unwrap();
+        crate::expand::expand(file_contents, &mut top_levels).unwrap();
+        let diagnostic = Diagnostic::with_contents("test", file_contents.to_string());
+        Context::new(&diagnostic).convert_top_levels(&top_levels)
+    }
+
+    #[test]
+    fn test_convert_top_levels_flattens_item_macros() {
+        let top_levels = convert_expanded(
+            "macro_rules! two { ($a:ident $b:ident) => { fn $a() {} fn $b() {} } } two!(f g);",
+        );
+        assert_eq!(top_levels.len(), 2);
+        assert_matches!(top_levels[0].kind, syntax::TopLevelKind::Function(_));
+        assert_matches!(top_levels[1].kind, syntax::TopLevelKind::Function(_));
+    }
+
+    #[test]
+    fn test_macro_expression_has_invocation_span() {
+        let top_levels = convert_expanded("macro_rules! one { () => { 1 } } fn f() { one!() }");
+        assert_matches!(&top_levels[0].kind, syntax::TopLevelKind::Function(f) => {
+            let expression = f.body.expression.as_ref().unwrap();
+            assert_eq!(expression.span, Span { file: 0, start: 42, end: 48, expn: None });
+            assert_eq!(expression.kind, syntax::ExpressionKind::Value(syntax::Value::Integer(1)));
+        });
+    }
+
+    #[test]
+    fn test_macro_local_names_are_hygienic() {
+        let top_levels = convert_expanded(
+            "macro_rules! m { ($e:expr) => { { let x = 1; $e + x } } }
+             fn f() { let x = 2; m!(x) }",
+        );
+        assert_matches!(&top_levels[0].kind, syntax::TopLevelKind::Function(f) => {
+            let outer_x = match &f.body.statements[0].kind {
+                syntax::StatementKind::Let(syntax::Let {
+                    pattern: syntax::Pattern { kind: syntax::PatternKind::Named(id), .. },
+                    ..
+                }) => *id,
+                _ => unreachable!(),
+            };
+            let block = match &f.body.expression.as_ref().unwrap().kind {
+                syntax::ExpressionKind::Block(block) => block,
+                _ => unreachable!(),
+            };
+            let inner_x = match &block.statements[0].kind {
+                syntax::StatementKind::Let(syntax::Let {
+                    pattern: syntax::Pattern { kind: syntax::PatternKind::Named(id), .. },
+                    ..
+                }) => *id,
+                _ => unreachable!(),
+            };
+            assert_ne!(outer_x, inner_x);
+            assert_matches!(&block.expression.as_ref().unwrap().kind,
+                            syntax::ExpressionKind::Binary(b) => {
+                assert_matches!(&b.left.kind, syntax::ExpressionKind::Variable(v) => {
+                    assert_eq!(v.id, outer_x);
+                });
+                assert_matches!(&b.right.kind, syntax::ExpressionKind::Variable(v) => {
+                    assert_eq!(v.id, inner_x);
+                });
+            });
+        });
+    }
+
+    #[test]
+    fn test_macro_item_names_are_not_hygienic() {
+        let file_contents = "macro_rules! m { () => { fn g() {} } } m!(); fn g() {}";
+        let top_levels = convert_expanded(file_contents);
+        assert_matches!((&top_levels[0].kind, &top_levels[1].kind), (
+            syntax::TopLevelKind::Function(a),
+            syntax::TopLevelKind::Function(b),
+        ) => {
+            assert_eq!(a.name.id, b.name.id);
+        });
+    }
+
+    #[test]
+    fn test_let_records_mut() {
+        let top_levels = convert_expanded("fn f() { let mut x = 1; let y = 2; }");
+        assert_matches!(&top_levels[0].kind, syntax::TopLevelKind::Function(f) => {
+            assert_matches!(&f.body.statements[0].kind, syntax::StatementKind::Let(l) => {
+                assert!(l.mutable);
+            });
+            assert_matches!(&f.body.statements[1].kind, syntax::StatementKind::Let(l) => {
+                assert!(!l.mutable);
+            });
+        });
+    }
+
+    #[test]
+    fn test_deref_cast_span_covers_operand_and_type() {
+        let top_levels = convert_expanded("fn f() { *p as usize }");
+        assert_matches!(&top_levels[0].kind, syntax::TopLevelKind::Function(f) => {
+            let expression = f.body.expression.as_ref().unwrap();
+            assert_eq!(expression.span, Span { file: 0, start: 9, end: 20, expn: None });
+            assert_matches!(&expression.kind, syntax::ExpressionKind::Cast(c) => {
+                assert_eq!(c.expression.span, Span { file: 0, start: 9, end: 11, expn: None });
+                assert_matches!(c.expression.kind, syntax::ExpressionKind::Deref(_));
+            });
+        });
+    }
+
+    #[test]
+    fn test_try_span_covers_operand_and_question() {
+        let top_levels = convert_expanded("fn f() -> Option { g()? }");
+        assert_matches!(&top_levels[0].kind, syntax::TopLevelKind::Function(f) => {
+            let expression = f.body.expression.as_ref().unwrap();
+            assert_eq!(expression.span, Span { file: 0, start: 19, end: 23, expn: None });
+            assert_matches!(&expression.kind, syntax::ExpressionKind::Try(e) => {
+                assert_matches!(e.kind, syntax::ExpressionKind::FunctionCall(_));
+            });
+        });
+    }
+
+    #[test]
+    fn test_unhygienic_names_match_symbol_id_of_name() {
+        let top_levels = convert_expanded("fn Result() {}");
+        assert_matches!(&top_levels[0].kind, syntax::TopLevelKind::Function(f) => {
+            assert_eq!(f.name.id, syntax::SymbolId::of_name("Result"));
+        });
+    }
+
+    #[test]
+    fn test_trailing_if_is_block_expression() {
+        let top_levels = convert_expanded("fn f() { g(); if b { 1 } else { 2 } }");
+        assert_matches!(&top_levels[0].kind, syntax::TopLevelKind::Function(f) => {
+            assert_eq!(f.body.statements.len(), 1);
+            assert_matches!(
+                f.body.expression.as_ref().unwrap().kind,
+                syntax::ExpressionKind::If(_)
+            );
+        });
+    }
+
+    #[test]
+    fn test_trailing_if_with_semicolon_is_statement() {
+        let top_levels = convert_expanded("fn f() { if b { 1 } else { 2 }; }");
+        assert_matches!(&top_levels[0].kind, syntax::TopLevelKind::Function(f) => {
+            assert_matches!(&f.body.statements[0].kind, syntax::StatementKind::Expression(_));
+            assert!(f.body.expression.is_none());
+        });
+    }
 }
@@ -11,13 +11,19 @@ pub struct Token {
 pub enum TokenKind {
     Ampersand,
     And,
+    As,
+    Bang,
     Bar,
+    Break,
     CloseCurly,
     CloseParen,
+    CloseSquare,
     Colon,
     ColonColon,
     Comma,
     Const,
+    Continue,
+    Dollar,
     Dot,
     Else,
     Enum,
@@ -40,12 +46,17 @@ pub enum TokenKind {
     Integer,
     OpenCurly,
     OpenParen,
+    OpenSquare,
     Or,
     Plus,
+    Pound,
     Pub,
+    Question,
+    Return,
     Semicolon,
     Set,
     Star,
+    String,
     Struct,
     ThinArrow,
     True,
@@ -63,13 +74,19 @@ impl fmt::Display for TokenKind {
             match self {
                 Ampersand => "`&`",
                 And => "`&&`",
+                As => "`as`",
+                Bang => "`!`",
                 Bar => "`|`",
+                Break => "`break`",
                 CloseCurly => "`}`",
                 CloseParen => "`)`",
+                CloseSquare => "`]`",
                 Colon => "`:`",
                 ColonColon => "`::`",
                 Comma => "`,`",
                 Const => "`const`",
+                Continue => "`continue`",
+                Dollar => "`$`",
                 Dot => "`.`",
                 Else => "`else`",
                 Enum => "`enum`",
@@ -92,12 +109,17 @@ impl fmt::Display for TokenKind {
                 NotEquals => "`!=`",
                 OpenCurly => "`{`",
                 OpenParen => "`(`",
+                OpenSquare => "`[`",
                 Or => "`||`",
                 Plus => "`+`",
+                Pound => "`#`",
                 Pub => "`pub`",
+                Question => "`?`",
+                Return => "`return`",
                 Semicolon => "`;`",
                 Set => "`=`",
                 Star => "`*`",
+                String => "a string",
                 Struct => "`struct`",
                 ThinArrow => "`->`",
                 True => "`true`",
@@ -0,0 +1,40 @@
+use super::*;
+use std::fmt;
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Integer(integer) => write!(f, "{}", integer.value),
+            Value::Tuple(values) => {
+                write!(f, "(")?;
+                write_values(f, values)?;
+                if values.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            Value::Variant(variant, values) => {
+                write!(f, "{}", variant.name)?;
+                if !values.is_empty() {
+                    write!(f, "(")?;
+                    write_values(f, values)?;
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+            Value::Function(_) => write!(f, "fn"),
+            Value::Pointer(place) => write!(f, "<pointer to slot {}>", place.slot),
+        }
+    }
+}
+
+fn write_values(f: &mut fmt::Formatter, values: &[Value]) -> fmt::Result {
+    for (i, value) in values.iter().enumerate() {
+        if i != 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", value)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,1092 @@
+use super::*;
+use rust_comp_check::integers::{self, Integers};
+use rust_comp_core::diagnostic::Diagnostic;
+use rust_comp_core::integer;
+use rust_comp_core::profile::Profile;
+use rust_comp_syntax as syntax;
+use rust_comp_syntax::{BinaryOp, ExpressionKind, PatternKind, TypeKind};
+use std::collections::HashMap;
+use std::io::Write;
+use std::thread;
+
+/// The maximum depth of nested function calls before `StackOverflow` is
+/// reported.
+const MAX_CALL_DEPTH: usize = 1024;
+
+/// The size of the stack of the thread running the interpreter.  Each
+/// interpreted call uses many native frames.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// Calls `main` and returns its result.  `?` must have been desugared.
+/// Integer literals have the types `integers::infer_integers` gives them.
+/// Arithmetic wraps instead of overflowing in release builds.  `print_i64`
+/// writes to `output`.  Structs are out of scope until there is syntax for
+/// constructing them, so field accesses and method calls are unsupported.
+pub fn run_main(
+    diagnostic: &Diagnostic,
+    profile: Profile,
+    top_levels: &[syntax::TopLevel],
+    output: &mut (dyn Write + Send),
+) -> Result<Value, Error> {
+    thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || {
+                let mut interpreter = Interpreter::new(diagnostic, profile, top_levels, output);
+                let main = SymbolId::of_name("main");
+                let (span, _) = *interpreter.functions.get(&main).ok_or(Error::NoMain)?;
+                interpreter.call(span, main, Vec::new())
+            })
+            .unwrap()
+            .join()
+            .unwrap()
+    })
+}
+
+/// How evaluation of an expression ended other than producing a value.
+enum Flow {
+    Break(Value),
+    Continue,
+    Return(Value),
+    Error(Error),
+}
+
+impl From<Error> for Flow {
+    fn from(error: Error) -> Flow {
+        Flow::Error(error)
+    }
+}
+
+type Eval<T> = Result<T, Flow>;
+
+struct Slot {
+    allocation: u64,
+    /// `None` until the variable is assigned.
+    value: Option<Value>,
+}
+
+/// The lengths of `bindings` and `slots` when a scope was entered.
+#[derive(Clone, Copy)]
+struct Scope {
+    bindings: usize,
+    slots: usize,
+}
+
+struct Interpreter<'a> {
+    diagnostic: &'a Diagnostic,
+    profile: Profile,
+    output: &'a mut (dyn Write + Send),
+    integers: Integers,
+    functions: HashMap<SymbolId, (Span, &'a syntax::Function)>,
+    /// Enum variants and whether they have data.
+    variants: HashMap<SymbolId, (Variant, bool)>,
+    /// Bindings of the current function in scope, innermost last.
+    bindings: Vec<(SymbolId, usize)>,
+    slots: Vec<Slot>,
+    next_allocation: u64,
+    depth: usize,
+}
+
+impl<'a> Interpreter<'a> {
+    fn new(
+        diagnostic: &'a Diagnostic,
+        profile: Profile,
+        top_levels: &'a [syntax::TopLevel],
+        output: &'a mut (dyn Write + Send),
+    ) -> Self {
+        let mut interpreter = Interpreter {
+            diagnostic,
+            profile,
+            output,
+            integers: integers::infer_integers(diagnostic, top_levels),
+            functions: HashMap::new(),
+            variants: HashMap::new(),
+            bindings: Vec::new(),
+            slots: Vec::new(),
+            next_allocation: 0,
+            depth: 0,
+        };
+        // `Option` and `Result` are used without being declared until there
+        // is a standard library.
+        interpreter.add_prelude_enum("Option", &[("None", false), ("Some", true)]);
+        interpreter.add_prelude_enum("Result", &[("Ok", true), ("Err", true)]);
+        for top_level in top_levels {
+            match &top_level.kind {
+                syntax::TopLevelKind::Function(function) => {
+                    interpreter
+                        .functions
+                        .insert(function.name.id, (top_level.span, function));
+                }
+                syntax::TopLevelKind::Enum(enum_) => {
+                    let enum_name = diagnostic.file_span(enum_.name.span).to_string();
+                    for (discriminant, variant) in enum_.variants.iter().enumerate() {
+                        let has_data = variant.data != syntax::VariantData::None;
+                        let variant_ = Variant {
+                            enum_: enum_name.clone(),
+                            name: diagnostic.file_span(variant.name.span).to_string(),
+                            discriminant: discriminant as u128,
+                        };
+                        interpreter
+                            .variants
+                            .insert(variant.name.id, (variant_, has_data));
+                    }
+                }
+                _ => (),
+            }
+        }
+        interpreter
+    }
+
+    fn add_prelude_enum(&mut self, enum_: &str, variants: &[(&str, bool)]) {
+        for (discriminant, (name, has_data)) in variants.iter().enumerate() {
+            let variant = Variant {
+                enum_: enum_.to_string(),
+                name: name.to_string(),
+                discriminant: discriminant as u128,
+            };
+            self.variants
+                .insert(SymbolId::of_name(name), (variant, *has_data));
+        }
+    }
+
+    fn call(
+        &mut self,
+        span: Span,
+        function: SymbolId,
+        arguments: Vec<Value>,
+    ) -> Result<Value, Error> {
+        let (_, function) = self.functions[&function];
+        if arguments.len() != function.parameters.len() {
+            return Err(Error::ArgumentCount(span));
+        }
+        if self.depth == MAX_CALL_DEPTH {
+            return Err(Error::StackOverflow(span));
+        }
+
+        let bindings = std::mem::take(&mut self.bindings);
+        let scope = self.enter_scope();
+        self.depth += 1;
+        let result = self.call_body(span, function, arguments);
+        self.depth -= 1;
+        self.exit_scope(scope);
+        self.bindings = bindings;
+        result
+    }
+
+    fn call_body(
+        &mut self,
+        span: Span,
+        function: &syntax::Function,
+        arguments: Vec<Value>,
+    ) -> Result<Value, Error> {
+        for (parameter, argument) in function.parameters.iter().zip(arguments) {
+            let argument = self.coerce(argument, &parameter.type_, span)?;
+            let slot = self.allocate(Some(argument));
+            self.bindings.push((parameter.name.id, slot));
+        }
+        let value = match self.block(&function.body) {
+            Ok(value) | Err(Flow::Return(value)) => value,
+            Err(Flow::Error(error)) => return Err(error),
+            // `break` and `continue` outside of a loop are rejected by the
+            // parser but can't be interpreted regardless.
+            Err(Flow::Break(_)) | Err(Flow::Continue) => return Err(Error::Unsupported(span)),
+        };
+        self.coerce(value, &function.return_type, span)
+    }
+
+    fn enter_scope(&self) -> Scope {
+        Scope {
+            bindings: self.bindings.len(),
+            slots: self.slots.len(),
+        }
+    }
+
+    fn exit_scope(&mut self, scope: Scope) {
+        self.bindings.truncate(scope.bindings);
+        self.slots.truncate(scope.slots);
+    }
+
+    fn allocate(&mut self, value: Option<Value>) -> usize {
+        self.slots.push(Slot {
+            allocation: self.next_allocation,
+            value,
+        });
+        self.next_allocation += 1;
+        self.slots.len() - 1
+    }
+
+    fn bind(&mut self, id: SymbolId, value: Option<Value>) {
+        let slot = self.allocate(value);
+        self.bindings.push((id, slot));
+    }
+
+    fn lookup(&self, id: SymbolId) -> Option<usize> {
+        self.bindings
+            .iter()
+            .rev()
+            .find(|(binding, _)| *binding == id)
+            .map(|(_, slot)| *slot)
+    }
+
+    fn block(&mut self, block: &syntax::Block) -> Eval<Value> {
+        let scope = self.enter_scope();
+        let result = self.block_in_scope(block);
+        self.exit_scope(scope);
+        result
+    }
+
+    fn block_in_scope(&mut self, block: &syntax::Block) -> Eval<Value> {
+        for statement in &block.statements {
+            match &statement.kind {
+                syntax::StatementKind::Empty => (),
+                syntax::StatementKind::Expression(expression) => {
+                    self.expression(expression)?;
+                }
+                syntax::StatementKind::Let(let_) => self.let_statement(statement.span, let_)?,
+            }
+        }
+        match &block.expression {
+            Some(expression) => self.expression(expression),
+            None => Ok(Value::unit()),
+        }
+    }
+
+    fn let_statement(&mut self, span: Span, let_: &syntax::Let) -> Eval<()> {
+        let value = match &let_.value {
+            Some(value) => self.expression(value)?,
+            None => {
+                self.bind_uninit(&let_.pattern);
+                return Ok(());
+            }
+        };
+        let value = match &let_.type_ {
+            Some(type_) => self.coerce(value, type_, span)?,
+            None => value,
+        };
+        if self.matches(&let_.pattern, &value) {
+            self.bind_pattern(&let_.pattern, value);
+            return Ok(());
+        }
+        match &let_.else_ {
+            Some(else_) => {
+                // The `else` block diverges so its value is never used.
+                self.block(&else_.block)?;
+                Err(Error::NoMatch(span).into())
+            }
+            None => Err(Error::NoMatch(span).into()),
+        }
+    }
+
+    fn expression(&mut self, expression: &syntax::Expression) -> Eval<Value> {
+        let span = expression.span;
+        match &expression.kind {
+            ExpressionKind::Variable(symbol) => self.variable(symbol),
+            ExpressionKind::Block(block) => self.block(block),
+            ExpressionKind::If(if_) => self.if_(if_),
+            ExpressionKind::Loop(loop_) => loop {
+                match self.block(&loop_.block) {
+                    Ok(_) | Err(Flow::Continue) => (),
+                    Err(Flow::Break(value)) => return Ok(value),
+                    Err(flow) => return Err(flow),
+                }
+            },
+            ExpressionKind::While(while_) => {
+                loop {
+                    let scope = self.enter_scope();
+                    let result = match self.condition(&while_.condition) {
+                        Ok(true) => self.block(&while_.block).map(Some),
+                        Ok(false) => Ok(None),
+                        Err(flow) => Err(flow),
+                    };
+                    self.exit_scope(scope);
+                    match result {
+                        Ok(Some(_)) | Err(Flow::Continue) => (),
+                        Ok(None) | Err(Flow::Break(_)) => break,
+                        Err(flow) => return Err(flow),
+                    }
+                }
+                Ok(Value::unit())
+            }
+            ExpressionKind::For(for_) => self.for_(span, for_),
+            ExpressionKind::Match(match_) => {
+                let value = self.expression(&match_.value)?;
+                for item in &match_.matches {
+                    if self.matches(&item.pattern, &value) {
+                        let scope = self.enter_scope();
+                        self.bind_pattern(&item.pattern, value);
+                        let result = self.expression(&item.value);
+                        self.exit_scope(scope);
+                        return result;
+                    }
+                }
+                Err(Error::NoMatch(span).into())
+            }
+            ExpressionKind::Binary(binary) => self.binary(span, binary),
+            ExpressionKind::FunctionCall(function_call) => self.function_call(span, function_call),
+            ExpressionKind::Deref(_) => {
+                let place = self.place(expression)?;
+                Ok(self.read(place, span)?)
+            }
+            ExpressionKind::Tuple(expressions) => Ok(Value::Tuple(self.expressions(expressions)?)),
+            ExpressionKind::Value(value) => match value {
+                syntax::Value::Bool(b) => Ok(Value::Bool(*b)),
+                syntax::Value::Integer(i) => {
+                    let type_ = self.integers.of(span).and_then(IntegerType::from_name);
+                    let value = *i as i128;
+                    // A literal too large for its type is out of range.
+                    if *i > i128::MAX as u128 || type_.is_some_and(|type_| !type_.contains(value)) {
+                        return Err(Error::Overflow(span).into());
+                    }
+                    Ok(Value::Integer(Integer { value, type_ }))
+                }
+            },
+            ExpressionKind::Ref(expression)
+            | ExpressionKind::RefMut(expression)
+            | ExpressionKind::RawConst(expression)
+            | ExpressionKind::RawMut(expression) => Ok(Value::Pointer(self.place(expression)?)),
+            ExpressionKind::Cast(cast) => {
+                let value = self.expression(&cast.expression)?;
+                Ok(self.cast(span, value, &cast.type_)?)
+            }
+            ExpressionKind::Return(value) => {
+                let value = match value {
+                    Some(value) => self.expression(value)?,
+                    None => Value::unit(),
+                };
+                Err(Flow::Return(value))
+            }
+            ExpressionKind::Break(value) => {
+                let value = match value {
+                    Some(value) => self.expression(value)?,
+                    None => Value::unit(),
+                };
+                Err(Flow::Break(value))
+            }
+            ExpressionKind::Continue => Err(Flow::Continue),
+            // Structs are out of scope: fields can only be named by
+            // identifiers and there is no syntax for constructing structs
+            // yet.
+            ExpressionKind::MemberAccess(_)
+            | ExpressionKind::MemberCall(_)
+            | ExpressionKind::Try(_) => Err(Error::Unsupported(span).into()),
+        }
+    }
+
+    fn expressions(&mut self, expressions: &[syntax::Expression]) -> Eval<Vec<Value>> {
+        expressions
+            .iter()
+            .map(|expression| self.expression(expression))
+            .collect()
+    }
+
+    fn variable(&mut self, symbol: &syntax::Symbol) -> Eval<Value> {
+        if let Some(slot) = self.lookup(symbol.id) {
+            return match &self.slots[slot].value {
+                Some(value) => Ok(value.clone()),
+                None => Err(Error::Uninitialized(symbol.span).into()),
+            };
+        }
+        if self.functions.contains_key(&symbol.id) {
+            return Ok(Value::Function(symbol.id));
+        }
+        match self.variants.get(&symbol.id) {
+            Some((variant, false)) => Ok(Value::Variant(variant.clone(), Vec::new())),
+            // Constructors can only be called directly.
+            Some((_, true)) => Err(Error::Unsupported(symbol.span).into()),
+            None => Err(Error::UndefinedName(symbol.span).into()),
+        }
+    }
+
+    fn if_(&mut self, if_: &syntax::If) -> Eval<Value> {
+        let scope = self.enter_scope();
+        let result = match self.condition(&if_.condition) {
+            Ok(true) => self.block(&if_.then).map(Some),
+            Ok(false) => Ok(None),
+            Err(flow) => Err(flow),
+        };
+        self.exit_scope(scope);
+        if let Some(value) = result? {
+            return Ok(value);
+        }
+        match if_.else_.as_ref().map(|else_| &else_.kind) {
+            Some(syntax::ElseKind::If(if_)) => self.if_(if_),
+            Some(syntax::ElseKind::Block(block)) => self.block(block),
+            None => Ok(Value::unit()),
+        }
+    }
+
+    /// Evaluates `condition`, binding the pattern of `if let` and
+    /// `while let` in the current scope when it matches.
+    fn condition(&mut self, condition: &syntax::Condition) -> Eval<bool> {
+        match condition {
+            syntax::Condition::Expression(expression) => match self.expression(expression)? {
+                Value::Bool(b) => Ok(b),
+                _ => Err(Error::TypeMismatch(expression.span).into()),
+            },
+            syntax::Condition::Let(let_) => {
+                let value = self.expression(&let_.value)?;
+                if self.matches(&let_.pattern, &value) {
+                    self.bind_pattern(&let_.pattern, value);
+                    Ok(true)
+                } else {
+                    Ok(false)
+                }
+            }
+        }
+    }
+
+    /// Like the MIR, `for` loops call `next(&mut iter)` until it returns
+    /// `None`.
+    fn for_(&mut self, span: Span, for_: &syntax::For) -> Eval<Value> {
+        let next = SymbolId::of_name("next");
+        if !self.functions.contains_key(&next) {
+            return Err(Error::Unsupported(span).into());
+        }
+        let iterator = self.expression(&for_.expr)?;
+        let scope = self.enter_scope();
+        let result = self.for_in_scope(span, next, iterator, for_);
+        self.exit_scope(scope);
+        result
+    }
+
+    fn for_in_scope(
+        &mut self,
+        span: Span,
+        next: SymbolId,
+        iterator: Value,
+        for_: &syntax::For,
+    ) -> Eval<Value> {
+        let slot = self.allocate(Some(iterator));
+        let iterator = self.place_of(slot);
+        loop {
+            let item = match self.call(span, next, vec![Value::Pointer(iterator)])? {
+                Value::Variant(variant, mut values) if variant.enum_ == "Option" => {
+                    match values.pop() {
+                        Some(value) => value,
+                        None => break,
+                    }
+                }
+                _ => return Err(Error::TypeMismatch(for_.expr.span).into()),
+            };
+            let scope = self.enter_scope();
+            self.bind(for_.var.id, Some(item));
+            let result = self.block(&for_.block);
+            self.exit_scope(scope);
+            match result {
+                Ok(_) | Err(Flow::Continue) => (),
+                Err(Flow::Break(_)) => break,
+                Err(flow) => return Err(flow),
+            }
+        }
+        Ok(Value::unit())
+    }
+
+    fn binary(&mut self, span: Span, binary: &syntax::Binary) -> Eval<Value> {
+        match binary.op {
+            BinaryOp::SetTo => {
+                let place = self.place(&binary.left)?;
+                let value = self.expression(&binary.right)?;
+                self.write(place, value, span)?;
+                return Ok(Value::unit());
+            }
+            BinaryOp::And | BinaryOp::Or => {
+                let left = self.bool(&binary.left)?;
+                if left == (binary.op == BinaryOp::Or) {
+                    return Ok(Value::Bool(left));
+                }
+                return Ok(Value::Bool(self.bool(&binary.right)?));
+            }
+            _ => (),
+        }
+
+        let left = self.expression(&binary.left)?;
+        let right = self.expression(&binary.right)?;
+        match binary.op {
+            BinaryOp::IsEqualTo => return Ok(Value::Bool(equal(&left, &right))),
+            BinaryOp::IsNotEqualTo => return Ok(Value::Bool(!equal(&left, &right))),
+            _ => (),
+        }
+        let (left, right) = match (left, right) {
+            (Value::Integer(left), Value::Integer(right)) => (left, right),
+            (Value::Bool(left), Value::Bool(right)) => match binary.op {
+                BinaryOp::BitAnd => return Ok(Value::Bool(left & right)),
+                BinaryOp::BitOr => return Ok(Value::Bool(left | right)),
+                _ => return Err(Error::TypeMismatch(span).into()),
+            },
+            _ => return Err(Error::TypeMismatch(span).into()),
+        };
+
+        let type_ = left.type_.or(right.type_);
+        let checked_type =
+            type_.unwrap_or_else(|| IntegerType::from_name(integer::DEFAULT).unwrap());
+        if !checked_type.contains(left.value) {
+            return Err(Error::Overflow(binary.left.span).into());
+        }
+        if !checked_type.contains(right.value) {
+            return Err(Error::Overflow(binary.right.span).into());
+        }
+        let value = match binary.op {
+            BinaryOp::Plus => left.value.checked_add(right.value),
+            BinaryOp::Minus => left.value.checked_sub(right.value),
+            BinaryOp::Times => left.value.checked_mul(right.value),
+            BinaryOp::DividedBy => {
+                if right.value == 0 {
+                    return Err(Error::DivisionByZero(span).into());
+                }
+                left.value.checked_div(right.value)
+            }
+            BinaryOp::BitAnd => Some(left.value & right.value),
+            BinaryOp::BitOr => Some(left.value | right.value),
+            _ => unreachable!(),
+        };
+        // Dividing the minimum value by `-1` overflows even in release
+        // builds.
+        let wraps = !self.profile.overflow_checks() && binary.op != BinaryOp::DividedBy;
+        match value {
+            Some(value) if checked_type.contains(value) => {
+                Ok(Value::Integer(Integer { value, type_ }))
+            }
+            Some(value) if wraps => Ok(Value::Integer(Integer {
+                value: checked_type.wrap(value),
+                type_,
+            })),
+            _ => Err(Error::Overflow(span).into()),
+        }
+    }
+
+    fn bool(&mut self, expression: &syntax::Expression) -> Eval<bool> {
+        match self.expression(expression)? {
+            Value::Bool(b) => Ok(b),
+            _ => Err(Error::TypeMismatch(expression.span).into()),
+        }
+    }
+
+    fn function_call(&mut self, span: Span, function_call: &syntax::FunctionCall) -> Eval<Value> {
+        if let ExpressionKind::Variable(symbol) = &function_call.function.kind {
+            if self.lookup(symbol.id).is_none() {
+                if let Some((variant, true)) = self.variants.get(&symbol.id) {
+                    let variant = variant.clone();
+                    let values = self.expressions(&function_call.arguments)?;
+                    return Ok(Value::Variant(variant, values));
+                }
+                if !self.functions.contains_key(&symbol.id) && self.name(symbol) == "print_i64" {
+                    return self.print_i64(span, &function_call.arguments);
+                }
+            }
+        }
+        match self.expression(&function_call.function)? {
+            Value::Function(function) => {
+                let arguments = self.expressions(&function_call.arguments)?;
+                Ok(self.call(span, function, arguments)?)
+            }
+            _ => Err(Error::TypeMismatch(function_call.function.span).into()),
+        }
+    }
+
+    /// The intrinsic the native backends' runtimes provide.
+    fn print_i64(&mut self, span: Span, arguments: &[syntax::Expression]) -> Eval<Value> {
+        match self.expressions(arguments)?.as_slice() {
+            [Value::Integer(integer)] => {
+                writeln!(self.output, "{}", integer.value).unwrap();
+                Ok(Value::unit())
+            }
+            [_] => Err(Error::TypeMismatch(arguments[0].span).into()),
+            _ => Err(Error::ArgumentCount(span).into()),
+        }
+    }
+
+    fn cast(&self, span: Span, value: Value, type_: &syntax::Type) -> Result<Value, Error> {
+        match &type_.kind {
+            TypeKind::Named(name) => {
+                let integer_type = match IntegerType::from_name(self.name(name)) {
+                    Some(integer_type) => integer_type,
+                    None => return Err(Error::Unsupported(span)),
+                };
+                let value = match value {
+                    Value::Integer(integer) => integer.value,
+                    Value::Bool(b) => b as i128,
+                    Value::Variant(variant, ref values) if values.is_empty() => {
+                        variant.discriminant as i128
+                    }
+                    _ => return Err(Error::TypeMismatch(span)),
+                };
+                Ok(Value::Integer(Integer {
+                    value: integer_type.wrap(value),
+                    type_: Some(integer_type),
+                }))
+            }
+            TypeKind::PtrConst(_) | TypeKind::PtrMut(_) => match value {
+                Value::Pointer(_) => Ok(value),
+                _ => Err(Error::Unsupported(span)),
+            },
+            _ => Err(Error::Unsupported(span)),
+        }
+    }
+
+    /// Gives integer literals in `value` the integer types in `type_`.
+    fn coerce(&self, value: Value, type_: &syntax::Type, span: Span) -> Result<Value, Error> {
+        match (value, &type_.kind) {
+            (Value::Integer(Integer { value, type_: None }), TypeKind::Named(name)) => {
+                match IntegerType::from_name(self.name(name)) {
+                    Some(integer_type) if integer_type.contains(value) => {
+                        Ok(Value::Integer(Integer {
+                            value,
+                            type_: Some(integer_type),
+                        }))
+                    }
+                    Some(_) => Err(Error::Overflow(span)),
+                    None => Ok(Value::Integer(Integer { value, type_: None })),
+                }
+            }
+            (Value::Tuple(values), TypeKind::Tuple(types)) if values.len() == types.len() => {
+                Ok(Value::Tuple(
+                    values
+                        .into_iter()
+                        .zip(types)
+                        .map(|(value, type_)| self.coerce(value, type_, span))
+                        .collect::<Result<_, _>>()?,
+                ))
+            }
+            (value, _) => Ok(value),
+        }
+    }
+
+    fn name(&self, symbol: &syntax::Symbol) -> &'a str {
+        self.diagnostic.file_span(symbol.span)
+    }
+
+    fn place(&mut self, expression: &syntax::Expression) -> Eval<Place> {
+        match &expression.kind {
+            ExpressionKind::Variable(symbol) => {
+                if let Some(slot) = self.lookup(symbol.id) {
+                    return Ok(self.place_of(slot));
+                }
+            }
+            ExpressionKind::Deref(pointer) => {
+                return match self.expression(pointer)? {
+                    Value::Pointer(place) => Ok(place),
+                    _ => Err(Error::TypeMismatch(pointer.span).into()),
+                };
+            }
+            _ => (),
+        }
+        // Other expressions are evaluated into a temporary.
+        let value = self.expression(expression)?;
+        let slot = self.allocate(Some(value));
+        Ok(self.place_of(slot))
+    }
+
+    fn place_of(&self, slot: usize) -> Place {
+        Place {
+            slot,
+            allocation: self.slots[slot].allocation,
+        }
+    }
+
+    fn slot(&self, place: Place, span: Span) -> Result<&Slot, Error> {
+        match self.slots.get(place.slot) {
+            Some(slot) if slot.allocation == place.allocation => Ok(slot),
+            _ => Err(Error::DanglingPointer(span)),
+        }
+    }
+
+    fn read(&self, place: Place, span: Span) -> Result<Value, Error> {
+        match &self.slot(place, span)?.value {
+            Some(value) => Ok(value.clone()),
+            None => Err(Error::Uninitialized(span)),
+        }
+    }
+
+    fn write(&mut self, place: Place, value: Value, span: Span) -> Result<(), Error> {
+        let value = match &self.slot(place, span)?.value {
+            Some(old) => assigned(old, value, span)?,
+            None => value,
+        };
+        self.slots[place.slot].value = Some(value);
+        Ok(())
+    }
+
+    fn matches(&self, pattern: &syntax::Pattern, value: &Value) -> bool {
+        match (&pattern.kind, value) {
+            (PatternKind::Named(id), _) => match self.variants.get(id) {
+                Some((variant, _)) => match value {
+                    Value::Variant(value, _) => same_variant(variant, value),
+                    _ => false,
+                },
+                None => true,
+            },
+            (PatternKind::Hole, _) => true,
+            (PatternKind::Tuple(patterns), Value::Tuple(values)) => {
+                patterns.len() == values.len()
+                    && patterns
+                        .iter()
+                        .zip(values)
+                        .all(|(pattern, value)| self.matches(pattern, value))
+            }
+            (PatternKind::NamedTuple(symbol, patterns), Value::Variant(variant, values)) => {
+                match self.variants.get(&symbol.id) {
+                    Some((pattern_variant, _)) => {
+                        same_variant(pattern_variant, variant)
+                            && patterns.len() == values.len()
+                            && patterns
+                                .iter()
+                                .zip(values)
+                                .all(|(pattern, value)| self.matches(pattern, value))
+                    }
+                    None => false,
+                }
+            }
+            (PatternKind::Value(syntax::Value::Bool(a)), Value::Bool(b)) => a == b,
+            (PatternKind::Value(syntax::Value::Integer(a)), Value::Integer(b)) => {
+                b.value >= 0 && *a == b.value as u128
+            }
+            _ => false,
+        }
+    }
+
+    /// Binds the names in `pattern`.  `value` must match it.
+    fn bind_pattern(&mut self, pattern: &syntax::Pattern, value: Value) {
+        match (&pattern.kind, value) {
+            (PatternKind::Named(id), value) if !self.variants.contains_key(id) => {
+                self.bind(*id, Some(value));
+            }
+            (PatternKind::Tuple(patterns), Value::Tuple(values))
+            | (PatternKind::NamedTuple(_, patterns), Value::Variant(_, values)) => {
+                for (pattern, value) in patterns.iter().zip(values) {
+                    self.bind_pattern(pattern, value);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn bind_uninit(&mut self, pattern: &syntax::Pattern) {
+        match &pattern.kind {
+            PatternKind::Named(id) => {
+                if !self.variants.contains_key(id) {
+                    self.bind(*id, None);
+                }
+            }
+            PatternKind::Tuple(patterns) | PatternKind::NamedTuple(_, patterns) => {
+                for pattern in patterns {
+                    self.bind_uninit(pattern);
+                }
+            }
+            PatternKind::Hole | PatternKind::Value(_) => (),
+        }
+    }
+}
+
+fn same_variant(a: &Variant, b: &Variant) -> bool {
+    a.enum_ == b.enum_ && a.discriminant == b.discriminant
+}
+
+fn equal(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        // Integer literals compare equal to typed integers with the same
+        // value.
+        (Value::Integer(left), Value::Integer(right)) => left.value == right.value,
+        (Value::Tuple(left), Value::Tuple(right)) => {
+            left.len() == right.len() && left.iter().zip(right).all(|(l, r)| equal(l, r))
+        }
+        (Value::Variant(left_variant, left), Value::Variant(right_variant, right)) => {
+            same_variant(left_variant, right_variant)
+                && left.len() == right.len()
+                && left.iter().zip(right).all(|(l, r)| equal(l, r))
+        }
+        _ => left == right,
+    }
+}
+
+/// The value to store when assigning `value` over `old`.  Integer literals
+/// take the type of the integer they replace.
+fn assigned(old: &Value, value: Value, span: Span) -> Result<Value, Error> {
+    match (old, value) {
+        (
+            Value::Integer(Integer {
+                type_: Some(integer_type),
+                ..
+            }),
+            Value::Integer(Integer { value, type_: None }),
+        ) => {
+            if integer_type.contains(value) {
+                Ok(Value::Integer(Integer {
+                    value,
+                    type_: Some(*integer_type),
+                }))
+            } else {
+                Err(Error::Overflow(span))
+            }
+        }
+        (_, value) => Ok(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{run_str, run_str_output, run_str_with};
+    use assert_matches::assert_matches;
+
+    fn integer(value: i128, type_: Option<IntegerType>) -> Value {
+        Value::Integer(Integer { value, type_ })
+    }
+
+    #[test]
+    fn test_run_arithmetic_and_calls() {
+        assert_eq!(
+            run_str("fn add(a: i64, b: i64) -> i64 { a + b } fn main() -> i64 { add(2, 3) * 4 }"),
+            Ok(integer(20, Some(IntegerType::I64)))
+        );
+    }
+
+    #[test]
+    fn test_run_recursion() {
+        assert_eq!(
+            run_str(
+                "fn fib(n: u32) -> u32 { if n == 0 || n == 1 { n } else { fib(n - 1) + fib(n - 2) } }
+                 fn main() -> u32 { fib(15) }"
+            ),
+            Ok(integer(610, Some(IntegerType::U32)))
+        );
+    }
+
+    #[test]
+    fn test_run_unicode_identifiers_and_crlf() {
+        assert_eq!(
+            run_str(
+                "\u{feff}#!/usr/bin/env rust-comp\r\n\
+                 // Calcule le double — ça marche.\r\n\
+                 fn doublé(größe: i32) -> i32 { größe * 2 }\r\n\
+                 fn main() -> i32 { let 值 = 21; doublé(值) }\r\n"
+            ),
+            Ok(integer(42, Some(IntegerType::I32)))
+        );
+    }
+
+    #[test]
+    fn test_run_print_i64() {
+        let mut output = Vec::new();
+        assert_eq!(
+            run_str_output(
+                "fn main() { print_i64(3); print_i64(0 - 4); }",
+                Profile::Debug,
+                &mut output
+            ),
+            Ok(Value::unit())
+        );
+        assert_eq!(String::from_utf8(output).unwrap(), "3\n-4\n");
+        assert_matches!(
+            run_str("fn main() { print_i64(true); }"),
+            Err(Error::TypeMismatch(_))
+        );
+        assert_matches!(
+            run_str("fn main() { print_i64(1, 2); }"),
+            Err(Error::ArgumentCount(_))
+        );
+    }
+
+    #[test]
+    fn test_run_system_tests() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../system-tests/ex5.rs");
+        let mut output = Vec::new();
+        run_str_output(
+            &std::fs::read_to_string(path).unwrap(),
+            Profile::Debug,
+            &mut output,
+        )
+        .unwrap();
+        let fibs = [
+            0, 1, 1, 2, 3, 5, 8, 13, 21, 34, 55, 89, 144, 233, 377, 610, 987, 1597, 2584, 4181,
+        ];
+        let expected: String = fibs.iter().map(|fib| format!("{}\n", fib)).collect();
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_run_loops() {
+        assert_eq!(
+            run_str(
+                "fn main() -> i32 {
+                     let mut i = 0;
+                     let mut sum = 0;
+                     while i != 10 { i = i + 1; if i == 5 { continue; } sum = sum + i; }
+                     loop { if sum == 50 { break sum; } sum = sum - 1; }
+                 }"
+            ),
+            Ok(integer(50, Some(IntegerType::I32)))
+        );
+    }
+
+    #[test]
+    fn test_run_match_enums_and_tuples() {
+        assert_eq!(
+            run_str(
+                "enum Shape { Square(i32), Rect(i32, i32), Empty }
+                 fn area(s: Shape) -> i32 {
+                     match s { Square(x) => x * x, Rect(w, h) => w * h, Empty => 0 }
+                 }
+                 fn main() -> (i32, i32, i32, bool) {
+                     let t = (area(Square(3)), area(Rect(2, 5)), area(Empty), true);
+                     t
+                 }"
+            ),
+            Ok(Value::Tuple(vec![
+                integer(9, Some(IntegerType::I32)),
+                integer(10, Some(IntegerType::I32)),
+                integer(0, Some(IntegerType::I32)),
+                Value::Bool(true),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_run_if_let_let_else_and_try() {
+        assert_eq!(
+            run_str(
+                "fn half(x: i32) -> Option { if x == 4 { Some(2) } else { None } }
+                 fn twice(x: i32) -> Option { let y = half(x)?; Some(y * 2) }
+                 fn main() -> i32 {
+                     let Some(a) = twice(4) else { return 0; };
+                     if let Some(b) = twice(5) { b } else { a }
+                 }"
+            ),
+            Ok(integer(4, Some(IntegerType::I32)))
+        );
+    }
+
+    #[test]
+    fn test_run_references_and_for() {
+        assert_eq!(
+            run_str(
+                "fn next(i: &mut i32) -> Option {
+                     if *i == 3 { None } else { *i = *i + 1; Some(*i) }
+                 }
+                 fn main() -> i32 {
+                     let mut sum = 0;
+                     let p = &mut sum;
+                     for x in 0 { *p = *p + x; }
+                     sum
+                 }"
+            ),
+            Ok(integer(6, Some(IntegerType::I32)))
+        );
+    }
+
+    #[test]
+    fn test_run_casts_wrap() {
+        assert_eq!(
+            run_str("fn main() -> (u8, i8) { (300 as u8, 200 as i8) }"),
+            Ok(Value::Tuple(vec![
+                integer(44, Some(IntegerType::U8)),
+                integer(-56, Some(IntegerType::I8)),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_run_overflow() {
+        assert_eq!(
+            run_str("fn main() -> u8 { let x: u8 = 200; x + 100 }"),
+            Err(Error::Overflow(Span {
+                file: 0,
+                start: 35,
+                end: 42,
+                expn: None,
+            }))
+        );
+        assert_matches!(
+            run_str("fn main() -> u32 { let x: u32 = 0; x - 1 }"),
+            Err(Error::Overflow(_))
+        );
+        assert_matches!(
+            run_str("fn main() { 2147483647 + 1; }"),
+            Err(Error::Overflow(_))
+        );
+        assert_matches!(
+            run_str("fn main() { let x: u8 = 256; }"),
+            Err(Error::Overflow(_))
+        );
+    }
+
+    #[test]
+    fn test_run_release_wraps() {
+        let run = |s| run_str_with(s, Profile::Release);
+        assert_eq!(
+            run("fn main() -> u8 { let x: u8 = 200; x + 100 }"),
+            Ok(integer(44, Some(IntegerType::U8)))
+        );
+        assert_eq!(
+            run("fn main() -> i32 { let x: i32 = 0 - 2147483647; x - 2 }"),
+            Ok(integer(2147483647, Some(IntegerType::I32)))
+        );
+        assert_matches!(
+            run("fn main() -> i8 { let x: i8 = 0 - 128; x / (0 - 1) }"),
+            Err(Error::Overflow(_))
+        );
+        assert_matches!(
+            run("fn main() -> i32 { let x = 0; 1 / x }"),
+            Err(Error::DivisionByZero(_))
+        );
+    }
+
+    #[test]
+    fn test_run_literals_have_their_inferred_types() {
+        for profile in [Profile::Debug, Profile::Release] {
+            assert_eq!(
+                run_str_with(
+                    "fn main() -> i64 { let m: i64 = 0 - 9223372036854775807 - 1; m }",
+                    profile
+                ),
+                Ok(integer(i64::MIN as i128, Some(IntegerType::I64)))
+            );
+            assert_eq!(
+                run_str_with(
+                    "fn f(p: &mut i64) -> i64 { *p + 4294967296 }
+                     fn main() -> i64 { let mut x: i64 = 1; f(&mut x) }",
+                    profile
+                ),
+                Ok(integer(4294967297, Some(IntegerType::I64)))
+            );
+        }
+        let mut output = Vec::new();
+        assert_eq!(
+            run_str_output(
+                "fn main() { let x = 2147483647; print_i64(x + 1); }",
+                Profile::Debug,
+                &mut output
+            ),
+            Ok(Value::unit())
+        );
+        assert_eq!(String::from_utf8(output).unwrap(), "2147483648\n");
+    }
+
+    #[test]
+    fn test_run_division_by_zero() {
+        assert_matches!(
+            run_str("fn main() -> i32 { let x = 0; 1 / x }"),
+            Err(Error::DivisionByZero(_))
+        );
+    }
+
+    #[test]
+    fn test_run_dangling_pointer() {
+        assert_matches!(
+            run_str("fn f() -> &i32 { let x = 1; &x } fn main() -> i32 { *f() }"),
+            Err(Error::DanglingPointer(_))
+        );
+    }
+
+    #[test]
+    fn test_run_uninitialized_and_stack_overflow() {
+        assert_matches!(
+            run_str("fn main() -> i32 { let x; x }"),
+            Err(Error::Uninitialized(_))
+        );
+        assert_matches!(
+            run_str("fn f() { f() } fn main() { f() }"),
+            Err(Error::StackOverflow(_))
+        );
+        assert_eq!(run_str("fn f() {}"), Err(Error::NoMain));
+    }
+}
@@ -0,0 +1,168 @@
+use rust_comp_core::pos::Span;
+use rust_comp_syntax::SymbolId;
+
+mod display;
+pub mod eval;
+
+#[cfg(test)]
+mod test;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    Bool(bool),
+    Integer(Integer),
+    /// `()` is the empty tuple.
+    Tuple(Vec<Value>),
+    Variant(Variant, Vec<Value>),
+    Function(SymbolId),
+    /// A reference or raw pointer.
+    Pointer(Place),
+}
+
+impl Value {
+    pub fn unit() -> Value {
+        Value::Tuple(Vec::new())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Integer {
+    pub value: i128,
+    /// `None` for integer literals without a suffix whose type hasn't been
+    /// inferred.  They are treated as `i32` when overflow is checked.
+    pub type_: Option<IntegerType>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntegerType {
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    Isize,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    Usize,
+}
+
+impl IntegerType {
+    pub fn from_name(name: &str) -> Option<IntegerType> {
+        Some(match name {
+            "i8" => IntegerType::I8,
+            "i16" => IntegerType::I16,
+            "i32" => IntegerType::I32,
+            "i64" => IntegerType::I64,
+            "i128" => IntegerType::I128,
+            "isize" => IntegerType::Isize,
+            "u8" => IntegerType::U8,
+            "u16" => IntegerType::U16,
+            "u32" => IntegerType::U32,
+            "u64" => IntegerType::U64,
+            "u128" => IntegerType::U128,
+            "usize" => IntegerType::Usize,
+            _ => return None,
+        })
+    }
+
+    pub fn bits(self) -> u32 {
+        match self {
+            IntegerType::I8 | IntegerType::U8 => 8,
+            IntegerType::I16 | IntegerType::U16 => 16,
+            IntegerType::I32 | IntegerType::U32 => 32,
+            IntegerType::I64 | IntegerType::U64 => 64,
+            IntegerType::I128 | IntegerType::U128 => 128,
+            IntegerType::Isize | IntegerType::Usize => 64,
+        }
+    }
+
+    pub fn is_signed(self) -> bool {
+        matches!(
+            self,
+            IntegerType::I8
+                | IntegerType::I16
+                | IntegerType::I32
+                | IntegerType::I64
+                | IntegerType::I128
+                | IntegerType::Isize
+        )
+    }
+
+    pub fn min(self) -> i128 {
+        if self.is_signed() {
+            i128::MIN >> (128 - self.bits())
+        } else {
+            0
+        }
+    }
+
+    /// `u128` values above `i128::MAX` can't be represented.
+    pub fn max(self) -> i128 {
+        if self.is_signed() || self.bits() == 128 {
+            i128::MAX >> (128 - self.bits())
+        } else {
+            (1 << self.bits()) - 1
+        }
+    }
+
+    pub fn contains(self, value: i128) -> bool {
+        self.min() <= value && value <= self.max()
+    }
+
+    /// Truncates `value` to the width of this type, as `as` does.
+    pub fn wrap(self, value: i128) -> i128 {
+        let bits = self.bits();
+        if bits == 128 {
+            return value;
+        }
+        let value = value.rem_euclid(1 << bits);
+        if self.is_signed() && value > self.max() {
+            value - (1 << bits)
+        } else {
+            value
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Variant {
+    pub enum_: String,
+    pub name: String,
+    pub discriminant: u128,
+}
+
+/// A variable or temporary in the interpreter's memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Place {
+    pub slot: usize,
+    /// Distinguishes the values that have lived in `slot` so pointers to
+    /// values that went out of scope can be detected.
+    pub allocation: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// There is no `main` function.
+    NoMain,
+    /// An arithmetic operation overflowed the type of its result.
+    Overflow(Span),
+    DivisionByZero(Span),
+    /// No pattern matched the value.  Holds the `match` or `let`.
+    NoMatch(Span),
+    /// A variable was read before it was assigned.
+    Uninitialized(Span),
+    /// A pointer was used after the value it points to went out of scope.
+    DanglingPointer(Span),
+    /// The operand doesn't support the operation applied to it.
+    TypeMismatch(Span),
+    /// The function call has the wrong number of arguments.
+    ArgumentCount(Span),
+    UndefinedName(Span),
+    /// The function call exceeded the maximum call depth.
+    StackOverflow(Span),
+    /// The expression can't be interpreted yet.
+    Unsupported(Span),
+}
@@ -0,0 +1,23 @@
+use crate::eval::run_main;
+use crate::{Error, Value};
+use rust_comp_check::test_support;
+use rust_comp_core::profile::Profile;
+
+/// Runs `file_contents` as file 0 with overflow checks.
+pub fn run_str(file_contents: &str) -> Result<Value, Error> {
+    run_str_with(file_contents, Profile::Debug)
+}
+
+pub fn run_str_with(file_contents: &str, profile: Profile) -> Result<Value, Error> {
+    run_str_output(file_contents, profile, &mut Vec::new())
+}
+
+/// Like `run_str_with` but writes what the program prints to `output`.
+pub fn run_str_output(
+    file_contents: &str,
+    profile: Profile,
+    output: &mut Vec<u8>,
+) -> Result<Value, Error> {
+    let (diagnostic, top_levels) = test_support::desugar(file_contents);
+    run_main(&diagnostic, profile, &top_levels, output)
+}
@@ -0,0 +1,688 @@
+use super::*;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The new borrow, the existing borrow, and a later use of the existing
+    /// borrow.
+    ConflictingBorrow(Span, Span, Span),
+    /// The assignment, the borrow, and a later use of the borrow.
+    AssignToBorrowed(Span, Span, Span),
+    /// The use, the mutable borrow, and a later use of the borrow.
+    UseWhileMutablyBorrowed(Span, Span, Span),
+    /// The move, the borrow, and a later use of the borrow.
+    MoveWhileBorrowed(Span, Span, Span),
+    /// The borrow, where the borrowed local goes out of scope, and a later
+    /// use of the borrow.
+    DoesNotLiveLongEnough(Span, Span, Span),
+    /// The borrow and the declaration of the borrowed local.
+    ReturnsReferenceToLocal(Span, Span),
+}
+
+/// Checks that every borrow in `bodies` is used while the borrowed place is
+/// valid and isn't accessed in a conflicting way.  A borrow lasts as long as
+/// a local holding the reference, or a value derived from it, is live.  Uses
+/// of moved values are reported by `rust_comp_check::moves`.
+///
+/// Until there are types, only values built from variants of user enums
+/// are known to be moved rather than copied.
+pub fn check(bodies: &[Body]) -> Vec<Error> {
+    let returns_reference: HashMap<&str, bool> = bodies
+        .iter()
+        .map(|body| (body.name.as_str(), body.returns_reference))
+        .collect();
+    let mut errors = Vec::new();
+    for body in bodies {
+        Checker::new(body, &returns_reference).check(&mut errors);
+    }
+    errors
+}
+
+/// A statement or, when `index` is the number of statements, the
+/// terminator of `block`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Location {
+    block: usize,
+    index: usize,
+}
+
+struct Loan {
+    place: Place,
+    mutable: bool,
+    span: Span,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Read,
+    Move,
+    Write,
+    Borrow,
+    BorrowMut,
+}
+
+/// The loans each local may hold a reference from.
+type Holds = Vec<BTreeSet<usize>>;
+
+struct Checker<'a> {
+    body: &'a Body,
+    returns_reference: &'a HashMap<&'a str, bool>,
+    loans: Vec<Loan>,
+    loan_at: HashMap<Location, usize>,
+    /// Whether each local holds a value that is moved when it is used.
+    move_only: Vec<bool>,
+    /// The locals that are live before each location.
+    live: Vec<Vec<Vec<bool>>>,
+}
+
+impl<'a> Checker<'a> {
+    fn new(body: &'a Body, returns_reference: &'a HashMap<&'a str, bool>) -> Self {
+        let mut checker = Checker {
+            body,
+            returns_reference,
+            loans: Vec::new(),
+            loan_at: HashMap::new(),
+            move_only: vec![false; body.locals.len()],
+            live: Vec::new(),
+        };
+        for (block, data) in body.blocks.iter().enumerate() {
+            for (index, statement) in data.statements.iter().enumerate() {
+                if let StatementKind::Assign(_, Rvalue::Ref(place))
+                | StatementKind::Assign(_, Rvalue::RefMut(place)) = &statement.kind
+                {
+                    let mutable =
+                        matches!(statement.kind, StatementKind::Assign(_, Rvalue::RefMut(_)));
+                    checker
+                        .loan_at
+                        .insert(Location { block, index }, checker.loans.len());
+                    checker.loans.push(Loan {
+                        place: place.clone(),
+                        mutable,
+                        span: statement.span,
+                    });
+                }
+            }
+        }
+        checker.classify_moves();
+        checker.compute_liveness();
+        checker
+    }
+
+    fn location_span(&self, location: Location) -> Span {
+        let block = &self.body.blocks[location.block];
+        match block.statements.get(location.index) {
+            Some(statement) => statement.span,
+            None => block.terminator.span,
+        }
+    }
+
+    fn successors(&self, location: Location) -> Vec<Location> {
+        let block = &self.body.blocks[location.block];
+        if location.index < block.statements.len() {
+            vec![Location {
+                block: location.block,
+                index: location.index + 1,
+            }]
+        } else {
+            block
+                .terminator
+                .kind
+                .successors()
+                .into_iter()
+                .map(|target| Location {
+                    block: target.0,
+                    index: 0,
+                })
+                .collect()
+        }
+    }
+
+    /// Finds the locals holding values built from user enum variants.
+    fn classify_moves(&mut self) {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in &self.body.blocks {
+                for statement in &block.statements {
+                    if let StatementKind::Assign(place, rvalue) = &statement.kind {
+                        if place.projection.is_empty()
+                            && !self.move_only[place.local.0]
+                            && self.is_move_only(rvalue)
+                        {
+                            self.move_only[place.local.0] = true;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_move_only(&self, rvalue: &Rvalue) -> bool {
+        let operand_move_only = |operand: &Operand| match operand {
+            Operand::Place(place) => place.projection.is_empty() && self.move_only[place.local.0],
+            Operand::Constant(_) => false,
+        };
+        match rvalue {
+            // `Option` and `Result` are `Copy` when their data is.
+            Rvalue::Variant(variant, operands) => {
+                (variant.enum_ != "Option" && variant.enum_ != "Result")
+                    || operands.iter().any(operand_move_only)
+            }
+            Rvalue::Tuple(operands) => operands.iter().any(operand_move_only),
+            Rvalue::Use(operand) => operand_move_only(operand),
+            _ => false,
+        }
+    }
+
+    /// The locals read at `location` and the local it overwrites.
+    fn uses_and_def(&self, location: Location) -> (Vec<Local>, Option<Local>) {
+        let block = &self.body.blocks[location.block];
+        let mut uses = Vec::new();
+        let mut def = None;
+        match block.statements.get(location.index) {
+            Some(statement) => match &statement.kind {
+                StatementKind::Assign(place, rvalue) => {
+                    for (place, _) in self.rvalue_accesses(rvalue) {
+                        uses.push(place.local);
+                    }
+                    if place.projection.is_empty() {
+                        def = Some(place.local);
+                    } else if place.projection.contains(&Projection::Deref) {
+                        uses.push(place.local);
+                    }
+                }
+                StatementKind::StorageDead(local) => def = Some(*local),
+                // Nothing has drop glue so drops don't use borrows.
+                StatementKind::StorageLive(_) | StatementKind::Drop { .. } | StatementKind::Nop => {
+                }
+            },
+            None => match &block.terminator.kind {
+                TerminatorKind::SwitchInt { operand, .. }
+                | TerminatorKind::Assert {
+                    condition: operand, ..
+                } => {
+                    if let Operand::Place(place) = operand {
+                        uses.push(place.local);
+                    }
+                }
+                TerminatorKind::Call {
+                    arguments,
+                    destination,
+                    ..
+                } => {
+                    for argument in arguments {
+                        if let Operand::Place(place) = argument {
+                            uses.push(place.local);
+                        }
+                    }
+                    if destination.projection.is_empty() {
+                        def = Some(destination.local);
+                    } else if destination.projection.contains(&Projection::Deref) {
+                        uses.push(destination.local);
+                    }
+                }
+                TerminatorKind::Return => uses.push(RETURN_PLACE),
+                TerminatorKind::Goto(_) | TerminatorKind::Unreachable => {}
+            },
+        }
+        (uses, def)
+    }
+
+    /// The places `rvalue` reads and how.
+    fn rvalue_accesses<'b>(&self, rvalue: &'b Rvalue) -> Vec<(&'b Place, Access)> {
+        let mut accesses = Vec::new();
+        let mut operand = |operand: &'b Operand| {
+            if let Operand::Place(place) = operand {
+                accesses.push((place, self.operand_access(place)));
+            }
+        };
+        match rvalue {
+            Rvalue::Use(o) | Rvalue::Cast(o, _) => operand(o),
+            Rvalue::Binary(_, left, right) | Rvalue::Overflows(_, _, left, right) => {
+                operand(left);
+                operand(right);
+            }
+            Rvalue::Tuple(operands) | Rvalue::Variant(_, operands) => {
+                operands.iter().for_each(operand)
+            }
+            Rvalue::Ref(place) | Rvalue::RawConst(place) => accesses.push((place, Access::Borrow)),
+            Rvalue::RefMut(place) | Rvalue::RawMut(place) => {
+                accesses.push((place, Access::BorrowMut))
+            }
+            Rvalue::Discriminant(place) => accesses.push((place, Access::Read)),
+        }
+        accesses
+    }
+
+    fn operand_access(&self, place: &Place) -> Access {
+        if place.projection.is_empty() && self.move_only[place.local.0] {
+            Access::Move
+        } else {
+            Access::Read
+        }
+    }
+
+    /// Computes which locals are live before every location by iterating
+    /// backwards to a fixed point.
+    fn compute_liveness(&mut self) {
+        let locals = self.body.locals.len();
+        self.live = self
+            .body
+            .blocks
+            .iter()
+            .map(|block| vec![vec![false; locals]; block.statements.len() + 1])
+            .collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in (0..self.body.blocks.len()).rev() {
+                let mut live = vec![false; locals];
+                for successor in self.body.blocks[block].terminator.kind.successors() {
+                    for (local, is_live) in self.live[successor.0][0].iter().enumerate() {
+                        live[local] |= is_live;
+                    }
+                }
+                for index in (0..=self.body.blocks[block].statements.len()).rev() {
+                    let (uses, def) = self.uses_and_def(Location { block, index });
+                    if let Some(def) = def {
+                        live[def.0] = false;
+                    }
+                    for local in uses {
+                        live[local.0] = true;
+                    }
+                    if self.live[block][index] != live {
+                        self.live[block][index] = live.clone();
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+
+    fn loans_of_operand(&self, holds: &Holds, operand: &Operand) -> BTreeSet<usize> {
+        match operand {
+            Operand::Place(place) => holds[place.local.0].clone(),
+            Operand::Constant(_) => BTreeSet::new(),
+        }
+    }
+
+    /// Updates the loans each local holds after `location`.
+    fn transfer_holds(&self, holds: &mut Holds, location: Location) {
+        let block = &self.body.blocks[location.block];
+        let (place, loans) = match block.statements.get(location.index) {
+            Some(statement) => match &statement.kind {
+                StatementKind::Assign(place, rvalue) => {
+                    let mut loans = BTreeSet::new();
+                    match rvalue {
+                        Rvalue::Use(operand) | Rvalue::Cast(operand, _) => {
+                            loans = self.loans_of_operand(holds, operand)
+                        }
+                        Rvalue::Tuple(operands) | Rvalue::Variant(_, operands) => {
+                            for operand in operands {
+                                loans.extend(self.loans_of_operand(holds, operand));
+                            }
+                        }
+                        Rvalue::Ref(borrowed)
+                        | Rvalue::RefMut(borrowed)
+                        | Rvalue::RawConst(borrowed)
+                        | Rvalue::RawMut(borrowed) => {
+                            // Reborrows keep the original loans alive.
+                            if borrowed.projection.contains(&Projection::Deref) {
+                                loans = holds[borrowed.local.0].clone();
+                            }
+                            if let Some(loan) = self.loan_at.get(&location) {
+                                loans.insert(*loan);
+                            }
+                        }
+                        Rvalue::Binary(..) | Rvalue::Overflows(..) | Rvalue::Discriminant(_) => {}
+                    }
+                    (place, loans)
+                }
+                StatementKind::StorageDead(local) => {
+                    holds[local.0].clear();
+                    return;
+                }
+                StatementKind::StorageLive(_) | StatementKind::Drop { .. } | StatementKind::Nop => {
+                    return
+                }
+            },
+            None => match &block.terminator.kind {
+                TerminatorKind::Call {
+                    function,
+                    arguments,
+                    destination,
+                    ..
+                } => {
+                    let mut loans = BTreeSet::new();
+                    let returns_reference = match function {
+                        Operand::Constant(Constant::Function(name)) => self
+                            .returns_reference
+                            .get(name.as_str())
+                            .copied()
+                            .unwrap_or(false),
+                        _ => true,
+                    };
+                    if returns_reference {
+                        for argument in arguments {
+                            loans.extend(self.loans_of_operand(holds, argument));
+                        }
+                    }
+                    (destination, loans)
+                }
+                _ => return,
+            },
+        };
+        if place.projection.is_empty() {
+            holds[place.local.0] = loans;
+        } else {
+            holds[place.local.0].extend(loans);
+        }
+    }
+
+    /// The places read at `location` and the place written.
+    fn accesses(&self, location: Location) -> (Vec<(&'a Place, Access)>, Option<&'a Place>) {
+        let block = &self.body.blocks[location.block];
+        match block.statements.get(location.index) {
+            Some(statement) => match &statement.kind {
+                StatementKind::Assign(place, rvalue) => (self.rvalue_accesses(rvalue), Some(place)),
+                _ => (Vec::new(), None),
+            },
+            None => match &block.terminator.kind {
+                TerminatorKind::SwitchInt {
+                    operand: Operand::Place(place),
+                    ..
+                }
+                | TerminatorKind::Assert {
+                    condition: Operand::Place(place),
+                    ..
+                } => (vec![(place, Access::Read)], None),
+                TerminatorKind::Call {
+                    arguments,
+                    destination,
+                    ..
+                } => {
+                    let accesses = arguments
+                        .iter()
+                        .filter_map(|argument| match argument {
+                            Operand::Place(place) => Some((place, self.operand_access(place))),
+                            Operand::Constant(_) => None,
+                        })
+                        .collect();
+                    (accesses, Some(destination))
+                }
+                _ => (Vec::new(), None),
+            },
+        }
+    }
+
+    /// Runs the loan analysis to a fixed point and returns the loans held at
+    /// the start of each block.
+    fn forward_states(&self) -> Vec<Option<Holds>> {
+        let mut states: Vec<Option<Holds>> = vec![None; self.body.blocks.len()];
+        states[START_BLOCK.0] = Some(vec![BTreeSet::new(); self.body.locals.len()]);
+        let mut worklist = VecDeque::new();
+        worklist.push_back(START_BLOCK.0);
+        while let Some(block) = worklist.pop_front() {
+            let mut holds = states[block].clone().unwrap();
+            for index in 0..=self.body.blocks[block].statements.len() {
+                self.transfer_holds(&mut holds, Location { block, index });
+            }
+            for successor in self.body.blocks[block].terminator.kind.successors() {
+                let changed = match &mut states[successor.0] {
+                    Some(successor_holds) => {
+                        let mut changed = false;
+                        for (into, from) in successor_holds.iter_mut().zip(&holds) {
+                            let len = into.len();
+                            into.extend(from.iter().copied());
+                            changed |= into.len() != len;
+                        }
+                        changed
+                    }
+                    state => {
+                        *state = Some(holds.clone());
+                        true
+                    }
+                };
+                if changed && !worklist.contains(&successor.0) {
+                    worklist.push_back(successor.0);
+                }
+            }
+        }
+        states
+    }
+
+    fn check(&self, errors: &mut Vec<Error>) {
+        let states = self.forward_states();
+        for (block, state) in states.into_iter().enumerate() {
+            let mut holds = match state {
+                Some(holds) => holds,
+                None => continue,
+            };
+            for index in 0..=self.body.blocks[block].statements.len() {
+                let location = Location { block, index };
+                self.check_location(errors, &holds, location);
+                self.transfer_holds(&mut holds, location);
+            }
+        }
+    }
+
+    fn check_location(&self, errors: &mut Vec<Error>, holds: &Holds, location: Location) {
+        let span = self.location_span(location);
+        let live = &self.live[location.block][location.index];
+        let (mut accesses, destination) = self.accesses(location);
+        if let Some(destination) = destination {
+            accesses.push((destination, Access::Write));
+        }
+        let created = self.loan_at.get(&location);
+        for (place, access) in accesses {
+            for (loan_index, loan) in self.loans.iter().enumerate() {
+                if Some(&loan_index) == created || !overlaps(place, &loan.place) {
+                    continue;
+                }
+                let holders = self.holders(holds, live, loan_index);
+                if holders.is_empty() {
+                    continue;
+                }
+                let later = self.later_use(location, &holders, true);
+                let error = match access {
+                    Access::Read | Access::Borrow if loan.mutable => {
+                        if access == Access::Read {
+                            Error::UseWhileMutablyBorrowed(span, loan.span, later)
+                        } else {
+                            Error::ConflictingBorrow(span, loan.span, later)
+                        }
+                    }
+                    Access::Read | Access::Borrow => continue,
+                    Access::BorrowMut => Error::ConflictingBorrow(span, loan.span, later),
+                    Access::Write => Error::AssignToBorrowed(span, loan.span, later),
+                    Access::Move => Error::MoveWhileBorrowed(span, loan.span, later),
+                };
+                push(errors, error);
+            }
+        }
+
+        let block = &self.body.blocks[location.block];
+        match block.statements.get(location.index) {
+            Some(Statement {
+                kind: StatementKind::StorageDead(local),
+                span,
+            }) => {
+                for (loan_index, loan) in self.loans.iter().enumerate() {
+                    if loan.place.local != *local
+                        || loan.place.projection.contains(&Projection::Deref)
+                    {
+                        continue;
+                    }
+                    // Returned references are reported when returning.
+                    let holders: Vec<Local> = self
+                        .holders(holds, live, loan_index)
+                        .into_iter()
+                        .filter(|holder| *holder != RETURN_PLACE)
+                        .collect();
+                    if !holders.is_empty() {
+                        let later = self.later_use(location, &holders, false);
+                        push(
+                            errors,
+                            Error::DoesNotLiveLongEnough(loan.span, end_of(*span), later),
+                        );
+                    }
+                }
+            }
+            None if block.terminator.kind == TerminatorKind::Return => {
+                for loan in &holds[RETURN_PLACE.0] {
+                    let loan = &self.loans[*loan];
+                    if !loan.place.projection.contains(&Projection::Deref) {
+                        let declaration = self.body.local(loan.place.local).span;
+                        push(
+                            errors,
+                            Error::ReturnsReferenceToLocal(loan.span, declaration),
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The live locals holding `loan`.
+    fn holders(&self, holds: &Holds, live: &[bool], loan: usize) -> Vec<Local> {
+        (0..self.body.locals.len())
+            .filter(|local| live[*local] && holds[*local].contains(&loan))
+            .map(Local)
+            .collect()
+    }
+
+    /// The span of the first location reachable from `location` that uses
+    /// one of `holders`.
+    fn later_use(&self, location: Location, holders: &[Local], inclusive: bool) -> Span {
+        let mut visited = Vec::new();
+        let mut queue = VecDeque::new();
+        if inclusive {
+            queue.push_back(location);
+        } else {
+            queue.extend(self.successors(location));
+        }
+        while let Some(location) = queue.pop_front() {
+            if visited.contains(&location) {
+                continue;
+            }
+            visited.push(location);
+            let (uses, _) = self.uses_and_def(location);
+            if uses.iter().any(|local| holders.contains(local)) {
+                return self.location_span(location);
+            }
+            queue.extend(self.successors(location));
+        }
+        self.location_span(location)
+    }
+}
+
+/// Whether accessing one place can access the other.
+fn overlaps(a: &Place, b: &Place) -> bool {
+    a.local == b.local && a.projection.iter().zip(&b.projection).all(|(a, b)| a == b)
+}
+
+/// The last character of `span`, where a scope ends.
+fn end_of(span: Span) -> Span {
+    Span {
+        file: span.file,
+        start: span.end.saturating_sub(1).max(span.start),
+        end: span.end,
+        expn: span.expn,
+    }
+}
+
+fn push(errors: &mut Vec<Error>, error: Error) {
+    if !errors.contains(&error) {
+        errors.push(error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::lower_str;
+    use assert_matches::assert_matches;
+
+    fn check_str(s: &str) -> Vec<Error> {
+        check(&lower_str(s))
+    }
+
+    #[test]
+    fn test_borrows_end_at_last_use() {
+        assert_eq!(
+            check_str(
+                "fn main() {
+                     let mut x = 1;
+                     let r = &mut x;
+                     *r = 2;
+                     x = 3;
+                     let a = &x;
+                     let b = &x;
+                     let y = *a + *b;
+                 }"
+            ),
+            []
+        );
+    }
+
+    #[test]
+    fn test_assign_to_borrowed() {
+        let s = "fn main() { let mut x = 1; let r = &x; x = 2; let y = *r; }";
+        let errors = check_str(s);
+        assert_eq!(errors.len(), 1);
+        assert_matches!(errors[0], Error::AssignToBorrowed(assign, borrow, later)
+            if &s[assign] == "x = 2" && &s[borrow] == "&x" && &s[later] == "*r");
+    }
+
+    #[test]
+    fn test_conflicting_mutable_borrows() {
+        let s = "fn main() { let mut x = 1; let a = &mut x; let b = &mut x; *a = 2; }";
+        let errors = check_str(s);
+        assert_eq!(errors.len(), 1);
+        assert_matches!(errors[0], Error::ConflictingBorrow(new, old, later)
+            if &s[new] == "&mut x" && old.start < new.start && &s[later] == "*a = 2");
+    }
+
+    #[test]
+    fn test_use_while_mutably_borrowed() {
+        let s = "fn main() { let mut x = 1; let a = &mut x; let y = x; *a = 2; }";
+        assert_matches!(check_str(s)[..], [Error::UseWhileMutablyBorrowed(..)]);
+    }
+
+    #[test]
+    fn test_move_while_borrowed() {
+        let s = "enum E { A }
+                 fn take(e: E) {}
+                 fn main() { let e = A; let r = &e; take(e); let f = r; }";
+        assert_matches!(check_str(s)[..], [Error::MoveWhileBorrowed(moved, borrow, later)]
+            if &s[moved] == "take(e)" && &s[borrow] == "&e" && &s[later] == "r");
+        assert_eq!(
+            check_str("fn main() { let a = (1, Some(2)); let r = &a; let b = a; let c = r; }"),
+            []
+        );
+    }
+
+    #[test]
+    fn test_does_not_live_long_enough() {
+        let s = "fn id(p: &i32) -> &i32 { p }
+                 fn main() {
+                     let q;
+                     { let y = 5; q = id(&y); }
+                     let z = *q;
+                 }";
+        let errors = check_str(s);
+        assert_eq!(errors.len(), 1);
+        assert_matches!(errors[0], Error::DoesNotLiveLongEnough(borrow, dropped, later)
+            if &s[borrow] == "&y" && &s[dropped] == "}" && &s[later] == "*q");
+    }
+
+    #[test]
+    fn test_returns_reference_to_local() {
+        let s = "fn f(p: &i32) -> &i32 { let x = *p; if *p == 0 { p } else { &x } }";
+        let errors = check_str(s);
+        assert_eq!(errors.len(), 1);
+        assert_matches!(errors[0], Error::ReturnsReferenceToLocal(borrow, _) if &s[borrow] == "&x");
+    }
+}
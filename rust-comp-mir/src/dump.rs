@@ -0,0 +1,201 @@
+use super::*;
+use std::fmt;
+
+impl fmt::Display for Body {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "fn {}(", self.name)?;
+        for (i, argument) in self.arguments().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", argument)?;
+        }
+        writeln!(f, ") {{")?;
+        for (i, local) in self.locals.iter().enumerate() {
+            write!(
+                f,
+                "    let {}{}",
+                if local.mutable { "mut " } else { "" },
+                Local(i)
+            )?;
+            match local.integer {
+                Some(integer) => write!(f, ": {};", integer)?,
+                None => write!(f, ";")?,
+            }
+            match &local.name {
+                Some(name) => writeln!(f, " // {}", name)?,
+                None => writeln!(f)?,
+            }
+        }
+        for (i, block) in self.blocks.iter().enumerate() {
+            writeln!(f)?;
+            writeln!(f, "    {}: {{", BasicBlock(i))?;
+            for statement in &block.statements {
+                if statement.kind != StatementKind::Nop {
+                    writeln!(f, "        {};", statement.kind)?;
+                }
+            }
+            writeln!(f, "        {};", block.terminator.kind)?;
+            writeln!(f, "    }}")?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl fmt::Display for Local {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "_{}", self.0)
+    }
+}
+
+impl fmt::Display for BasicBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+
+impl fmt::Display for IntegerType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", if self.signed { "i" } else { "u" }, self.bits)
+    }
+}
+
+impl fmt::Display for StatementKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StatementKind::Assign(place, rvalue) => write!(f, "{} = {}", place, rvalue),
+            StatementKind::StorageLive(local) => write!(f, "StorageLive({})", local),
+            StatementKind::StorageDead(local) => write!(f, "StorageDead({})", local),
+            StatementKind::Drop { place } => write!(f, "drop({})", place),
+            StatementKind::Nop => write!(f, "nop"),
+        }
+    }
+}
+
+impl fmt::Display for Place {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut place = self.local.to_string();
+        for projection in &self.projection {
+            place = match projection {
+                Projection::Deref => format!("(*{})", place),
+                Projection::Field(name) => format!("{}.{}", place, name),
+                Projection::Downcast(name, _) => format!("({} as {})", place, name),
+            };
+        }
+        write!(f, "{}", place)
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Place(place) => write!(f, "{}", place),
+            Operand::Constant(constant) => write!(f, "const {}", constant),
+        }
+    }
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Constant::Bool(b) => write!(f, "{}", b),
+            Constant::Integer(i) => write!(f, "{}", i),
+            Constant::Unit => write!(f, "()"),
+            Constant::Function(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl fmt::Display for Rvalue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rvalue::Use(operand) => write!(f, "{}", operand),
+            Rvalue::Binary(op, left, right) => write!(f, "{:?}({}, {})", op, left, right),
+            Rvalue::Ref(place) => write!(f, "&{}", place),
+            Rvalue::RefMut(place) => write!(f, "&mut {}", place),
+            Rvalue::RawConst(place) => write!(f, "&raw const {}", place),
+            Rvalue::RawMut(place) => write!(f, "&raw mut {}", place),
+            Rvalue::Cast(operand, type_) => write!(f, "{} as {}", operand, type_),
+            Rvalue::Tuple(operands) => {
+                write!(f, "(")?;
+                write_operands(f, operands)?;
+                if operands.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            Rvalue::Variant(variant, operands) => {
+                write!(f, "{}::{}", variant.enum_, variant.name)?;
+                if !operands.is_empty() {
+                    write!(f, "(")?;
+                    write_operands(f, operands)?;
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+            Rvalue::Discriminant(place) => write!(f, "discriminant({})", place),
+            Rvalue::Overflows(op, integer, left, right) => {
+                write!(f, "Overflows{:?}::<{}>({}, {})", op, integer, left, right)
+            }
+        }
+    }
+}
+
+impl fmt::Display for TerminatorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TerminatorKind::Goto(target) => write!(f, "goto -> {}", target),
+            TerminatorKind::SwitchInt {
+                operand,
+                targets,
+                otherwise,
+            } => {
+                write!(f, "switchInt({}) -> [", operand)?;
+                for (value, target) in targets {
+                    write!(f, "{}: {}, ", value, target)?;
+                }
+                write!(f, "otherwise: {}]", otherwise)
+            }
+            TerminatorKind::Call {
+                function,
+                arguments,
+                destination,
+                target,
+            } => {
+                match function {
+                    Operand::Constant(Constant::Function(name)) => {
+                        write!(f, "{} = {}(", destination, name)?
+                    }
+                    _ => write!(f, "{} = {}(", destination, function)?,
+                }
+                write_operands(f, arguments)?;
+                write!(f, ") -> {}", target)
+            }
+            TerminatorKind::Assert {
+                condition,
+                expected,
+                message,
+                target,
+            } => {
+                let not = if *expected { "" } else { "!" };
+                write!(
+                    f,
+                    "assert({}{}, {:?}) -> {}",
+                    not, condition, message, target
+                )
+            }
+            TerminatorKind::Return => write!(f, "return"),
+            TerminatorKind::Unreachable => write!(f, "unreachable"),
+        }
+    }
+}
+
+fn write_operands(f: &mut fmt::Formatter, operands: &[Operand]) -> fmt::Result {
+    for (i, operand) in operands.iter().enumerate() {
+        if i != 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", operand)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,393 @@
+use rust_comp_core::pos::Span;
+
+pub mod borrowck;
+mod dump;
+pub mod lower;
+pub mod transform;
+
+#[cfg(test)]
+mod test;
+
+/// A function lowered to a control-flow graph of basic blocks operating on
+/// numbered locals.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Body {
+    pub name: String,
+    pub span: Span,
+    /// Whether the function is declared `pub`.
+    pub public: bool,
+    /// Whether the return type contains a reference, which may borrow from
+    /// the arguments.
+    pub returns_reference: bool,
+    /// `_0` is the return place and `_1` through `_arg_count` are the
+    /// parameters.
+    pub locals: Vec<LocalDecl>,
+    pub arg_count: usize,
+    /// Execution starts at `bb0`.
+    pub blocks: Vec<BasicBlockData>,
+}
+
+pub const RETURN_PLACE: Local = Local(0);
+pub const START_BLOCK: BasicBlock = BasicBlock(0);
+
+impl Body {
+    pub fn block(&self, block: BasicBlock) -> &BasicBlockData {
+        &self.blocks[block.0]
+    }
+
+    pub fn block_mut(&mut self, block: BasicBlock) -> &mut BasicBlockData {
+        &mut self.blocks[block.0]
+    }
+
+    pub fn local(&self, local: Local) -> &LocalDecl {
+        &self.locals[local.0]
+    }
+
+    pub fn arguments(&self) -> impl Iterator<Item = Local> {
+        (1..=self.arg_count).map(Local)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Local(pub usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BasicBlock(pub usize);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LocalDecl {
+    pub span: Span,
+    /// The name of the binding or `None` for temporaries.
+    pub name: Option<String>,
+    pub mutable: bool,
+    /// The local's type if it's an integer whose type is known.  Other
+    /// integers are treated as `i64`.
+    pub integer: Option<IntegerType>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IntegerType {
+    pub signed: bool,
+    pub bits: u32,
+}
+
+impl IntegerType {
+    pub const I64: IntegerType = IntegerType {
+        signed: true,
+        bits: 64,
+    };
+
+    /// Parses a primitive integer type's name.  `isize` and `usize` are 64
+    /// bits.
+    pub fn from_name(name: &str) -> Option<IntegerType> {
+        let signed = match name.as_bytes().first() {
+            Some(b'i') => true,
+            Some(b'u') => false,
+            _ => return None,
+        };
+        let bits = match &name[1..] {
+            "8" => 8,
+            "16" => 16,
+            "32" => 32,
+            "64" | "size" => 64,
+            _ => return None,
+        };
+        Some(IntegerType { signed, bits })
+    }
+
+    pub fn min(self) -> i128 {
+        if self.signed {
+            -(1 << (self.bits - 1))
+        } else {
+            0
+        }
+    }
+
+    pub fn max(self) -> i128 {
+        if self.signed {
+            (1 << (self.bits - 1)) - 1
+        } else {
+            (1 << self.bits) - 1
+        }
+    }
+
+    /// Truncates `value` to the type's width and sign or zero extends it.
+    pub fn wrap(self, value: i128) -> i128 {
+        let shift = 128 - self.bits;
+        if self.signed {
+            (value << shift) >> shift
+        } else {
+            ((value as u128) << shift >> shift) as i128
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BasicBlockData {
+    pub statements: Vec<Statement>,
+    pub terminator: Terminator,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Statement {
+    pub span: Span,
+    pub kind: StatementKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StatementKind {
+    Assign(Place, Rvalue),
+    /// The local's binding comes into scope.
+    StorageLive(Local),
+    /// The local's binding goes out of scope.
+    StorageDead(Local),
+    /// The value in the place is dropped.  Bindings that are only moved
+    /// out of on some paths are guarded by a drop flag local that is tested
+    /// before the drop.
+    Drop {
+        place: Place,
+    },
+    Nop,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Place {
+    pub local: Local,
+    pub projection: Vec<Projection>,
+}
+
+impl Place {
+    pub fn project(&self, projection: Projection) -> Place {
+        let mut place = self.clone();
+        place.projection.push(projection);
+        place
+    }
+}
+
+impl From<Local> for Place {
+    fn from(local: Local) -> Place {
+        Place {
+            local,
+            projection: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Projection {
+    Deref,
+    /// A named field of a struct or the index of a tuple field.
+    Field(String),
+    /// The data of an enum variant.  Holds the variant's name and
+    /// discriminant.
+    Downcast(String, u128),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Operand {
+    Place(Place),
+    Constant(Constant),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Constant {
+    Bool(bool),
+    Integer(u128),
+    Unit,
+    Function(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Rvalue {
+    Use(Operand),
+    Binary(BinaryOp, Operand, Operand),
+    Ref(Place),
+    RefMut(Place),
+    RawConst(Place),
+    RawMut(Place),
+    /// The operand and the name of the type to cast to.
+    Cast(Operand, String),
+    Tuple(Vec<Operand>),
+    Variant(Variant, Vec<Operand>),
+    Discriminant(Place),
+    /// Whether `Binary` with the same operands overflows the integer type.
+    /// Division overflows when dividing the minimum value by `-1`.
+    Overflows(BinaryOp, IntegerType, Operand, Operand),
+}
+
+impl Rvalue {
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Rvalue::Use(operand) | Rvalue::Cast(operand, _) => vec![operand],
+            Rvalue::Binary(_, left, right) | Rvalue::Overflows(_, _, left, right) => {
+                vec![left, right]
+            }
+            Rvalue::Tuple(operands) | Rvalue::Variant(_, operands) => operands.iter().collect(),
+            Rvalue::Ref(_)
+            | Rvalue::RefMut(_)
+            | Rvalue::RawConst(_)
+            | Rvalue::RawMut(_)
+            | Rvalue::Discriminant(_) => vec![],
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Rvalue::Use(operand) | Rvalue::Cast(operand, _) => vec![operand],
+            Rvalue::Binary(_, left, right) | Rvalue::Overflows(_, _, left, right) => {
+                vec![left, right]
+            }
+            Rvalue::Tuple(operands) | Rvalue::Variant(_, operands) => operands.iter_mut().collect(),
+            Rvalue::Ref(_)
+            | Rvalue::RefMut(_)
+            | Rvalue::RawConst(_)
+            | Rvalue::RawMut(_)
+            | Rvalue::Discriminant(_) => vec![],
+        }
+    }
+
+    /// The place borrowed or read without an operand.
+    pub fn place(&self) -> Option<&Place> {
+        match self {
+            Rvalue::Ref(place)
+            | Rvalue::RefMut(place)
+            | Rvalue::RawConst(place)
+            | Rvalue::RawMut(place)
+            | Rvalue::Discriminant(place) => Some(place),
+            _ => None,
+        }
+    }
+
+    pub fn place_mut(&mut self) -> Option<&mut Place> {
+        match self {
+            Rvalue::Ref(place)
+            | Rvalue::RefMut(place)
+            | Rvalue::RawConst(place)
+            | Rvalue::RawMut(place)
+            | Rvalue::Discriminant(place) => Some(place),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Variant {
+    pub enum_: String,
+    pub name: String,
+    pub discriminant: u128,
+}
+
+/// `&&` and `||` are lowered to branches.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    BitAnd,
+    BitOr,
+    Eq,
+    Ne,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Terminator {
+    pub span: Span,
+    pub kind: TerminatorKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TerminatorKind {
+    Goto(BasicBlock),
+    /// Jumps to the target whose value is equal to the operand.  `false` is
+    /// `0` and `true` is `1`.
+    SwitchInt {
+        operand: Operand,
+        targets: Vec<(u128, BasicBlock)>,
+        otherwise: BasicBlock,
+    },
+    Call {
+        function: Operand,
+        arguments: Vec<Operand>,
+        destination: Place,
+        target: BasicBlock,
+    },
+    /// Continues to `target` if the operand is `expected` and panics with
+    /// `message` otherwise.
+    Assert {
+        condition: Operand,
+        expected: bool,
+        message: String,
+        target: BasicBlock,
+    },
+    Return,
+    Unreachable,
+}
+
+impl TerminatorKind {
+    pub fn successors(&self) -> Vec<BasicBlock> {
+        match self {
+            TerminatorKind::Goto(target)
+            | TerminatorKind::Call { target, .. }
+            | TerminatorKind::Assert { target, .. } => vec![*target],
+            TerminatorKind::SwitchInt {
+                targets, otherwise, ..
+            } => targets
+                .iter()
+                .map(|(_, target)| *target)
+                .chain(Some(*otherwise))
+                .collect(),
+            TerminatorKind::Return | TerminatorKind::Unreachable => vec![],
+        }
+    }
+
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            TerminatorKind::SwitchInt { operand, .. }
+            | TerminatorKind::Assert {
+                condition: operand, ..
+            } => vec![operand],
+            TerminatorKind::Call {
+                function,
+                arguments,
+                ..
+            } => Some(function).into_iter().chain(arguments).collect(),
+            TerminatorKind::Goto(_) | TerminatorKind::Return | TerminatorKind::Unreachable => {
+                vec![]
+            }
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            TerminatorKind::SwitchInt { operand, .. }
+            | TerminatorKind::Assert {
+                condition: operand, ..
+            } => vec![operand],
+            TerminatorKind::Call {
+                function,
+                arguments,
+                ..
+            } => Some(function).into_iter().chain(arguments).collect(),
+            TerminatorKind::Goto(_) | TerminatorKind::Return | TerminatorKind::Unreachable => {
+                vec![]
+            }
+        }
+    }
+
+    pub fn successors_mut(&mut self) -> Vec<&mut BasicBlock> {
+        match self {
+            TerminatorKind::Goto(target)
+            | TerminatorKind::Call { target, .. }
+            | TerminatorKind::Assert { target, .. } => vec![target],
+            TerminatorKind::SwitchInt {
+                targets, otherwise, ..
+            } => targets
+                .iter_mut()
+                .map(|(_, target)| target)
+                .chain(Some(otherwise))
+                .collect(),
+            TerminatorKind::Return | TerminatorKind::Unreachable => vec![],
+        }
+    }
+}
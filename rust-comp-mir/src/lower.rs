@@ -0,0 +1,1579 @@
+use super::*;
+use rust_comp_check::integers::{self, Integers};
+use rust_comp_check::moves::{self, Drop, Move};
+use rust_comp_core::diagnostic::Diagnostic;
+use rust_comp_core::integer;
+use rust_comp_core::profile::Profile;
+use rust_comp_syntax as syntax;
+use rust_comp_syntax::{ExpressionKind, PatternKind, SymbolId};
+use std::collections::HashMap;
+
+/// Lowers every function in `top_levels`.  `?` must have been desugared.
+/// Arithmetic is checked as `profile` requires.  Bindings are dropped where
+/// `moves::analyze_moves` says they are.
+pub fn lower(
+    diagnostic: &Diagnostic,
+    profile: Profile,
+    top_levels: &[syntax::TopLevel],
+) -> Vec<Body> {
+    let items = Items::new(diagnostic, profile, top_levels);
+    top_levels
+        .iter()
+        .filter_map(|top_level| match &top_level.kind {
+            syntax::TopLevelKind::Function(function) => {
+                Some(lower_function(&items, top_level, function))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The items names can refer to other than locals.
+struct Items<'a> {
+    diagnostic: &'a Diagnostic,
+    profile: Profile,
+    drops: Vec<Drop>,
+    moves: Vec<Move>,
+    integers: Integers,
+    functions: HashMap<SymbolId, String>,
+    /// The return types of functions that return integers.
+    returns: HashMap<SymbolId, IntegerType>,
+    /// Enum variants and whether they have data.
+    variants: HashMap<SymbolId, (Variant, bool)>,
+}
+
+impl<'a> Items<'a> {
+    fn new(diagnostic: &'a Diagnostic, profile: Profile, top_levels: &[syntax::TopLevel]) -> Self {
+        let moves = moves::analyze_moves(top_levels);
+        let mut items = Items {
+            diagnostic,
+            profile,
+            drops: moves.drops,
+            moves: moves.moves,
+            integers: integers::infer_integers(diagnostic, top_levels),
+            functions: HashMap::new(),
+            returns: HashMap::new(),
+            variants: HashMap::new(),
+        };
+        // `Option` and `Result` are used without being declared until there
+        // is a standard library.
+        items.add_prelude_enum("Option", &[("None", false), ("Some", true)]);
+        items.add_prelude_enum("Result", &[("Ok", true), ("Err", true)]);
+        for top_level in top_levels {
+            match &top_level.kind {
+                syntax::TopLevelKind::Function(function) => {
+                    let name = items.name(&function.name);
+                    items.functions.insert(function.name.id, name);
+                    if let Some(integer) = items.integer(&function.return_type) {
+                        items.returns.insert(function.name.id, integer);
+                    }
+                }
+                syntax::TopLevelKind::Enum(enum_) => {
+                    let enum_name = items.name(&enum_.name);
+                    for (discriminant, variant) in enum_.variants.iter().enumerate() {
+                        let has_data = variant.data != syntax::VariantData::None;
+                        let variant_ = Variant {
+                            enum_: enum_name.clone(),
+                            name: items.name(&variant.name),
+                            discriminant: discriminant as u128,
+                        };
+                        items.variants.insert(variant.name.id, (variant_, has_data));
+                    }
+                }
+                _ => (),
+            }
+        }
+        items
+    }
+
+    fn add_prelude_enum(&mut self, enum_: &str, variants: &[(&str, bool)]) {
+        for (discriminant, (name, has_data)) in variants.iter().enumerate() {
+            let variant = Variant {
+                enum_: enum_.to_string(),
+                name: name.to_string(),
+                discriminant: discriminant as u128,
+            };
+            self.variants
+                .insert(SymbolId::of_name(name), (variant, *has_data));
+        }
+    }
+
+    fn name(&self, symbol: &syntax::Symbol) -> String {
+        self.diagnostic.file_span(symbol.span).to_string()
+    }
+
+    fn integer(&self, type_: &syntax::Type) -> Option<IntegerType> {
+        match &type_.kind {
+            syntax::TypeKind::Named(name) => IntegerType::from_name(&self.name(name)),
+            _ => None,
+        }
+    }
+}
+
+fn lower_function(
+    items: &Items,
+    top_level: &syntax::TopLevel,
+    function: &syntax::Function,
+) -> Body {
+    let span = top_level.span;
+    let mut builder = Builder {
+        items,
+        locals: Vec::new(),
+        blocks: Vec::new(),
+        current: START_BLOCK,
+        bindings: Vec::new(),
+        storage: Vec::new(),
+        scopes: Vec::new(),
+        loops: Vec::new(),
+        flags: HashMap::new(),
+        arg_count: function.parameters.len(),
+    };
+    builder.new_local(function.return_type.span, None, true);
+    builder.locals[0].integer = items.integer(&function.return_type);
+    for parameter in &function.parameters {
+        let local = builder.new_local(parameter.span, Some(items.name(&parameter.name)), false);
+        builder.locals[local.0].integer = items.integer(&parameter.type_);
+        builder.bindings.push((parameter.name.id, local));
+    }
+    builder.current = builder.new_block();
+    // Flags come after the parameters so they are numbered first.
+    for i in 1..=builder.arg_count {
+        builder.add_flag(Local(i));
+        builder.set_flag(span, Local(i), true);
+    }
+    builder.block_into(RETURN_PLACE.into(), span, &function.body);
+    builder.terminate(span, TerminatorKind::Return);
+
+    Body {
+        name: items.name(&function.name),
+        span,
+        public: matches!(top_level.visibility, syntax::Visibility::Public(_)),
+        returns_reference: contains_reference(&function.return_type),
+        locals: builder.locals,
+        arg_count: function.parameters.len(),
+        blocks: builder
+            .blocks
+            .into_iter()
+            .map(|(statements, terminator)| BasicBlockData {
+                statements,
+                // Blocks are only left unterminated when nothing jumps to
+                // them.
+                terminator: terminator.unwrap_or(Terminator {
+                    span,
+                    kind: TerminatorKind::Unreachable,
+                }),
+            })
+            .collect(),
+    }
+}
+
+fn contains_reference(type_: &syntax::Type) -> bool {
+    match &type_.kind {
+        syntax::TypeKind::Ref(_) | syntax::TypeKind::RefMut(_) => true,
+        syntax::TypeKind::Tuple(types) => types.iter().any(contains_reference),
+        _ => false,
+    }
+}
+
+struct Loop {
+    break_block: BasicBlock,
+    continue_block: BasicBlock,
+    /// Where the value of `break` is stored.
+    destination: Place,
+    /// The length of `storage` when the loop was entered.
+    storage: usize,
+}
+
+struct Builder<'a> {
+    items: &'a Items<'a>,
+    locals: Vec<LocalDecl>,
+    blocks: Vec<(Vec<Statement>, Option<Terminator>)>,
+    /// The block statements are being added to.
+    current: BasicBlock,
+    /// Bindings in scope, innermost last.
+    bindings: Vec<(SymbolId, Local)>,
+    /// Locals that have been marked `StorageLive`, innermost last.
+    storage: Vec<Local>,
+    /// The lengths of `bindings` and `storage` when each scope was entered.
+    scopes: Vec<(usize, usize)>,
+    loops: Vec<Loop>,
+    /// The drop flags of bindings that are only moved out of on some paths.
+    /// A flag is set while its binding holds a value.
+    flags: HashMap<Local, Local>,
+    arg_count: usize,
+}
+
+impl<'a> Builder<'a> {
+    fn new_local(&mut self, span: Span, name: Option<String>, mutable: bool) -> Local {
+        self.locals.push(LocalDecl {
+            span,
+            name,
+            mutable,
+            integer: None,
+        });
+        Local(self.locals.len() - 1)
+    }
+
+    fn new_temp(&mut self, span: Span) -> Place {
+        self.new_local(span, None, true).into()
+    }
+
+    /// A temporary holding the value of `expression`.
+    fn new_temp_for(&mut self, expression: &syntax::Expression) -> Place {
+        let temp = self.new_temp(expression.span);
+        self.locals[temp.local.0].integer = self.integer_of(expression);
+        temp
+    }
+
+    /// The integer type of `expression` if its bindings, literals, casts,
+    /// or calls give it one.
+    fn integer_of(&self, expression: &syntax::Expression) -> Option<IntegerType> {
+        match &expression.kind {
+            ExpressionKind::Variable(symbol) => self
+                .lookup(symbol.id)
+                .and_then(|local| self.locals[local.0].integer),
+            ExpressionKind::Binary(binary) => match binary.op {
+                syntax::BinaryOp::Plus
+                | syntax::BinaryOp::Minus
+                | syntax::BinaryOp::Times
+                | syntax::BinaryOp::DividedBy
+                | syntax::BinaryOp::BitAnd
+                | syntax::BinaryOp::BitOr => self
+                    .integer_of(&binary.left)
+                    .or_else(|| self.integer_of(&binary.right)),
+                _ => None,
+            },
+            ExpressionKind::Value(syntax::Value::Integer(_)) => self
+                .items
+                .integers
+                .of(expression.span)
+                .and_then(IntegerType::from_name),
+            ExpressionKind::Cast(cast) => self.items.integer(&cast.type_),
+            ExpressionKind::FunctionCall(function_call) => match &function_call.function.kind {
+                ExpressionKind::Variable(symbol) if self.lookup(symbol.id).is_none() => {
+                    self.items.returns.get(&symbol.id).copied()
+                }
+                _ => None,
+            },
+            ExpressionKind::Block(block) => block
+                .expression
+                .as_ref()
+                .and_then(|expression| self.integer_of(expression)),
+            _ => None,
+        }
+    }
+
+    fn new_block(&mut self) -> BasicBlock {
+        self.blocks.push((Vec::new(), None));
+        BasicBlock(self.blocks.len() - 1)
+    }
+
+    fn push(&mut self, span: Span, kind: StatementKind) {
+        self.blocks[self.current.0].0.push(Statement { span, kind });
+    }
+
+    fn assign(&mut self, span: Span, place: Place, rvalue: Rvalue) {
+        self.push(span, StatementKind::Assign(place, rvalue));
+    }
+
+    fn assign_unit(&mut self, span: Span, place: Place) {
+        self.assign(span, place, Rvalue::Use(Operand::Constant(Constant::Unit)));
+    }
+
+    fn terminate(&mut self, span: Span, kind: TerminatorKind) {
+        let terminator = &mut self.blocks[self.current.0].1;
+        debug_assert!(terminator.is_none());
+        *terminator = Some(Terminator { span, kind });
+    }
+
+    fn goto(&mut self, span: Span, target: BasicBlock) {
+        self.terminate(span, TerminatorKind::Goto(target));
+    }
+
+    /// Terminates the current block and continues in a new block that
+    /// nothing jumps to.
+    fn diverge(&mut self, span: Span, kind: TerminatorKind) {
+        self.terminate(span, kind);
+        self.current = self.new_block();
+    }
+
+    /// Branches to `otherwise` if `operand` is `false`.
+    fn branch_if_false(&mut self, span: Span, operand: Operand, otherwise: BasicBlock) {
+        let then = self.new_block();
+        self.switch(span, operand, 0, otherwise, then);
+        self.current = then;
+    }
+
+    fn switch(
+        &mut self,
+        span: Span,
+        operand: Operand,
+        value: u128,
+        target: BasicBlock,
+        otherwise: BasicBlock,
+    ) {
+        self.terminate(
+            span,
+            TerminatorKind::SwitchInt {
+                operand,
+                targets: vec![(value, target)],
+                otherwise,
+            },
+        );
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push((self.bindings.len(), self.storage.len()));
+    }
+
+    fn exit_scope(&mut self, span: Span) {
+        let (bindings, storage) = self.scopes.pop().unwrap();
+        self.bindings.truncate(bindings);
+        self.storage_dead(span, storage);
+        self.storage.truncate(storage);
+    }
+
+    /// Drops and marks dead the locals in `storage[len..]` without removing
+    /// them.  Leaving the function with `len` of 0 also drops the arguments.
+    fn storage_dead(&mut self, span: Span, len: usize) {
+        for i in (len..self.storage.len()).rev() {
+            let local = self.storage[i];
+            self.drop(span, local);
+            self.push(span, StatementKind::StorageDead(local));
+        }
+        if len == 0 {
+            for i in (1..=self.arg_count).rev() {
+                self.drop(span, Local(i));
+            }
+        }
+    }
+
+    /// Drops `local` if its binding is dropped when leaving by `span`.
+    fn drop(&mut self, span: Span, local: Local) {
+        let binding = self.locals[local.0].span;
+        let drop = self
+            .items
+            .drops
+            .iter()
+            .find(|drop| drop.span == span && drop.binding == binding);
+        let flag = match drop {
+            Some(drop) if drop.flag => self.flags.get(&local).copied(),
+            Some(_) => None,
+            None => return,
+        };
+        if let Some(flag) = flag {
+            let dropping = self.new_block();
+            let next = self.new_block();
+            self.switch(span, Operand::Place(flag.into()), 0, next, dropping);
+            self.current = dropping;
+            self.push(
+                span,
+                StatementKind::Drop {
+                    place: local.into(),
+                },
+            );
+            self.goto(span, next);
+            self.current = next;
+        } else {
+            self.push(
+                span,
+                StatementKind::Drop {
+                    place: local.into(),
+                },
+            );
+        }
+    }
+
+    /// Gives `local` a drop flag if its binding is only moved out of on
+    /// some paths.
+    fn add_flag(&mut self, local: Local) {
+        let binding = self.locals[local.0].span;
+        let flagged = self
+            .items
+            .drops
+            .iter()
+            .any(|drop| drop.binding == binding && drop.flag);
+        if flagged {
+            let flag = self.new_local(binding, None, true);
+            self.flags.insert(local, flag);
+        }
+    }
+
+    /// Sets the drop flag of `local`, if it has one, to `value`.
+    fn set_flag(&mut self, span: Span, local: Local, value: bool) {
+        if let Some(&flag) = self.flags.get(&local) {
+            self.assign(
+                span,
+                flag.into(),
+                Rvalue::Use(Operand::Constant(Constant::Bool(value))),
+            );
+        }
+    }
+
+    /// Clears the drop flag of the binding `expression` names if it moves
+    /// out of it.
+    fn moved(&mut self, expression: &syntax::Expression) {
+        if let ExpressionKind::Variable(symbol) = &expression.kind {
+            let moved = self
+                .items
+                .moves
+                .iter()
+                .any(|move_| move_.span == expression.span);
+            if let (true, Some(local)) = (moved, self.lookup(symbol.id)) {
+                self.set_flag(expression.span, local, false);
+            }
+        }
+    }
+
+    /// Declares a local for the binding spelled at `span` without bringing
+    /// it into scope.  It has the integer type inferred for the binding.
+    fn declare(&mut self, span: Span, mutable: bool) -> Local {
+        // Bindings introduced by desugaring have the span of the expression
+        // they replace so they are left unnamed.
+        let name = self.items.diagnostic.file_span(span);
+        let name = if name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            Some(name.to_string())
+        } else {
+            None
+        };
+        let local = self.new_local(span, name, mutable);
+        self.locals[local.0].integer = self
+            .items
+            .integers
+            .of(span)
+            .and_then(IntegerType::from_name);
+        self.add_flag(local);
+        self.push(span, StatementKind::StorageLive(local));
+        self.storage.push(local);
+        self.set_flag(span, local, false);
+        local
+    }
+
+    fn bind(&mut self, span: Span, id: SymbolId, mutable: bool) -> Local {
+        let local = self.declare(span, mutable);
+        self.bindings.push((id, local));
+        local
+    }
+
+    fn lookup(&self, id: SymbolId) -> Option<Local> {
+        self.bindings
+            .iter()
+            .rev()
+            .find(|(binding, _)| *binding == id)
+            .map(|(_, local)| *local)
+    }
+
+    /// Lowers `block`.  `span` is the span of the expression the block is a
+    /// part of.
+    fn block_into(&mut self, destination: Place, span: Span, block: &syntax::Block) {
+        self.enter_scope();
+        for statement in &block.statements {
+            match &statement.kind {
+                syntax::StatementKind::Empty => (),
+                syntax::StatementKind::Expression(expression) => {
+                    let temp = self.new_temp_for(expression);
+                    self.expression_into(temp, expression);
+                }
+                syntax::StatementKind::Let(let_) => self.let_statement(let_),
+            }
+        }
+        match &block.expression {
+            Some(expression) => self.expression_into(destination, expression),
+            None => self.assign_unit(span, destination),
+        }
+        self.exit_scope(span);
+    }
+
+    fn let_statement(&mut self, let_: &syntax::Let) {
+        let value = match &let_.value {
+            Some(value) => value,
+            None => {
+                self.bind_pattern_uninit(&let_.pattern, let_.mutable);
+                return;
+            }
+        };
+        if let PatternKind::Named(id) = let_.pattern.kind {
+            if !self.items.variants.contains_key(&id) {
+                // Evaluate the value before the binding is in scope so
+                // `let x = x;` refers to the outer `x`.
+                let local = self.declare(let_.pattern.span, let_.mutable);
+                self.expression_into(local.into(), value);
+                self.set_flag(let_.pattern.span, local, true);
+                self.bindings.push((id, local));
+                return;
+            }
+        }
+        let place = self.place(value);
+        if let Some(else_) = &let_.else_ {
+            let failure = self.new_block();
+            self.test_pattern(&place, &let_.pattern, failure);
+            let success = self.current;
+            self.current = failure;
+            let temp = self.new_temp(else_.span);
+            self.block_into(temp, else_.span, &else_.block);
+            self.terminate(else_.span, TerminatorKind::Unreachable);
+            self.current = success;
+        }
+        self.bind_pattern(&place, &let_.pattern);
+    }
+
+    fn expression_into(&mut self, destination: Place, expression: &syntax::Expression) {
+        let span = expression.span;
+        match &expression.kind {
+            ExpressionKind::Variable(symbol) => {
+                let operand = self.variable(symbol);
+                match operand {
+                    Ok(operand) => {
+                        self.moved(expression);
+                        self.assign(span, destination, Rvalue::Use(operand))
+                    }
+                    Err(variant) => {
+                        self.assign(span, destination, Rvalue::Variant(variant, vec![]))
+                    }
+                }
+            }
+            ExpressionKind::Value(value) => self.assign(
+                span,
+                destination,
+                Rvalue::Use(Operand::Constant(constant(value))),
+            ),
+            ExpressionKind::Block(block) => self.block_into(destination, span, block),
+            ExpressionKind::If(if_) => self.if_into(destination, span, if_),
+            ExpressionKind::Loop(loop_) => {
+                let body = self.new_block();
+                let exit = self.new_block();
+                self.goto(span, body);
+                self.current = body;
+                let storage = self.storage.len();
+                self.loop_body(span, destination, body, exit, storage, &loop_.block);
+                self.goto(span, body);
+                self.current = exit;
+            }
+            ExpressionKind::While(while_) => {
+                let header = self.new_block();
+                let exit = self.new_block();
+                self.goto(span, header);
+                self.current = header;
+                let storage = self.storage.len();
+                self.enter_scope();
+                self.condition(&while_.condition, exit);
+                self.loop_body(
+                    span,
+                    destination.clone(),
+                    header,
+                    exit,
+                    storage,
+                    &while_.block,
+                );
+                self.exit_scope(span);
+                self.goto(span, header);
+                self.current = exit;
+                self.assign_unit(span, destination);
+            }
+            ExpressionKind::For(for_) => self.for_into(destination, span, for_),
+            ExpressionKind::Match(match_) => self.match_into(destination, span, match_),
+            ExpressionKind::Binary(binary) => self.binary_into(destination, span, binary),
+            ExpressionKind::FunctionCall(function_call) => {
+                if let ExpressionKind::Variable(symbol) = &function_call.function.kind {
+                    if self.lookup(symbol.id).is_none() {
+                        if let Some((variant, _)) = self.items.variants.get(&symbol.id) {
+                            let variant = variant.clone();
+                            let arguments = self.operands(&function_call.arguments);
+                            self.assign(span, destination, Rvalue::Variant(variant, arguments));
+                            return;
+                        }
+                    }
+                }
+                let function = self.operand(&function_call.function);
+                let arguments = self.operands(&function_call.arguments);
+                self.call(span, function, arguments, destination);
+            }
+            ExpressionKind::MemberCall(member_call) => {
+                // Without types methods can't be resolved so they are called
+                // by name with the object as the first argument.
+                let name = self.items.name(&member_call.member.member);
+                let mut arguments = vec![self.operand(&member_call.member.object)];
+                arguments.extend(self.operands(&member_call.arguments));
+                self.call(
+                    span,
+                    Operand::Constant(Constant::Function(name)),
+                    arguments,
+                    destination,
+                );
+            }
+            ExpressionKind::MemberAccess(_) | ExpressionKind::Deref(_) => {
+                let place = self.place(expression);
+                self.assign(span, destination, Rvalue::Use(Operand::Place(place)));
+            }
+            ExpressionKind::Tuple(expressions) => {
+                let rvalue = if expressions.is_empty() {
+                    Rvalue::Use(Operand::Constant(Constant::Unit))
+                } else {
+                    Rvalue::Tuple(self.operands(expressions))
+                };
+                self.assign(span, destination, rvalue);
+            }
+            ExpressionKind::Ref(expression) => {
+                let place = self.place(expression);
+                self.assign(span, destination, Rvalue::Ref(place));
+            }
+            ExpressionKind::RefMut(expression) => {
+                let place = self.place(expression);
+                self.assign(span, destination, Rvalue::RefMut(place));
+            }
+            ExpressionKind::RawConst(expression) => {
+                let place = self.place(expression);
+                self.assign(span, destination, Rvalue::RawConst(place));
+            }
+            ExpressionKind::RawMut(expression) => {
+                let place = self.place(expression);
+                self.assign(span, destination, Rvalue::RawMut(place));
+            }
+            ExpressionKind::Cast(cast) => {
+                let operand = self.operand(&cast.expression);
+                let type_ = self.items.diagnostic.file_span(cast.type_.span).to_string();
+                self.assign(span, destination, Rvalue::Cast(operand, type_));
+            }
+            ExpressionKind::Try(_) => panic!("`?` must be desugared before lowering"),
+            ExpressionKind::Return(value) => {
+                match value {
+                    Some(value) => self.expression_into(RETURN_PLACE.into(), value),
+                    None => self.assign_unit(span, RETURN_PLACE.into()),
+                }
+                self.storage_dead(span, 0);
+                self.diverge(span, TerminatorKind::Return);
+            }
+            ExpressionKind::Break(value) => {
+                let loop_ = self.loops.last().expect("`break` outside of a loop");
+                let (destination, break_block, storage) =
+                    (loop_.destination.clone(), loop_.break_block, loop_.storage);
+                match value {
+                    Some(value) => self.expression_into(destination, value),
+                    None => self.assign_unit(span, destination),
+                }
+                self.storage_dead(span, storage);
+                self.diverge(span, TerminatorKind::Goto(break_block));
+            }
+            ExpressionKind::Continue => {
+                let loop_ = self.loops.last().expect("`continue` outside of a loop");
+                let (continue_block, storage) = (loop_.continue_block, loop_.storage);
+                self.storage_dead(span, storage);
+                self.diverge(span, TerminatorKind::Goto(continue_block));
+            }
+        }
+    }
+
+    /// Lowers the body of a loop.  The value of the loop is written to
+    /// `destination` by `break`.
+    fn loop_body(
+        &mut self,
+        span: Span,
+        destination: Place,
+        continue_block: BasicBlock,
+        break_block: BasicBlock,
+        storage: usize,
+        block: &syntax::Block,
+    ) {
+        self.loops.push(Loop {
+            break_block,
+            continue_block,
+            destination,
+            storage,
+        });
+        let temp = self.new_temp(span);
+        self.block_into(temp, span, block);
+        self.loops.pop();
+    }
+
+    fn if_into(&mut self, destination: Place, span: Span, if_: &syntax::If) {
+        let else_block = self.new_block();
+        let end = self.new_block();
+        self.enter_scope();
+        self.condition(&if_.condition, else_block);
+        self.block_into(destination.clone(), span, &if_.then);
+        self.exit_scope(span);
+        self.goto(span, end);
+
+        self.current = else_block;
+        match if_.else_.as_ref().map(|else_| &else_.kind) {
+            Some(syntax::ElseKind::If(if_)) => self.if_into(destination, span, if_),
+            Some(syntax::ElseKind::Block(block)) => self.block_into(destination, span, block),
+            None => self.assign_unit(span, destination),
+        }
+        self.goto(span, end);
+        self.current = end;
+    }
+
+    /// Continues in a new block if `condition` holds and branches to
+    /// `otherwise` if it doesn't.  Bindings are added to the current scope.
+    fn condition(&mut self, condition: &syntax::Condition, otherwise: BasicBlock) {
+        match condition {
+            syntax::Condition::Expression(expression) => {
+                let operand = self.operand(expression);
+                self.branch_if_false(expression.span, operand, otherwise);
+            }
+            syntax::Condition::Let(let_) => {
+                let place = self.place(&let_.value);
+                self.test_pattern(&place, &let_.pattern, otherwise);
+                self.bind_pattern(&place, &let_.pattern);
+            }
+        }
+    }
+
+    /// `for x in e { body }` is lowered like
+    /// `let mut i = e; loop { match next(&mut i) { Some(x) => body, None => break } }`.
+    fn for_into(&mut self, destination: Place, span: Span, for_: &syntax::For) {
+        let iterator = self.new_temp(for_.expr.span);
+        self.expression_into(iterator.clone(), &for_.expr);
+        let header = self.new_block();
+        let exit = self.new_block();
+        self.goto(span, header);
+        self.current = header;
+
+        let reference = self.new_temp(for_.expr.span);
+        self.assign(span, reference.clone(), Rvalue::RefMut(iterator));
+        let next = self.new_temp(for_.expr.span);
+        self.call(
+            span,
+            Operand::Constant(Constant::Function("next".to_string())),
+            vec![Operand::Place(reference)],
+            next.clone(),
+        );
+        let (some, _) = self.items.variants[&SymbolId::of_name("Some")].clone();
+        let discriminant = self.new_temp(for_.expr.span);
+        self.assign(
+            span,
+            discriminant.clone(),
+            Rvalue::Discriminant(next.clone()),
+        );
+        let body = self.new_block();
+        self.switch(
+            span,
+            Operand::Place(discriminant),
+            some.discriminant,
+            body,
+            exit,
+        );
+        self.current = body;
+
+        let storage = self.storage.len();
+        self.enter_scope();
+        let var = self.bind(for_.var.span, for_.var.id, false);
+        let value = next
+            .project(Projection::Downcast(some.name, some.discriminant))
+            .project(Projection::Field("0".to_string()));
+        self.assign(span, var.into(), Rvalue::Use(Operand::Place(value)));
+        self.loop_body(
+            span,
+            destination.clone(),
+            header,
+            exit,
+            storage,
+            &for_.block,
+        );
+        self.exit_scope(span);
+        self.goto(span, header);
+
+        self.current = exit;
+        self.assign_unit(span, destination);
+    }
+
+    fn match_into(&mut self, destination: Place, span: Span, match_: &syntax::Match) {
+        let place = self.place(&match_.value);
+        let end = self.new_block();
+        for item in &match_.matches {
+            let next = self.new_block();
+            self.enter_scope();
+            self.test_pattern(&place, &item.pattern, next);
+            self.bind_pattern(&place, &item.pattern);
+            self.expression_into(destination.clone(), &item.value);
+            self.exit_scope(item.span);
+            self.goto(item.span, end);
+            self.current = next;
+        }
+        self.terminate(span, TerminatorKind::Unreachable);
+        self.current = end;
+    }
+
+    fn binary_into(&mut self, destination: Place, span: Span, binary: &syntax::Binary) {
+        let op = match binary.op {
+            syntax::BinaryOp::SetTo => {
+                let operand = self.operand(&binary.right);
+                let place = self.place(&binary.left);
+                self.assign(span, place.clone(), Rvalue::Use(operand));
+                if place.projection.is_empty() {
+                    self.set_flag(span, place.local, true);
+                }
+                self.assign_unit(span, destination);
+                return;
+            }
+            syntax::BinaryOp::And | syntax::BinaryOp::Or => {
+                // The right side is only evaluated if the left side doesn't
+                // decide the result.
+                let end = self.new_block();
+                let right = self.new_block();
+                self.expression_into(destination.clone(), &binary.left);
+                let skip = if binary.op == syntax::BinaryOp::And {
+                    0
+                } else {
+                    1
+                };
+                self.switch(span, Operand::Place(destination.clone()), skip, end, right);
+                self.current = right;
+                self.expression_into(destination, &binary.right);
+                self.goto(span, end);
+                self.current = end;
+                return;
+            }
+            syntax::BinaryOp::Times => BinaryOp::Mul,
+            syntax::BinaryOp::DividedBy => BinaryOp::Div,
+            syntax::BinaryOp::Plus => BinaryOp::Add,
+            syntax::BinaryOp::Minus => BinaryOp::Sub,
+            syntax::BinaryOp::BitAnd => BinaryOp::BitAnd,
+            syntax::BinaryOp::BitOr => BinaryOp::BitOr,
+            syntax::BinaryOp::IsEqualTo => BinaryOp::Eq,
+            syntax::BinaryOp::IsNotEqualTo => BinaryOp::Ne,
+        };
+        let integer = self
+            .integer_of(&binary.left)
+            .or_else(|| self.integer_of(&binary.right))
+            .or_else(|| match destination.projection.is_empty() {
+                true => self.locals[destination.local.0].integer,
+                false => None,
+            })
+            .unwrap_or_else(|| IntegerType::from_name(integer::DEFAULT).unwrap());
+        let left = self.operand(&binary.left);
+        let right = self.operand(&binary.right);
+        if op == BinaryOp::Div {
+            let zero = self.new_temp(span);
+            let rvalue = Rvalue::Binary(
+                BinaryOp::Eq,
+                right.clone(),
+                Operand::Constant(Constant::Integer(0)),
+            );
+            self.assign(span, zero.clone(), rvalue);
+            self.assert(span, Operand::Place(zero), "attempt to divide by zero");
+        }
+        let verb = match op {
+            BinaryOp::Add => "add",
+            BinaryOp::Sub => "subtract",
+            BinaryOp::Mul => "multiply",
+            BinaryOp::Div => "divide",
+            _ => "",
+        };
+        if op == BinaryOp::Div || (!verb.is_empty() && self.items.profile.overflow_checks()) {
+            let overflows = self.new_temp(span);
+            let rvalue = Rvalue::Overflows(op, integer, left.clone(), right.clone());
+            self.assign(span, overflows.clone(), rvalue);
+            let message = format!("attempt to {} with overflow", verb);
+            self.assert(span, Operand::Place(overflows), &message);
+        }
+        self.assign(span, destination, Rvalue::Binary(op, left, right));
+    }
+
+    /// Panics with `message` at `span` if `condition` is true.
+    fn assert(&mut self, span: Span, condition: Operand, message: &str) {
+        let target = self.new_block();
+        let message = format!("{}: {}", self.items.diagnostic.location(span), message);
+        self.terminate(
+            span,
+            TerminatorKind::Assert {
+                condition,
+                expected: false,
+                message,
+                target,
+            },
+        );
+        self.current = target;
+    }
+
+    fn call(&mut self, span: Span, function: Operand, arguments: Vec<Operand>, destination: Place) {
+        let target = self.new_block();
+        self.terminate(
+            span,
+            TerminatorKind::Call {
+                function,
+                arguments,
+                destination,
+                target,
+            },
+        );
+        self.current = target;
+    }
+
+    /// Resolves a variable to a local, a function, or a variant without data.
+    fn variable(&self, symbol: &syntax::Symbol) -> Result<Operand, Variant> {
+        if let Some(local) = self.lookup(symbol.id) {
+            return Ok(Operand::Place(local.into()));
+        }
+        if let Some((variant, false)) = self.items.variants.get(&symbol.id) {
+            return Err(variant.clone());
+        }
+        let name = match self.items.functions.get(&symbol.id) {
+            Some(name) => name.clone(),
+            // Unresolved names are assumed to be functions defined
+            // elsewhere.
+            None => self.items.name(symbol),
+        };
+        Ok(Operand::Constant(Constant::Function(name)))
+    }
+
+    fn operand(&mut self, expression: &syntax::Expression) -> Operand {
+        match &expression.kind {
+            ExpressionKind::Value(value) => Operand::Constant(constant(value)),
+            ExpressionKind::Variable(symbol) => match self.variable(symbol) {
+                Ok(operand) => {
+                    self.moved(expression);
+                    operand
+                }
+                Err(_) => self.temp_operand(expression),
+            },
+            _ => self.temp_operand(expression),
+        }
+    }
+
+    fn temp_operand(&mut self, expression: &syntax::Expression) -> Operand {
+        let temp = self.new_temp_for(expression);
+        self.expression_into(temp.clone(), expression);
+        Operand::Place(temp)
+    }
+
+    fn operands(&mut self, expressions: &[syntax::Expression]) -> Vec<Operand> {
+        expressions
+            .iter()
+            .map(|expression| self.operand(expression))
+            .collect()
+    }
+
+    /// Lowers an expression that is used as a place.  Expressions that
+    /// aren't places are stored in a temporary.
+    fn place(&mut self, expression: &syntax::Expression) -> Place {
+        match &expression.kind {
+            ExpressionKind::Variable(symbol) => {
+                if let Some(local) = self.lookup(symbol.id) {
+                    self.moved(expression);
+                    return local.into();
+                }
+            }
+            ExpressionKind::Deref(pointer) => {
+                return self.place(pointer).project(Projection::Deref);
+            }
+            ExpressionKind::MemberAccess(member_access) => {
+                let name = self.items.name(&member_access.member);
+                return self
+                    .place(&member_access.object)
+                    .project(Projection::Field(name));
+            }
+            _ => (),
+        }
+        let temp = self.new_temp_for(expression);
+        self.expression_into(temp.clone(), expression);
+        temp
+    }
+
+    /// Branches to `otherwise` if `place` doesn't match `pattern`.
+    fn test_pattern(&mut self, place: &Place, pattern: &syntax::Pattern, otherwise: BasicBlock) {
+        let span = pattern.span;
+        match &pattern.kind {
+            PatternKind::Hole => (),
+            PatternKind::Named(id) => {
+                if let Some((variant, _)) = self.items.variants.get(id) {
+                    let variant = variant.clone();
+                    self.test_variant(span, place, &variant, otherwise);
+                }
+            }
+            PatternKind::Tuple(patterns) => {
+                for (i, pattern) in patterns.iter().enumerate() {
+                    let field = place.project(Projection::Field(i.to_string()));
+                    self.test_pattern(&field, pattern, otherwise);
+                }
+            }
+            PatternKind::NamedTuple(name, patterns) => {
+                let place = match self.items.variants.get(&name.id) {
+                    Some((variant, _)) => {
+                        let variant = variant.clone();
+                        self.test_variant(span, place, &variant, otherwise);
+                        place.project(Projection::Downcast(variant.name, variant.discriminant))
+                    }
+                    None => place.clone(),
+                };
+                for (i, pattern) in patterns.iter().enumerate() {
+                    let field = place.project(Projection::Field(i.to_string()));
+                    self.test_pattern(&field, pattern, otherwise);
+                }
+            }
+            PatternKind::Value(value) => {
+                let value = match value {
+                    syntax::Value::Bool(b) => *b as u128,
+                    syntax::Value::Integer(i) => *i,
+                };
+                let matched = self.new_block();
+                self.switch(
+                    span,
+                    Operand::Place(place.clone()),
+                    value,
+                    matched,
+                    otherwise,
+                );
+                self.current = matched;
+            }
+        }
+    }
+
+    fn test_variant(
+        &mut self,
+        span: Span,
+        place: &Place,
+        variant: &Variant,
+        otherwise: BasicBlock,
+    ) {
+        let discriminant = self.new_temp(span);
+        self.assign(
+            span,
+            discriminant.clone(),
+            Rvalue::Discriminant(place.clone()),
+        );
+        let matched = self.new_block();
+        self.switch(
+            span,
+            Operand::Place(discriminant),
+            variant.discriminant,
+            matched,
+            otherwise,
+        );
+        self.current = matched;
+    }
+
+    /// Binds the names in `pattern` to the parts of `place`.  The pattern
+    /// must already be known to match.
+    fn bind_pattern(&mut self, place: &Place, pattern: &syntax::Pattern) {
+        match &pattern.kind {
+            PatternKind::Hole | PatternKind::Value(_) => (),
+            PatternKind::Named(id) => {
+                if !self.items.variants.contains_key(id) {
+                    let local = self.bind(pattern.span, *id, false);
+                    self.assign(
+                        pattern.span,
+                        local.into(),
+                        Rvalue::Use(Operand::Place(place.clone())),
+                    );
+                    self.set_flag(pattern.span, local, true);
+                }
+            }
+            PatternKind::Tuple(patterns) => {
+                for (i, pattern) in patterns.iter().enumerate() {
+                    let field = place.project(Projection::Field(i.to_string()));
+                    self.bind_pattern(&field, pattern);
+                }
+            }
+            PatternKind::NamedTuple(name, patterns) => {
+                let place = match self.items.variants.get(&name.id) {
+                    Some((variant, _)) => place.project(Projection::Downcast(
+                        variant.name.clone(),
+                        variant.discriminant,
+                    )),
+                    None => place.clone(),
+                };
+                for (i, pattern) in patterns.iter().enumerate() {
+                    let field = place.project(Projection::Field(i.to_string()));
+                    self.bind_pattern(&field, pattern);
+                }
+            }
+        }
+    }
+
+    /// Binds the names in `pattern` without initializing them.
+    fn bind_pattern_uninit(&mut self, pattern: &syntax::Pattern, mutable: bool) {
+        match &pattern.kind {
+            PatternKind::Named(id) => {
+                self.bind(pattern.span, *id, mutable);
+            }
+            PatternKind::Tuple(patterns) | PatternKind::NamedTuple(_, patterns) => {
+                for pattern in patterns {
+                    self.bind_pattern_uninit(pattern, mutable);
+                }
+            }
+            PatternKind::Hole | PatternKind::Value(_) => (),
+        }
+    }
+}
+
+fn constant(value: &syntax::Value) -> Constant {
+    match value {
+        syntax::Value::Bool(b) => Constant::Bool(*b),
+        syntax::Value::Integer(i) => Constant::Integer(*i),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test::{dump, lower_str, lower_str_with};
+    use crate::*;
+    use rust_comp_core::profile::Profile;
+
+    #[test]
+    fn test_lower_straight_line() {
+        assert_eq!(
+            dump("fn f(a: i32, b: i32) -> i32 { let x = a + 1; x * b }"),
+            "\
+fn f(_1, _2) {
+    let mut _0: i32;
+    let _1: i32; // a
+    let _2: i32; // b
+    let _3: i32; // x
+
+    bb0: {
+        StorageLive(_3);
+        _3 = Add(_1, const 1);
+        _0 = Mul(_3, _2);
+        StorageDead(_3);
+        return;
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_lower_if_else() {
+        assert_eq!(
+            dump("fn f(b: bool) -> i32 { if b { 1 } else { 2 } }"),
+            "\
+fn f(_1) {
+    let mut _0: i32;
+    let _1; // b
+
+    bb0: {
+        switchInt(_1) -> [0: bb1, otherwise: bb3];
+    }
+
+    bb1: {
+        _0 = const 2;
+        goto -> bb2;
+    }
+
+    bb2: {
+        return;
+    }
+
+    bb3: {
+        _0 = const 1;
+        goto -> bb2;
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_lower_match_option() {
+        assert_eq!(
+            dump("fn f(o: Option) -> i32 { match o { Some(x) => x, None => 0 } }"),
+            "\
+fn f(_1) {
+    let mut _0: i32;
+    let _1; // o
+    let mut _2;
+    let _3: i32; // x
+    let mut _4;
+
+    bb0: {
+        _2 = discriminant(_1);
+        switchInt(_2) -> [1: bb3, otherwise: bb2];
+    }
+
+    bb1: {
+        return;
+    }
+
+    bb2: {
+        _4 = discriminant(_1);
+        switchInt(_4) -> [0: bb5, otherwise: bb4];
+    }
+
+    bb3: {
+        StorageLive(_3);
+        _3 = (_1 as Some).0;
+        _0 = _3;
+        StorageDead(_3);
+        goto -> bb1;
+    }
+
+    bb4: {
+        unreachable;
+    }
+
+    bb5: {
+        _0 = const 0;
+        goto -> bb1;
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_lower_short_circuit() {
+        assert_eq!(
+            dump("fn f(a: bool, b: bool) -> bool { a && b }"),
+            "\
+fn f(_1, _2) {
+    let mut _0;
+    let _1; // a
+    let _2; // b
+
+    bb0: {
+        _0 = _1;
+        switchInt(_0) -> [0: bb1, otherwise: bb2];
+    }
+
+    bb1: {
+        return;
+    }
+
+    bb2: {
+        _0 = _2;
+        goto -> bb1;
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_lower_assign_through_deref() {
+        assert_eq!(
+            dump("fn f(p: &mut Point) { (*p).x = g(1, p.y); }"),
+            "\
+fn f(_1) {
+    let mut _0;
+    let _1; // p
+    let mut _2;
+    let mut _3;
+    let mut _4;
+
+    bb0: {
+        _4 = _1.y;
+        _3 = g(const 1, _4) -> bb1;
+    }
+
+    bb1: {
+        (*_1).x = _3;
+        _2 = const ();
+        _0 = const ();
+        return;
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_lower_let_else_branches_to_else_block() {
+        let bodies = lower_str("fn f(o: Option) -> i32 { let Some(x) = o else { return 0; }; x }");
+        let body = &bodies[0];
+        let else_block = match &body.block(START_BLOCK).terminator.kind {
+            TerminatorKind::SwitchInt {
+                targets, otherwise, ..
+            } => {
+                assert_eq!(targets.len(), 1);
+                *otherwise
+            }
+            kind => panic!("{:?}", kind),
+        };
+        assert_eq!(
+            body.block(else_block).terminator.kind,
+            TerminatorKind::Return
+        );
+    }
+
+    #[test]
+    fn test_lower_loop_break_value() {
+        let bodies = lower_str(
+            "fn f() -> i32 { let mut i = 0; loop { if i == 10 { break i; } i = i + 1; } }",
+        );
+        let body = &bodies[0];
+        assert!(body.local(Local(1)).mutable);
+        // The loop body jumps back to its header.
+        assert!(body.blocks.iter().any(|block| block.terminator.kind
+            == TerminatorKind::Goto(BasicBlock(1))
+            && block.statements.iter().any(|s| s.kind
+                == StatementKind::Assign(
+                    Local(1).into(),
+                    Rvalue::Use(Operand::Place(Local(7).into()))
+                ))));
+        // `break i` writes the loop's value into the return place.
+        assert!(body
+            .blocks
+            .iter()
+            .any(|block| block.statements.iter().any(|s| s.kind
+                == StatementKind::Assign(
+                    RETURN_PLACE.into(),
+                    Rvalue::Use(Operand::Place(Local(1).into()))
+                ))));
+    }
+
+    #[test]
+    fn test_lower_try_returns_none() {
+        let bodies = lower_str("fn f() -> Option { g()? }");
+        let body = &bodies[0];
+        assert_eq!(body.local(Local(3)).name, None);
+        assert!(body
+            .blocks
+            .iter()
+            .any(|block| block.terminator.kind == TerminatorKind::Return
+                && block.statements.iter().any(|s| match &s.kind {
+                    StatementKind::Assign(place, Rvalue::Variant(variant, _)) =>
+                        *place == RETURN_PLACE.into() && variant.name == "None",
+                    _ => false,
+                })));
+    }
+
+    #[test]
+    fn test_lower_drops() {
+        let bodies = lower_str(
+            "enum S { A } fn g(s: S) {}
+             fn f(a: S, c: bool) { let b = A; if c { g(b); } if c { return; } }",
+        );
+        // `b` is only moved on some paths so it needs a drop flag.
+        assert_eq!(
+            bodies[1].to_string(),
+            "\
+fn f(_1, _2) {
+    let mut _0;
+    let _1; // a
+    let _2; // c
+    let _3; // b
+    let mut _4;
+    let mut _5;
+    let mut _6;
+    let mut _7;
+
+    bb0: {
+        StorageLive(_3);
+        _4 = const false;
+        _3 = S::A;
+        _4 = const true;
+        switchInt(_2) -> [0: bb1, otherwise: bb3];
+    }
+
+    bb1: {
+        _5 = const ();
+        goto -> bb2;
+    }
+
+    bb2: {
+        switchInt(_2) -> [0: bb5, otherwise: bb7];
+    }
+
+    bb3: {
+        _4 = const false;
+        _6 = g(_3) -> bb4;
+    }
+
+    bb4: {
+        _5 = const ();
+        goto -> bb2;
+    }
+
+    bb5: {
+        _0 = const ();
+        goto -> bb6;
+    }
+
+    bb6: {
+        switchInt(_4) -> [0: bb12, otherwise: bb11];
+    }
+
+    bb7: {
+        _0 = const ();
+        switchInt(_4) -> [0: bb9, otherwise: bb8];
+    }
+
+    bb8: {
+        drop(_3);
+        goto -> bb9;
+    }
+
+    bb9: {
+        StorageDead(_3);
+        drop(_1);
+        return;
+    }
+
+    bb10: {
+        _0 = const ();
+        goto -> bb6;
+    }
+
+    bb11: {
+        drop(_3);
+        goto -> bb12;
+    }
+
+    bb12: {
+        StorageDead(_3);
+        drop(_1);
+        return;
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_lower_drop_flags() {
+        let bodies = lower_str(
+            "enum S { A } fn g(s: S) {}
+             fn f(s: S, c: bool) { if c { g(s); } let mut b = A; g(b); if c { b = A; } }",
+        );
+        // The flags are set when `s` and `b` are initialized, cleared when
+        // they are moved out of, set again when `b` is assigned, and tested
+        // before they are dropped.
+        assert_eq!(
+            bodies[1].to_string(),
+            "\
+fn f(_1, _2) {
+    let mut _0;
+    let _1; // s
+    let _2; // c
+    let mut _3;
+    let mut _4;
+    let mut _5;
+    let mut _6; // b
+    let mut _7;
+    let mut _8;
+    let mut _9;
+    let mut _10;
+
+    bb0: {
+        _3 = const true;
+        switchInt(_2) -> [0: bb1, otherwise: bb3];
+    }
+
+    bb1: {
+        _4 = const ();
+        goto -> bb2;
+    }
+
+    bb2: {
+        StorageLive(_6);
+        _7 = const false;
+        _6 = S::A;
+        _7 = const true;
+        _7 = const false;
+        _8 = g(_6) -> bb5;
+    }
+
+    bb3: {
+        _3 = const false;
+        _5 = g(_1) -> bb4;
+    }
+
+    bb4: {
+        _4 = const ();
+        goto -> bb2;
+    }
+
+    bb5: {
+        switchInt(_2) -> [0: bb6, otherwise: bb8];
+    }
+
+    bb6: {
+        _0 = const ();
+        goto -> bb7;
+    }
+
+    bb7: {
+        switchInt(_7) -> [0: bb10, otherwise: bb9];
+    }
+
+    bb8: {
+        _10 = S::A;
+        _6 = _10;
+        _7 = const true;
+        _9 = const ();
+        _0 = const ();
+        goto -> bb7;
+    }
+
+    bb9: {
+        drop(_6);
+        goto -> bb10;
+    }
+
+    bb10: {
+        StorageDead(_6);
+        switchInt(_3) -> [0: bb12, otherwise: bb11];
+    }
+
+    bb11: {
+        drop(_1);
+        goto -> bb12;
+    }
+
+    bb12: {
+        return;
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_lower_successors_are_in_bounds() {
+        let bodies = lower_str(
+            "fn f(v: Vec) { for x in v { if x == 1 { continue; } while x != 2 { break; } } }",
+        );
+        let body = &bodies[0];
+        for block in &body.blocks {
+            for successor in block.terminator.kind.successors() {
+                assert!(successor.0 < body.blocks.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_lower_overflow_checks() {
+        let debug = lower_str_with("fn f(a: i32, b: i32) -> i32 { a * b }", Profile::Debug);
+        assert_eq!(
+            debug[0].to_string(),
+            "\
+fn f(_1, _2) {
+    let mut _0: i32;
+    let _1: i32; // a
+    let _2: i32; // b
+    let mut _3;
+
+    bb0: {
+        _3 = OverflowsMul::<i32>(_1, _2);
+        assert(!_3, \"<test>:1:31: attempt to multiply with overflow\") -> bb1;
+    }
+
+    bb1: {
+        _0 = Mul(_1, _2);
+        return;
+    }
+}
+"
+        );
+
+        // Division is checked in every profile.
+        let release = lower_str("fn f(a: i32, b: i32) -> i32 { a / b }");
+        let messages: Vec<&str> = release[0]
+            .blocks
+            .iter()
+            .filter_map(|block| match &block.terminator.kind {
+                TerminatorKind::Assert { message, .. } => Some(message.split_once(": ").unwrap().1),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "attempt to divide by zero",
+                "attempt to divide with overflow"
+            ]
+        );
+    }
+}
@@ -0,0 +1,23 @@
+use crate::lower::lower;
+use crate::Body;
+use rust_comp_check::test_support;
+use rust_comp_core::profile::Profile;
+
+/// Lowers `file_contents` as file 0.  Arithmetic wraps so it isn't followed
+/// by checks.
+pub fn lower_str(file_contents: &str) -> Vec<Body> {
+    lower_str_with(file_contents, Profile::Release)
+}
+
+pub fn lower_str_with(file_contents: &str, profile: Profile) -> Vec<Body> {
+    let (diagnostic, top_levels) = test_support::desugar(file_contents);
+    lower(&diagnostic, profile, &top_levels)
+}
+
+/// Lowers `file_contents` and dumps its functions.
+pub fn dump(file_contents: &str) -> String {
+    lower_str(file_contents)
+        .iter()
+        .map(|body| body.to_string())
+        .collect()
+}
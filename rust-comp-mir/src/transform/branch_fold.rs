@@ -0,0 +1,49 @@
+use crate::*;
+
+/// Replaces `SwitchInt` on a constant and `Assert` of a constant that holds
+/// with `Goto`.
+pub fn branch_fold(body: &mut Body) {
+    for block in &mut body.blocks {
+        let target = match &block.terminator.kind {
+            TerminatorKind::SwitchInt {
+                operand: Operand::Constant(constant),
+                targets,
+                otherwise,
+            } => {
+                let value = match constant {
+                    Constant::Bool(b) => *b as u128,
+                    Constant::Integer(i) => *i,
+                    Constant::Unit | Constant::Function(_) => continue,
+                };
+                targets
+                    .iter()
+                    .find(|(v, _)| *v == value)
+                    .map_or(*otherwise, |(_, target)| *target)
+            }
+            TerminatorKind::Assert {
+                condition: Operand::Constant(Constant::Bool(b)),
+                expected,
+                target,
+                ..
+            } if b == expected => *target,
+            _ => continue,
+        };
+        block.terminator.kind = TerminatorKind::Goto(target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::lower_str;
+
+    #[test]
+    fn test_branch_fold() {
+        let mut bodies = lower_str("fn f() -> i32 { if true { 1 } else { 2 } }");
+        branch_fold(&mut bodies[0]);
+        assert_eq!(
+            bodies[0].blocks[0].terminator.kind,
+            TerminatorKind::Goto(BasicBlock(3))
+        );
+    }
+}
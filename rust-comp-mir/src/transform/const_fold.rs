@@ -0,0 +1,230 @@
+use super::operands_mut;
+use crate::*;
+use std::collections::HashMap;
+
+/// Evaluates `Binary` and `Overflows` of constants and replaces uses of
+/// locals that are only ever assigned a constant with the constant.
+/// `Binary` wraps to the type of the local it's assigned to.
+pub fn const_fold(body: &mut Body) {
+    let integers: Vec<_> = body.locals.iter().map(|local| local.integer).collect();
+    loop {
+        let constants = constant_locals(body);
+        let mut changed = false;
+        operands_mut(body, |operand| {
+            if let Operand::Place(place) = operand {
+                if !place.projection.is_empty() {
+                    return;
+                }
+                if let Some(constant) = constants.get(&place.local) {
+                    *operand = Operand::Constant(constant.clone());
+                    changed = true;
+                }
+            }
+        });
+        for block in &mut body.blocks {
+            for statement in &mut block.statements {
+                if let StatementKind::Assign(place, rvalue) = &mut statement.kind {
+                    let integer = match place.projection.is_empty() {
+                        true => integers[place.local.0],
+                        false => None,
+                    };
+                    if let Some(constant) = fold(rvalue, integer.unwrap_or(IntegerType::I64)) {
+                        *rvalue = Rvalue::Use(Operand::Constant(constant));
+                        changed = true;
+                    }
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+}
+
+/// The locals other than the return place and arguments that are assigned
+/// once, to a constant, and never borrowed.
+fn constant_locals(body: &Body) -> HashMap<Local, Constant> {
+    let mut definitions = vec![0; body.locals.len()];
+    let mut constants = HashMap::new();
+    let mut disqualified = vec![false; body.locals.len()];
+    for local in body.arguments().chain(Some(RETURN_PLACE)) {
+        disqualified[local.0] = true;
+    }
+    for block in &body.blocks {
+        for statement in &block.statements {
+            if let StatementKind::Assign(place, rvalue) = &statement.kind {
+                if !place.projection.is_empty() {
+                    disqualified[place.local.0] = true;
+                }
+                definitions[place.local.0] += 1;
+                match rvalue {
+                    Rvalue::Use(Operand::Constant(constant)) => {
+                        constants.insert(place.local, constant.clone());
+                    }
+                    Rvalue::Ref(borrowed)
+                    | Rvalue::RefMut(borrowed)
+                    | Rvalue::RawConst(borrowed)
+                    | Rvalue::RawMut(borrowed) => disqualified[borrowed.local.0] = true,
+                    _ => {}
+                }
+            }
+        }
+        if let TerminatorKind::Call { destination, .. } = &block.terminator.kind {
+            disqualified[destination.local.0] = true;
+        }
+    }
+    constants.retain(|local, _| definitions[local.0] == 1 && !disqualified[local.0]);
+    constants
+}
+
+/// Folds `rvalue`, assigned to a local of type `integer`.
+fn fold(rvalue: &Rvalue, integer: IntegerType) -> Option<Constant> {
+    match rvalue {
+        Rvalue::Binary(op, Operand::Constant(left), Operand::Constant(right)) => {
+            binary(*op, integer, left, right)
+        }
+        Rvalue::Overflows(
+            op,
+            integer,
+            Operand::Constant(Constant::Integer(left)),
+            Operand::Constant(Constant::Integer(right)),
+        ) => {
+            let value = match arithmetic(*op, *integer, *left, *right) {
+                Some(value) => value,
+                None if *op == BinaryOp::Mul => return Some(Constant::Bool(true)),
+                // Division by zero is checked separately.
+                None if *op == BinaryOp::Div => return Some(Constant::Bool(false)),
+                None => return None,
+            };
+            Some(Constant::Bool(
+                value < integer.min() || value > integer.max(),
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// The exact result of an arithmetic operation on integers of type
+/// `integer` or `None` if it isn't arithmetic, divides by zero, or doesn't
+/// fit in an `i128`.
+fn arithmetic(op: BinaryOp, integer: IntegerType, left: u128, right: u128) -> Option<i128> {
+    // Constants hold the value's 64 bit representation.
+    let left = integer.wrap(left as u64 as i64 as i128);
+    let right = integer.wrap(right as u64 as i64 as i128);
+    match op {
+        BinaryOp::Add => Some(left + right),
+        BinaryOp::Sub => Some(left - right),
+        BinaryOp::Mul => left.checked_mul(right),
+        BinaryOp::Div => left.checked_div(right),
+        _ => None,
+    }
+}
+
+fn binary(
+    op: BinaryOp,
+    integer: IntegerType,
+    left: &Constant,
+    right: &Constant,
+) -> Option<Constant> {
+    match (left, right) {
+        (Constant::Integer(left), Constant::Integer(right)) => {
+            let value = match op {
+                BinaryOp::Mul => {
+                    let (left, right) = (*left as i64, *right as i64);
+                    left.wrapping_mul(right) as i128
+                }
+                // Division by zero is left to panic at run time.
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Div => {
+                    arithmetic(op, integer, *left, *right)?
+                }
+                BinaryOp::BitAnd => (*left & *right) as i128,
+                BinaryOp::BitOr => (*left | *right) as i128,
+                BinaryOp::Eq => return Some(Constant::Bool(left == right)),
+                BinaryOp::Ne => return Some(Constant::Bool(left != right)),
+            };
+            Some(Constant::Integer(integer.wrap(value) as u64 as u128))
+        }
+        (Constant::Bool(left), Constant::Bool(right)) => Some(Constant::Bool(match op {
+            BinaryOp::BitAnd => left & right,
+            BinaryOp::BitOr => left | right,
+            BinaryOp::Eq => left == right,
+            BinaryOp::Ne => left != right,
+            _ => return None,
+        })),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{lower_str, lower_str_with};
+    use rust_comp_core::profile::Profile;
+
+    fn assignments(body: &Body) -> Vec<String> {
+        body.blocks
+            .iter()
+            .flat_map(|block| &block.statements)
+            .filter_map(|statement| match &statement.kind {
+                StatementKind::Assign(..) => Some(statement.kind.to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_const_fold_propagates_constants() {
+        let mut bodies =
+            lower_str("fn f() -> i64 { let x = 2 + 3; let mut y = x * 4; y = y - 30; y == 1 }");
+        const_fold(&mut bodies[0]);
+        assert_eq!(
+            assignments(&bodies[0]),
+            [
+                "_1 = const 5",
+                "_2 = const 20",
+                "_4 = Sub(_2, const 30)",
+                "_2 = _4",
+                "_3 = const ()",
+                "_0 = Eq(_2, const 1)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_const_fold_wraps_and_checks() {
+        let mut bodies = lower_str_with(
+            "fn f() -> i64 { (9223372036854775807 + 1) / 0 }",
+            Profile::Debug,
+        );
+        const_fold(&mut bodies[0]);
+        assert_eq!(
+            assignments(&bodies[0]),
+            [
+                "_2 = const true",
+                "_1 = const 9223372036854775808",
+                "_3 = const true",
+                "_4 = const false",
+                "_0 = Div(const 9223372036854775808, const 0)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_const_fold_wraps_to_the_type() {
+        let mut bodies = lower_str_with(
+            "fn f() -> i32 { let x: i32 = 2147483647; x + 1 }
+             fn g() -> u32 { let x: u32 = 0; x - 1 }",
+            Profile::Debug,
+        );
+        const_fold(&mut bodies[0]);
+        const_fold(&mut bodies[1]);
+        assert_eq!(
+            assignments(&bodies[0])[1..],
+            ["_2 = const true", "_0 = const 18446744071562067968"]
+        );
+        assert_eq!(
+            assignments(&bodies[1])[1..],
+            ["_2 = const true", "_0 = const 4294967295"]
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use super::dominators::Dominators;
+use super::ssa::SsaLocals;
+use super::{statement_uses_mut, terminator_uses_mut};
+use crate::*;
+
+/// Replaces uses of a local in SSA form that's a copy of another with the
+/// original.  The copies are left for `remove_unused_locals`.
+pub fn copy_prop(body: &mut Body) {
+    let ssa = SsaLocals::new(body, &Dominators::new(body));
+    let mut copies: Vec<Option<Local>> = vec![None; body.locals.len()];
+    for statement in body.blocks.iter().flat_map(|block| &block.statements) {
+        if let StatementKind::Assign(place, Rvalue::Use(Operand::Place(source))) = &statement.kind {
+            if place.projection.is_empty()
+                && source.projection.is_empty()
+                && place.local != source.local
+                && ssa.is_ssa(place.local)
+                && ssa.is_ssa(source.local)
+            {
+                copies[place.local.0] = Some(source.local);
+            }
+        }
+    }
+    let root = |mut local: Local| {
+        while let Some(source) = copies[local.0] {
+            local = source;
+        }
+        local
+    };
+    for block in &mut body.blocks {
+        for statement in &mut block.statements {
+            for place in statement_uses_mut(&mut statement.kind) {
+                place.local = root(place.local);
+            }
+        }
+        for place in terminator_uses_mut(&mut block.terminator.kind) {
+            place.local = root(place.local);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::lower_str;
+
+    #[test]
+    fn test_copy_prop() {
+        let mut bodies =
+            lower_str("fn f(a: i64) -> i64 { let x = a; let y = x; let r = &y; *r + y }");
+        copy_prop(&mut bodies[0]);
+        assert_eq!(
+            bodies[0].blocks[0]
+                .statements
+                .iter()
+                .filter_map(|statement| match &statement.kind {
+                    StatementKind::Assign(..) => Some(statement.kind.to_string()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+            [
+                "_2 = _1",
+                "_3 = _1",
+                "_4 = &_3",
+                "_5 = (*_4)",
+                "_0 = Add(_5, _3)"
+            ]
+        );
+    }
+}
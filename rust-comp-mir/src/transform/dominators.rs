@@ -0,0 +1,192 @@
+use crate::*;
+
+/// The dominator tree of the blocks reachable from the start block.
+pub struct Dominators {
+    /// The immediate dominator of each block.  The start block is its own
+    /// and unreachable blocks don't have one.
+    idom: Vec<Option<BasicBlock>>,
+    /// The reachable blocks in reverse postorder.
+    order: Vec<BasicBlock>,
+    children: Vec<Vec<BasicBlock>>,
+}
+
+impl Dominators {
+    /// Computes dominators with the algorithm from "A Simple, Fast
+    /// Dominance Algorithm" by Cooper, Harvey, and Kennedy.
+    pub fn new(body: &Body) -> Self {
+        let order = reverse_postorder(body);
+        let mut position = vec![usize::MAX; body.blocks.len()];
+        for (i, block) in order.iter().enumerate() {
+            position[block.0] = i;
+        }
+        let predecessors = predecessors(body);
+        let mut idom = vec![None; body.blocks.len()];
+        idom[START_BLOCK.0] = Some(START_BLOCK);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in &order[1..] {
+                let mut new_idom = None;
+                for &predecessor in &predecessors[block.0] {
+                    if idom[predecessor.0].is_none() {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => predecessor,
+                        Some(other) => intersect(&idom, &position, predecessor, other),
+                    });
+                }
+                if new_idom != idom[block.0] {
+                    idom[block.0] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut children = vec![Vec::new(); body.blocks.len()];
+        for &block in &order[1..] {
+            if let Some(parent) = idom[block.0] {
+                children[parent.0].push(block);
+            }
+        }
+        Dominators {
+            idom,
+            order,
+            children,
+        }
+    }
+
+    pub fn is_reachable(&self, block: BasicBlock) -> bool {
+        self.idom[block.0].is_some()
+    }
+
+    /// Whether every path from the start block to `b` goes through `a`.
+    /// Blocks dominate themselves.
+    pub fn dominates(&self, a: BasicBlock, mut b: BasicBlock) -> bool {
+        if !self.is_reachable(b) {
+            return false;
+        }
+        loop {
+            if a == b {
+                return true;
+            }
+            if b == START_BLOCK {
+                return false;
+            }
+            b = self.idom[b.0].unwrap();
+        }
+    }
+
+    /// The blocks immediately dominated by `block`.
+    pub fn children(&self, block: BasicBlock) -> &[BasicBlock] {
+        &self.children[block.0]
+    }
+
+    pub fn reverse_postorder(&self) -> &[BasicBlock] {
+        &self.order
+    }
+
+    /// The blocks where `block`'s dominance ends: those with a predecessor
+    /// dominated by `block` that aren't strictly dominated by it.
+    pub fn frontiers(&self, body: &Body) -> Vec<Vec<BasicBlock>> {
+        let mut frontiers = vec![Vec::new(); body.blocks.len()];
+        for (block, predecessors) in predecessors(body).into_iter().enumerate() {
+            let idom = match self.idom[block] {
+                Some(idom) => idom,
+                None => continue,
+            };
+            if predecessors.len() < 2 {
+                continue;
+            }
+            for predecessor in predecessors {
+                if !self.is_reachable(predecessor) {
+                    continue;
+                }
+                let mut runner = predecessor;
+                while runner != idom {
+                    if !frontiers[runner.0].contains(&BasicBlock(block)) {
+                        frontiers[runner.0].push(BasicBlock(block));
+                    }
+                    runner = self.idom[runner.0].unwrap();
+                }
+            }
+        }
+        frontiers
+    }
+}
+
+fn intersect(
+    idom: &[Option<BasicBlock>],
+    position: &[usize],
+    mut a: BasicBlock,
+    mut b: BasicBlock,
+) -> BasicBlock {
+    while a != b {
+        while position[a.0] > position[b.0] {
+            a = idom[a.0].unwrap();
+        }
+        while position[b.0] > position[a.0] {
+            b = idom[b.0].unwrap();
+        }
+    }
+    a
+}
+
+/// The predecessors of each block.  A block is listed once per edge.
+pub fn predecessors(body: &Body) -> Vec<Vec<BasicBlock>> {
+    let mut predecessors = vec![Vec::new(); body.blocks.len()];
+    for (i, block) in body.blocks.iter().enumerate() {
+        for successor in block.terminator.kind.successors() {
+            predecessors[successor.0].push(BasicBlock(i));
+        }
+    }
+    predecessors
+}
+
+fn reverse_postorder(body: &Body) -> Vec<BasicBlock> {
+    let mut visited = vec![false; body.blocks.len()];
+    let mut postorder = Vec::new();
+    // Each entry is a block and the index of its next successor to visit.
+    let mut stack = vec![(START_BLOCK, 0)];
+    visited[START_BLOCK.0] = true;
+    while let Some((block, next)) = stack.pop() {
+        let successors = body.block(block).terminator.kind.successors();
+        match successors.get(next) {
+            Some(&successor) => {
+                stack.push((block, next + 1));
+                if !visited[successor.0] {
+                    visited[successor.0] = true;
+                    stack.push((successor, 0));
+                }
+            }
+            None => postorder.push(block),
+        }
+    }
+    postorder.reverse();
+    postorder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::lower_str;
+
+    #[test]
+    fn test_dominators_of_loop() {
+        let bodies = lower_str("fn f(x: i32) { let mut i = 0; while i != x { i = i + 1; } }");
+        let body = &bodies[0];
+        let dominators = Dominators::new(body);
+        for (i, block) in body.blocks.iter().enumerate() {
+            assert!(dominators.dominates(START_BLOCK, BasicBlock(i)));
+            for successor in block.terminator.kind.successors() {
+                // The loop header dominates the end of the body that jumps
+                // back to it.
+                if successor.0 <= i {
+                    assert!(dominators.dominates(successor, BasicBlock(i)));
+                    assert!(dominators.frontiers(body)[i].contains(&successor));
+                }
+            }
+        }
+        assert_eq!(dominators.reverse_postorder()[0], START_BLOCK);
+    }
+}
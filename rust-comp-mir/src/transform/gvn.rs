@@ -0,0 +1,142 @@
+use super::dominators::Dominators;
+use super::ssa::SsaLocals;
+use crate::*;
+use std::collections::HashMap;
+
+/// Replaces an expression that was already computed by a dominating
+/// assignment with a copy of its value.  Only arithmetic, comparisons, and
+/// casts of constants and locals in SSA form are numbered.
+pub fn gvn(body: &mut Body) {
+    enum Visit {
+        Enter(BasicBlock),
+        Leave(Vec<Rvalue>),
+    }
+
+    let dominators = Dominators::new(body);
+    let ssa = SsaLocals::new(body, &dominators);
+    let mut values: HashMap<Rvalue, Local> = HashMap::new();
+    // The local that first computed the value of each redundant local.
+    let mut leaders: HashMap<Local, Local> = HashMap::new();
+    let mut visits = vec![Visit::Enter(START_BLOCK)];
+    while let Some(visit) = visits.pop() {
+        let block = match visit {
+            Visit::Enter(block) => block,
+            Visit::Leave(keys) => {
+                for key in keys {
+                    values.remove(&key);
+                }
+                continue;
+            }
+        };
+        let mut keys = Vec::new();
+        for statement in &mut body.block_mut(block).statements {
+            let (place, rvalue) = match &mut statement.kind {
+                StatementKind::Assign(place, rvalue)
+                    if place.projection.is_empty() && ssa.is_ssa(place.local) =>
+                {
+                    (place, rvalue)
+                }
+                _ => continue,
+            };
+            let key = match key(rvalue, &ssa, &leaders) {
+                Some(key) => key,
+                None => continue,
+            };
+            match values.get(&key) {
+                Some(&leader) => {
+                    leaders.insert(place.local, leader);
+                    *rvalue = Rvalue::Use(Operand::Place(leader.into()));
+                }
+                None => {
+                    values.insert(key.clone(), place.local);
+                    keys.push(key);
+                }
+            }
+        }
+        visits.push(Visit::Leave(keys));
+        for &child in dominators.children(block).iter().rev() {
+            visits.push(Visit::Enter(child));
+        }
+    }
+}
+
+/// The expression computed by `rvalue` with its operands replaced by their
+/// leaders and the operands of commutative operators ordered.
+fn key(rvalue: &Rvalue, ssa: &SsaLocals, leaders: &HashMap<Local, Local>) -> Option<Rvalue> {
+    let mut key = rvalue.clone();
+    match &mut key {
+        Rvalue::Binary(op, left, right) | Rvalue::Overflows(op, _, left, right) => {
+            let commutative = match op {
+                BinaryOp::Add
+                | BinaryOp::Mul
+                | BinaryOp::BitAnd
+                | BinaryOp::BitOr
+                | BinaryOp::Eq
+                | BinaryOp::Ne => true,
+                BinaryOp::Sub | BinaryOp::Div => false,
+            };
+            if commutative && rank(left) > rank(right) {
+                std::mem::swap(left, right);
+            }
+        }
+        Rvalue::Cast(..) => {}
+        _ => return None,
+    }
+    for operand in key.operands_mut() {
+        if let Operand::Place(place) = operand {
+            if !place.projection.is_empty() || !ssa.is_ssa(place.local) {
+                return None;
+            }
+            if let Some(&leader) = leaders.get(&place.local) {
+                place.local = leader;
+            }
+        }
+    }
+    Some(key)
+}
+
+/// Orders constants before locals and locals by number.
+fn rank(operand: &Operand) -> (bool, usize) {
+    match operand {
+        Operand::Constant(_) => (false, 0),
+        Operand::Place(place) => (true, place.local.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::lower_str;
+
+    #[test]
+    fn test_gvn() {
+        let mut bodies = lower_str(
+            "fn f(a: i64, b: i64) -> i64 {
+                 let x = a + b;
+                 let y = b + a;
+                 let z = y * 2;
+                 let w = x * 2;
+                 z - w
+             }",
+        );
+        gvn(&mut bodies[0]);
+        let assignments: Vec<String> = bodies[0].blocks[0]
+            .statements
+            .iter()
+            .filter_map(|statement| match &statement.kind {
+                StatementKind::Assign(..) => Some(statement.kind.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            assignments,
+            [
+                "_3 = Add(_1, _2)",
+                "_4 = _3",
+                "_5 = Mul(_4, const 2)",
+                "_6 = _5",
+                "_0 = Sub(_5, _6)",
+            ]
+        );
+    }
+}
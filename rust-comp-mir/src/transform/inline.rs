@@ -0,0 +1,183 @@
+use super::{operands_mut, places_mut};
+use crate::*;
+use std::collections::HashMap;
+
+/// The largest function that's inlined everywhere it's called.
+const INLINE_THRESHOLD: usize = 20;
+
+/// Replaces calls to small functions and to functions called only once with
+/// their bodies.  Recursive functions aren't inlined and inlined bodies
+/// aren't inlined into further.
+pub fn inline(bodies: &mut [Body]) {
+    let mut calls = HashMap::new();
+    for body in bodies.iter() {
+        for block in &body.blocks {
+            if let Some(callee) = callee(&block.terminator.kind) {
+                *calls.entry(callee).or_insert(0) += 1;
+            }
+        }
+    }
+    let callees: HashMap<String, Body> = bodies
+        .iter()
+        .filter(|body| {
+            !body
+                .blocks
+                .iter()
+                .any(|block| callee(&block.terminator.kind) == Some(&body.name))
+                && (cost(body) <= INLINE_THRESHOLD || calls.get(&body.name) == Some(&1))
+        })
+        .map(|body| (body.name.clone(), body.clone()))
+        .collect();
+
+    for body in bodies.iter_mut() {
+        for block in 0..body.blocks.len() {
+            let callee = match callee(&body.blocks[block].terminator.kind) {
+                Some(callee) if *callee != body.name => callees.get(callee),
+                _ => None,
+            };
+            if let Some(callee) = callee {
+                inline_call(body, BasicBlock(block), callee.clone());
+            }
+        }
+    }
+}
+
+/// The name of the function `terminator` calls directly.
+fn callee(terminator: &TerminatorKind) -> Option<&String> {
+    match terminator {
+        TerminatorKind::Call {
+            function: Operand::Constant(Constant::Function(name)),
+            ..
+        } => Some(name),
+        _ => None,
+    }
+}
+
+/// The number of statements other than storage markers and blocks.
+fn cost(body: &Body) -> usize {
+    let statements = body
+        .blocks
+        .iter()
+        .flat_map(|block| &block.statements)
+        .filter(|statement| match statement.kind {
+            StatementKind::Assign(..) => true,
+            StatementKind::StorageLive(_) | StatementKind::StorageDead(_) => false,
+            // Nothing has drop glue yet.
+            StatementKind::Drop { .. } | StatementKind::Nop => false,
+        })
+        .count();
+    statements + body.blocks.len()
+}
+
+/// Replaces the call ending `block` with `callee`'s blocks, whose locals and
+/// blocks are appended to `body`'s.
+fn inline_call(body: &mut Body, block: BasicBlock, mut callee: Body) {
+    let (arguments, destination, target) = match &body.block(block).terminator.kind {
+        TerminatorKind::Call {
+            arguments,
+            destination,
+            target,
+            ..
+        } => (arguments.clone(), destination.clone(), *target),
+        _ => unreachable!(),
+    };
+    let local_offset = body.locals.len();
+    let block_offset = body.blocks.len();
+    let local = |local: Local| Local(local.0 + local_offset);
+    operands_mut(&mut callee, |operand| {
+        if let Operand::Place(place) = operand {
+            place.local = local(place.local);
+        }
+    });
+    places_mut(&mut callee, |place| place.local = local(place.local));
+    for callee_block in &mut callee.blocks {
+        for statement in &mut callee_block.statements {
+            match &mut statement.kind {
+                StatementKind::StorageLive(l) | StatementKind::StorageDead(l) => *l = local(*l),
+                StatementKind::Assign(..) | StatementKind::Drop { .. } | StatementKind::Nop => {}
+            }
+        }
+        for successor in callee_block.terminator.kind.successors_mut() {
+            successor.0 += block_offset;
+        }
+        if callee_block.terminator.kind == TerminatorKind::Return {
+            callee_block.statements.push(Statement {
+                span: callee_block.terminator.span,
+                kind: StatementKind::Assign(
+                    destination.clone(),
+                    Rvalue::Use(Operand::Place(local(RETURN_PLACE).into())),
+                ),
+            });
+            callee_block.terminator.kind = TerminatorKind::Goto(target);
+        }
+    }
+
+    let span = body.block(block).terminator.span;
+    let caller_block = body.block_mut(block);
+    for (i, argument) in arguments.into_iter().enumerate() {
+        caller_block.statements.push(Statement {
+            span,
+            kind: StatementKind::Assign(local(Local(i + 1)).into(), Rvalue::Use(argument)),
+        });
+    }
+    caller_block.terminator.kind = TerminatorKind::Goto(BasicBlock(block_offset + START_BLOCK.0));
+    body.locals.extend(callee.locals);
+    body.blocks.extend(callee.blocks);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::lower_str;
+
+    #[test]
+    fn test_inline() {
+        let mut bodies = lower_str(
+            "fn double(x: i64) -> i64 { x + x }
+             fn fact(n: i64) -> i64 { if n == 0 { 1 } else { n * fact(n - 1) } }
+             fn f(a: i64) -> i64 { double(a) + fact(a) }",
+        );
+        inline(&mut bodies);
+        let calls = |body: &Body| {
+            body.blocks
+                .iter()
+                .filter_map(|block| callee(&block.terminator.kind).cloned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(calls(&bodies[1]), ["fact"]);
+        assert_eq!(calls(&bodies[2]), ["fact"]);
+        assert_eq!(
+            bodies[2].to_string(),
+            "\
+fn f(_1) {
+    let mut _0: i64;
+    let _1: i64; // a
+    let mut _2: i64;
+    let mut _3: i64;
+    let mut _4: i64;
+    let _5: i64; // x
+
+    bb0: {
+        _5 = _1;
+        goto -> bb3;
+    }
+
+    bb1: {
+        _3 = fact(_1) -> bb2;
+    }
+
+    bb2: {
+        _0 = Add(_2, _3);
+        return;
+    }
+
+    bb3: {
+        _4 = Add(_5, _5);
+        _2 = _4;
+        goto -> bb1;
+    }
+}
+"
+        );
+    }
+}
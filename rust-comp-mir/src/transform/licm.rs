@@ -0,0 +1,202 @@
+use super::dominators::{predecessors, Dominators};
+use super::ssa::{Definition, SsaLocals};
+use crate::*;
+
+/// Moves assignments in loops whose value doesn't change between iterations
+/// to a block before the loop.  Only locals in SSA form assigned arithmetic,
+/// comparisons, and casts that can't panic are moved.
+pub fn licm(body: &mut Body) {
+    let mut done = Vec::new();
+    // Moving statements and adding preheaders changes the analyses, so they
+    // are recomputed after each loop.
+    loop {
+        let dominators = Dominators::new(body);
+        let ssa = SsaLocals::new(body, &dominators);
+        let header = loops(body, &dominators)
+            .into_iter()
+            .find(|(header, _)| !done.contains(header));
+        let (header, blocks) = match header {
+            Some(header) => header,
+            None => break,
+        };
+        done.push(header);
+        let invariant = invariant_statements(body, &ssa, &blocks);
+        if invariant.is_empty() {
+            continue;
+        }
+        let preheader = preheader(body, header, &blocks);
+        // The statements are moved in the order they were found so the
+        // operands of each are assigned before it.
+        let hoisted: Vec<Statement> = invariant
+            .iter()
+            .map(|&(block, index)| body.block(block).statements[index].clone())
+            .collect();
+        let mut removed = invariant;
+        removed.sort_unstable_by(|a, b| b.cmp(a));
+        for (block, index) in removed {
+            body.block_mut(block).statements.remove(index);
+        }
+        body.block_mut(preheader).statements.extend(hoisted);
+    }
+}
+
+/// The natural loops of `body` as their header and the blocks in them.
+/// Loops sharing a header are merged.
+fn loops(body: &Body, dominators: &Dominators) -> Vec<(BasicBlock, Vec<BasicBlock>)> {
+    let predecessors = predecessors(body);
+    let mut loops: Vec<(BasicBlock, Vec<BasicBlock>)> = Vec::new();
+    for &latch in dominators.reverse_postorder() {
+        for header in body.block(latch).terminator.kind.successors() {
+            if !dominators.dominates(header, latch) {
+                continue;
+            }
+            let index = match loops.iter().position(|(other, _)| *other == header) {
+                Some(index) => index,
+                None => {
+                    loops.push((header, vec![header]));
+                    loops.len() - 1
+                }
+            };
+            let blocks = &mut loops[index].1;
+            let mut worklist = vec![latch];
+            while let Some(block) = worklist.pop() {
+                if blocks.contains(&block) {
+                    continue;
+                }
+                blocks.push(block);
+                worklist.extend(&predecessors[block.0]);
+            }
+        }
+    }
+    loops
+}
+
+/// The assignments in `blocks` whose operands are defined outside them or
+/// by other invariant assignments.
+fn invariant_statements(
+    body: &Body,
+    ssa: &SsaLocals,
+    blocks: &[BasicBlock],
+) -> Vec<(BasicBlock, usize)> {
+    let mut invariant: Vec<(BasicBlock, usize)> = Vec::new();
+    let mut hoisted: Vec<Local> = Vec::new();
+    let defined_outside = |local: Local, hoisted: &[Local]| match ssa.definition(local) {
+        Some(Definition::Argument) => true,
+        Some(Definition::Statement(block, _)) | Some(Definition::Terminator(block)) => {
+            !blocks.contains(&block) || hoisted.contains(&local)
+        }
+        None => false,
+    };
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &block in blocks {
+            for (index, statement) in body.block(block).statements.iter().enumerate() {
+                let (place, rvalue) = match &statement.kind {
+                    StatementKind::Assign(place, rvalue) => (place, rvalue),
+                    _ => continue,
+                };
+                if !place.projection.is_empty()
+                    || !ssa.is_ssa(place.local)
+                    || hoisted.contains(&place.local)
+                {
+                    continue;
+                }
+                match rvalue {
+                    Rvalue::Binary(op, ..) if *op != BinaryOp::Div => {}
+                    Rvalue::Overflows(..) | Rvalue::Cast(..) => {}
+                    _ => continue,
+                }
+                let operands_invariant =
+                    rvalue.operands().into_iter().all(|operand| match operand {
+                        Operand::Constant(_) => true,
+                        Operand::Place(place) => {
+                            place.projection.is_empty() && defined_outside(place.local, &hoisted)
+                        }
+                    });
+                if operands_invariant {
+                    invariant.push((block, index));
+                    hoisted.push(place.local);
+                    changed = true;
+                }
+            }
+        }
+    }
+    invariant
+}
+
+/// The block that jumps to `header` from outside the loop, adding one if
+/// there isn't a single such block ending in a `Goto`.
+fn preheader(body: &mut Body, header: BasicBlock, blocks: &[BasicBlock]) -> BasicBlock {
+    let outside: Vec<BasicBlock> = predecessors(body)[header.0]
+        .iter()
+        .copied()
+        .filter(|predecessor| !blocks.contains(predecessor))
+        .collect();
+    if let [predecessor] = outside[..] {
+        if body.block(predecessor).terminator.kind == TerminatorKind::Goto(header) {
+            return predecessor;
+        }
+    }
+    let preheader = BasicBlock(body.blocks.len());
+    for predecessor in outside {
+        for successor in body.block_mut(predecessor).terminator.kind.successors_mut() {
+            if *successor == header {
+                *successor = preheader;
+            }
+        }
+    }
+    let span = body.block(header).terminator.span;
+    body.blocks.push(BasicBlockData {
+        statements: Vec::new(),
+        terminator: Terminator {
+            span,
+            kind: TerminatorKind::Goto(header),
+        },
+    });
+    preheader
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::lower_str;
+    use crate::transform::into_ssa;
+
+    #[test]
+    fn test_licm() {
+        let mut bodies = lower_str(
+            "fn f(a: i64, n: i64) -> i64 {
+                 let mut i = 0;
+                 let mut sum = 0;
+                 while i != n {
+                     let k = a * 3;
+                     let l = k + 1;
+                     sum = sum + l;
+                     i = i + 1;
+                 }
+                 sum
+             }",
+        );
+        into_ssa(&mut bodies[0]);
+        let body = &bodies[0];
+        let header = loops(body, &Dominators::new(body))[0].0;
+        licm(&mut bodies[0]);
+        let body = &bodies[0];
+        let predecessors = predecessors(body);
+        let preheader = predecessors[header.0]
+            .iter()
+            .copied()
+            .find(|predecessor| !Dominators::new(body).dominates(header, *predecessor))
+            .unwrap();
+        let hoisted: Vec<String> = body
+            .block(preheader)
+            .statements
+            .iter()
+            .map(|statement| statement.kind.to_string())
+            .filter(|statement| statement.contains("Mul") || statement.contains("Add"))
+            .collect();
+        assert_eq!(hoisted.len(), 2);
+        assert!(hoisted[0].contains("Mul(_1, const 3)"));
+    }
+}
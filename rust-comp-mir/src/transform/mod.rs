@@ -0,0 +1,349 @@
+mod branch_fold;
+mod const_fold;
+mod copy_prop;
+mod dominators;
+mod gvn;
+mod inline;
+mod licm;
+mod ssa;
+mod unreachable_blocks;
+mod unused_locals;
+
+pub use self::branch_fold::branch_fold;
+pub use self::const_fold::const_fold;
+pub use self::copy_prop::copy_prop;
+pub use self::gvn::gvn;
+pub use self::inline::inline;
+pub use self::licm::licm;
+pub use self::ssa::into_ssa;
+pub use self::unreachable_blocks::remove_unreachable_blocks;
+pub use self::unused_locals::remove_unused_locals;
+
+use crate::*;
+
+/// A transformation of the program that doesn't change its behavior.
+#[derive(Clone, Copy)]
+pub struct Pass {
+    pub name: &'static str,
+    /// The lowest optimization level that runs the pass.
+    pub level: u32,
+    pub run: fn(&mut [Body]),
+}
+
+/// Every pass in the order they run.
+pub fn all_passes() -> Vec<Pass> {
+    vec![
+        Pass {
+            name: "inline",
+            level: 2,
+            run: inline,
+        },
+        Pass {
+            name: "ssa",
+            level: 2,
+            run: |bodies| bodies.iter_mut().for_each(into_ssa),
+        },
+        Pass {
+            name: "const_fold",
+            level: 1,
+            run: |bodies| bodies.iter_mut().for_each(const_fold),
+        },
+        Pass {
+            name: "gvn",
+            level: 2,
+            run: |bodies| bodies.iter_mut().for_each(gvn),
+        },
+        Pass {
+            name: "copy_prop",
+            level: 2,
+            run: |bodies| bodies.iter_mut().for_each(copy_prop),
+        },
+        Pass {
+            name: "licm",
+            level: 2,
+            run: |bodies| bodies.iter_mut().for_each(licm),
+        },
+        Pass {
+            name: "branch_fold",
+            level: 1,
+            run: |bodies| bodies.iter_mut().for_each(branch_fold),
+        },
+        Pass {
+            name: "remove_unreachable_blocks",
+            level: 1,
+            run: |bodies| bodies.iter_mut().for_each(remove_unreachable_blocks),
+        },
+        Pass {
+            name: "remove_unused_locals",
+            level: 1,
+            run: |bodies| bodies.iter_mut().for_each(remove_unused_locals),
+        },
+    ]
+}
+
+/// The passes run at optimization `level` with the passes named in
+/// `enabled` added and those in `disabled` removed.  Returns the first
+/// unknown name as an error.
+pub fn passes(level: u32, enabled: &[String], disabled: &[String]) -> Result<Vec<Pass>, String> {
+    let all = all_passes();
+    for name in enabled.iter().chain(disabled) {
+        if !all.iter().any(|pass| pass.name == name) {
+            return Err(name.clone());
+        }
+    }
+    Ok(all
+        .into_iter()
+        .filter(|pass| pass.level <= level || enabled.iter().any(|name| name == pass.name))
+        .filter(|pass| !disabled.iter().any(|name| name == pass.name))
+        .collect())
+}
+
+/// Runs the passes for `level` on `bodies`.
+pub fn optimize(bodies: &mut [Body], level: u32) {
+    for pass in passes(level, &[], &[]).unwrap() {
+        (pass.run)(bodies);
+    }
+}
+
+/// Calls `f` on every operand in `body`.
+fn operands_mut(body: &mut Body, mut f: impl FnMut(&mut Operand)) {
+    for block in &mut body.blocks {
+        for statement in &mut block.statements {
+            if let StatementKind::Assign(_, rvalue) = &mut statement.kind {
+                rvalue.operands_mut().into_iter().for_each(&mut f);
+            }
+        }
+        block
+            .terminator
+            .kind
+            .operands_mut()
+            .into_iter()
+            .for_each(&mut f);
+    }
+}
+
+/// Calls `f` on every place in `body` other than those of operands.
+fn places_mut(body: &mut Body, mut f: impl FnMut(&mut Place)) {
+    for block in &mut body.blocks {
+        for statement in &mut block.statements {
+            match &mut statement.kind {
+                StatementKind::Assign(place, rvalue) => {
+                    f(place);
+                    rvalue.place_mut().into_iter().for_each(&mut f);
+                }
+                StatementKind::Drop { place, .. } => f(place),
+                StatementKind::StorageLive(_)
+                | StatementKind::StorageDead(_)
+                | StatementKind::Nop => {}
+            }
+        }
+        if let TerminatorKind::Call { destination, .. } = &mut block.terminator.kind {
+            f(destination);
+        }
+    }
+}
+
+/// Whether assigning to `place` reads its local because it writes through
+/// a pointer held by it.
+fn writes_through_pointer(place: &Place) -> bool {
+    place.projection.contains(&Projection::Deref)
+}
+
+/// The places `statement` reads.
+fn statement_uses(statement: &StatementKind) -> Vec<&Place> {
+    match statement {
+        StatementKind::Assign(place, rvalue) => {
+            let mut uses = operand_places(rvalue.operands());
+            uses.extend(rvalue.place());
+            if writes_through_pointer(place) {
+                uses.push(place);
+            }
+            uses
+        }
+        StatementKind::Drop { place, .. } => vec![place],
+        StatementKind::StorageLive(_) | StatementKind::StorageDead(_) | StatementKind::Nop => {
+            vec![]
+        }
+    }
+}
+
+fn statement_uses_mut(statement: &mut StatementKind) -> Vec<&mut Place> {
+    match statement {
+        StatementKind::Assign(place, rvalue) => {
+            let mut uses = Vec::new();
+            if writes_through_pointer(place) {
+                uses.push(place);
+            }
+            // An rvalue reads either operands or a place.
+            if rvalue.place().is_some() {
+                uses.extend(rvalue.place_mut());
+            } else {
+                uses.extend(operand_places_mut(rvalue.operands_mut()));
+            }
+            uses
+        }
+        StatementKind::Drop { place, .. } => vec![place],
+        StatementKind::StorageLive(_) | StatementKind::StorageDead(_) | StatementKind::Nop => {
+            vec![]
+        }
+    }
+}
+
+/// The places `terminator` reads other than the return place.
+fn terminator_uses(terminator: &TerminatorKind) -> Vec<&Place> {
+    let mut uses = operand_places(terminator.operands());
+    if let TerminatorKind::Call { destination, .. } = terminator {
+        if writes_through_pointer(destination) {
+            uses.push(destination);
+        }
+    }
+    uses
+}
+
+fn terminator_uses_mut(terminator: &mut TerminatorKind) -> Vec<&mut Place> {
+    match terminator {
+        TerminatorKind::Call {
+            function,
+            arguments,
+            destination,
+            ..
+        } => {
+            let operands = Some(function).into_iter().chain(arguments).collect();
+            let mut uses = operand_places_mut(operands);
+            if writes_through_pointer(destination) {
+                uses.push(destination);
+            }
+            uses
+        }
+        _ => operand_places_mut(terminator.operands_mut()),
+    }
+}
+
+/// The local assigned as a whole by a statement or terminator.
+fn statement_definition(statement: &StatementKind) -> Option<Local> {
+    match statement {
+        StatementKind::Assign(place, _) if place.projection.is_empty() => Some(place.local),
+        _ => None,
+    }
+}
+
+fn terminator_definition(terminator: &TerminatorKind) -> Option<Local> {
+    match terminator {
+        TerminatorKind::Call { destination, .. } if destination.projection.is_empty() => {
+            Some(destination.local)
+        }
+        _ => None,
+    }
+}
+
+/// The locals that are borrowed or partially assigned, which passes can't
+/// track the value of.
+fn untracked_locals(body: &Body) -> Vec<bool> {
+    let mut untracked = vec![false; body.locals.len()];
+    untracked[RETURN_PLACE.0] = true;
+    let partial = |place: &Place| !place.projection.is_empty() && !writes_through_pointer(place);
+    for block in &body.blocks {
+        for statement in &block.statements {
+            if let StatementKind::Assign(place, rvalue) = &statement.kind {
+                if partial(place) {
+                    untracked[place.local.0] = true;
+                }
+                match rvalue {
+                    Rvalue::Ref(borrowed)
+                    | Rvalue::RefMut(borrowed)
+                    | Rvalue::RawConst(borrowed)
+                    | Rvalue::RawMut(borrowed) => untracked[borrowed.local.0] = true,
+                    _ => {}
+                }
+            }
+        }
+        if let TerminatorKind::Call { destination, .. } = &block.terminator.kind {
+            if partial(destination) {
+                untracked[destination.local.0] = true;
+            }
+        }
+    }
+    untracked
+}
+
+fn operand_places(operands: Vec<&Operand>) -> Vec<&Place> {
+    operands
+        .into_iter()
+        .filter_map(|operand| match operand {
+            Operand::Place(place) => Some(place),
+            Operand::Constant(_) => None,
+        })
+        .collect()
+}
+
+fn operand_places_mut(operands: Vec<&mut Operand>) -> Vec<&mut Place> {
+    operands
+        .into_iter()
+        .filter_map(|operand| match operand {
+            Operand::Place(place) => Some(place),
+            Operand::Constant(_) => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::lower_str;
+
+    #[test]
+    fn test_optimize() {
+        let mut bodies = lower_str(
+            "fn f(a: i64) -> i64 {
+                 let unused = a * 7;
+                 let k = 6 * 7;
+                 if k == 42 { a + k } else { a - 1 }
+             }",
+        );
+        let unoptimized = bodies.clone();
+        optimize(&mut bodies, 0);
+        assert_eq!(bodies, unoptimized);
+        optimize(&mut bodies, 1);
+        assert_eq!(
+            bodies[0].to_string(),
+            "\
+fn f(_1) {
+    let mut _0: i64;
+    let _1: i64; // a
+
+    bb0: {
+        goto -> bb2;
+    }
+
+    bb1: {
+        return;
+    }
+
+    bb2: {
+        _0 = Add(_1, const 42);
+        goto -> bb1;
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_passes() {
+        let names = |passes: Vec<Pass>| passes.iter().map(|pass| pass.name).collect::<Vec<_>>();
+        assert!(passes(0, &[], &[]).unwrap().is_empty());
+        assert_eq!(
+            names(passes(1, &["gvn".to_string()], &["branch_fold".to_string()]).unwrap()),
+            [
+                "const_fold",
+                "gvn",
+                "remove_unreachable_blocks",
+                "remove_unused_locals"
+            ]
+        );
+        assert_eq!(
+            passes(2, &[], &["fold".to_string()]).err(),
+            Some("fold".to_string())
+        );
+    }
+}
@@ -0,0 +1,434 @@
+use super::dominators::{predecessors, Dominators};
+use super::*;
+
+/// A join of the values a local has at the end of each predecessor.
+struct Phi {
+    original: Local,
+    destination: Local,
+    sources: Vec<(BasicBlock, Local)>,
+}
+
+/// Renames the locals that are never borrowed or partially assigned so that
+/// each assignment defines a new local.  Where definitions meet, the joined
+/// value is copied into one local at the end of each predecessor, splitting
+/// the edge if the predecessor has other successors.
+pub fn into_ssa(body: &mut Body) {
+    let dominators = Dominators::new(body);
+    let untracked = untracked_locals(body);
+    let live_in = live_in(body);
+    let mut phis = place_phis(body, &dominators, &untracked, &live_in);
+    rename(body, &dominators, &untracked, &mut phis);
+    for block in &mut body.blocks {
+        block.statements.retain(|statement| match &statement.kind {
+            StatementKind::StorageLive(local) | StatementKind::StorageDead(local) => {
+                untracked[local.0]
+            }
+            StatementKind::Assign(..) | StatementKind::Drop { .. } | StatementKind::Nop => true,
+        });
+    }
+    destruct_phis(body, &dominators, phis);
+}
+
+/// The locals read in each block before being assigned.
+fn live_in(body: &Body) -> Vec<Vec<bool>> {
+    let n = body.locals.len();
+    let mut uses = vec![vec![false; n]; body.blocks.len()];
+    let mut definitions = vec![vec![false; n]; body.blocks.len()];
+    for (i, block) in body.blocks.iter().enumerate() {
+        let (uses, definitions) = (&mut uses[i], &mut definitions[i]);
+        let mut read = |local: Local, definitions: &[bool]| {
+            if !definitions[local.0] {
+                uses[local.0] = true;
+            }
+        };
+        for statement in &block.statements {
+            for place in statement_uses(&statement.kind) {
+                read(place.local, definitions);
+            }
+            if let Some(local) = statement_definition(&statement.kind) {
+                definitions[local.0] = true;
+            }
+        }
+        for place in terminator_uses(&block.terminator.kind) {
+            read(place.local, definitions);
+        }
+        if let Some(local) = terminator_definition(&block.terminator.kind) {
+            definitions[local.0] = true;
+        }
+    }
+
+    let mut live_in = uses.clone();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (i, block) in body.blocks.iter().enumerate().rev() {
+            for successor in block.terminator.kind.successors() {
+                for local in 0..n {
+                    if live_in[successor.0][local] && !definitions[i][local] && !live_in[i][local] {
+                        live_in[i][local] = true;
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+    live_in
+}
+
+/// Places phis for each tracked local on the iterated dominance frontier of
+/// its definitions where it's live.
+fn place_phis(
+    body: &Body,
+    dominators: &Dominators,
+    untracked: &[bool],
+    live_in: &[Vec<bool>],
+) -> Vec<Vec<Phi>> {
+    let mut definitions = vec![Vec::new(); body.locals.len()];
+    for local in body.arguments() {
+        definitions[local.0].push(START_BLOCK);
+    }
+    for (i, block) in body.blocks.iter().enumerate() {
+        let defined = block
+            .statements
+            .iter()
+            .filter_map(|statement| statement_definition(&statement.kind))
+            .chain(terminator_definition(&block.terminator.kind));
+        for local in defined {
+            definitions[local.0].push(BasicBlock(i));
+        }
+    }
+
+    let frontiers = dominators.frontiers(body);
+    let mut phis: Vec<Vec<Phi>> = body.blocks.iter().map(|_| Vec::new()).collect();
+    for (local, mut worklist) in definitions.into_iter().enumerate() {
+        if untracked[local] {
+            continue;
+        }
+        let mut has_phi = vec![false; body.blocks.len()];
+        while let Some(block) = worklist.pop() {
+            for &frontier in &frontiers[block.0] {
+                if has_phi[frontier.0] || !live_in[frontier.0][local] {
+                    continue;
+                }
+                has_phi[frontier.0] = true;
+                phis[frontier.0].push(Phi {
+                    original: Local(local),
+                    destination: Local(local),
+                    sources: Vec::new(),
+                });
+                worklist.push(frontier);
+            }
+        }
+    }
+    phis
+}
+
+/// Gives every definition of a tracked local its own local and renames
+/// uses to the definition that reaches them, walking the dominator tree.
+fn rename(body: &mut Body, dominators: &Dominators, untracked: &[bool], phis: &mut [Vec<Phi>]) {
+    enum Visit {
+        Enter(BasicBlock),
+        Leave(Vec<Local>),
+    }
+
+    let mut stacks = vec![Vec::new(); body.locals.len()];
+    // The first definition of each local keeps its original local.
+    let mut renamed = vec![false; body.locals.len()];
+    for local in body.arguments() {
+        stacks[local.0].push(local);
+        renamed[local.0] = true;
+    }
+    let mut define = |body: &mut Body, stacks: &mut Vec<Vec<Local>>, local: Local| {
+        let new = if renamed[local.0] {
+            body.locals.push(body.locals[local.0].clone());
+            Local(body.locals.len() - 1)
+        } else {
+            renamed[local.0] = true;
+            local
+        };
+        stacks[local.0].push(new);
+        new
+    };
+    let current = |stacks: &[Vec<Local>], local: Local| *stacks[local.0].last().unwrap_or(&local);
+
+    let mut visits = vec![Visit::Enter(START_BLOCK)];
+    while let Some(visit) = visits.pop() {
+        let block = match visit {
+            Visit::Enter(block) => block,
+            Visit::Leave(defined) => {
+                for local in defined {
+                    stacks[local.0].pop();
+                }
+                continue;
+            }
+        };
+        let mut defined = Vec::new();
+        for phi in &mut phis[block.0] {
+            phi.destination = define(body, &mut stacks, phi.original);
+            defined.push(phi.original);
+        }
+        for i in 0..body.block(block).statements.len() {
+            let kind = &mut body.block_mut(block).statements[i].kind;
+            for place in statement_uses_mut(kind) {
+                if !untracked[place.local.0] {
+                    place.local = current(&stacks, place.local);
+                }
+            }
+            if let Some(local) = statement_definition(kind) {
+                if !untracked[local.0] {
+                    let new = define(body, &mut stacks, local);
+                    if let StatementKind::Assign(place, _) =
+                        &mut body.block_mut(block).statements[i].kind
+                    {
+                        place.local = new;
+                    }
+                    defined.push(local);
+                }
+            }
+        }
+        let kind = &mut body.block_mut(block).terminator.kind;
+        for place in terminator_uses_mut(kind) {
+            if !untracked[place.local.0] {
+                place.local = current(&stacks, place.local);
+            }
+        }
+        if let Some(local) = terminator_definition(kind) {
+            if !untracked[local.0] {
+                let new = define(body, &mut stacks, local);
+                if let TerminatorKind::Call { destination, .. } =
+                    &mut body.block_mut(block).terminator.kind
+                {
+                    destination.local = new;
+                }
+                defined.push(local);
+            }
+        }
+
+        let mut successors = body.block(block).terminator.kind.successors();
+        successors.dedup();
+        for successor in successors {
+            for phi in &mut phis[successor.0] {
+                if !phi.sources.iter().any(|(source, _)| *source == block) {
+                    phi.sources.push((block, current(&stacks, phi.original)));
+                }
+            }
+        }
+        visits.push(Visit::Leave(defined));
+        for &child in dominators.children(block).iter().rev() {
+            visits.push(Visit::Enter(child));
+        }
+    }
+}
+
+/// Replaces the phis with copies at the end of their predecessors.
+fn destruct_phis(body: &mut Body, dominators: &Dominators, phis: Vec<Vec<Phi>>) {
+    let predecessors = predecessors(body);
+    for (block, phis) in phis.into_iter().enumerate() {
+        if phis.is_empty() || !dominators.is_reachable(BasicBlock(block)) {
+            continue;
+        }
+        let span = body.blocks[block].terminator.span;
+        let mut done = Vec::new();
+        for &predecessor in &predecessors[block] {
+            if !dominators.is_reachable(predecessor) || done.contains(&predecessor) {
+                continue;
+            }
+            done.push(predecessor);
+            let copies: Vec<(Local, Local)> = phis
+                .iter()
+                .map(|phi| {
+                    let (_, source) = phi.sources.iter().find(|(b, _)| *b == predecessor).unwrap();
+                    (phi.destination, *source)
+                })
+                .collect();
+            let copy = |destination: Local, source: Local| Statement {
+                span,
+                kind: StatementKind::Assign(
+                    destination.into(),
+                    Rvalue::Use(Operand::Place(source.into())),
+                ),
+            };
+            let mut statements = Vec::new();
+            // The copies happen at once, so a source that's another
+            // destination must be read before it's overwritten.
+            if copies
+                .iter()
+                .any(|(_, source)| copies.iter().any(|(destination, _)| destination == source))
+            {
+                let mut temporaries = Vec::new();
+                for &(destination, source) in &copies {
+                    body.locals.push(body.locals[destination.0].clone());
+                    let temporary = Local(body.locals.len() - 1);
+                    statements.push(copy(temporary, source));
+                    temporaries.push((destination, temporary));
+                }
+                for (destination, temporary) in temporaries {
+                    statements.push(copy(destination, temporary));
+                }
+            } else {
+                for (destination, source) in copies {
+                    if destination != source {
+                        statements.push(copy(destination, source));
+                    }
+                }
+            }
+
+            let edge = BasicBlock(body.blocks.len());
+            let terminator = &mut body.blocks[predecessor.0].terminator.kind;
+            if let TerminatorKind::Goto(_) = terminator {
+                body.blocks[predecessor.0].statements.extend(statements);
+            } else {
+                for successor in terminator.successors_mut() {
+                    if successor.0 == block {
+                        *successor = edge;
+                    }
+                }
+                body.blocks.push(BasicBlockData {
+                    statements,
+                    terminator: Terminator {
+                        span,
+                        kind: TerminatorKind::Goto(BasicBlock(block)),
+                    },
+                });
+            }
+        }
+    }
+}
+
+/// Where a local in SSA form is assigned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Definition {
+    Argument,
+    Statement(BasicBlock, usize),
+    Terminator(BasicBlock),
+}
+
+/// The locals that are assigned once, at a definition that dominates every
+/// use, and never borrowed or partially assigned.
+pub struct SsaLocals {
+    definitions: Vec<Option<Definition>>,
+}
+
+impl SsaLocals {
+    pub fn new(body: &Body, dominators: &Dominators) -> Self {
+        let untracked = untracked_locals(body);
+        let mut definitions = vec![None; body.locals.len()];
+        let mut disqualified = untracked;
+        for local in body.arguments() {
+            definitions[local.0] = Some(Definition::Argument);
+        }
+        let mut define = |local: Local, definition: Definition| {
+            if definitions[local.0].is_some() {
+                disqualified[local.0] = true;
+            }
+            definitions[local.0] = Some(definition);
+        };
+        for (i, block) in body.blocks.iter().enumerate() {
+            let block_index = BasicBlock(i);
+            for (j, statement) in block.statements.iter().enumerate() {
+                if let Some(local) = statement_definition(&statement.kind) {
+                    define(local, Definition::Statement(block_index, j));
+                }
+            }
+            if let Some(local) = terminator_definition(&block.terminator.kind) {
+                define(local, Definition::Terminator(block_index));
+            }
+        }
+
+        let dominated = |definition: Option<Definition>, block: BasicBlock, index: usize| {
+            let strictly = |other: BasicBlock| other != block && dominators.dominates(other, block);
+            match definition {
+                None => false,
+                Some(Definition::Argument) => true,
+                Some(Definition::Statement(other, j)) => {
+                    (other == block && j < index) || strictly(other)
+                }
+                Some(Definition::Terminator(other)) => strictly(other),
+            }
+        };
+        for (i, block) in body.blocks.iter().enumerate() {
+            let block_index = BasicBlock(i);
+            let uses = block
+                .statements
+                .iter()
+                .enumerate()
+                .flat_map(|(j, statement)| {
+                    statement_uses(&statement.kind)
+                        .into_iter()
+                        .map(move |place| (place, j))
+                })
+                .chain(
+                    terminator_uses(&block.terminator.kind)
+                        .into_iter()
+                        .map(|place| (place, block.statements.len())),
+                );
+            for (place, index) in uses {
+                if !dominated(definitions[place.local.0], block_index, index) {
+                    disqualified[place.local.0] = true;
+                }
+            }
+        }
+        for (definition, disqualified) in definitions.iter_mut().zip(disqualified) {
+            if disqualified {
+                *definition = None;
+            }
+        }
+        SsaLocals { definitions }
+    }
+
+    pub fn is_ssa(&self, local: Local) -> bool {
+        self.definitions[local.0].is_some()
+    }
+
+    pub fn definition(&self, local: Local) -> Option<Definition> {
+        self.definitions[local.0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::lower_str;
+
+    #[test]
+    fn test_into_ssa_of_loop() {
+        let mut bodies = lower_str(
+            "fn f(n: i64) -> i64 {
+                 let mut i = 0;
+                 let mut sum = 0;
+                 while i != n { sum = sum + i; i = i + 1; }
+                 sum
+             }",
+        );
+        into_ssa(&mut bodies[0]);
+        let body = &bodies[0];
+        let ssa = SsaLocals::new(body, &Dominators::new(body));
+        let mut assigned = vec![0; body.locals.len()];
+        for statement in body.blocks.iter().flat_map(|block| &block.statements) {
+            if let StatementKind::Assign(place, _) = &statement.kind {
+                assigned[place.local.0] += 1;
+            }
+        }
+        // Only the locals joined at the loop header are assigned more than
+        // once, by the copies on the entry edge and at the end of the body.
+        assert_eq!(assigned.iter().filter(|count| **count > 1).count(), 2);
+        assert!(body
+            .locals
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(i, _)| assigned[*i] == 1)
+            .all(|(i, _)| ssa.is_ssa(Local(i))));
+    }
+
+    #[test]
+    fn test_ssa_locals() {
+        let bodies = lower_str("fn f(a: i64) -> i64 { let mut x = a; let r = &mut x; *r = 1; x }");
+        let body = &bodies[0];
+        let ssa = SsaLocals::new(body, &Dominators::new(body));
+        assert_eq!(ssa.definition(Local(1)), Some(Definition::Argument));
+        assert!(!ssa.is_ssa(RETURN_PLACE));
+        assert!(!ssa.is_ssa(Local(2)));
+        assert!(ssa.is_ssa(Local(3)));
+    }
+}
@@ -0,0 +1,59 @@
+use crate::*;
+
+/// Removes the blocks that can't be reached from the start block.  The
+/// order of the remaining blocks is kept.
+pub fn remove_unreachable_blocks(body: &mut Body) {
+    let mut reachable = vec![false; body.blocks.len()];
+    let mut worklist = vec![START_BLOCK];
+    while let Some(block) = worklist.pop() {
+        if !reachable[block.0] {
+            reachable[block.0] = true;
+            worklist.extend(body.block(block).terminator.kind.successors());
+        }
+    }
+
+    let mut renumbered = Vec::new();
+    let mut next = 0;
+    for &reachable in &reachable {
+        renumbered.push(BasicBlock(next));
+        next += reachable as usize;
+    }
+    let mut index = 0;
+    body.blocks.retain(|_| {
+        index += 1;
+        reachable[index - 1]
+    });
+    for block in &mut body.blocks {
+        for successor in block.terminator.kind.successors_mut() {
+            *successor = renumbered[successor.0];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::lower_str;
+    use crate::transform::branch_fold;
+
+    #[test]
+    fn test_remove_unreachable_blocks() {
+        let mut bodies = lower_str(
+            "fn f(x: i32) -> i32 {
+                 if false { return 1; }
+                 if x == 0 { 2 } else { 3 }
+             }",
+        );
+        let blocks = bodies[0].blocks.len();
+        branch_fold(&mut bodies[0]);
+        remove_unreachable_blocks(&mut bodies[0]);
+        let body = &bodies[0];
+        assert!(body.blocks.len() < blocks);
+        for block in &body.blocks {
+            for successor in block.terminator.kind.successors() {
+                assert!(successor.0 < body.blocks.len());
+            }
+        }
+        assert!(!body.to_string().contains("const 1"));
+    }
+}
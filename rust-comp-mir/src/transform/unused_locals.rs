@@ -0,0 +1,138 @@
+use super::{operands_mut, places_mut};
+use crate::*;
+
+/// Removes the assignments to and declarations of locals that are never
+/// read.  The return place and arguments are kept.  Call destinations are
+/// kept since the call can't be removed.
+pub fn remove_unused_locals(body: &mut Body) {
+    loop {
+        let used = used_locals(body);
+        if used.iter().all(|used| *used) {
+            break;
+        }
+        for block in &mut body.blocks {
+            block.statements.retain(|statement| match &statement.kind {
+                StatementKind::Assign(place, _) => used[place.local.0],
+                StatementKind::StorageLive(local) | StatementKind::StorageDead(local) => {
+                    used[local.0]
+                }
+                StatementKind::Drop { .. } | StatementKind::Nop => true,
+            });
+        }
+
+        let mut renumbered = Vec::new();
+        let mut next = 0;
+        for &used in &used {
+            renumbered.push(Local(next));
+            next += used as usize;
+        }
+        let mut index = 0;
+        body.locals.retain(|_| {
+            index += 1;
+            used[index - 1]
+        });
+        operands_mut(body, |operand| {
+            if let Operand::Place(place) = operand {
+                place.local = renumbered[place.local.0];
+            }
+        });
+        places_mut(body, |place| place.local = renumbered[place.local.0]);
+        for block in &mut body.blocks {
+            for statement in &mut block.statements {
+                match &mut statement.kind {
+                    StatementKind::StorageLive(local) | StatementKind::StorageDead(local) => {
+                        *local = renumbered[local.0]
+                    }
+                    StatementKind::Assign(..) | StatementKind::Drop { .. } | StatementKind::Nop => {
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Whether each local is read.  Assigning to a local doesn't read it unless
+/// the assignment is through a pointer it holds.
+fn used_locals(body: &Body) -> Vec<bool> {
+    let mut used = vec![false; body.locals.len()];
+    for local in body.arguments().chain(Some(RETURN_PLACE)) {
+        used[local.0] = true;
+    }
+    let mut read = |place: &Place| used[place.local.0] = true;
+    for block in &body.blocks {
+        for statement in &block.statements {
+            match &statement.kind {
+                StatementKind::Assign(place, rvalue) => {
+                    if place.projection.contains(&Projection::Deref) {
+                        read(place);
+                    }
+                    for operand in rvalue.operands() {
+                        if let Operand::Place(place) = operand {
+                            read(place);
+                        }
+                    }
+                    rvalue.place().into_iter().for_each(&mut read);
+                }
+                StatementKind::Drop { place, .. } => read(place),
+                StatementKind::StorageLive(_)
+                | StatementKind::StorageDead(_)
+                | StatementKind::Nop => {}
+            }
+        }
+        for operand in block.terminator.kind.operands() {
+            if let Operand::Place(place) = operand {
+                read(place);
+            }
+        }
+        if let TerminatorKind::Call { destination, .. } = &block.terminator.kind {
+            read(destination);
+        }
+    }
+    used
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::lower_str;
+    use crate::transform::const_fold;
+
+    #[test]
+    fn test_remove_unused_locals() {
+        let mut bodies = lower_str(
+            "fn f(a: i32) -> i32 {
+                 let x = 2 + 3;
+                 let unused = a * 2;
+                 let mut p = 0;
+                 let r = &mut p;
+                 *r = 1;
+                 x + p
+             }",
+        );
+        const_fold(&mut bodies[0]);
+        remove_unused_locals(&mut bodies[0]);
+        assert_eq!(
+            bodies[0].to_string(),
+            "\
+fn f(_1) {
+    let mut _0: i32;
+    let _1: i32; // a
+    let mut _2: i32; // p
+    let _3; // r
+
+    bb0: {
+        StorageLive(_2);
+        _2 = const 0;
+        StorageLive(_3);
+        _3 = &mut _2;
+        (*_3) = const 1;
+        _0 = Add(const 5, _2);
+        StorageDead(_3);
+        StorageDead(_2);
+        return;
+    }
+}
+"
+        );
+    }
+}
@@ -1,5 +1,7 @@
 #![feature(custom_attribute)]
 
+use rust_comp_core::diagnostic::{ColorChoice, ErrorFormat};
+use rust_comp_core::profile::Profile;
 use structopt::StructOpt;
 
 #[derive(StructOpt, Debug)]
@@ -14,7 +16,71 @@ pub struct Args {
 }
 
 #[derive(StructOpt, Debug)]
-pub struct Opt {}
+pub struct Opt {
+    /// Print the MIR of each function after lowering.
+    #[structopt(long = "dump-mir")]
+    pub dump_mir: bool,
+    /// Print the MIR of each function before and after each optimization
+    /// pass.
+    #[structopt(long = "dump-passes")]
+    pub dump_passes: bool,
+    /// The optimization level.  `0` doesn't optimize, `1` folds constants
+    /// and removes dead code, and `2` also inlines functions and runs the
+    /// passes based on SSA form.
+    #[structopt(short = "O", default_value = "0")]
+    pub opt_level: u32,
+    /// Run the named optimization pass regardless of the level.
+    #[structopt(long = "enable-pass", number_of_values = 1)]
+    pub enable_passes: Vec<String>,
+    /// Don't run the named optimization pass.
+    #[structopt(long = "disable-pass", number_of_values = 1)]
+    pub disable_passes: Vec<String>,
+    /// Interpret `main` and print its result.
+    #[structopt(long = "run")]
+    pub run: bool,
+    /// Write an x86-64 ELF object file to this path.
+    #[structopt(short = "o", long = "output")]
+    pub output: Option<String>,
+    /// Write the program translated to C99 to this path.
+    #[structopt(long = "emit-c")]
+    pub emit_c: Option<String>,
+    /// Write a WebAssembly module to this path.  Paths ending in `.wat` get
+    /// the text format.
+    #[structopt(long = "emit-wasm")]
+    pub emit_wasm: Option<String>,
+    /// `debug` panics on arithmetic overflow and `release` wraps.
+    #[structopt(long = "profile", default_value = "debug")]
+    pub profile: Profile,
+    /// `human` prints errors with snippets of the code and `json` prints
+    /// one JSON object per line as described in `diagnostics.md`.
+    #[structopt(long = "error-format", default_value = "human")]
+    pub error_format: ErrorFormat,
+    /// Color errors with `always`, `never`, or `auto`, which colors them
+    /// when stderr is a terminal.
+    #[structopt(long = "color", default_value = "auto")]
+    pub color: ColorChoice,
+    /// Apply the fixes suggested for errors that are certain to be right
+    /// to the source files.
+    #[structopt(long = "fix")]
+    pub fix: bool,
+    /// Don't report the named lint.
+    #[structopt(short = "A", long = "allow", number_of_values = 1)]
+    pub allow: Vec<String>,
+    /// Report the named lint as a warning.
+    #[structopt(short = "W", long = "warn", number_of_values = 1)]
+    pub warn: Vec<String>,
+    /// Report the named lint as an error.
+    #[structopt(short = "D", long = "deny", number_of_values = 1)]
+    pub deny: Vec<String>,
+    /// Report the named lint as an error and don't let attributes lower
+    /// its level.
+    #[structopt(short = "F", long = "forbid", number_of_values = 1)]
+    pub forbid: Vec<String>,
+    /// Print a detailed explanation of an error code, such as `RC0001`,
+    /// instead of compiling.
+    #[structopt(long = "explain")]
+    pub explain: Option<String>,
+}
 
 pub fn parse() -> Args {
     Args::from_args()
@@ -1,86 +1,965 @@
+use rust_comp_check::lint::{Level, LintStore};
+use rust_comp_check::{cast, divergence, integers, moves, mutability, question, scope};
+use rust_comp_core::codes;
 use rust_comp_core::diagnostic::*;
+use rust_comp_core::pos::Span;
+use rust_comp_core::source_map::{FileName, SourceMap};
+use rust_comp_front::token::TokenKind;
 use rust_comp_front::*;
-use rust_comp_opt::Args;
+use rust_comp_mir::borrowck;
+use rust_comp_mir::transform::Pass;
+use rust_comp_opt::{Args, Opt};
+use rust_comp_syntax::TopLevel;
 use std::time;
 
 pub enum Error {
     File(String),
+    Write(String),
+    UnknownPass(String),
+    UnknownCode(String),
+    UnknownLint(String),
     Handled,
 }
 
 pub fn run(args: Args) -> Result<(), Error> {
+    run_with_lints(args, LintStore::new())
+}
+
+/// Like `run` but checks the lints in `lints`, which can have lints
+/// registered besides the builtin ones.
+pub fn run_with_lints(args: Args, mut lints: LintStore) -> Result<(), Error> {
+    if let Some(code) = &args.opt.explain {
+        let explanation =
+            codes::explanation(code).ok_or_else(|| Error::UnknownCode(code.clone()))?;
+        print!("{}", explanation);
+        return Ok(());
+    }
+
     let mut lines = 0;
     let mut bytes = 0;
-    let mut diagnostic = Diagnostic::new(args.files);
-    for i in 0..diagnostic.files() {
-        let file_contents = match read_file::read_file(diagnostic.file_name(i)) {
+    let mut source_map = SourceMap::new();
+    for name in args.files {
+        let file_contents = match read_file::read_file(&name) {
             Ok(file_contents) => file_contents,
-            Err(_) => return Err(Error::File(diagnostic.file_name(i).to_string())),
+            Err(_) => return Err(Error::File(name)),
         };
         bytes += file_contents.len();
-        diagnostic.add_file_contents(file_contents);
-        lines += diagnostic.file_lines(i);
+        let file = source_map.add_file(FileName::Real(name), file_contents);
+        lines += source_map.file(file).lines();
+    }
+    let mut diagnostic = Diagnostic::new(source_map);
+    let passes = rust_comp_mir::transform::passes(
+        args.opt.opt_level,
+        &args.opt.enable_passes,
+        &args.opt.disable_passes,
+    )
+    .map_err(Error::UnknownPass)?;
+    let levels = [
+        (Level::Allow, &args.opt.allow),
+        (Level::Warn, &args.opt.warn),
+        (Level::Deny, &args.opt.deny),
+        (Level::Forbid, &args.opt.forbid),
+    ];
+    for (level, names) in levels.iter() {
+        for name in names.iter() {
+            lints.set_level(name, *level).map_err(Error::UnknownLint)?;
+        }
     }
     println!("Lines: {}", lines);
     println!("Bytes: {}", bytes);
 
+    let mut emitter =
+        Emitter::with_format(args.opt.error_format).with_style(Style::stderr(args.opt.color));
+    let result = compile(&args.opt, &mut diagnostic, passes, &mut lints, &mut emitter);
+    if args.opt.fix {
+        fix(&diagnostic, &emitter)?;
+    }
+    emitter.flush(&diagnostic);
+    result
+}
+
+/// Applies the machine-applicable suggestions to the files they're in.
+fn fix(diagnostic: &Diagnostic, emitter: &Emitter) -> Result<(), Error> {
+    let suggestions = emitter.machine_applicable();
+    for file in 0..diagnostic.files() {
+        let suggestions: Vec<&Suggestion> = suggestions
+            .iter()
+            .copied()
+            .filter(|suggestion| suggestion.span.file == file)
+            .collect();
+        if suggestions.is_empty() {
+            continue;
+        }
+        let name = match &diagnostic.source_map().file(file).name {
+            FileName::Real(name) => name,
+            FileName::Virtual(_) => continue,
+        };
+        let (fixed, applied) = apply_suggestions(diagnostic.file_contents(file), &suggestions);
+        if std::fs::write(name, fixed).is_err() {
+            return Err(Error::Write(name.to_string()));
+        }
+        println!("Applied {} fix(es) to {}", applied, name);
+    }
+    Ok(())
+}
+
+fn compile(
+    opt: &Opt,
+    diagnostic: &mut Diagnostic,
+    passes: Vec<Pass>,
+    lints: &mut LintStore,
+    emitter: &mut Emitter,
+) -> Result<(), Error> {
     let start = time::Instant::now();
 
     let mut lex_total = time::Duration::default();
     let mut parse_total = time::Duration::default();
+    let mut expand_total = time::Duration::default();
     let mut parse_to_syntax_total = time::Duration::default();
+    let mut check_total = time::Duration::default();
+    let mut desugar_total = time::Duration::default();
+    let mut lower_total = time::Duration::default();
+    let mut codegen_total = time::Duration::default();
+    let mut all_bodies = Vec::new();
+    let mut all_top_levels = Vec::new();
     for i in 0..diagnostic.files() {
         let file_contents = diagnostic.file_contents(i);
 
         let start = time::Instant::now();
         let (tokens, eofpos) =
-            lex::read_tokens(i, &file_contents).map_err(|e| handle_lex_error(&diagnostic, e))?;
+            lex::read_tokens(i, file_contents).map_err(|e| handle_lex_error(emitter, e))?;
         lex_total += start.elapsed();
 
         let start = time::Instant::now();
-        let top_levels = parse::parse(&file_contents, &tokens, eofpos)
-            .map_err(|e| handle_parse_error(&diagnostic, e))?;
+        let mut top_levels = parse::parse(file_contents, &tokens, eofpos)
+            .map_err(|e| handle_parse_error(emitter, file_contents, e))?;
         parse_total += start.elapsed();
 
         let start = time::Instant::now();
-        let mut context = parse_to_syntax::Context::new(&diagnostic);
-        let _top_levels: Vec<_> = top_levels
-            .iter()
-            .map(|tl| context.convert_top_level(tl))
-            .collect();
+        let expansions = expand::expand(file_contents, &mut top_levels)
+            .map_err(|e| handle_expand_error(emitter, e))?;
+        diagnostic.source_map_mut().set_expansions(i, expansions);
+        expand_total += start.elapsed();
+
+        let start = time::Instant::now();
+        let mut context = parse_to_syntax::Context::new(diagnostic);
+        let mut top_levels = context.convert_top_levels(&top_levels);
         parse_to_syntax_total += start.elapsed();
+
+        let start = time::Instant::now();
+        let mutability_errors = mutability::check_mutability(&top_levels);
+        let cast_errors = cast::check_casts(diagnostic, &top_levels);
+        let divergence_errors = divergence::check_let_else(&top_levels);
+        let question_errors = question::check_try(&top_levels);
+        let move_errors = moves::check_moves(&top_levels);
+        let scope_errors = scope::check_names(&top_levels);
+        let integer_errors = integers::check_literals(diagnostic, &top_levels);
+        check_total += start.elapsed();
+        emit_mutability_errors(emitter, mutability_errors);
+        emit_cast_errors(diagnostic, emitter, cast_errors);
+        emit_divergence_errors(emitter, divergence_errors);
+        emit_question_errors(emitter, question_errors);
+        emit_move_errors(emitter, move_errors);
+        emit_scope_errors(emitter, diagnostic, &top_levels, scope_errors);
+        emit_integer_errors(emitter, integer_errors);
+        let start = time::Instant::now();
+        lints.check(diagnostic, &top_levels, emitter);
+        check_total += start.elapsed();
+        if emitter.has_errors() {
+            return Err(Error::Handled);
+        }
+
+        let start = time::Instant::now();
+        question::desugar_try(&mut top_levels);
+        desugar_total += start.elapsed();
+
+        let start = time::Instant::now();
+        let bodies = rust_comp_mir::lower::lower(diagnostic, opt.profile, &top_levels);
+        lower_total += start.elapsed();
+        if opt.dump_mir {
+            for body in &bodies {
+                println!("{}", body);
+            }
+        }
+
+        let start = time::Instant::now();
+        let borrowck_errors = rust_comp_mir::borrowck::check(&bodies);
+        check_total += start.elapsed();
+        emit_borrowck_errors(emitter, borrowck_errors);
+        if emitter.has_errors() {
+            return Err(Error::Handled);
+        }
+
+        if opt.run {
+            match rust_comp_interp::eval::run_main(
+                diagnostic,
+                opt.profile,
+                &top_levels,
+                &mut std::io::stdout(),
+            ) {
+                Ok(value) => {
+                    if value != rust_comp_interp::Value::unit() {
+                        println!("{}", value);
+                    }
+                }
+                Err(e) => {
+                    emit_runtime_error(emitter, diagnostic, &top_levels, e);
+                    return Err(Error::Handled);
+                }
+            }
+        }
+        all_bodies.extend(bodies);
+        all_top_levels.extend(top_levels);
+    }
+
+    // Inlining needs the functions of every file.
+    let optimize_start = time::Instant::now();
+    for pass in passes {
+        if opt.dump_passes {
+            println!("// before {}", pass.name);
+            all_bodies.iter().for_each(|body| println!("{}", body));
+        }
+        (pass.run)(&mut all_bodies);
+        if opt.dump_passes {
+            println!("// after {}", pass.name);
+            all_bodies.iter().for_each(|body| println!("{}", body));
+        }
+    }
+    let optimize_total = optimize_start.elapsed();
+
+    if let Some(output) = &opt.output {
+        let start = time::Instant::now();
+        let object = rust_comp_x86::codegen::compile(&all_bodies).map_err(|e| {
+            emit_codegen_error(emitter, e);
+            Error::Handled
+        })?;
+        codegen_total += start.elapsed();
+        if std::fs::write(output, object).is_err() {
+            return Err(Error::Write(output.clone()));
+        }
+    }
+
+    if let Some(emit_wasm) = &opt.emit_wasm {
+        let start = time::Instant::now();
+        let module = rust_comp_wasm::codegen::compile(&all_bodies).map_err(|e| {
+            emit_wasm_error(emitter, e);
+            Error::Handled
+        })?;
+        let contents = if emit_wasm.ends_with(".wat") {
+            module.to_wat().into_bytes()
+        } else {
+            module.encode()
+        };
+        codegen_total += start.elapsed();
+        if std::fs::write(emit_wasm, contents).is_err() {
+            return Err(Error::Write(emit_wasm.clone()));
+        }
+    }
+
+    if let Some(emit_c) = &opt.emit_c {
+        let start = time::Instant::now();
+        let source = rust_comp_c::translate::translate(diagnostic, opt.profile, &all_top_levels)
+            .map_err(|e| {
+                emit_c_error(emitter, diagnostic, &all_top_levels, e);
+                Error::Handled
+            })?;
+        codegen_total += start.elapsed();
+        if std::fs::write(emit_c, source).is_err() {
+            return Err(Error::Write(emit_c.clone()));
+        }
     }
 
     print_duration("Total Non/IO", start.elapsed());
     print_duration("Lex", lex_total);
     print_duration("Parse", parse_total);
+    print_duration("Expand", expand_total);
     print_duration("Parse to Syntax", parse_to_syntax_total);
+    print_duration("Check", check_total);
+    print_duration("Desugar", desugar_total);
+    print_duration("Lower", lower_total);
+    print_duration("Optimize", optimize_total);
+    print_duration("Codegen", codegen_total);
     Ok(())
 }
 
-fn handle_lex_error(diagnostic: &Diagnostic, e: lex::Error) -> Error {
-    match e {
+fn handle_lex_error(emitter: &mut Emitter, e: lex::Error) -> Error {
+    let report = match e {
         lex::Error::UnterminatedBlockComment(pos) => {
-            diagnostic.print_pos_error(format_args!("unterminated block comment"), pos)
+            Report::error("unterminated block comment", span_for_pos(pos)).with_code("RC0001")
         }
         lex::Error::UnrecognizedControlChar(pos) => {
-            diagnostic.print_pos_error(format_args!("unrecognized control character"), pos)
+            Report::error("unrecognized control character", span_for_pos(pos)).with_code("RC0002")
+        }
+        lex::Error::UnterminatedString(pos) => {
+            Report::error("unterminated string", span_for_pos(pos)).with_code("RC0003")
+        }
+    };
+    emitter.emit(report);
+    Error::Handled
+}
+
+fn handle_parse_error(emitter: &mut Emitter, file_contents: &str, e: parse::Error) -> Error {
+    let mut report = parse_error(e.clone());
+    let span = match e {
+        parse::Error::ExpectedToken(_, span)
+        | parse::Error::Expected(_, span)
+        | parse::Error::IntegerOutOfRange(span) => span,
+    };
+    // A misspelled keyword is more likely than a missing token.
+    if let Some((word, keyword)) = lex::keyword_suggestion(file_contents, span) {
+        report = report.with_suggestion(
+            format!("there is a keyword `{}` with a similar name", keyword),
+            Suggestion::replace(word, keyword, Applicability::MaybeIncorrect),
+        );
+    } else if let parse::Error::ExpectedToken(token, span) = e {
+        // Only tokens that are always spelled the same can be inserted.
+        let text = token.to_string();
+        if let Some(text) = text
+            .strip_prefix('`')
+            .and_then(|text| text.strip_suffix('`'))
+        {
+            // The token goes right after the one before the unexpected one.
+            let index = file_contents[..span.start.min(file_contents.len())]
+                .trim_end()
+                .len();
+            let applicability = match token {
+                TokenKind::Colon => Applicability::MachineApplicable,
+                // `S { a: 1 }` isn't an expression yet, so a `;` before the
+                // `{` would compile but mean something else.
+                TokenKind::Semicolon if ends_statement(file_contents, index, span) => {
+                    Applicability::MachineApplicable
+                }
+                _ => Applicability::MaybeIncorrect,
+            };
+            report = report.with_suggestion(
+                format!("insert `{}`", text),
+                Suggestion::insert(span.file, index, text, applicability),
+            );
         }
     }
+    emitter.emit(report);
     Error::Handled
 }
 
-fn handle_parse_error(diagnostic: &Diagnostic, e: parse::Error) -> Error {
+/// Keywords that start a statement and can't continue an expression.
+const STATEMENT_KEYWORDS: &[&str] = &[
+    "break", "const", "continue", "enum", "fn", "for", "let", "loop", "mod", "pub", "return",
+    "struct", "use", "while",
+];
+
+/// Whether a `;` inserted at `index` certainly ends a statement: the token at
+/// `next` is on a later line, starts a statement, or closes the block.
+fn ends_statement(file_contents: &str, index: usize, next: Span) -> bool {
+    let start = next.start.min(file_contents.len());
+    let word = file_contents[start..]
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .next()
+        .unwrap_or("");
+    file_contents[index.min(start)..start].contains('\n')
+        || file_contents[start..].starts_with('}')
+        || STATEMENT_KEYWORDS.contains(&word)
+}
+
+fn parse_error(e: parse::Error) -> Report {
     match e {
         parse::Error::ExpectedToken(token, span) => {
-            diagnostic.print_span_error(format_args!("expected {}", token), span)
+            Report::error(format!("expected {}", token), span).with_code("RC0004")
         }
         parse::Error::Expected(thing, span) => {
-            diagnostic.print_span_error(format_args!("expected {}", thing), span)
+            Report::error(format!("expected {}", thing), span).with_code("RC0005")
         }
         parse::Error::IntegerOutOfRange(span) => {
-            diagnostic.print_span_error(format_args!("integer out of range"), span)
+            Report::error("integer out of range", span).with_code("RC0006")
         }
     }
+}
+
+fn handle_expand_error(emitter: &mut Emitter, e: expand::Error) -> Error {
+    let (message, span, code) = match e {
+        expand::Error::Parse(e) => {
+            emitter.emit(parse_error(e));
+            return Error::Handled;
+        }
+        expand::Error::UndefinedMacro(span) => ("cannot find macro", span, "RC0007"),
+        expand::Error::NoRulesMatched(span) => ("no rules expected this token", span, "RC0008"),
+        expand::Error::UnknownFragmentSpecifier(span) => {
+            ("unknown fragment specifier", span, "RC0009")
+        }
+        expand::Error::UnboundMetavariable(span) => ("unknown macro variable", span, "RC0010"),
+        expand::Error::InvalidRepetition(span) => (
+            "variable repeats a different number of times",
+            span,
+            "RC0011",
+        ),
+        expand::Error::RecursionLimit(span) => ("recursion limit reached", span, "RC0012"),
+    };
+    emitter.emit(Report::error(message, span).with_code(code));
     Error::Handled
 }
+
+fn emit_mutability_errors(emitter: &mut Emitter, errors: Vec<mutability::Error>) {
+    use mutability::Access;
+    for e in errors {
+        let (message, span, code) = match e {
+            mutability::Error::ImmutableBinding(Access::Assign, span, _) => {
+                ("cannot assign twice to immutable variable", span, "RC0013")
+            }
+            mutability::Error::ImmutableBinding(Access::BorrowMut, span, _) => (
+                "cannot borrow immutable variable as mutable",
+                span,
+                "RC0014",
+            ),
+            mutability::Error::BehindRef(Access::Assign, span) => {
+                ("cannot assign through a `&` reference", span, "RC0015")
+            }
+            mutability::Error::BehindRef(Access::BorrowMut, span) => (
+                "cannot borrow data behind a `&` reference as mutable",
+                span,
+                "RC0016",
+            ),
+            mutability::Error::BehindPtrConst(Access::Assign, span) => {
+                ("cannot assign through a `*const` pointer", span, "RC0017")
+            }
+            mutability::Error::BehindPtrConst(Access::BorrowMut, span) => (
+                "cannot borrow data behind a `*const` pointer as mutable",
+                span,
+                "RC0018",
+            ),
+        };
+        emitter.emit(Report::error(message, span).with_code(code));
+    }
+}
+
+fn emit_cast_errors(diagnostic: &Diagnostic, emitter: &mut Emitter, errors: Vec<cast::Error>) {
+    for e in errors {
+        match e {
+            cast::Error::InvalidCast(from, span, type_span) => emitter.emit(
+                Report::error(
+                    format!(
+                        "cannot cast {} as `{}`",
+                        from,
+                        diagnostic.file_span(type_span)
+                    ),
+                    span,
+                )
+                .with_code("RC0019"),
+            ),
+        }
+    }
+}
+
+fn emit_integer_errors(emitter: &mut Emitter, errors: Vec<integers::Error>) {
+    for e in errors {
+        match e {
+            integers::Error::LiteralOutOfRange(integer, span) => emitter.emit(
+                Report::error(format!("literal out of range for `{}`", integer), span)
+                    .with_code("RC0041"),
+            ),
+        }
+    }
+}
+
+fn emit_divergence_errors(emitter: &mut Emitter, errors: Vec<divergence::Error>) {
+    for e in errors {
+        match e {
+            divergence::Error::LetElseDoesNotDiverge(span) => emitter.emit(
+                Report::error("`else` block of `let...else` must diverge", span)
+                    .with_code("RC0020"),
+            ),
+        }
+    }
+}
+
+fn emit_question_errors(emitter: &mut Emitter, errors: Vec<question::Error>) {
+    for e in errors {
+        match e {
+            question::Error::IncompatibleReturnType(span, _) => emitter.emit(Report::error(
+                "the `?` operator can only be used in a function that returns `Result` or `Option`",
+                span,
+            )
+            .with_code("RC0021")),
+        }
+    }
+}
+
+fn emit_move_errors(emitter: &mut Emitter, errors: Vec<moves::Error>) {
+    for e in errors {
+        match e {
+            moves::Error::UseAfterMove(span, moved) => emitter.emit(
+                Report::error("use of moved value", span)
+                    .with_code("RC0022")
+                    .with_label(moved, "value moved here"),
+            ),
+        }
+    }
+}
+
+fn emit_borrowck_errors(emitter: &mut Emitter, errors: Vec<borrowck::Error>) {
+    for e in errors {
+        let report = match e {
+            borrowck::Error::ConflictingBorrow(span, borrow, later) => {
+                Report::error("cannot borrow value because it is already borrowed", span)
+                    .with_code("RC0023")
+                    .with_label(borrow, "first borrow occurs here")
+                    .with_label(later, "first borrow later used here")
+            }
+            borrowck::Error::AssignToBorrowed(span, borrow, later) => {
+                Report::error("cannot assign to borrowed value", span)
+                    .with_code("RC0024")
+                    .with_label(borrow, "borrow occurs here")
+                    .with_label(later, "borrow later used here")
+            }
+            borrowck::Error::UseWhileMutablyBorrowed(span, borrow, later) => {
+                Report::error("cannot use value because it is mutably borrowed", span)
+                    .with_code("RC0025")
+                    .with_label(borrow, "mutable borrow occurs here")
+                    .with_label(later, "borrow later used here")
+            }
+            borrowck::Error::MoveWhileBorrowed(span, borrow, later) => {
+                Report::error("cannot move out of value because it is borrowed", span)
+                    .with_code("RC0026")
+                    .with_label(borrow, "borrow occurs here")
+                    .with_label(later, "borrow later used here")
+            }
+            borrowck::Error::DoesNotLiveLongEnough(borrow, dropped, later) => {
+                Report::error("borrowed value does not live long enough", borrow)
+                    .with_code("RC0027")
+                    .with_label(dropped, "value dropped here while still borrowed")
+                    .with_label(later, "borrow later used here")
+            }
+            borrowck::Error::ReturnsReferenceToLocal(borrow, local) => {
+                Report::error("cannot return reference to local variable", borrow)
+                    .with_code("RC0028")
+                    .with_label(local, "variable declared here")
+            }
+        };
+        emitter.emit(report);
+    }
+}
+
+fn emit_runtime_error(
+    emitter: &mut Emitter,
+    diagnostic: &Diagnostic,
+    top_levels: &[TopLevel],
+    e: rust_comp_interp::Error,
+) {
+    use rust_comp_interp::Error;
+    let (message, span, code) = match e {
+        Error::NoMain => ("`main` function not found", None, "RC0029"),
+        Error::Overflow(span) => ("attempt to compute with overflow", Some(span), "RC0030"),
+        Error::DivisionByZero(span) => ("attempt to divide by zero", Some(span), "RC0031"),
+        Error::NoMatch(span) => ("no pattern matched the value", Some(span), "RC0032"),
+        Error::Uninitialized(span) => ("use of uninitialized variable", Some(span), "RC0033"),
+        Error::DanglingPointer(span) => (
+            "pointer used after its value went out of scope",
+            Some(span),
+            "RC0034",
+        ),
+        Error::TypeMismatch(span) => ("mismatched types", Some(span), "RC0035"),
+        Error::ArgumentCount(span) => (
+            "function called with the wrong number of arguments",
+            Some(span),
+            "RC0036",
+        ),
+        Error::UndefinedName(span) => ("cannot find value in this scope", Some(span), "RC0037"),
+        Error::StackOverflow(span) => ("stack overflow", Some(span), "RC0038"),
+        Error::Unsupported(span) => (
+            "the interpreter does not support this expression",
+            Some(span),
+            "RC0039",
+        ),
+    };
+    let mut report = Report::new(Severity::Error, message, span).with_code(code);
+    if let Error::UndefinedName(span) = e {
+        report = with_similar_name(report, diagnostic, top_levels, span);
+    }
+    emitter.emit(report);
+}
+
+fn emit_scope_errors(
+    emitter: &mut Emitter,
+    diagnostic: &Diagnostic,
+    top_levels: &[TopLevel],
+    errors: Vec<scope::Error>,
+) {
+    for e in errors {
+        match e {
+            scope::Error::UndefinedName(span) => {
+                let report =
+                    Report::error("cannot find value in this scope", span).with_code("RC0037");
+                emitter.emit(with_similar_name(report, diagnostic, top_levels, span))
+            }
+        }
+    }
+}
+
+/// Suggests a name in scope similar to the unresolved one at `span`.
+fn with_similar_name(
+    report: Report,
+    diagnostic: &Diagnostic,
+    top_levels: &[TopLevel],
+    span: Span,
+) -> Report {
+    match scope::similar_name(diagnostic, top_levels, span) {
+        Some(name) => report.with_suggestion(
+            format!("there is a value `{}` with a similar name", name),
+            Suggestion::replace(span, name, Applicability::MaybeIncorrect),
+        ),
+        None => report,
+    }
+}
+
+fn emit_codegen_error(emitter: &mut Emitter, e: rust_comp_x86::Error) {
+    match e {
+        rust_comp_x86::Error::Unsupported(span) => emitter.emit(
+            Report::error("code generation does not support this yet", span).with_code("RC0039"),
+        ),
+    }
+}
+
+fn emit_wasm_error(emitter: &mut Emitter, e: rust_comp_wasm::Error) {
+    match e {
+        rust_comp_wasm::Error::Unsupported(span) => emitter.emit(
+            Report::error("code generation does not support this yet", span).with_code("RC0039"),
+        ),
+    }
+}
+
+fn emit_c_error(
+    emitter: &mut Emitter,
+    diagnostic: &Diagnostic,
+    top_levels: &[TopLevel],
+    e: rust_comp_c::Error,
+) {
+    match e {
+        rust_comp_c::Error::Unsupported(span) => emitter.emit(
+            Report::error("translation to C does not support this yet", span).with_code("RC0039"),
+        ),
+        rust_comp_c::Error::UndefinedName(span) => {
+            let report = Report::error("undefined name", span).with_code("RC0037");
+            emitter.emit(with_similar_name(report, diagnostic, top_levels, span))
+        }
+        rust_comp_c::Error::LiteralOutOfRange(span) => emitter
+            .emit(Report::error("literal out of range for its type", span).with_code("RC0041")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_comp_check::test_support;
+    use rust_comp_core::pos::{Pos, Span};
+    use rust_comp_core::profile::Profile;
+    use std::path::PathBuf;
+    use std::process::Command;
+    use structopt::StructOpt;
+
+    #[test]
+    fn test_error_codes() {
+        let span = Span {
+            file: 0,
+            start: 0,
+            end: 1,
+            expn: None,
+        };
+        let mut emitter = Emitter::new();
+        handle_lex_error(
+            &mut emitter,
+            lex::Error::UnterminatedString(Pos { file: 0, index: 0 }),
+        );
+        handle_parse_error(&mut emitter, "", parse::Error::Expected("expression", span));
+        handle_expand_error(&mut emitter, expand::Error::RecursionLimit(span));
+        emit_runtime_error(
+            &mut emitter,
+            &Diagnostic::new(SourceMap::new()),
+            &[],
+            rust_comp_interp::Error::NoMain,
+        );
+        let reported: Vec<_> = emitter.reports().iter().map(|report| report.code).collect();
+        assert_eq!(
+            reported,
+            [
+                Some("RC0003"),
+                Some("RC0005"),
+                Some("RC0012"),
+                Some("RC0029")
+            ]
+        );
+        for code in reported {
+            assert!(codes::explanation(code.unwrap()).is_some());
+        }
+    }
+
+    #[test]
+    fn test_parse_error_suggestion() {
+        let contents = "fn f(x i32) {}\nfn g() { let y = 1 }";
+        let mut emitter = Emitter::new();
+        let span = |start, end| Span {
+            file: 0,
+            start,
+            end,
+            expn: None,
+        };
+        handle_parse_error(
+            &mut emitter,
+            contents,
+            parse::Error::ExpectedToken(TokenKind::Colon, span(7, 10)),
+        );
+        handle_parse_error(
+            &mut emitter,
+            contents,
+            parse::Error::ExpectedToken(TokenKind::Semicolon, span(34, 35)),
+        );
+        handle_parse_error(
+            &mut emitter,
+            contents,
+            parse::Error::ExpectedToken(TokenKind::CloseParen, span(7, 10)),
+        );
+        handle_parse_error(
+            &mut emitter,
+            contents,
+            parse::Error::ExpectedToken(TokenKind::Label, span(7, 10)),
+        );
+        assert_eq!(emitter.reports()[3].children, []);
+        let suggestions = emitter.machine_applicable();
+        assert_eq!(suggestions.len(), 2);
+        assert_eq!(
+            apply_suggestions(contents, &suggestions).0,
+            "fn f(x: i32) {}\nfn g() { let y = 1; }"
+        );
+    }
+
+    #[test]
+    fn test_semicolon_suggestion_applicability() {
+        let contents =
+            "fn f() {\n    let s = S { a: 1 };\n    let x = 1\n    let y = 2 let z = 3 }";
+        let mut emitter = Emitter::new();
+        let span = |start, end| Span {
+            file: 0,
+            start,
+            end,
+            expn: None,
+        };
+        for start in [23, 51, 61, 71] {
+            handle_parse_error(
+                &mut emitter,
+                contents,
+                parse::Error::ExpectedToken(TokenKind::Semicolon, span(start, start + 1)),
+            );
+        }
+        let applicability: Vec<_> = emitter
+            .reports()
+            .iter()
+            .map(|report| report.children[0].suggestions[0].applicability)
+            .collect();
+        assert_eq!(
+            applicability,
+            [
+                Applicability::MaybeIncorrect,
+                Applicability::MachineApplicable,
+                Applicability::MachineApplicable,
+                Applicability::MachineApplicable,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fix_leaves_struct_expressions_alone() {
+        let contents = "struct S { a: i32 }\nfn main() {\n    let s = S { a: 1 };\n}\n";
+        let path = std::env::temp_dir().join(format!("rust-comp-fix-{}.rs", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        let args = Args::from_iter(&["rust-comp", "--fix", path.to_str().unwrap()]);
+        assert!(run(args).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), contents);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_similar_name_suggestions() {
+        let contents = "strcut S {}\nfn f() { retrun 1; }";
+        let mut emitter = Emitter::new();
+        let span = |start, end| Span {
+            file: 0,
+            start,
+            end,
+            expn: None,
+        };
+        handle_parse_error(
+            &mut emitter,
+            contents,
+            parse::Error::Expected("top level", span(0, 6)),
+        );
+        handle_parse_error(
+            &mut emitter,
+            contents,
+            parse::Error::ExpectedToken(TokenKind::Semicolon, span(28, 29)),
+        );
+        let helps: Vec<_> = emitter
+            .reports()
+            .iter()
+            .map(|report| report.children[0].message.as_str())
+            .collect();
+        assert_eq!(
+            helps,
+            [
+                "there is a keyword `struct` with a similar name",
+                "there is a keyword `return` with a similar name",
+            ]
+        );
+        assert_eq!(emitter.machine_applicable(), Vec::<&Suggestion>::new());
+
+        let contents = "fn main() -> i32 { let length = 1; lenght }";
+        let (diagnostic, top_levels) = rust_comp_check::test_support::convert(contents);
+        let mut emitter = Emitter::new();
+        let errors = scope::check_names(&top_levels);
+        assert_eq!(errors, [scope::Error::UndefinedName(span(35, 41))]);
+        emit_scope_errors(&mut emitter, &diagnostic, &top_levels, errors);
+        assert_eq!(
+            emitter.reports()[0].children[0].message,
+            "there is a value `length` with a similar name"
+        );
+    }
+
+    #[test]
+    fn test_explanation_examples() {
+        let mut wrong = Vec::new();
+        for (code, explanation) in codes::CODES {
+            let start = explanation.find("Erroneous code example").unwrap();
+            let example = explanation[start..].split("```\n").nth(1).unwrap();
+            // The bell character is spelled out to keep the file printable.
+            let example = example.replace("^G", "\u{7}");
+            let path =
+                std::env::temp_dir().join(format!("rust-comp-{}-{}.c", code, std::process::id()));
+            let mut args = vec!["rust-comp", "--run"];
+            if explanation.contains("--emit-c") {
+                args.extend(["--emit-c", path.to_str().unwrap()]);
+            }
+            let opt = Opt::from_iter(&args);
+            let mut diagnostic = Diagnostic::with_contents("example", example);
+            let mut emitter = Emitter::new();
+            let _ = compile(
+                &opt,
+                &mut diagnostic,
+                Vec::new(),
+                &mut LintStore::new(),
+                &mut emitter,
+            );
+            let _ = std::fs::remove_file(&path);
+            let codes: Vec<_> = emitter
+                .reports()
+                .iter()
+                .filter_map(|report| report.code)
+                .collect();
+            if codes.first() != Some(code) {
+                wrong.push((*code, codes));
+            }
+        }
+        assert_eq!(wrong, []);
+    }
+
+    /// Runs `main` of `file_contents` with the interpreter and with the C,
+    /// x86, and wasm backends.  Returns the name of each with what it
+    /// printed, or `None` if it panicked.
+    fn run_everywhere(file_contents: &str, profile: Profile) -> Vec<(&str, Option<String>)> {
+        let (diagnostic, top_levels) = test_support::desugar(file_contents);
+        let mut results = Vec::new();
+
+        let mut output = Vec::new();
+        let result =
+            rust_comp_interp::eval::run_main(&diagnostic, profile, &top_levels, &mut output);
+        let output = result.ok().map(|_| String::from_utf8(output).unwrap());
+        results.push(("interp", output));
+
+        let directory = std::env::temp_dir().join(format!(
+            "rust-comp-run-{}-{:?}",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        std::fs::create_dir_all(&directory).unwrap();
+        let executable = directory.join("program");
+        let run = |sources: &[PathBuf], arguments: &[&str]| {
+            let status = Command::new("cc")
+                .args(arguments)
+                .args(sources)
+                .arg("-o")
+                .arg(&executable)
+                .status()
+                .unwrap();
+            assert!(status.success());
+            let output = Command::new(&executable).output().unwrap();
+            match output.status.success() {
+                true => Some(String::from_utf8(output.stdout).unwrap()),
+                false => None,
+            }
+        };
+
+        let source = directory.join("program.c");
+        let c = rust_comp_c::translate::translate(&diagnostic, profile, &top_levels).unwrap();
+        std::fs::write(&source, c).unwrap();
+        results.push(("c", run(&[source], &["-std=c99"])));
+
+        let bodies = rust_comp_mir::lower::lower(&diagnostic, profile, &top_levels);
+        let object = directory.join("program.o");
+        std::fs::write(&object, rust_comp_x86::codegen::compile(&bodies).unwrap()).unwrap();
+        let runtime: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "..",
+            "rust-comp-x86",
+            "runtime",
+            "runtime.c",
+        ]
+        .iter()
+        .collect();
+        results.push(("x86", run(&[object, runtime], &[])));
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let wasm = rust_comp_wasm::codegen::compile(&bodies).unwrap().encode();
+        let engine = wasmi::Engine::default();
+        let module = wasmi::Module::new(&engine, &wasm[..]).unwrap();
+        let mut store = wasmi::Store::new(&engine, String::new());
+        let print = wasmi::Func::wrap(
+            &mut store,
+            |mut caller: wasmi::Caller<'_, String>, value: i64| {
+                caller.data_mut().push_str(&format!("{}\n", value));
+                0i64
+            },
+        );
+        let panic = wasmi::Func::wrap(&mut store, |_: i64, _: i64| 0i64);
+        let mut linker = <wasmi::Linker<String>>::new(&engine);
+        linker.define("env", "print_i64", print).unwrap();
+        linker.define("env", "rust_panic", panic).unwrap();
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .unwrap()
+            .start(&mut store)
+            .unwrap();
+        let main = instance.get_func(&store, "main").unwrap();
+        let mut result = [wasmi::Value::I64(0)];
+        let result = main.call(&mut store, &[], &mut result);
+        results.push(("wasm", result.ok().map(|()| store.into_data())));
+        results
+    }
+
+    #[test]
+    fn test_integer_overflow_agrees_across_backends() {
+        // `x` and `y` are `i32`s because nothing else constrains them.
+        let program = "pub fn main() { let x = 2147483647; let y = x + 1; print_i64(y as i64); }";
+        let expect = |output: Option<&str>| {
+            ["interp", "c", "x86", "wasm"]
+                .iter()
+                .map(|backend| (*backend, output.map(str::to_string)))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(run_everywhere(program, Profile::Debug), expect(None));
+        assert_eq!(
+            run_everywhere(program, Profile::Release),
+            expect(Some("-2147483648\n"))
+        );
+        // Passing `x + 1` to `print_i64` makes `x` an `i64`, as in rustc.
+        let program = "pub fn main() { let x = 2147483647; print_i64(x + 1); }";
+        assert_eq!(
+            run_everywhere(program, Profile::Debug),
+            expect(Some("2147483648\n"))
+        );
+    }
+}
@@ -1,12 +1,35 @@
 use rust_comp_core::pos::Span;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct TopLevel {
     pub span: Span,
+    pub attributes: Vec<Attribute>,
     pub visibility: Visibility,
     pub kind: TopLevelKind,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Attribute {
+    pub span: Span,
+    pub path: Path,
+    /// The paths in parentheses, such as the lints in `#[allow(dead_code)]`.
+    pub arguments: Vec<Path>,
+}
+
+impl TopLevel {
+    /// Whether the item has `#[derive(..)]` naming `trait_`.
+    pub fn derives(&self, trait_: &str) -> bool {
+        let is = |path: &Path, name: &str| {
+            path.segments.last().map(|segment| segment.id) == Some(SymbolId::of_name(name))
+        };
+        self.attributes.iter().any(|attribute| {
+            is(&attribute.path, "derive") && attribute.arguments.iter().any(|path| is(path, trait_))
+        })
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Visibility {
     Private,
@@ -132,15 +155,38 @@ pub enum ExpressionKind {
     MemberAccess(MemberAccess),
     Tuple(Vec<Expression>),
     Value(Value),
+    Ref(Box<Expression>),
+    RefMut(Box<Expression>),
+    RawConst(Box<Expression>),
+    RawMut(Box<Expression>),
+    Deref(Box<Expression>),
+    Cast(Cast),
+    /// The `?` operator.
+    Try(Box<Expression>),
+    Return(Option<Box<Expression>>),
+    Break(Option<Box<Expression>>),
+    Continue,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct If {
-    pub condition: Box<Expression>,
+    pub condition: Condition,
     pub then: Block,
     pub else_: Option<Box<Else>>,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Condition {
+    Expression(Box<Expression>),
+    Let(LetCondition),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LetCondition {
+    pub pattern: Pattern,
+    pub value: Box<Expression>,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Else {
     pub span: Span,
@@ -160,7 +206,7 @@ pub struct Loop {
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct While {
-    pub condition: Box<Expression>,
+    pub condition: Condition,
     pub block: Block,
 }
 
@@ -239,6 +285,12 @@ pub struct MemberAccess {
     pub member: Symbol,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cast {
+    pub expression: Box<Expression>,
+    pub type_: Type,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Value {
     Bool(bool),
@@ -247,11 +299,17 @@ pub enum Value {
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Let {
-    pub name_span: Span,
-    /// Normal name => Some, _ => None.
-    pub name: Option<SymbolId>,
+    pub pattern: Pattern,
+    pub mutable: bool,
     pub type_: Option<Type>,
     pub value: Option<Expression>,
+    pub else_: Option<Box<LetElse>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LetElse {
+    pub span: Span,
+    pub block: Block,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -283,5 +341,22 @@ pub struct Symbol {
     pub id: SymbolId,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct SymbolId(pub u64);
+
+impl SymbolId {
+    /// The id of `name` when it is spelled outside of any macro.
+    pub fn of_name(name: &str) -> Self {
+        let mut hasher = DefaultHasher::new();
+        name.hash(&mut hasher);
+        SymbolId(hasher.finish())
+    }
+
+    /// The id of `name` when it is spelled inside of a macro expansion.
+    pub fn of_hygienic_name(name: &str, context: u32) -> Self {
+        let mut hasher = DefaultHasher::new();
+        name.hash(&mut hasher);
+        context.hash(&mut hasher);
+        SymbolId(hasher.finish())
+    }
+}
@@ -0,0 +1,976 @@
+use crate::module::*;
+use crate::Error;
+use rust_comp_core::pos::Span;
+use rust_comp_mir::*;
+use std::collections::HashMap;
+
+/// The shadow stack starts at the top of memory and grows down.
+const MEMORY_PAGES: u32 = 2;
+const STACK_TOP: i64 = MEMORY_PAGES as i64 * 65536;
+/// Panic messages are stored after the null pointer and the heap starts
+/// after them and grows up.
+const DATA_START: u32 = 8;
+
+const STACK_POINTER: u32 = 0;
+const HEAP_POINTER: u32 = 1;
+
+/// Compiles `bodies` to a WebAssembly module.  Integers of 32 bits or
+/// fewer are `i32`s and every other value is an `i64`.  Locals live in 8
+/// byte slots of a frame on a shadow stack in linear memory so they can be
+/// borrowed, with narrower integers sign or zero extended to 64 bits, and tuples and enum variants are allocated on a heap that is
+/// never freed.  Functions that are called but not defined are imported
+/// from `env` and `pub` functions are exported.  Failed assertions call
+/// `env.rust_panic` with the address and length of their message.
+pub fn compile(bodies: &[Body]) -> Result<Module, Error> {
+    let mut messages = HashMap::new();
+    let mut data = Vec::new();
+    let mut heap_start = DATA_START;
+    for body in bodies {
+        for block in &body.blocks {
+            if let TerminatorKind::Assert { message, .. } = &block.terminator.kind {
+                if !messages.contains_key(message) {
+                    messages.insert(message.clone(), heap_start);
+                    data.push(Data {
+                        offset: heap_start,
+                        bytes: message.as_bytes().to_vec(),
+                    });
+                    heap_start += message.len() as u32;
+                }
+            }
+        }
+    }
+    // Round up so allocations stay 8 byte aligned.
+    let heap_start = (heap_start + 7) & !7;
+
+    let mut module = Module {
+        types: Vec::new(),
+        imports: Vec::new(),
+        functions: Vec::new(),
+        memory_pages: MEMORY_PAGES,
+        globals: vec![
+            Global {
+                type_: ValType::I32,
+                mutable: true,
+                value: STACK_TOP,
+            },
+            Global {
+                type_: ValType::I32,
+                mutable: true,
+                value: heap_start as i64,
+            },
+        ],
+        exports: Vec::new(),
+        data,
+    };
+
+    // Imports come first in the function index space so they have to be
+    // found before any function can be called.
+    let mut imports: Vec<(String, usize)> = Vec::new();
+    for body in bodies {
+        for block in &body.blocks {
+            if let TerminatorKind::Call {
+                function: Operand::Constant(Constant::Function(name)),
+                arguments,
+                ..
+            } = &block.terminator.kind
+            {
+                let defined = bodies.iter().any(|body| body.name == *name);
+                if !defined && !imports.iter().any(|(import, _)| import == name) {
+                    imports.push((name.clone(), arguments.len()));
+                }
+            }
+        }
+    }
+    if !messages.is_empty() && !imports.iter().any(|(import, _)| import == "rust_panic") {
+        imports.push(("rust_panic".to_string(), 2));
+    }
+    let mut indices = HashMap::new();
+    let mut signatures = HashMap::new();
+    for (name, arguments) in &imports {
+        // The types of imports aren't known so they take and return `i64`s.
+        let signature = (vec![IntegerType::I64; *arguments], IntegerType::I64);
+        let type_ = function_type(&mut module, &signature);
+        signatures.insert(name.clone(), signature);
+        indices.insert(name.clone(), indices.len() as u32);
+        module.imports.push(Import {
+            module: "env".to_string(),
+            name: name.clone(),
+            type_,
+        });
+    }
+    for body in bodies {
+        indices.insert(body.name.clone(), indices.len() as u32);
+        let parameters = body
+            .arguments()
+            .map(|argument| local_integer(body, argument))
+            .collect();
+        signatures.insert(
+            body.name.clone(),
+            (parameters, local_integer(body, RETURN_PLACE)),
+        );
+    }
+
+    for body in bodies {
+        let type_ = function_type(&mut module, &signatures[&body.name]);
+        let mut codegen = Codegen {
+            body,
+            indices: &indices,
+            signatures: &signatures,
+            messages: &messages,
+            code: Vec::new(),
+        };
+        codegen.function()?;
+        if body.public {
+            module
+                .exports
+                .push(Export::Function(body.name.clone(), indices[&body.name]));
+        }
+        module.functions.push(Function {
+            name: body.name.clone(),
+            type_,
+            locals: vec![
+                ValType::I32,
+                ValType::I32,
+                ValType::I32,
+                ValType::I64,
+                ValType::I64,
+                ValType::I64,
+            ],
+            body: codegen.code,
+        });
+    }
+    module.exports.push(Export::Memory("memory".to_string()));
+    Ok(module)
+}
+
+/// The types of a function's parameters and result.
+type Signature = (Vec<IntegerType>, IntegerType);
+
+/// The type of values of `integer`.
+fn val_type(integer: IntegerType) -> ValType {
+    if integer.bits <= 32 {
+        ValType::I32
+    } else {
+        ValType::I64
+    }
+}
+
+/// The type of `local`.  Values other than integers are `i64`s.
+fn local_integer(body: &Body, local: Local) -> IntegerType {
+    body.local(local).integer.unwrap_or(IntegerType::I64)
+}
+
+/// The index of the type of a function with `signature`.
+fn function_type(module: &mut Module, (parameters, result): &Signature) -> u32 {
+    let parameters = parameters
+        .iter()
+        .map(|integer| val_type(*integer))
+        .collect();
+    let type_ = (parameters, vec![val_type(*result)]);
+    match module.types.iter().position(|t| *t == type_) {
+        Some(index) => index as u32,
+        None => {
+            module.types.push(type_);
+            module.types.len() as u32 - 1
+        }
+    }
+}
+
+struct Codegen<'a> {
+    body: &'a Body,
+    indices: &'a HashMap<String, u32>,
+    signatures: &'a HashMap<String, Signature>,
+    /// The address of each panic message.
+    messages: &'a HashMap<String, u32>,
+    code: Vec<Instruction>,
+}
+
+impl<'a> Codegen<'a> {
+    /// The local holding the address of the frame.
+    fn frame(&self) -> u32 {
+        self.body.arg_count as u32
+    }
+
+    /// The local holding the index of the next basic block to run.
+    fn state(&self) -> u32 {
+        self.body.arg_count as u32 + 1
+    }
+
+    /// A scratch local for addresses of allocations.
+    fn address_scratch(&self) -> u32 {
+        self.body.arg_count as u32 + 2
+    }
+
+    /// A scratch local for values being stored.
+    fn value_scratch(&self) -> u32 {
+        self.body.arg_count as u32 + 3
+    }
+
+    /// Scratch locals for the operands of overflow checks.
+    fn left_scratch(&self) -> u32 {
+        self.body.arg_count as u32 + 4
+    }
+
+    fn right_scratch(&self) -> u32 {
+        self.body.arg_count as u32 + 5
+    }
+
+    fn frame_size(&self) -> i32 {
+        self.body.locals.len() as i32 * 8
+    }
+
+    fn emit(&mut self, instruction: Instruction) {
+        self.code.push(instruction);
+    }
+
+    /// Basic blocks are dispatched by a loop around a `br_table` to nested
+    /// blocks.  The code for block `i` follows the end of the `i`th
+    /// innermost block so jumping to the loop runs the block in `state`.
+    fn function(&mut self) -> Result<(), Error> {
+        let body = self.body;
+        self.emit(Instruction::GlobalGet(STACK_POINTER));
+        self.emit(Instruction::I32Const(self.frame_size()));
+        self.emit(Instruction::I32Sub);
+        self.emit(Instruction::LocalTee(self.frame()));
+        self.emit(Instruction::GlobalSet(STACK_POINTER));
+        for (i, argument) in body.arguments().enumerate() {
+            self.emit(Instruction::LocalGet(self.frame()));
+            self.emit(Instruction::LocalGet(i as u32));
+            self.convert(local_integer(body, argument), IntegerType::I64);
+            self.emit(Instruction::I64Store(offset(argument)));
+        }
+
+        let blocks = body.blocks.len() as u32;
+        self.emit(Instruction::Loop);
+        for _ in 0..blocks {
+            self.emit(Instruction::Block);
+        }
+        self.emit(Instruction::LocalGet(self.state()));
+        self.emit(Instruction::BrTable((0..blocks).collect(), blocks - 1));
+        for (i, block) in body.blocks.iter().enumerate() {
+            self.emit(Instruction::End);
+            for statement in &block.statements {
+                self.statement(statement)?;
+            }
+            // Only the loop and the blocks of later basic blocks enclose
+            // this code.
+            let depth = blocks - 1 - i as u32;
+            self.terminator(depth, &block.terminator)?;
+        }
+        self.emit(Instruction::End);
+        self.emit(Instruction::Unreachable);
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Result<(), Error> {
+        match &statement.kind {
+            StatementKind::Assign(place, rvalue) => {
+                self.rvalue(statement.span, rvalue, self.integer(place))?;
+                self.store(statement.span, place)?;
+            }
+            StatementKind::StorageLive(_)
+            | StatementKind::StorageDead(_)
+            | StatementKind::Drop { .. }
+            | StatementKind::Nop => {}
+        }
+        Ok(())
+    }
+
+    /// Stores the value on top of the stack, of `place`'s type, in `place`.
+    fn store(&mut self, span: Span, place: &Place) -> Result<(), Error> {
+        self.convert(self.integer(place), IntegerType::I64);
+        self.emit(Instruction::LocalSet(self.value_scratch()));
+        let offset = self.address(span, place)?;
+        self.emit(Instruction::LocalGet(self.value_scratch()));
+        self.emit(Instruction::I64Store(offset));
+        Ok(())
+    }
+
+    /// Jumps to `target` from code enclosed by `depth` blocks and the loop.
+    fn goto(&mut self, depth: u32, target: BasicBlock) {
+        self.emit(Instruction::I32Const(target.0 as i32));
+        self.emit(Instruction::LocalSet(self.state()));
+        self.emit(Instruction::Br(depth));
+    }
+
+    fn terminator(&mut self, depth: u32, terminator: &Terminator) -> Result<(), Error> {
+        let span = terminator.span;
+        match &terminator.kind {
+            TerminatorKind::Goto(target) => self.goto(depth, *target),
+            TerminatorKind::SwitchInt {
+                operand,
+                targets,
+                otherwise,
+            } => {
+                self.operand(span, operand, IntegerType::I64)?;
+                self.emit(Instruction::LocalSet(self.value_scratch()));
+                for (value, target) in targets {
+                    self.emit(Instruction::LocalGet(self.value_scratch()));
+                    self.emit(Instruction::I64Const(*value as i64));
+                    self.emit(Instruction::I64Eq);
+                    self.emit(Instruction::If);
+                    self.goto(depth + 1, *target);
+                    self.emit(Instruction::End);
+                }
+                self.goto(depth, *otherwise);
+            }
+            TerminatorKind::Call {
+                function,
+                arguments,
+                destination,
+                target,
+            } => {
+                let name = match function {
+                    Operand::Constant(Constant::Function(name)) => name,
+                    _ => return Err(Error::Unsupported(span)),
+                };
+                let (parameters, result) = &self.signatures[name];
+                if arguments.len() != parameters.len() {
+                    return Err(Error::Unsupported(span));
+                }
+                for (argument, parameter) in arguments.iter().zip(parameters) {
+                    self.operand(span, argument, *parameter)?;
+                }
+                self.emit(Instruction::Call(self.indices[name]));
+                self.convert(*result, self.integer(destination));
+                self.store(span, destination)?;
+                self.goto(depth, *target);
+            }
+            TerminatorKind::Assert {
+                condition,
+                expected,
+                message,
+                target,
+            } => {
+                self.operand(span, condition, IntegerType::I64)?;
+                self.emit(Instruction::I64Const(*expected as i64));
+                self.emit(Instruction::I64Ne);
+                self.emit(Instruction::If);
+                self.emit(Instruction::I64Const(self.messages[message] as i64));
+                self.emit(Instruction::I64Const(message.len() as i64));
+                self.emit(Instruction::Call(self.indices["rust_panic"]));
+                self.emit(Instruction::Unreachable);
+                self.emit(Instruction::End);
+                self.goto(depth, *target);
+            }
+            TerminatorKind::Return => {
+                self.load(span, &RETURN_PLACE.into())?;
+                self.emit(Instruction::LocalGet(self.frame()));
+                self.emit(Instruction::I32Const(self.frame_size()));
+                self.emit(Instruction::I32Add);
+                self.emit(Instruction::GlobalSet(STACK_POINTER));
+                self.emit(Instruction::Return);
+            }
+            TerminatorKind::Unreachable => self.emit(Instruction::Unreachable),
+        }
+        Ok(())
+    }
+
+    /// The type of the integers assigned to `place`.  Places behind
+    /// pointers are treated as `i64`.
+    fn integer(&self, place: &Place) -> IntegerType {
+        match place.projection.is_empty() {
+            true => self.body.local(place.local).integer,
+            false => None,
+        }
+        .unwrap_or(IntegerType::I64)
+    }
+
+    /// Converts the value on top of the stack from `from`'s type to `to`'s.
+    fn convert(&mut self, from: IntegerType, to: IntegerType) {
+        match (val_type(from), val_type(to)) {
+            (ValType::I32, ValType::I64) if from.signed => self.emit(Instruction::I64ExtendI32S),
+            (ValType::I32, ValType::I64) => self.emit(Instruction::I64ExtendI32U),
+            (ValType::I64, ValType::I32) => self.emit(Instruction::I32WrapI64),
+            _ => {}
+        }
+    }
+
+    /// Wraps the value of `integer`'s type on top of the stack to its width.
+    fn wrap(&mut self, integer: IntegerType) {
+        match (integer.bits, integer.signed) {
+            (32 | 64, _) => {}
+            (8, true) => self.emit(Instruction::I32Extend8S),
+            (16, true) => self.emit(Instruction::I32Extend16S),
+            (bits, _) => {
+                self.emit(Instruction::I32Const((1 << bits) - 1));
+                self.emit(Instruction::I32And);
+            }
+        }
+    }
+
+    /// Sign or zero extends the low bits of the `i64` on top of the stack as
+    /// `integer` requires.
+    fn extend_i64(&mut self, integer: IntegerType) {
+        match (integer.bits, integer.signed) {
+            (64, _) => {}
+            (8, true) => self.emit(Instruction::I64Extend8S),
+            (16, true) => self.emit(Instruction::I64Extend16S),
+            (32, true) => self.emit(Instruction::I64Extend32S),
+            (bits, _) => {
+                self.emit(Instruction::I64Const((1 << bits) - 1));
+                self.emit(Instruction::I64And);
+            }
+        }
+    }
+
+    /// Pushes the value of `rvalue` as the type of the place it's assigned
+    /// to, `integer`.
+    fn rvalue(&mut self, span: Span, rvalue: &Rvalue, integer: IntegerType) -> Result<(), Error> {
+        match rvalue {
+            Rvalue::Use(operand) => self.operand(span, operand, integer)?,
+            Rvalue::Binary(op, left, right) => {
+                // Comparisons take their type from their operands.
+                let operands = match op {
+                    BinaryOp::Eq | BinaryOp::Ne => self
+                        .operand_integer(left)
+                        .or_else(|| self.operand_integer(right))
+                        .unwrap_or(IntegerType::I64),
+                    _ => integer,
+                };
+                self.operand(span, left, operands)?;
+                self.operand(span, right, operands)?;
+                let wide = val_type(operands) == ValType::I64;
+                let (i32_, i64_) = match op {
+                    BinaryOp::Add => (Instruction::I32Add, Instruction::I64Add),
+                    BinaryOp::Sub => (Instruction::I32Sub, Instruction::I64Sub),
+                    BinaryOp::Mul => (Instruction::I32Mul, Instruction::I64Mul),
+                    BinaryOp::Div if operands.signed => {
+                        (Instruction::I32DivS, Instruction::I64DivS)
+                    }
+                    BinaryOp::Div => (Instruction::I32DivU, Instruction::I64DivU),
+                    BinaryOp::BitAnd => (Instruction::I32And, Instruction::I64And),
+                    BinaryOp::BitOr => (Instruction::I32Or, Instruction::I64Or),
+                    BinaryOp::Eq => (Instruction::I32Eq, Instruction::I64Eq),
+                    BinaryOp::Ne => (Instruction::I32Ne, Instruction::I64Ne),
+                };
+                self.emit(if wide { i64_ } else { i32_ });
+                match op {
+                    BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul => self.wrap(integer),
+                    // Comparisons push an `i32`.
+                    BinaryOp::Eq | BinaryOp::Ne if val_type(integer) == ValType::I64 => {
+                        self.emit(Instruction::I64ExtendI32U)
+                    }
+                    _ => {}
+                }
+            }
+            Rvalue::Overflows(op, integer, left, right) => {
+                // Operands are extended to `i64`s to be checked.
+                let extended = IntegerType {
+                    bits: 64,
+                    ..*integer
+                };
+                self.operand(span, left, extended)?;
+                self.emit(Instruction::LocalSet(self.left_scratch()));
+                self.operand(span, right, extended)?;
+                self.emit(Instruction::LocalSet(self.right_scratch()));
+                self.overflows(span, *op, *integer)?;
+                self.emit(Instruction::I64ExtendI32U);
+            }
+            Rvalue::Ref(place)
+            | Rvalue::RefMut(place)
+            | Rvalue::RawConst(place)
+            | Rvalue::RawMut(place) => {
+                let offset = self.address(span, place)?;
+                self.emit(Instruction::I32Const(offset as i32));
+                self.emit(Instruction::I32Add);
+                self.emit(Instruction::I64ExtendI32U);
+            }
+            Rvalue::Cast(operand, type_) => {
+                self.operand(span, operand, IntegerType::I64)?;
+                if let Some(cast) = IntegerType::from_name(type_) {
+                    self.extend_i64(cast);
+                }
+                self.convert(IntegerType::I64, integer);
+            }
+            Rvalue::Tuple(operands) => self.allocate(span, None, operands)?,
+            Rvalue::Variant(variant, operands) => {
+                self.allocate(span, Some(variant.discriminant), operands)?
+            }
+            Rvalue::Discriminant(place) => {
+                self.load(span, place)?;
+                self.emit(Instruction::I32WrapI64);
+                self.emit(Instruction::I64Load(0));
+            }
+        }
+        Ok(())
+    }
+
+    /// Pushes an `i32` that is 1 if `op` overflows `integer` and 0 if it
+    /// doesn't.  The operands are in the left and right scratch locals,
+    /// extended to `i64`s.
+    fn overflows(&mut self, span: Span, op: BinaryOp, integer: IntegerType) -> Result<(), Error> {
+        let left = Instruction::LocalGet(self.left_scratch());
+        let right = Instruction::LocalGet(self.right_scratch());
+        let result = Instruction::LocalGet(self.value_scratch());
+        if op == BinaryOp::Div {
+            // Only the minimum value divided by `-1` overflows.
+            self.code.extend(match integer.signed {
+                true => vec![
+                    left,
+                    Instruction::I64Const(integer.min() as i64),
+                    Instruction::I64Eq,
+                    right,
+                    Instruction::I64Const(-1),
+                    Instruction::I64Eq,
+                    Instruction::I32And,
+                ],
+                false => vec![Instruction::I32Const(0)],
+            });
+            return Ok(());
+        }
+        if integer.bits < 64 {
+            // The result of extended operands is exact so it overflows if
+            // wrapping it changes it.
+            let op = match op {
+                BinaryOp::Add => Instruction::I64Add,
+                BinaryOp::Sub => Instruction::I64Sub,
+                BinaryOp::Mul => Instruction::I64Mul,
+                _ => return Err(Error::Unsupported(span)),
+            };
+            self.code.extend(vec![
+                left,
+                right,
+                op,
+                Instruction::LocalTee(self.value_scratch()),
+                result,
+            ]);
+            self.extend_i64(integer);
+            self.emit(Instruction::I64Ne);
+            return Ok(());
+        }
+        if !integer.signed {
+            let code = match op {
+                // The result wrapped below the left operand.
+                BinaryOp::Add => vec![
+                    left.clone(),
+                    right,
+                    Instruction::I64Add,
+                    left,
+                    Instruction::I64LtU,
+                ],
+                BinaryOp::Sub => vec![left, right, Instruction::I64LtU],
+                // Dividing the result by the left operand doesn't give back
+                // the right operand.
+                BinaryOp::Mul => vec![
+                    left.clone(),
+                    right.clone(),
+                    Instruction::I64Mul,
+                    left.clone(),
+                    Instruction::I64Const(1),
+                    left.clone(),
+                    Instruction::I64Const(0),
+                    Instruction::I64Ne,
+                    Instruction::Select,
+                    Instruction::I64DivU,
+                    right,
+                    Instruction::I64Ne,
+                    left,
+                    Instruction::I64Const(0),
+                    Instruction::I64Ne,
+                    Instruction::I32And,
+                ],
+                _ => return Err(Error::Unsupported(span)),
+            };
+            self.code.extend(code);
+            return Ok(());
+        }
+        let code = match op {
+            // The result's sign differs from both operands'.
+            BinaryOp::Add => vec![
+                left.clone(),
+                right.clone(),
+                Instruction::I64Add,
+                Instruction::LocalSet(self.value_scratch()),
+                left,
+                result.clone(),
+                Instruction::I64Xor,
+                right,
+                result,
+                Instruction::I64Xor,
+                Instruction::I64And,
+                Instruction::I64Const(0),
+                Instruction::I64LtS,
+            ],
+            // The operands' signs differ and the result's differs from the
+            // left operand's.
+            BinaryOp::Sub => vec![
+                left.clone(),
+                right.clone(),
+                Instruction::I64Sub,
+                Instruction::LocalSet(self.value_scratch()),
+                left.clone(),
+                right,
+                Instruction::I64Xor,
+                left,
+                result,
+                Instruction::I64Xor,
+                Instruction::I64And,
+                Instruction::I64Const(0),
+                Instruction::I64LtS,
+            ],
+            // Dividing the result by the left operand doesn't give back the
+            // right operand.  Dividing by `-1` could trap so that case is
+            // checked directly.
+            BinaryOp::Mul => {
+                let divides = vec![
+                    left.clone(),
+                    Instruction::I64Const(0),
+                    Instruction::I64Ne,
+                    left.clone(),
+                    Instruction::I64Const(-1),
+                    Instruction::I64Ne,
+                    Instruction::I32And,
+                ];
+                let mut code = vec![
+                    left.clone(),
+                    right.clone(),
+                    Instruction::I64Mul,
+                    Instruction::LocalSet(self.value_scratch()),
+                    result,
+                    left.clone(),
+                    Instruction::I64Const(1),
+                ];
+                code.extend(divides.iter().cloned());
+                code.extend(vec![
+                    Instruction::Select,
+                    Instruction::I64DivS,
+                    right.clone(),
+                    Instruction::I64Ne,
+                ]);
+                code.extend(divides);
+                code.extend(vec![
+                    Instruction::I32And,
+                    left,
+                    Instruction::I64Const(-1),
+                    Instruction::I64Eq,
+                    right,
+                    Instruction::I64Const(i64::MIN),
+                    Instruction::I64Eq,
+                    Instruction::I32And,
+                    Instruction::I32Or,
+                ]);
+                code
+            }
+            _ => return Err(Error::Unsupported(span)),
+        };
+        self.code.extend(code);
+        Ok(())
+    }
+
+    /// Allocates a tuple or enum variant and pushes its address.  Variants
+    /// store their discriminant before their fields.
+    fn allocate(
+        &mut self,
+        span: Span,
+        discriminant: Option<u128>,
+        operands: &[Operand],
+    ) -> Result<(), Error> {
+        let fields = discriminant.is_some() as usize + operands.len();
+        self.emit(Instruction::GlobalGet(HEAP_POINTER));
+        self.emit(Instruction::LocalTee(self.address_scratch()));
+        self.emit(Instruction::I32Const(fields.max(1) as i32 * 8));
+        self.emit(Instruction::I32Add);
+        self.emit(Instruction::GlobalSet(HEAP_POINTER));
+        let mut offset = 0;
+        if let Some(discriminant) = discriminant {
+            self.emit(Instruction::LocalGet(self.address_scratch()));
+            self.emit(Instruction::I64Const(discriminant as i64));
+            self.emit(Instruction::I64Store(0));
+            offset = 8;
+        }
+        for operand in operands {
+            self.emit(Instruction::LocalGet(self.address_scratch()));
+            self.operand(span, operand, IntegerType::I64)?;
+            self.emit(Instruction::I64Store(offset));
+            offset += 8;
+        }
+        self.emit(Instruction::LocalGet(self.address_scratch()));
+        self.emit(Instruction::I64ExtendI32U);
+        Ok(())
+    }
+
+    /// The type of `operand` or `None` if it's a constant.
+    fn operand_integer(&self, operand: &Operand) -> Option<IntegerType> {
+        match operand {
+            Operand::Place(place) => Some(self.integer(place)),
+            Operand::Constant(_) => None,
+        }
+    }
+
+    /// Pushes the value of `operand` as `integer`'s type.
+    fn operand(
+        &mut self,
+        span: Span,
+        operand: &Operand,
+        integer: IntegerType,
+    ) -> Result<(), Error> {
+        let constant = match operand {
+            Operand::Place(place) => {
+                self.load(span, place)?;
+                self.convert(self.integer(place), integer);
+                return Ok(());
+            }
+            Operand::Constant(Constant::Integer(value)) => *value as i64,
+            Operand::Constant(Constant::Bool(b)) => *b as i64,
+            Operand::Constant(Constant::Unit) => 0,
+            Operand::Constant(Constant::Function(_)) => return Err(Error::Unsupported(span)),
+        };
+        match val_type(integer) {
+            ValType::I32 => self.emit(Instruction::I32Const(constant as i32)),
+            ValType::I64 => self.emit(Instruction::I64Const(constant)),
+        }
+        Ok(())
+    }
+
+    /// Pushes the value of `place` as its type.
+    fn load(&mut self, span: Span, place: &Place) -> Result<(), Error> {
+        let offset = self.address(span, place)?;
+        match val_type(self.integer(place)) {
+            // Slots are little endian so the low bits come first.
+            ValType::I32 => self.emit(Instruction::I32Load(offset)),
+            ValType::I64 => self.emit(Instruction::I64Load(offset)),
+        }
+        Ok(())
+    }
+
+    /// Pushes the base address of `place` and returns the offset from it.
+    /// Tuples and variants are pointers to their fields.
+    fn address(&mut self, span: Span, place: &Place) -> Result<u32, Error> {
+        self.emit(Instruction::LocalGet(self.frame()));
+        let mut address = offset(place.local);
+        let mut downcast = false;
+        for projection in &place.projection {
+            match projection {
+                Projection::Deref => {
+                    self.emit(Instruction::I64Load(address));
+                    self.emit(Instruction::I32WrapI64);
+                    address = 0;
+                }
+                Projection::Field(field) => {
+                    let index: u32 = field.parse().map_err(|_| Error::Unsupported(span))?;
+                    self.emit(Instruction::I64Load(address));
+                    self.emit(Instruction::I32WrapI64);
+                    address = (index + downcast as u32) * 8;
+                    downcast = false;
+                }
+                Projection::Downcast(..) => downcast = true,
+            }
+        }
+        Ok(address)
+    }
+}
+
+/// The offset of `local`'s slot in the frame.
+fn offset(local: Local) -> u32 {
+    local.0 as u32 * 8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{compile_str, run, run_optimized, run_panicking, run_with};
+    use assert_matches::assert_matches;
+    use rust_comp_core::profile::Profile;
+
+    #[test]
+    fn test_compile_arithmetic_and_exports() {
+        let (result, output) = run(
+            "fn add(a: i64, b: i64) -> i64 { a + b }
+             pub fn compute(x: i64) -> i64 { print_i64(x); add(x, 3) * 4 - 30 / 3 }",
+            "compute",
+            &[2],
+        );
+        assert_eq!(result, 10);
+        assert_eq!(output, [2]);
+        let module = compile_str("fn private() {} pub fn public() {}").unwrap();
+        assert_eq!(module.exports.len(), 2);
+        assert_matches!(&module.exports[0], Export::Function(name, 1) if name == "public");
+    }
+
+    #[test]
+    fn test_compile_branches_and_loops() {
+        let (_, output) = run(
+            "fn fib(n: i64) -> i64 { if n == 0 || n == 1 { n } else { fib(n - 1) + fib(n - 2) } }
+             pub fn main() {
+                 let mut i = 0;
+                 while i != 10 { print_i64(fib(i)); i = i + 1; }
+             }",
+            "main",
+            &[],
+        );
+        assert_eq!(output, [0, 1, 1, 2, 3, 5, 8, 13, 21, 34]);
+    }
+
+    #[test]
+    fn test_compile_tuples_enums_and_pointers() {
+        let (result, _) = run(
+            "enum Shape { Square(i64), Rect(i64, i64), Empty }
+             fn area(s: Shape) -> i64 {
+                 match s { Square(x) => x * x, Rect(w, h) => w * h, Empty => 0 }
+             }
+             fn set(p: &mut i64, v: i64) { *p = v; }
+             pub fn main() -> i64 {
+                 let (a, b) = (area(Square(3)), area(Rect(2, 5)));
+                 let mut c = area(Empty);
+                 set(&mut c, 300);
+                 a + b + c as u8
+             }",
+            "main",
+            &[],
+        );
+        assert_eq!(result, 9 + 10 + 44);
+    }
+
+    #[test]
+    fn test_compile_overflow_panics() {
+        let program = "pub fn add(a: i64, b: i64) -> i64 { a + b }
+                       pub fn sub(a: i64, b: i64) -> i64 { a - b }
+                       pub fn mul(a: i64, b: i64) -> i64 { a * b }
+                       pub fn div(a: i64, b: i64) -> i64 { a / b }";
+        let max = i64::MAX;
+        let min = i64::MIN;
+        let cases: &[(&str, i64, i64, Result<i64, &str>)] = &[
+            ("add", max, 0, Ok(max)),
+            (
+                "add",
+                max,
+                1,
+                Err("<test>:1:37: attempt to add with overflow"),
+            ),
+            (
+                "add",
+                min,
+                -1,
+                Err("<test>:1:37: attempt to add with overflow"),
+            ),
+            (
+                "sub",
+                min,
+                1,
+                Err("<test>:2:60: attempt to subtract with overflow"),
+            ),
+            ("sub", -1, max, Ok(min)),
+            ("mul", -3, 5, Ok(-15)),
+            ("mul", 0, min, Ok(0)),
+            ("mul", -1, max, Ok(-max)),
+            (
+                "mul",
+                -1,
+                min,
+                Err("<test>:3:60: attempt to multiply with overflow"),
+            ),
+            (
+                "mul",
+                1 << 32,
+                1 << 31,
+                Err("<test>:3:60: attempt to multiply with overflow"),
+            ),
+            ("div", min, 2, Ok(min / 2)),
+            (
+                "div",
+                min,
+                -1,
+                Err("<test>:4:60: attempt to divide with overflow"),
+            ),
+            ("div", 1, 0, Err("<test>:4:60: attempt to divide by zero")),
+        ];
+        for (function, a, b, expected) in cases {
+            let (result, _) = run_panicking(program, function, &[*a, *b]);
+            assert_eq!(result, expected.map_err(|e| e.to_string()));
+        }
+        let module = compile_str(program).unwrap();
+        assert_eq!(module.imports[0].name, "rust_panic");
+        assert!(wat::parse_str(module.to_wat()).is_ok());
+    }
+
+    #[test]
+    fn test_compile_sized_integers() {
+        let program = "pub fn add(a: i32, b: i32) -> i32 { a + b }
+                       pub fn sub(a: u32, b: u32) -> u32 { a - b }
+                       pub fn mul(a: u8, b: u8) -> u8 { a * b }
+                       pub fn add_u64(a: u64, b: u64) -> u64 { a + b }
+                       pub fn mul_u64(a: u64, b: u64) -> u64 { a * b }
+                       pub fn div_u64(a: u64, b: u64) -> u64 { a / b }
+                       pub fn div(a: i32, b: i32) -> i32 { a / b }";
+        // The result in release and the panic message in debug, if any.
+        // Results of `i32`s are sign extended.
+        let cases: &[(&str, i64, i64, i64, Option<&str>)] = &[
+            ("add", i32::MAX as i64, 1, i32::MIN as i64, Some("add")),
+            ("sub", 0, 1, u32::MAX as i32 as i64, Some("subtract")),
+            ("mul", 16, 16, 0, Some("multiply")),
+            ("mul", 15, 17, 255, None),
+            ("add_u64", -1, 1, 0, Some("add")),
+            ("mul_u64", 1 << 32, 1 << 32, 0, Some("multiply")),
+            ("mul_u64", 1 << 32, 1 << 31, 1 << 63, None),
+            ("div_u64", -1, 2, i64::MAX, None),
+        ];
+        for (function, a, b, release, panic) in cases {
+            let (result, _) = run_with(program, function, &[*a, *b], Profile::Release, 0);
+            assert_eq!(result, Ok(*release), "{}({}, {})", function, a, b);
+            let (result, _) = run_with(program, function, &[*a, *b], Profile::Debug, 0);
+            match panic {
+                Some(verb) => {
+                    let message = format!("attempt to {} with overflow", verb);
+                    assert!(result.unwrap_err().ends_with(&message));
+                }
+                None => assert_eq!(result, Ok(*release)),
+            }
+        }
+        // Dividing the minimum value by `-1` panics in release too.
+        let arguments = [i32::MIN as i64, -1];
+        let (result, _) = run_with(program, "div", &arguments, Profile::Release, 0);
+        assert!(result
+            .unwrap_err()
+            .ends_with("attempt to divide with overflow"));
+    }
+
+    #[test]
+    fn test_compile_optimized() {
+        let program = "fn square(x: i64) -> i64 { x * x }
+                       fn fib(n: i64) -> i64 { if n == 0 || n == 1 { n } else { fib(n - 1) + fib(n - 2) } }
+                       fn set(p: &mut i64, v: i64) { *p = v; }
+                       pub fn main(n: i64, m: i64) -> i64 {
+                           let mut i = 0;
+                           let mut sum = 0;
+                           while i != n {
+                               let k = m * 3 + 1;
+                               let mut j = 0;
+                               while j != i { sum = sum + k; j = j + 1; }
+                               print_i64(fib(i) + square(i));
+                               i = i + 1;
+                           }
+                           let mut c = 0;
+                           set(&mut c, sum);
+                           c / m
+                       }";
+        let cases: &[(i64, i64)] = &[(10, 2), (0, 1), (5, 0), (3, i64::MAX)];
+        for (n, m) in cases {
+            let unoptimized = run_optimized(program, "main", &[*n, *m], 0);
+            assert_eq!(run_optimized(program, "main", &[*n, *m], 1), unoptimized);
+            assert_eq!(run_optimized(program, "main", &[*n, *m], 2), unoptimized);
+        }
+    }
+
+    #[test]
+    fn test_compile_wat() {
+        let wat = compile_str("pub fn one() -> i64 { 1 }").unwrap().to_wat();
+        assert!(wat.contains("(type (;0;) (func (result i64)))"));
+        assert!(wat.contains("(export \"one\" (func 0))"));
+        assert!(wat.contains("i64.const 1"));
+
+        let wat = compile_str("pub fn add(a: i32, b: u8) -> i32 { a + b as i32 }")
+            .unwrap()
+            .to_wat();
+        assert!(wat.contains("(func (param i32 i32) (result i32))"));
+        assert!(wat.contains("i32.load offset=8"));
+        assert!(wat.contains("i32.add"));
+
+        let module = compile_str(
+            "fn next(p: &mut i64) -> Option { *p = *p + 1; if *p == 3 { None } else { Some(*p) } }
+             pub fn main() { let mut i = 0; while let Some(x) = next(&mut i) { print_i64(x); } }",
+        )
+        .unwrap();
+        assert!(wat::parse_str(module.to_wat()).is_ok());
+    }
+}
@@ -0,0 +1,13 @@
+use rust_comp_core::pos::Span;
+
+pub mod codegen;
+pub mod module;
+
+#[cfg(test)]
+mod test;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The MIR statement or terminator can't be compiled yet.
+    Unsupported(Span),
+}
@@ -0,0 +1,587 @@
+use std::fmt::{self, Write};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValType {
+    I32,
+    I64,
+}
+
+/// The instructions the code generator uses.  Loads and stores hold their
+/// constant offset and are always aligned to their width.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    Unreachable,
+    Block,
+    Loop,
+    If,
+    End,
+    Br(u32),
+    BrTable(Vec<u32>, u32),
+    Return,
+    Call(u32),
+    Select,
+    LocalGet(u32),
+    LocalSet(u32),
+    LocalTee(u32),
+    GlobalGet(u32),
+    GlobalSet(u32),
+    I32Load(u32),
+    I64Load(u32),
+    I64Store(u32),
+    I32Const(i32),
+    I64Const(i64),
+    I32Eq,
+    I32Ne,
+    I64Eq,
+    I64Ne,
+    I64LtS,
+    I64LtU,
+    I32Add,
+    I32Sub,
+    I32Mul,
+    I32DivS,
+    I32DivU,
+    I32And,
+    I32Or,
+    I64Add,
+    I64Sub,
+    I64Mul,
+    I64DivS,
+    I64DivU,
+    I64And,
+    I64Or,
+    I64Xor,
+    I32WrapI64,
+    I64ExtendI32S,
+    I64ExtendI32U,
+    I32Extend8S,
+    I32Extend16S,
+    I64Extend8S,
+    I64Extend16S,
+    I64Extend32S,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Import {
+    pub module: String,
+    pub name: String,
+    pub type_: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    pub type_: u32,
+    pub locals: Vec<ValType>,
+    pub body: Vec<Instruction>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Global {
+    pub type_: ValType,
+    pub mutable: bool,
+    pub value: i64,
+}
+
+/// Bytes copied into memory at `offset` when the module is instantiated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Data {
+    pub offset: u32,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Export {
+    Function(String, u32),
+    Memory(String),
+}
+
+/// A WebAssembly module with a single memory.  Imported functions come before
+/// defined functions in the function index space.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Module {
+    pub types: Vec<(Vec<ValType>, Vec<ValType>)>,
+    pub imports: Vec<Import>,
+    pub functions: Vec<Function>,
+    /// The initial size of the memory in 64 KiB pages.
+    pub memory_pages: u32,
+    pub globals: Vec<Global>,
+    pub exports: Vec<Export>,
+    pub data: Vec<Data>,
+}
+
+impl Module {
+    /// Encodes the module in the binary format.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut module = b"\0asm".to_vec();
+        module.extend_from_slice(&1u32.to_le_bytes());
+
+        let mut types = Vec::new();
+        unsigned(&mut types, self.types.len() as u64);
+        for (parameters, results) in &self.types {
+            types.push(0x60);
+            value_types(&mut types, parameters);
+            value_types(&mut types, results);
+        }
+        section(&mut module, 1, &types);
+
+        let mut imports = Vec::new();
+        unsigned(&mut imports, self.imports.len() as u64);
+        for import in &self.imports {
+            name(&mut imports, &import.module);
+            name(&mut imports, &import.name);
+            imports.push(0x00);
+            unsigned(&mut imports, import.type_ as u64);
+        }
+        section(&mut module, 2, &imports);
+
+        let mut functions = Vec::new();
+        unsigned(&mut functions, self.functions.len() as u64);
+        for function in &self.functions {
+            unsigned(&mut functions, function.type_ as u64);
+        }
+        section(&mut module, 3, &functions);
+
+        let mut memory = Vec::new();
+        unsigned(&mut memory, 1);
+        memory.push(0x00);
+        unsigned(&mut memory, self.memory_pages as u64);
+        section(&mut module, 5, &memory);
+
+        let mut globals = Vec::new();
+        unsigned(&mut globals, self.globals.len() as u64);
+        for global in &self.globals {
+            globals.push(value_type(global.type_));
+            globals.push(global.mutable as u8);
+            match global.type_ {
+                ValType::I32 => Instruction::I32Const(global.value as i32).encode(&mut globals),
+                ValType::I64 => Instruction::I64Const(global.value).encode(&mut globals),
+            }
+            Instruction::End.encode(&mut globals);
+        }
+        section(&mut module, 6, &globals);
+
+        let mut exports = Vec::new();
+        unsigned(&mut exports, self.exports.len() as u64);
+        for export in &self.exports {
+            match export {
+                Export::Function(export, index) => {
+                    name(&mut exports, export);
+                    exports.push(0x00);
+                    unsigned(&mut exports, *index as u64);
+                }
+                Export::Memory(export) => {
+                    name(&mut exports, export);
+                    exports.push(0x02);
+                    unsigned(&mut exports, 0);
+                }
+            }
+        }
+        section(&mut module, 7, &exports);
+
+        let mut code = Vec::new();
+        unsigned(&mut code, self.functions.len() as u64);
+        for function in &self.functions {
+            let mut body = Vec::new();
+            let groups = local_groups(&function.locals);
+            unsigned(&mut body, groups.len() as u64);
+            for (count, type_) in groups {
+                unsigned(&mut body, count as u64);
+                body.push(value_type(type_));
+            }
+            for instruction in &function.body {
+                instruction.encode(&mut body);
+            }
+            Instruction::End.encode(&mut body);
+            unsigned(&mut code, body.len() as u64);
+            code.extend_from_slice(&body);
+        }
+        section(&mut module, 10, &code);
+
+        let mut data = Vec::new();
+        unsigned(&mut data, self.data.len() as u64);
+        for segment in &self.data {
+            // An active segment in memory 0.
+            data.push(0x00);
+            Instruction::I32Const(segment.offset as i32).encode(&mut data);
+            Instruction::End.encode(&mut data);
+            unsigned(&mut data, segment.bytes.len() as u64);
+            data.extend_from_slice(&segment.bytes);
+        }
+        section(&mut module, 11, &data);
+        module
+    }
+
+    /// Formats the module in the text format.
+    pub fn to_wat(&self) -> String {
+        let mut wat = String::from("(module\n");
+        for (i, (parameters, results)) in self.types.iter().enumerate() {
+            writeln!(
+                wat,
+                "  (type (;{};) (func{}{}))",
+                i,
+                value_types_wat(" (param", parameters),
+                value_types_wat(" (result", results)
+            )
+            .unwrap();
+        }
+        for (i, import) in self.imports.iter().enumerate() {
+            writeln!(
+                wat,
+                "  (import \"{}\" \"{}\" (func (;{};) (type {})))",
+                import.module, import.name, i, import.type_
+            )
+            .unwrap();
+        }
+        for function in &self.functions {
+            writeln!(wat, "  (func ${} (type {})", function.name, function.type_).unwrap();
+            if !function.locals.is_empty() {
+                writeln!(wat, "    {}", value_types_wat("(local", &function.locals)).unwrap();
+            }
+            let mut depth = 2;
+            for instruction in &function.body {
+                if *instruction == Instruction::End {
+                    depth -= 1;
+                }
+                writeln!(wat, "{}{}", "  ".repeat(depth), instruction).unwrap();
+                if let Instruction::Block | Instruction::Loop | Instruction::If = instruction {
+                    depth += 1;
+                }
+            }
+            writeln!(wat, "  )").unwrap();
+        }
+        writeln!(wat, "  (memory (;0;) {})", self.memory_pages).unwrap();
+        for (i, global) in self.globals.iter().enumerate() {
+            let type_ = match global.type_ {
+                ValType::I32 => "i32",
+                ValType::I64 => "i64",
+            };
+            let global_type = if global.mutable {
+                format!("(mut {})", type_)
+            } else {
+                type_.to_string()
+            };
+            writeln!(
+                wat,
+                "  (global (;{};) {} ({}.const {}))",
+                i, global_type, type_, global.value
+            )
+            .unwrap();
+        }
+        for export in &self.exports {
+            match export {
+                Export::Function(name, index) => {
+                    writeln!(wat, "  (export \"{}\" (func {}))", name, index).unwrap()
+                }
+                Export::Memory(name) => {
+                    writeln!(wat, "  (export \"{}\" (memory 0))", name).unwrap()
+                }
+            }
+        }
+        for (i, segment) in self.data.iter().enumerate() {
+            let mut bytes = String::new();
+            for byte in &segment.bytes {
+                let printable = byte.is_ascii_graphic() || *byte == b' ';
+                if printable && *byte != b'"' && *byte != b'\\' {
+                    bytes.push(*byte as char);
+                } else {
+                    write!(bytes, "\\{:02x}", byte).unwrap();
+                }
+            }
+            writeln!(
+                wat,
+                "  (data (;{};) (i32.const {}) \"{}\")",
+                i, segment.offset, bytes
+            )
+            .unwrap();
+        }
+        wat.push_str(")\n");
+        wat
+    }
+}
+
+impl Instruction {
+    fn encode(&self, code: &mut Vec<u8>) {
+        match self {
+            Instruction::Unreachable => code.push(0x00),
+            // Blocks have no parameters or results.
+            Instruction::Block => code.extend_from_slice(&[0x02, 0x40]),
+            Instruction::Loop => code.extend_from_slice(&[0x03, 0x40]),
+            Instruction::If => code.extend_from_slice(&[0x04, 0x40]),
+            Instruction::End => code.push(0x0B),
+            Instruction::Br(depth) => {
+                code.push(0x0C);
+                unsigned(code, *depth as u64);
+            }
+            Instruction::BrTable(depths, default) => {
+                code.push(0x0E);
+                unsigned(code, depths.len() as u64);
+                for depth in depths {
+                    unsigned(code, *depth as u64);
+                }
+                unsigned(code, *default as u64);
+            }
+            Instruction::Return => code.push(0x0F),
+            Instruction::Call(index) => {
+                code.push(0x10);
+                unsigned(code, *index as u64);
+            }
+            Instruction::Select => code.push(0x1B),
+            Instruction::LocalGet(index) => index_instruction(code, 0x20, *index),
+            Instruction::LocalSet(index) => index_instruction(code, 0x21, *index),
+            Instruction::LocalTee(index) => index_instruction(code, 0x22, *index),
+            Instruction::GlobalGet(index) => index_instruction(code, 0x23, *index),
+            Instruction::GlobalSet(index) => index_instruction(code, 0x24, *index),
+            Instruction::I32Load(offset) => memory_instruction(code, 0x28, 2, *offset),
+            Instruction::I64Load(offset) => memory_instruction(code, 0x29, 3, *offset),
+            Instruction::I64Store(offset) => memory_instruction(code, 0x37, 3, *offset),
+            Instruction::I32Const(value) => {
+                code.push(0x41);
+                signed(code, *value as i64);
+            }
+            Instruction::I64Const(value) => {
+                code.push(0x42);
+                signed(code, *value);
+            }
+            Instruction::I32Eq => code.push(0x46),
+            Instruction::I32Ne => code.push(0x47),
+            Instruction::I64Eq => code.push(0x51),
+            Instruction::I64Ne => code.push(0x52),
+            Instruction::I64LtS => code.push(0x53),
+            Instruction::I64LtU => code.push(0x54),
+            Instruction::I32Add => code.push(0x6A),
+            Instruction::I32Sub => code.push(0x6B),
+            Instruction::I32Mul => code.push(0x6C),
+            Instruction::I32DivS => code.push(0x6D),
+            Instruction::I32DivU => code.push(0x6E),
+            Instruction::I32And => code.push(0x71),
+            Instruction::I32Or => code.push(0x72),
+            Instruction::I64Add => code.push(0x7C),
+            Instruction::I64Sub => code.push(0x7D),
+            Instruction::I64Mul => code.push(0x7E),
+            Instruction::I64DivS => code.push(0x7F),
+            Instruction::I64DivU => code.push(0x80),
+            Instruction::I64And => code.push(0x83),
+            Instruction::I64Or => code.push(0x84),
+            Instruction::I64Xor => code.push(0x85),
+            Instruction::I32WrapI64 => code.push(0xA7),
+            Instruction::I64ExtendI32S => code.push(0xAC),
+            Instruction::I64ExtendI32U => code.push(0xAD),
+            Instruction::I32Extend8S => code.push(0xC0),
+            Instruction::I32Extend16S => code.push(0xC1),
+            Instruction::I64Extend8S => code.push(0xC2),
+            Instruction::I64Extend16S => code.push(0xC3),
+            Instruction::I64Extend32S => code.push(0xC4),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::Unreachable => write!(f, "unreachable"),
+            Instruction::Block => write!(f, "block"),
+            Instruction::Loop => write!(f, "loop"),
+            Instruction::If => write!(f, "if"),
+            Instruction::End => write!(f, "end"),
+            Instruction::Br(depth) => write!(f, "br {}", depth),
+            Instruction::BrTable(depths, default) => {
+                write!(f, "br_table")?;
+                for depth in depths {
+                    write!(f, " {}", depth)?;
+                }
+                write!(f, " {}", default)
+            }
+            Instruction::Return => write!(f, "return"),
+            Instruction::Call(index) => write!(f, "call {}", index),
+            Instruction::Select => write!(f, "select"),
+            Instruction::LocalGet(index) => write!(f, "local.get {}", index),
+            Instruction::LocalSet(index) => write!(f, "local.set {}", index),
+            Instruction::LocalTee(index) => write!(f, "local.tee {}", index),
+            Instruction::GlobalGet(index) => write!(f, "global.get {}", index),
+            Instruction::GlobalSet(index) => write!(f, "global.set {}", index),
+            Instruction::I32Load(offset) => write!(f, "i32.load offset={}", offset),
+            Instruction::I64Load(offset) => write!(f, "i64.load offset={}", offset),
+            Instruction::I64Store(offset) => write!(f, "i64.store offset={}", offset),
+            Instruction::I32Const(value) => write!(f, "i32.const {}", value),
+            Instruction::I64Const(value) => write!(f, "i64.const {}", value),
+            Instruction::I32Eq => write!(f, "i32.eq"),
+            Instruction::I32Ne => write!(f, "i32.ne"),
+            Instruction::I64Eq => write!(f, "i64.eq"),
+            Instruction::I64Ne => write!(f, "i64.ne"),
+            Instruction::I64LtS => write!(f, "i64.lt_s"),
+            Instruction::I64LtU => write!(f, "i64.lt_u"),
+            Instruction::I32Add => write!(f, "i32.add"),
+            Instruction::I32Sub => write!(f, "i32.sub"),
+            Instruction::I32Mul => write!(f, "i32.mul"),
+            Instruction::I32DivS => write!(f, "i32.div_s"),
+            Instruction::I32DivU => write!(f, "i32.div_u"),
+            Instruction::I32And => write!(f, "i32.and"),
+            Instruction::I32Or => write!(f, "i32.or"),
+            Instruction::I64Add => write!(f, "i64.add"),
+            Instruction::I64Sub => write!(f, "i64.sub"),
+            Instruction::I64Mul => write!(f, "i64.mul"),
+            Instruction::I64DivS => write!(f, "i64.div_s"),
+            Instruction::I64DivU => write!(f, "i64.div_u"),
+            Instruction::I64And => write!(f, "i64.and"),
+            Instruction::I64Or => write!(f, "i64.or"),
+            Instruction::I64Xor => write!(f, "i64.xor"),
+            Instruction::I32WrapI64 => write!(f, "i32.wrap_i64"),
+            Instruction::I64ExtendI32S => write!(f, "i64.extend_i32_s"),
+            Instruction::I64ExtendI32U => write!(f, "i64.extend_i32_u"),
+            Instruction::I32Extend8S => write!(f, "i32.extend8_s"),
+            Instruction::I32Extend16S => write!(f, "i32.extend16_s"),
+            Instruction::I64Extend8S => write!(f, "i64.extend8_s"),
+            Instruction::I64Extend16S => write!(f, "i64.extend16_s"),
+            Instruction::I64Extend32S => write!(f, "i64.extend32_s"),
+        }
+    }
+}
+
+fn index_instruction(code: &mut Vec<u8>, opcode: u8, index: u32) {
+    code.push(opcode);
+    unsigned(code, index as u64);
+}
+
+fn memory_instruction(code: &mut Vec<u8>, opcode: u8, align: u32, offset: u32) {
+    code.push(opcode);
+    // The alignment is encoded as a power of two.
+    unsigned(code, align as u64);
+    unsigned(code, offset as u64);
+}
+
+fn section(module: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    module.push(id);
+    unsigned(module, contents.len() as u64);
+    module.extend_from_slice(contents);
+}
+
+fn name(output: &mut Vec<u8>, name: &str) {
+    unsigned(output, name.len() as u64);
+    output.extend_from_slice(name.as_bytes());
+}
+
+fn value_type(type_: ValType) -> u8 {
+    match type_ {
+        ValType::I32 => 0x7F,
+        ValType::I64 => 0x7E,
+    }
+}
+
+fn value_types(output: &mut Vec<u8>, types: &[ValType]) {
+    unsigned(output, types.len() as u64);
+    output.extend(types.iter().map(|type_| value_type(*type_)));
+}
+
+fn value_types_wat(prefix: &str, types: &[ValType]) -> String {
+    if types.is_empty() {
+        return String::new();
+    }
+    let mut wat = prefix.to_string();
+    for type_ in types {
+        wat.push_str(match type_ {
+            ValType::I32 => " i32",
+            ValType::I64 => " i64",
+        });
+    }
+    wat.push(')');
+    wat
+}
+
+/// Groups runs of locals with the same type.
+fn local_groups(locals: &[ValType]) -> Vec<(usize, ValType)> {
+    let mut groups: Vec<(usize, ValType)> = Vec::new();
+    for local in locals {
+        match groups.last_mut() {
+            Some((count, type_)) if type_ == local => *count += 1,
+            _ => groups.push((1, *local)),
+        }
+    }
+    groups
+}
+
+/// Writes `value` as unsigned LEB128.
+fn unsigned(output: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            output.push(byte);
+            return;
+        }
+        output.push(byte | 0x80);
+    }
+}
+
+/// Writes `value` as signed LEB128.
+fn signed(output: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            output.push(byte);
+            return;
+        }
+        output.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leb128() {
+        let mut output = Vec::new();
+        unsigned(&mut output, 624485);
+        assert_eq!(output, [0xE5, 0x8E, 0x26]);
+        output.clear();
+        signed(&mut output, -123456);
+        assert_eq!(output, [0xC0, 0xBB, 0x78]);
+        output.clear();
+        signed(&mut output, 64);
+        assert_eq!(output, [0xC0, 0x00]);
+    }
+
+    #[test]
+    fn test_encode_empty_module() {
+        let module = Module {
+            types: Vec::new(),
+            imports: Vec::new(),
+            functions: Vec::new(),
+            memory_pages: 1,
+            globals: Vec::new(),
+            exports: Vec::new(),
+            data: Vec::new(),
+        };
+        let bytes = module.encode();
+        assert_eq!(&bytes[..8], b"\0asm\x01\0\0\0");
+        // The memory section declares one memory without a maximum.
+        assert!(bytes.windows(5).any(|w| w == [5, 3, 1, 0, 1]));
+    }
+
+    #[test]
+    fn test_encode_data() {
+        let module = Module {
+            types: Vec::new(),
+            imports: Vec::new(),
+            functions: Vec::new(),
+            memory_pages: 1,
+            globals: Vec::new(),
+            exports: Vec::new(),
+            data: vec![Data {
+                offset: 8,
+                bytes: b"a\"\n".to_vec(),
+            }],
+        };
+        let bytes = module.encode();
+        assert_eq!(
+            bytes[bytes.len() - 11..],
+            [11, 9, 1, 0x00, 0x41, 8, 0x0B, 3, b'a', b'"', b'\n']
+        );
+        assert!(module
+            .to_wat()
+            .contains("(data (;0;) (i32.const 8) \"a\\22\\0a\")"));
+    }
+}
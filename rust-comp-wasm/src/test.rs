@@ -0,0 +1,137 @@
+use crate::codegen::compile;
+use crate::module::Module;
+use crate::Error;
+use rust_comp_check::test_support;
+use rust_comp_core::profile::Profile;
+use rust_comp_mir::transform;
+use wasmi::core::ValueType;
+use wasmi::{Caller, Engine, Func, Linker, Store, Value};
+
+/// Compiles `file_contents` as file 0 with overflow checks.
+pub fn compile_str(file_contents: &str) -> Result<Module, Error> {
+    compile_str_optimized(file_contents, 0)
+}
+
+/// Like `compile_str` but optimizes the MIR at `opt_level` first.
+pub fn compile_str_optimized(file_contents: &str, opt_level: u32) -> Result<Module, Error> {
+    compile_str_with(file_contents, Profile::Debug, opt_level)
+}
+
+/// Like `compile_str_optimized` but checks arithmetic as `profile` requires.
+pub fn compile_str_with(
+    file_contents: &str,
+    profile: Profile,
+    opt_level: u32,
+) -> Result<Module, Error> {
+    let (diagnostic, top_levels) = test_support::desugar(file_contents);
+    let mut bodies = rust_comp_mir::lower::lower(&diagnostic, profile, &top_levels);
+    transform::optimize(&mut bodies, opt_level);
+    compile(&bodies)
+}
+
+#[derive(Default)]
+struct Host {
+    output: Vec<i64>,
+    panic: Option<String>,
+}
+
+/// Compiles `file_contents`, validates and instantiates the module, and
+/// calls the export `function`.  Returns its result and the values passed
+/// to `print_i64`.  Arguments and results of `i32`s are truncated and sign
+/// extended.
+pub fn run(file_contents: &str, function: &str, arguments: &[i64]) -> (i64, Vec<i64>) {
+    let (result, output) = run_panicking(file_contents, function, arguments);
+    (result.unwrap(), output)
+}
+
+/// Like `run` but returns the message passed to `rust_panic` if the function
+/// panics.
+pub fn run_panicking(
+    file_contents: &str,
+    function: &str,
+    arguments: &[i64],
+) -> (Result<i64, String>, Vec<i64>) {
+    run_optimized(file_contents, function, arguments, 0)
+}
+
+/// Like `run_panicking` but optimizes the MIR at `opt_level` first.
+pub fn run_optimized(
+    file_contents: &str,
+    function: &str,
+    arguments: &[i64],
+    opt_level: u32,
+) -> (Result<i64, String>, Vec<i64>) {
+    run_with(
+        file_contents,
+        function,
+        arguments,
+        Profile::Debug,
+        opt_level,
+    )
+}
+
+/// Like `run_optimized` but checks arithmetic as `profile` requires.
+pub fn run_with(
+    file_contents: &str,
+    function: &str,
+    arguments: &[i64],
+    profile: Profile,
+    opt_level: u32,
+) -> (Result<i64, String>, Vec<i64>) {
+    let wasm = compile_str_with(file_contents, profile, opt_level)
+        .unwrap()
+        .encode();
+    let engine = Engine::default();
+    let module = wasmi::Module::new(&engine, &wasm[..]).unwrap();
+    let mut store = Store::new(&engine, Host::default());
+    let print = Func::wrap(&mut store, |mut caller: Caller<'_, Host>, value: i64| {
+        caller.data_mut().output.push(value);
+        0i64
+    });
+    let panic = Func::wrap(
+        &mut store,
+        |mut caller: Caller<'_, Host>, address: i64, length: i64| {
+            let memory = caller
+                .get_export("memory")
+                .and_then(|export| export.into_memory())
+                .unwrap();
+            let mut message = vec![0; length as usize];
+            memory
+                .read(&caller, address as usize, &mut message)
+                .unwrap();
+            caller.data_mut().panic = Some(String::from_utf8(message).unwrap());
+            0i64
+        },
+    );
+    let mut linker = <Linker<Host>>::new(&engine);
+    linker.define("env", "print_i64", print).unwrap();
+    linker.define("env", "rust_panic", panic).unwrap();
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .unwrap()
+        .start(&mut store)
+        .unwrap();
+    let function = instance.get_func(&store, function).unwrap();
+    let type_ = function.ty(&store);
+    let arguments: Vec<Value> = arguments
+        .iter()
+        .zip(type_.params())
+        .map(|(argument, type_)| value(*argument, type_))
+        .collect();
+    let mut result = [value(0, &type_.results()[0])];
+    let result = match function.call(&mut store, &arguments, &mut result) {
+        Ok(()) => Ok(match result[0] {
+            Value::I32(result) => result as i64,
+            _ => result[0].i64().unwrap(),
+        }),
+        Err(_) => Err(store.data_mut().panic.take().unwrap()),
+    };
+    (result, store.into_data().output)
+}
+
+fn value(value: i64, type_: &ValueType) -> Value {
+    match type_ {
+        ValueType::I32 => Value::I32(value as i32),
+        _ => Value::I64(value),
+    }
+}
@@ -0,0 +1,26 @@
+/* The runtime programs compiled by rust-comp are linked against:
+ *
+ *     cc program.o rust-comp-x86/runtime/runtime.c -o program
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+
+void rust_main(void);
+
+void print_i64(long value) {
+    printf("%ld\n", value);
+}
+
+/* Called when an `Assert` fails.  Exits with the same status as a Rust
+ * program that panics. */
+void rust_panic(const char *message) {
+    fflush(stdout);
+    fprintf(stderr, "panicked at %s\n", message);
+    exit(101);
+}
+
+int main(void) {
+    rust_main();
+    return 0;
+}
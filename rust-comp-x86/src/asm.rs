@@ -0,0 +1,356 @@
+use crate::elf::Relocation;
+
+/// A 64 bit general purpose register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reg {
+    Rax = 0,
+    Rcx = 1,
+    Rdx = 2,
+    Rbx = 3,
+    Rsp = 4,
+    Rbp = 5,
+    Rsi = 6,
+    Rdi = 7,
+    R8 = 8,
+    R9 = 9,
+    R11 = 11,
+}
+
+impl Reg {
+    fn low(self) -> u8 {
+        self as u8 & 7
+    }
+
+    fn high(self) -> u8 {
+        (self as u8 >> 3) & 1
+    }
+}
+
+/// The System V registers integer arguments are passed in.
+pub const ARGUMENT_REGISTERS: [Reg; 6] = [Reg::Rdi, Reg::Rsi, Reg::Rdx, Reg::Rcx, Reg::R8, Reg::R9];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Condition {
+    Overflow = 0x0,
+    /// Unsigned overflow.
+    Carry = 0x2,
+    Equal = 0x4,
+    NotEqual = 0x5,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arithmetic {
+    Add = 0x01,
+    Or = 0x09,
+    And = 0x21,
+    Sub = 0x29,
+    Cmp = 0x39,
+}
+
+/// The width and signedness of an integer being extended to 64 bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Extend {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Label(pub usize);
+
+/// Encodes x86-64 instructions.  Jumps refer to labels that are resolved by
+/// `finish`.
+#[derive(Default)]
+pub struct Assembler {
+    pub code: Vec<u8>,
+    pub relocations: Vec<Relocation>,
+    labels: Vec<Option<usize>>,
+    /// Offsets of 32 bit displacements to labels.
+    jumps: Vec<(usize, Label)>,
+}
+
+impl Assembler {
+    pub fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    pub fn bind(&mut self, label: Label) {
+        self.labels[label.0] = Some(self.code.len());
+    }
+
+    /// Patches jumps to their labels.  Every label jumped to must be bound.
+    pub fn finish(&mut self) {
+        for (offset, label) in self.jumps.drain(..) {
+            let target = self.labels[label.0].expect("jump to unbound label");
+            let displacement = target as i64 - (offset as i64 + 4);
+            self.code[offset..offset + 4].copy_from_slice(&(displacement as i32).to_le_bytes());
+        }
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+
+    fn rex_w(&mut self, reg: Reg, rm: Reg) {
+        self.code.push(0x48 | (reg.high() << 2) | rm.high());
+    }
+
+    /// Emits a ModRM byte addressing `[base + displacement]`.  `base` must
+    /// not be `rsp`.
+    fn memory(&mut self, reg: Reg, base: Reg, displacement: i32) {
+        assert_ne!(base.low(), Reg::Rsp.low());
+        self.code.push(0x80 | (reg.low() << 3) | base.low());
+        self.bytes(&displacement.to_le_bytes());
+    }
+
+    fn registers(&mut self, reg: Reg, rm: Reg) {
+        self.code.push(0xC0 | (reg.low() << 3) | rm.low());
+    }
+
+    pub fn push(&mut self, reg: Reg) {
+        if reg.high() != 0 {
+            self.code.push(0x41);
+        }
+        self.code.push(0x50 + reg.low());
+    }
+
+    /// `mov dst, src`
+    pub fn mov(&mut self, dst: Reg, src: Reg) {
+        self.rex_w(src, dst);
+        self.code.push(0x89);
+        self.registers(src, dst);
+    }
+
+    /// `mov dst, imm64`
+    pub fn mov_imm(&mut self, dst: Reg, imm: i64) {
+        self.rex_w(Reg::Rax, dst);
+        self.code.push(0xB8 + dst.low());
+        self.bytes(&imm.to_le_bytes());
+    }
+
+    /// `mov dst, [base + displacement]`
+    pub fn load(&mut self, dst: Reg, base: Reg, displacement: i32) {
+        self.rex_w(dst, base);
+        self.code.push(0x8B);
+        self.memory(dst, base, displacement);
+    }
+
+    /// `mov [base + displacement], src`
+    pub fn store(&mut self, base: Reg, displacement: i32, src: Reg) {
+        self.rex_w(src, base);
+        self.code.push(0x89);
+        self.memory(src, base, displacement);
+    }
+
+    /// `lea dst, [base + displacement]`
+    pub fn lea(&mut self, dst: Reg, base: Reg, displacement: i32) {
+        self.rex_w(dst, base);
+        self.code.push(0x8D);
+        self.memory(dst, base, displacement);
+    }
+
+    /// `lea dst, [rip + label]`
+    pub fn lea_label(&mut self, dst: Reg, label: Label) {
+        self.rex_w(dst, Reg::Rax);
+        self.code.push(0x8D);
+        self.code.push((dst.low() << 3) | 0x05);
+        self.displacement(label);
+    }
+
+    /// Emits bytes that aren't instructions, like strings.
+    pub fn data(&mut self, bytes: &[u8]) {
+        self.bytes(bytes);
+    }
+
+    /// `op dst, src` for `op` in `add`, `sub`, `and`, `or`, and `cmp`.
+    pub fn arithmetic(&mut self, op: Arithmetic, dst: Reg, src: Reg) {
+        self.rex_w(src, dst);
+        self.code.push(op as u8);
+        self.registers(src, dst);
+    }
+
+    /// `sub rsp, imm32`
+    pub fn sub_rsp(&mut self, imm: i32) {
+        self.bytes(&[0x48, 0x81, 0xEC]);
+        self.bytes(&imm.to_le_bytes());
+    }
+
+    /// `cmp rax, imm32`
+    pub fn cmp_rax_imm(&mut self, imm: i32) {
+        self.bytes(&[0x48, 0x3D]);
+        self.bytes(&imm.to_le_bytes());
+    }
+
+    /// `imul dst, src`
+    pub fn imul(&mut self, dst: Reg, src: Reg) {
+        self.rex_w(dst, src);
+        self.bytes(&[0x0F, 0xAF]);
+        self.registers(dst, src);
+    }
+
+    /// `cqo; idiv divisor`: divides `rdx:rax` by `divisor` leaving the
+    /// quotient in `rax`.
+    pub fn idiv(&mut self, divisor: Reg) {
+        self.bytes(&[0x48, 0x99]);
+        self.rex_w(Reg::Rax, divisor);
+        self.code.push(0xF7);
+        // `idiv` is encoded as `F7 /7`.
+        self.registers(Reg::Rdi, divisor);
+    }
+
+    /// `xor edx, edx; div divisor`: divides `rdx:rax` by `divisor` as
+    /// unsigned integers leaving the quotient in `rax`.
+    pub fn div(&mut self, divisor: Reg) {
+        self.bytes(&[0x31, 0xD2]);
+        self.rex_w(Reg::Rax, divisor);
+        self.code.push(0xF7);
+        // `div` is encoded as `F7 /6`.
+        self.registers(Reg::Rsi, divisor);
+    }
+
+    /// `mul src`: multiplies `rax` by `src` as unsigned integers into
+    /// `rdx:rax`, setting the carry flag if `rdx` isn't zero.
+    pub fn mul(&mut self, src: Reg) {
+        self.rex_w(Reg::Rax, src);
+        self.code.push(0xF7);
+        // `mul` is encoded as `F7 /4`.
+        self.registers(Reg::Rsp, src);
+    }
+
+    /// `setcc al; movzx rax, al`
+    pub fn set(&mut self, condition: Condition) {
+        self.bytes(&[0x0F, 0x90 + condition as u8, 0xC0]);
+        self.bytes(&[0x48, 0x0F, 0xB6, 0xC0]);
+    }
+
+    /// Sign or zero extends the low bits of `rax` into all of `rax`.
+    pub fn extend_rax(&mut self, extend: Extend) {
+        match extend {
+            Extend::U8 => self.bytes(&[0x48, 0x0F, 0xB6, 0xC0]),
+            Extend::I8 => self.bytes(&[0x48, 0x0F, 0xBE, 0xC0]),
+            Extend::U16 => self.bytes(&[0x48, 0x0F, 0xB7, 0xC0]),
+            Extend::I16 => self.bytes(&[0x48, 0x0F, 0xBF, 0xC0]),
+            Extend::U32 => self.bytes(&[0x89, 0xC0]),
+            Extend::I32 => self.bytes(&[0x48, 0x63, 0xC0]),
+        }
+    }
+
+    pub fn jmp(&mut self, label: Label) {
+        self.code.push(0xE9);
+        self.displacement(label);
+    }
+
+    pub fn jcc(&mut self, condition: Condition, label: Label) {
+        self.bytes(&[0x0F, 0x80 + condition as u8]);
+        self.displacement(label);
+    }
+
+    fn displacement(&mut self, label: Label) {
+        self.jumps.push((self.code.len(), label));
+        self.bytes(&[0; 4]);
+    }
+
+    /// `call symbol` through the PLT.
+    pub fn call(&mut self, symbol: &str) {
+        self.code.push(0xE8);
+        self.relocations.push(Relocation {
+            offset: self.code.len(),
+            symbol: symbol.to_string(),
+        });
+        self.bytes(&[0; 4]);
+    }
+
+    pub fn leave(&mut self) {
+        self.code.push(0xC9);
+    }
+
+    pub fn ret(&mut self) {
+        self.code.push(0xC3);
+    }
+
+    pub fn ud2(&mut self) {
+        self.bytes(&[0x0F, 0x0B]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assemble(f: impl FnOnce(&mut Assembler)) -> Vec<u8> {
+        let mut asm = Assembler::default();
+        f(&mut asm);
+        asm.finish();
+        asm.code
+    }
+
+    #[test]
+    fn test_mov_registers() {
+        assert_eq!(assemble(|a| a.mov(Reg::Rbp, Reg::Rsp)), [0x48, 0x89, 0xE5]);
+        assert_eq!(assemble(|a| a.mov(Reg::R9, Reg::Rax)), [0x49, 0x89, 0xC1]);
+    }
+
+    #[test]
+    fn test_load_store_stack() {
+        assert_eq!(
+            assemble(|a| a.load(Reg::Rax, Reg::Rbp, -8)),
+            [0x48, 0x8B, 0x85, 0xF8, 0xFF, 0xFF, 0xFF]
+        );
+        assert_eq!(
+            assemble(|a| a.store(Reg::Rbp, -16, Reg::R8)),
+            [0x4C, 0x89, 0x85, 0xF0, 0xFF, 0xFF, 0xFF]
+        );
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(
+            assemble(|a| a.arithmetic(Arithmetic::Add, Reg::Rax, Reg::Rcx)),
+            [0x48, 0x01, 0xC8]
+        );
+        assert_eq!(
+            assemble(|a| a.imul(Reg::Rax, Reg::Rcx)),
+            [0x48, 0x0F, 0xAF, 0xC1]
+        );
+        assert_eq!(
+            assemble(|a| a.idiv(Reg::Rcx)),
+            [0x48, 0x99, 0x48, 0xF7, 0xF9]
+        );
+        assert_eq!(
+            assemble(|a| a.div(Reg::Rcx)),
+            [0x31, 0xD2, 0x48, 0xF7, 0xF1]
+        );
+        assert_eq!(assemble(|a| a.mul(Reg::Rcx)), [0x48, 0xF7, 0xE1]);
+    }
+
+    #[test]
+    fn test_lea_label() {
+        let code = assemble(|a| {
+            let message = a.new_label();
+            a.lea_label(Reg::Rdi, message);
+            a.bind(message);
+            a.data(b"hi\0");
+        });
+        assert_eq!(code, [0x48, 0x8D, 0x3D, 0, 0, 0, 0, b'h', b'i', 0]);
+    }
+
+    #[test]
+    fn test_jumps_are_patched() {
+        let code = assemble(|a| {
+            let start = a.new_label();
+            let end = a.new_label();
+            a.bind(start);
+            a.jcc(Condition::Equal, end);
+            a.jmp(start);
+            a.bind(end);
+        });
+        assert_eq!(
+            code,
+            [0x0F, 0x84, 0x05, 0x00, 0x00, 0x00, 0xE9, 0xF5, 0xFF, 0xFF, 0xFF]
+        );
+    }
+}
@@ -0,0 +1,468 @@
+use crate::asm::*;
+use crate::elf;
+use crate::{symbol_name, Error};
+use rust_comp_core::pos::Span;
+use rust_comp_mir::*;
+
+/// Compiles `bodies` to an ELF relocatable object.  Every value is stored
+/// in its local's 64 bit stack slot.  Narrower integers are kept sign or
+/// zero extended and arithmetic wraps to the local's type unless checked by
+/// an `Assert`.
+pub fn compile(bodies: &[Body]) -> Result<Vec<u8>, Error> {
+    let mut asm = Assembler::default();
+    let mut symbols = Vec::new();
+    for body in bodies {
+        let offset = asm.code.len();
+        Codegen {
+            asm: &mut asm,
+            body,
+            labels: Vec::new(),
+            messages: Vec::new(),
+        }
+        .function()?;
+        symbols.push(elf::Symbol {
+            name: symbol_name(&body.name),
+            offset,
+            size: asm.code.len() - offset,
+        });
+    }
+    asm.finish();
+    Ok(elf::write_object(&asm.code, &symbols, &asm.relocations))
+}
+
+/// Holds the address of places behind pointers.  It isn't used to pass
+/// arguments so it can be used while they are being loaded.
+const ADDRESS: Reg = Reg::R11;
+
+struct Codegen<'a> {
+    asm: &'a mut Assembler,
+    body: &'a Body,
+    labels: Vec<Label>,
+    /// Panic messages to emit after the function's code.
+    messages: Vec<(Label, String)>,
+}
+
+impl<'a> Codegen<'a> {
+    fn function(&mut self) -> Result<(), Error> {
+        let body = self.body;
+        if body.arg_count > ARGUMENT_REGISTERS.len() {
+            return Err(Error::Unsupported(body.span));
+        }
+        self.labels = body.blocks.iter().map(|_| self.asm.new_label()).collect();
+
+        self.asm.push(Reg::Rbp);
+        self.asm.mov(Reg::Rbp, Reg::Rsp);
+        // Keep the stack 16 byte aligned for calls.
+        let frame = (body.locals.len() * 8).next_multiple_of(16);
+        self.asm.sub_rsp(frame as i32);
+        for (argument, register) in body.arguments().zip(ARGUMENT_REGISTERS.iter()) {
+            self.asm.store(Reg::Rbp, offset(argument), *register);
+        }
+
+        for (i, block) in body.blocks.iter().enumerate() {
+            self.asm.bind(self.labels[i]);
+            for statement in &block.statements {
+                self.statement(statement)?;
+            }
+            self.terminator(BasicBlock(i), &block.terminator)?;
+        }
+        for (label, message) in self.messages.drain(..) {
+            self.asm.bind(label);
+            self.asm.data(message.as_bytes());
+            self.asm.data(&[0]);
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Result<(), Error> {
+        match &statement.kind {
+            StatementKind::Assign(place, rvalue) => {
+                self.rvalue(statement.span, rvalue, self.integer(place))?;
+                let (base, displacement) = self.address(statement.span, place)?;
+                self.asm.store(base, displacement, Reg::Rax);
+            }
+            StatementKind::StorageLive(_)
+            | StatementKind::StorageDead(_)
+            | StatementKind::Drop { .. }
+            | StatementKind::Nop => {}
+        }
+        Ok(())
+    }
+
+    fn terminator(&mut self, block: BasicBlock, terminator: &Terminator) -> Result<(), Error> {
+        let span = terminator.span;
+        match &terminator.kind {
+            TerminatorKind::Goto(target) => self.goto(block, *target),
+            TerminatorKind::SwitchInt {
+                operand,
+                targets,
+                otherwise,
+            } => {
+                self.operand(span, Reg::Rax, operand)?;
+                for (value, target) in targets {
+                    if *value <= i32::MAX as u128 {
+                        self.asm.cmp_rax_imm(*value as i32);
+                    } else {
+                        self.asm.mov_imm(Reg::Rcx, *value as i64);
+                        self.asm.arithmetic(Arithmetic::Cmp, Reg::Rax, Reg::Rcx);
+                    }
+                    self.asm.jcc(Condition::Equal, self.labels[target.0]);
+                }
+                self.goto(block, *otherwise);
+            }
+            TerminatorKind::Call {
+                function,
+                arguments,
+                destination,
+                target,
+            } => {
+                let function = match function {
+                    Operand::Constant(Constant::Function(name)) => name,
+                    _ => return Err(Error::Unsupported(span)),
+                };
+                if arguments.len() > ARGUMENT_REGISTERS.len() {
+                    return Err(Error::Unsupported(span));
+                }
+                for (argument, register) in arguments.iter().zip(ARGUMENT_REGISTERS.iter()) {
+                    self.operand(span, *register, argument)?;
+                }
+                self.asm.call(&symbol_name(function));
+                let (base, displacement) = self.address(span, destination)?;
+                self.asm.store(base, displacement, Reg::Rax);
+                self.goto(block, *target);
+            }
+            TerminatorKind::Assert {
+                condition,
+                expected,
+                message,
+                target,
+            } => {
+                self.operand(span, Reg::Rax, condition)?;
+                self.asm.cmp_rax_imm(*expected as i32);
+                self.asm.jcc(Condition::Equal, self.labels[target.0]);
+                let label = self.asm.new_label();
+                self.messages.push((label, message.clone()));
+                self.asm.lea_label(ARGUMENT_REGISTERS[0], label);
+                self.asm.call("rust_panic");
+                self.asm.ud2();
+            }
+            TerminatorKind::Return => {
+                self.asm.load(Reg::Rax, Reg::Rbp, offset(RETURN_PLACE));
+                self.asm.leave();
+                self.asm.ret();
+            }
+            TerminatorKind::Unreachable => self.asm.ud2(),
+        }
+        Ok(())
+    }
+
+    fn goto(&mut self, block: BasicBlock, target: BasicBlock) {
+        // Fall through to the next block.
+        if target.0 != block.0 + 1 {
+            self.asm.jmp(self.labels[target.0]);
+        }
+    }
+
+    /// The type of the integers assigned to `place`.  Places behind
+    /// pointers are treated as `i64`.
+    fn integer(&self, place: &Place) -> IntegerType {
+        match place.projection.is_empty() {
+            true => self.body.local(place.local).integer,
+            false => None,
+        }
+        .unwrap_or(IntegerType::I64)
+    }
+
+    /// Evaluates `rvalue`, assigned to a place of type `integer`, into
+    /// `rax`.
+    fn rvalue(&mut self, span: Span, rvalue: &Rvalue, integer: IntegerType) -> Result<(), Error> {
+        match rvalue {
+            Rvalue::Use(operand) => self.operand(span, Reg::Rax, operand)?,
+            Rvalue::Binary(op, left, right) => {
+                self.operand(span, Reg::Rax, left)?;
+                self.operand(span, Reg::Rcx, right)?;
+                match op {
+                    BinaryOp::Add => self.asm.arithmetic(Arithmetic::Add, Reg::Rax, Reg::Rcx),
+                    BinaryOp::Sub => self.asm.arithmetic(Arithmetic::Sub, Reg::Rax, Reg::Rcx),
+                    BinaryOp::Mul => self.asm.imul(Reg::Rax, Reg::Rcx),
+                    BinaryOp::Div if integer.signed => self.asm.idiv(Reg::Rcx),
+                    BinaryOp::Div => self.asm.div(Reg::Rcx),
+                    BinaryOp::BitAnd => self.asm.arithmetic(Arithmetic::And, Reg::Rax, Reg::Rcx),
+                    BinaryOp::BitOr => self.asm.arithmetic(Arithmetic::Or, Reg::Rax, Reg::Rcx),
+                    BinaryOp::Eq | BinaryOp::Ne => {
+                        self.asm.arithmetic(Arithmetic::Cmp, Reg::Rax, Reg::Rcx);
+                        self.asm.set(if *op == BinaryOp::Eq {
+                            Condition::Equal
+                        } else {
+                            Condition::NotEqual
+                        });
+                    }
+                }
+                // Wrap the result to the type's width.
+                if let (BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul, Some(extend)) =
+                    (op, extend(integer))
+                {
+                    self.asm.extend_rax(extend);
+                }
+            }
+            Rvalue::Ref(place)
+            | Rvalue::RefMut(place)
+            | Rvalue::RawConst(place)
+            | Rvalue::RawMut(place) => {
+                let (base, displacement) = self.address(span, place)?;
+                self.asm.lea(Reg::Rax, base, displacement);
+            }
+            Rvalue::Cast(operand, type_) => {
+                self.operand(span, Reg::Rax, operand)?;
+                let extend = match type_.as_str() {
+                    "u8" => Some(Extend::U8),
+                    "i8" => Some(Extend::I8),
+                    "u16" => Some(Extend::U16),
+                    "i16" => Some(Extend::I16),
+                    "u32" => Some(Extend::U32),
+                    "i32" => Some(Extend::I32),
+                    _ => None,
+                };
+                if let Some(extend) = extend {
+                    self.asm.extend_rax(extend);
+                }
+            }
+            Rvalue::Overflows(op, integer, left, right) => {
+                self.operand(span, Reg::Rax, left)?;
+                self.operand(span, Reg::Rcx, right)?;
+                match op {
+                    BinaryOp::Add => self.asm.arithmetic(Arithmetic::Add, Reg::Rax, Reg::Rcx),
+                    BinaryOp::Sub => self.asm.arithmetic(Arithmetic::Sub, Reg::Rax, Reg::Rcx),
+                    BinaryOp::Mul if integer.signed || integer.bits < 64 => {
+                        self.asm.imul(Reg::Rax, Reg::Rcx)
+                    }
+                    BinaryOp::Mul => self.asm.mul(Reg::Rcx),
+                    // Unsigned division can't overflow.
+                    BinaryOp::Div if !integer.signed => self.asm.mov_imm(Reg::Rax, 0),
+                    BinaryOp::Div => {
+                        // The quotient of the minimum value and `-1` doesn't
+                        // fit.
+                        self.asm.mov_imm(Reg::Rdx, integer.min() as i64);
+                        self.asm.arithmetic(Arithmetic::Cmp, Reg::Rax, Reg::Rdx);
+                        self.asm.set(Condition::Equal);
+                        self.asm.mov(Reg::Rdx, Reg::Rax);
+                        self.asm.mov_imm(Reg::Rax, -1);
+                        self.asm.arithmetic(Arithmetic::Cmp, Reg::Rcx, Reg::Rax);
+                        self.asm.set(Condition::Equal);
+                        self.asm.arithmetic(Arithmetic::And, Reg::Rax, Reg::Rdx);
+                    }
+                    _ => return Err(Error::Unsupported(span)),
+                }
+                if *op == BinaryOp::Div {
+                    return Ok(());
+                }
+                match extend(*integer) {
+                    // The 64 bit result of extended operands is exact so it
+                    // overflows if wrapping it changes it.
+                    Some(extend) => {
+                        self.asm.mov(Reg::Rcx, Reg::Rax);
+                        self.asm.extend_rax(extend);
+                        self.asm.arithmetic(Arithmetic::Cmp, Reg::Rax, Reg::Rcx);
+                        self.asm.set(Condition::NotEqual);
+                    }
+                    None if integer.signed => self.asm.set(Condition::Overflow),
+                    None => self.asm.set(Condition::Carry),
+                }
+            }
+            Rvalue::Tuple(_) | Rvalue::Variant(..) | Rvalue::Discriminant(_) => {
+                return Err(Error::Unsupported(span))
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads `operand` into `register`.
+    fn operand(&mut self, span: Span, register: Reg, operand: &Operand) -> Result<(), Error> {
+        match operand {
+            Operand::Place(place) => {
+                let (base, displacement) = self.address(span, place)?;
+                self.asm.load(register, base, displacement);
+            }
+            Operand::Constant(Constant::Integer(value)) => {
+                self.asm.mov_imm(register, *value as i64)
+            }
+            Operand::Constant(Constant::Bool(b)) => self.asm.mov_imm(register, *b as i64),
+            Operand::Constant(Constant::Unit) => self.asm.mov_imm(register, 0),
+            Operand::Constant(Constant::Function(_)) => return Err(Error::Unsupported(span)),
+        }
+        Ok(())
+    }
+
+    /// The address of `place` as a base register and displacement.  Places
+    /// behind pointers are addressed through `ADDRESS`.
+    fn address(&mut self, span: Span, place: &Place) -> Result<(Reg, i32), Error> {
+        let mut address = (Reg::Rbp, offset(place.local));
+        for projection in &place.projection {
+            match projection {
+                Projection::Deref => {
+                    self.asm.load(ADDRESS, address.0, address.1);
+                    address = (ADDRESS, 0);
+                }
+                Projection::Field(_) | Projection::Downcast(..) => {
+                    return Err(Error::Unsupported(span))
+                }
+            }
+        }
+        Ok(address)
+    }
+}
+
+/// How to wrap a 64 bit value to `integer` or `None` if it's 64 bits.
+fn extend(integer: IntegerType) -> Option<Extend> {
+    match (integer.bits, integer.signed) {
+        (8, false) => Some(Extend::U8),
+        (8, true) => Some(Extend::I8),
+        (16, false) => Some(Extend::U16),
+        (16, true) => Some(Extend::I16),
+        (32, false) => Some(Extend::U32),
+        (32, true) => Some(Extend::I32),
+        _ => None,
+    }
+}
+
+/// The offset of `local`'s stack slot from `rbp`.
+fn offset(local: Local) -> i32 {
+    -8 * (local.0 as i32 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{
+        compile_str, run_executable, run_executable_output, run_executable_output_with,
+    };
+    use assert_matches::assert_matches;
+    use rust_comp_core::profile::Profile;
+
+    #[test]
+    fn test_compile_arithmetic_and_calls() {
+        assert_eq!(
+            run_executable(
+                "fn add(a: i64, b: i64) -> i64 { a + b }
+                 fn main() { print_i64(add(2, 3) * 4 - 30 / 3); }"
+            ),
+            "10\n"
+        );
+    }
+
+    #[test]
+    fn test_compile_branches_and_loops() {
+        assert_eq!(
+            run_executable(
+                "fn fib(n: i64) -> i64 { if n == 0 || n == 1 { n } else { fib(n - 1) + fib(n - 2) } }
+                 fn main() {
+                     let mut i = 0;
+                     while i != 10 { print_i64(fib(i)); i = i + 1; }
+                 }"
+            ),
+            "0\n1\n1\n2\n3\n5\n8\n13\n21\n34\n"
+        );
+    }
+
+    #[test]
+    fn test_compile_pointers_and_casts() {
+        assert_eq!(
+            run_executable(
+                "fn set(p: &mut i64, v: i64) { *p = v; }
+                 fn main() {
+                     let mut x = 1;
+                     set(&mut x, 300);
+                     print_i64(x as u8);
+                     print_i64((0 - 1) as u32);
+                 }"
+            ),
+            "44\n4294967295\n"
+        );
+    }
+
+    #[test]
+    fn test_compile_overflow_panics() {
+        let output = run_executable_output(
+            "fn mul(a: i64, b: i64) -> i64 { a * b }
+             fn main() { print_i64(mul(3, 4)); print_i64(mul(9223372036854775807, 2)); }",
+        );
+        assert_eq!(output.status.code(), Some(101));
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "12\n");
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            "panicked at <test>:1:33: attempt to multiply with overflow\n"
+        );
+
+        let output = run_executable_output(
+            "fn div(a: i64, b: i64) -> i64 { a / b }
+             fn main() { print_i64(div(7, 0)); }",
+        );
+        assert_eq!(output.status.code(), Some(101));
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            "panicked at <test>:1:33: attempt to divide by zero\n"
+        );
+    }
+
+    #[test]
+    fn test_compile_sized_integers() {
+        // The result in release and the panic message in debug, if any.
+        let cases: &[(&str, &str, Option<&str>)] = &[
+            ("add(2147483647, 1)", "-2147483648", Some("add")),
+            ("sub(0, 1)", "4294967295", Some("subtract")),
+            ("mul(16, 16)", "0", Some("multiply")),
+            ("mul(15, 17)", "255", None),
+            ("add_u64(18446744073709551615, 1)", "0", Some("add")),
+            (
+                "div_u64(18446744073709551615, 2)",
+                "9223372036854775807",
+                None,
+            ),
+        ];
+        for (call, release, panic) in cases {
+            let program = format!(
+                "fn add(a: i32, b: i32) -> i32 {{ a + b }}
+                 fn sub(a: u32, b: u32) -> u32 {{ a - b }}
+                 fn mul(a: u8, b: u8) -> u8 {{ a * b }}
+                 fn add_u64(a: u64, b: u64) -> u64 {{ a + b }}
+                 fn div_u64(a: u64, b: u64) -> u64 {{ a / b }}
+                 fn main() {{ print_i64({}); }}",
+                call
+            );
+            let output = run_executable_output_with(&program, Profile::Release);
+            assert_eq!(
+                String::from_utf8(output.stdout).unwrap(),
+                format!("{}\n", release)
+            );
+            let output = run_executable_output_with(&program, Profile::Debug);
+            match panic {
+                Some(verb) => {
+                    assert_eq!(output.status.code(), Some(101));
+                    let stderr = String::from_utf8(output.stderr).unwrap();
+                    let message = format!("attempt to {} with overflow\n", verb);
+                    assert!(stderr.ends_with(&message), "{}: {}", call, stderr);
+                }
+                None => assert_eq!(
+                    String::from_utf8(output.stdout).unwrap(),
+                    format!("{}\n", release)
+                ),
+            }
+        }
+
+        // Dividing the minimum value by `-1` panics in release too.
+        let output = run_executable_output_with(
+            "fn div(a: i32, b: i32) -> i32 { a / b }
+             fn main() { print_i64(div(0 - 2147483647 - 1, 0 - 1)); }",
+            Profile::Release,
+        );
+        assert_eq!(output.status.code(), Some(101));
+    }
+
+    #[test]
+    fn test_compile_tuples_are_unsupported() {
+        assert_matches!(
+            compile_str("fn main() { let x = (1, 2); }"),
+            Err(Error::Unsupported(_))
+        );
+    }
+}
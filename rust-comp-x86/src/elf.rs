@@ -0,0 +1,275 @@
+/// A function defined in the `.text` section.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub offset: usize,
+    pub size: usize,
+}
+
+/// A call from `.text` to `symbol`.  The 32 bit displacement at `offset` is
+/// patched by the linker.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Relocation {
+    pub offset: usize,
+    pub symbol: String,
+}
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_RELA: u32 = 4;
+
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+const SHF_INFO_LINK: u64 = 0x40;
+
+const STB_GLOBAL: u8 = 1;
+const STT_NOTYPE: u8 = 0;
+const STT_FUNC: u8 = 2;
+
+const R_X86_64_PLT32: u64 = 4;
+
+const TEXT: u32 = 1;
+const RELA_TEXT: u32 = 2;
+const SYMTAB: u32 = 3;
+const STRTAB: u32 = 4;
+const SHSTRTAB: u32 = 5;
+const NOTE_GNU_STACK: u32 = 6;
+const SECTIONS: u16 = 7;
+
+/// Writes an x86-64 ELF relocatable object file.  Symbols that are called
+/// but not defined are left undefined for the linker to resolve.
+pub fn write_object(text: &[u8], symbols: &[Symbol], relocations: &[Relocation]) -> Vec<u8> {
+    let mut strtab = StringTable::default();
+    let mut symtab = Vec::new();
+    // Symbol 0 is always the null symbol.
+    symtab.extend_from_slice(&[0; 24]);
+    let mut indices = Vec::new();
+    for symbol in symbols {
+        indices.push(symbol.name.as_str());
+        write_symbol(
+            &mut symtab,
+            strtab.add(&symbol.name),
+            STT_FUNC,
+            TEXT as u16,
+            symbol.offset,
+            symbol.size,
+        );
+    }
+    for relocation in relocations {
+        if !indices.contains(&relocation.symbol.as_str()) {
+            indices.push(&relocation.symbol);
+            write_symbol(
+                &mut symtab,
+                strtab.add(&relocation.symbol),
+                STT_NOTYPE,
+                0,
+                0,
+                0,
+            );
+        }
+    }
+
+    let mut rela = Vec::new();
+    for relocation in relocations {
+        let index = indices
+            .iter()
+            .position(|name| *name == relocation.symbol)
+            .unwrap() as u64
+            + 1;
+        rela.extend_from_slice(&(relocation.offset as u64).to_le_bytes());
+        rela.extend_from_slice(&((index << 32) | R_X86_64_PLT32).to_le_bytes());
+        // The displacement is relative to the end of the instruction.
+        rela.extend_from_slice(&(-4i64).to_le_bytes());
+    }
+
+    let mut shstrtab = StringTable::default();
+    let names = [
+        shstrtab.add(".text"),
+        shstrtab.add(".rela.text"),
+        shstrtab.add(".symtab"),
+        shstrtab.add(".strtab"),
+        shstrtab.add(".shstrtab"),
+        shstrtab.add(".note.GNU-stack"),
+    ];
+
+    let mut file = vec![0; 64];
+    let mut sections = Vec::new();
+    for contents in [text, &rela, &symtab, &strtab.bytes, &shstrtab.bytes] {
+        while !file.len().is_multiple_of(16) {
+            file.push(0);
+        }
+        sections.push((file.len(), contents.len()));
+        file.extend_from_slice(contents);
+    }
+    while !file.len().is_multiple_of(8) {
+        file.push(0);
+    }
+    let section_headers = file.len();
+
+    write_header(&mut file, section_headers);
+
+    // Section 0 is always the null section.
+    file.extend_from_slice(&[0; 64]);
+    let section = |i: u32| sections[i as usize - 1];
+    let headers = [
+        (TEXT, SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, 0, 0, 16, 0),
+        (RELA_TEXT, SHT_RELA, SHF_INFO_LINK, SYMTAB, TEXT, 8, 24),
+        // `info` is the index of the first global symbol.
+        (SYMTAB, SHT_SYMTAB, 0, STRTAB, 1, 8, 24),
+        (STRTAB, SHT_STRTAB, 0, 0, 0, 1, 0),
+        (SHSTRTAB, SHT_STRTAB, 0, 0, 0, 1, 0),
+    ];
+    for (index, type_, flags, link, info, align, entsize) in headers.iter().copied() {
+        let (offset, size) = section(index);
+        write_section_header(
+            &mut file,
+            names[index as usize - 1],
+            type_,
+            flags,
+            offset,
+            size,
+            link,
+            info,
+            align,
+            entsize,
+        );
+    }
+    // Marks the stack as not executable.
+    write_section_header(
+        &mut file,
+        names[NOTE_GNU_STACK as usize - 1],
+        SHT_PROGBITS,
+        0,
+        section_headers,
+        0,
+        0,
+        0,
+        1,
+        0,
+    );
+    file
+}
+
+fn write_header(file: &mut [u8], section_headers: usize) {
+    let mut header = Vec::with_capacity(64);
+    // Magic, 64 bit, little endian, version 1, System V ABI.
+    header.extend_from_slice(&[0x7F, b'E', b'L', b'F', 2, 1, 1, 0]);
+    header.extend_from_slice(&[0; 8]);
+    // Relocatable, x86-64, version 1.
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&62u16.to_le_bytes());
+    header.extend_from_slice(&1u32.to_le_bytes());
+    // No entry point or program headers.
+    header.extend_from_slice(&0u64.to_le_bytes());
+    header.extend_from_slice(&0u64.to_le_bytes());
+    header.extend_from_slice(&(section_headers as u64).to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes());
+    header.extend_from_slice(&64u16.to_le_bytes());
+    header.extend_from_slice(&0u16.to_le_bytes());
+    header.extend_from_slice(&0u16.to_le_bytes());
+    header.extend_from_slice(&64u16.to_le_bytes());
+    header.extend_from_slice(&SECTIONS.to_le_bytes());
+    header.extend_from_slice(&(SHSTRTAB as u16).to_le_bytes());
+    file[..64].copy_from_slice(&header);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_section_header(
+    file: &mut Vec<u8>,
+    name: u32,
+    type_: u32,
+    flags: u64,
+    offset: usize,
+    size: usize,
+    link: u32,
+    info: u32,
+    align: u64,
+    entsize: u64,
+) {
+    file.extend_from_slice(&name.to_le_bytes());
+    file.extend_from_slice(&type_.to_le_bytes());
+    file.extend_from_slice(&flags.to_le_bytes());
+    file.extend_from_slice(&0u64.to_le_bytes());
+    file.extend_from_slice(&(offset as u64).to_le_bytes());
+    file.extend_from_slice(&(size as u64).to_le_bytes());
+    file.extend_from_slice(&link.to_le_bytes());
+    file.extend_from_slice(&info.to_le_bytes());
+    file.extend_from_slice(&align.to_le_bytes());
+    file.extend_from_slice(&entsize.to_le_bytes());
+}
+
+fn write_symbol(
+    symtab: &mut Vec<u8>,
+    name: u32,
+    type_: u8,
+    section: u16,
+    value: usize,
+    size: usize,
+) {
+    symtab.extend_from_slice(&name.to_le_bytes());
+    symtab.push((STB_GLOBAL << 4) | type_);
+    symtab.push(0);
+    symtab.extend_from_slice(&section.to_le_bytes());
+    symtab.extend_from_slice(&(value as u64).to_le_bytes());
+    symtab.extend_from_slice(&(size as u64).to_le_bytes());
+}
+
+struct StringTable {
+    bytes: Vec<u8>,
+}
+
+impl Default for StringTable {
+    fn default() -> Self {
+        // Offset 0 is the empty string.
+        StringTable { bytes: vec![0] }
+    }
+}
+
+impl StringTable {
+    fn add(&mut self, string: &str) -> u32 {
+        let offset = self.bytes.len() as u32;
+        self.bytes.extend_from_slice(string.as_bytes());
+        self.bytes.push(0);
+        offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    fn read_u16(file: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([file[offset], file[offset + 1]])
+    }
+
+    #[test]
+    fn test_write_object_header() {
+        let file = write_object(&[0xC3], &[], &[]);
+        assert_eq!(&file[..4], b"\x7FELF");
+        assert_eq!(read_u16(&file, 16), 1);
+        assert_eq!(read_u16(&file, 18), 62);
+        assert_eq!(read_u16(&file, 60), SECTIONS);
+        assert_eq!(file[64], 0xC3);
+    }
+
+    #[test]
+    fn test_undefined_symbols_follow_defined_symbols() {
+        let file = write_object(
+            &[0xE8, 0, 0, 0, 0, 0xC3],
+            &[Symbol {
+                name: "f".to_string(),
+                offset: 0,
+                size: 6,
+            }],
+            &[Relocation {
+                offset: 1,
+                symbol: "g".to_string(),
+            }],
+        );
+        let rela = 80;
+        let info = u64::from_le_bytes(file[rela + 8..rela + 16].try_into().unwrap());
+        assert_eq!(info, (2 << 32) | R_X86_64_PLT32);
+    }
+}
@@ -0,0 +1,24 @@
+use rust_comp_core::pos::Span;
+
+pub mod asm;
+pub mod codegen;
+pub mod elf;
+
+#[cfg(test)]
+mod test;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The MIR statement or terminator can't be compiled yet.
+    Unsupported(Span),
+}
+
+/// The symbol a function is emitted as.  `main` is renamed so the runtime
+/// can define the C `main` that calls it.
+pub fn symbol_name(name: &str) -> String {
+    if name == "main" {
+        "rust_main".to_string()
+    } else {
+        name.to_string()
+    }
+}
@@ -0,0 +1,66 @@
+use crate::codegen::compile;
+use crate::Error;
+use rust_comp_check::test_support;
+use rust_comp_core::profile::Profile;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Compiles `file_contents` as file 0 with overflow checks.
+pub fn compile_str(file_contents: &str) -> Result<Vec<u8>, Error> {
+    compile_str_with(file_contents, Profile::Debug)
+}
+
+/// Like `compile_str` but checks arithmetic as `profile` requires.
+pub fn compile_str_with(file_contents: &str, profile: Profile) -> Result<Vec<u8>, Error> {
+    let (diagnostic, top_levels) = test_support::desugar(file_contents);
+    compile(&rust_comp_mir::lower::lower(
+        &diagnostic,
+        profile,
+        &top_levels,
+    ))
+}
+
+/// Compiles `file_contents`, links it with the runtime using `cc`, runs it,
+/// and returns its output.
+pub fn run_executable(file_contents: &str) -> String {
+    let output = run_executable_output(file_contents);
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Like `run_executable` but returns the output whether or not the program
+/// succeeds.
+pub fn run_executable_output(file_contents: &str) -> Output {
+    run_executable_output_with(file_contents, Profile::Debug)
+}
+
+/// Like `run_executable_output` but checks arithmetic as `profile`
+/// requires.
+pub fn run_executable_output_with(file_contents: &str, profile: Profile) -> Output {
+    let object = compile_str_with(file_contents, profile).unwrap();
+    let directory = std::env::temp_dir().join(format!(
+        "rust-comp-x86-{}-{:?}",
+        std::process::id(),
+        std::thread::current().id()
+    ));
+    fs::create_dir_all(&directory).unwrap();
+    let object_path = directory.join("program.o");
+    let executable_path = directory.join("program");
+    fs::write(&object_path, object).unwrap();
+
+    let runtime: PathBuf = [env!("CARGO_MANIFEST_DIR"), "runtime", "runtime.c"]
+        .iter()
+        .collect();
+    let status = Command::new("cc")
+        .arg(&object_path)
+        .arg(&runtime)
+        .arg("-o")
+        .arg(&executable_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let output = Command::new(&executable_path).output().unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+    output
+}
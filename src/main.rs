@@ -18,6 +18,10 @@ fn run_main() -> Result<(), Error> {
 fn handle_error(e: Error) {
     match e {
         Error::File(f) => eprintln!("Error: Could not read from {}", f),
+        Error::Write(f) => eprintln!("Error: Could not write to {}", f),
+        Error::UnknownPass(name) => eprintln!("Error: Unknown optimization pass {}", name),
+        Error::UnknownCode(code) => eprintln!("Error: Unknown error code {}", code),
+        Error::UnknownLint(name) => eprintln!("Error: Unknown lint {}", name),
         Error::Handled => (),
     }
 }
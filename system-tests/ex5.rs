@@ -0,0 +1,15 @@
+fn fib(n: i64) -> i64 {
+    if n == 0 || n == 1 {
+        n
+    } else {
+        fib(n - 1) + fib(n - 2)
+    }
+}
+
+fn main() {
+    let mut i = 0;
+    while i != 20 {
+        print_i64(fib(i));
+        i = i + 1;
+    }
+}